@@ -1,7 +1,7 @@
 use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::time::{SystemTime, Duration};
-use crate::capture::{PacketInfo, TcpConnection, TcpState};
+use crate::capture::{PacketInfo, ProcessResolver, TcpConnection, TcpState};
 use crate::analysis::protocols::{ProtocolType, ProtocolAnalyzer};
 
 #[derive(Debug, Clone, PartialEq)]
@@ -27,11 +27,109 @@ pub struct ConnectionInfo {
     pub packets_received: u64,
     pub process_id: Option<u32>,
     pub process_name: Option<String>,
+    /// Only for sockets seen in the kernel socket table
+    pub tcp_health: Option<TcpHealth>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum HealthStatus {
+    Healthy,
+    Degraded,
+    Unhealthy,
+}
+
+/// TCP health data from tcp_info (netlink backend) or the queue and
+/// retransmit columns of /proc/net/tcp. RTT and cwnd need tcp_info.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TcpHealth {
+    pub srtt_us: Option<u32>,
+    pub rttvar_us: Option<u32>,
+    /// Unrecovered retransmission timeouts right now
+    pub retransmits: u32,
+    /// Segments retransmitted over the connection's lifetime
+    pub total_retrans: Option<u32>,
+    pub segs_out: Option<u32>,
+    pub send_queue: u32,
+    pub recv_queue: u32,
+    pub cwnd: Option<u32>,
+}
+
+impl TcpHealth {
+    const DEGRADED_RTT_US: u32 = 200_000;
+    const UNHEALTHY_RTT_US: u32 = 1_000_000;
+    const DEGRADED_RETRANS_PERCENT: f64 = 2.0;
+    const DEGRADED_QUEUE_BYTES: u32 = 1024 * 1024;
+    
+    pub fn from_connection(conn: &TcpConnection) -> Self {
+        let info = conn.tcp_info.as_ref();
+        // The kernel reports srtt 0 until the first RTT sample
+        let sampled = info.filter(|info| info.rtt_us > 0);
+        Self {
+            srtt_us: sampled.map(|info| info.rtt_us),
+            rttvar_us: sampled.map(|info| info.rttvar_us),
+            retransmits: conn.retransmits,
+            total_retrans: info.map(|info| info.total_retrans),
+            segs_out: info.map(|info| info.segs_out),
+            send_queue: conn.tx_queue,
+            recv_queue: conn.rx_queue,
+            cwnd: info.map(|info| info.snd_cwnd),
+        }
+    }
+    
+    /// Share of sent segments that had to be retransmitted
+    pub fn retrans_percent(&self) -> Option<f64> {
+        match (self.total_retrans, self.segs_out) {
+            (Some(retrans), Some(segs_out)) if segs_out > 0 => Some(retrans as f64 * 100.0 / segs_out as f64),
+            _ => None,
+        }
+    }
+    
+    pub fn status(&self) -> HealthStatus {
+        self.problems()
+            .iter()
+            .map(|(status, _)| *status)
+            .max()
+            .unwrap_or(HealthStatus::Healthy)
+    }
+    
+    /// Human-readable reasons the connection looks unhealthy
+    pub fn problems(&self) -> Vec<(HealthStatus, String)> {
+        let mut problems = Vec::new();
+        
+        // A pending RTO retransmit means the peer has stopped acking
+        if self.retransmits > 0 {
+            problems.push((HealthStatus::Unhealthy, format!("{} unacked retransmits", self.retransmits)));
+        }
+        
+        if let Some(srtt) = self.srtt_us {
+            if srtt >= Self::UNHEALTHY_RTT_US {
+                problems.push((HealthStatus::Unhealthy, format!("RTT {:.0}ms", srtt as f64 / 1000.0)));
+            } else if srtt >= Self::DEGRADED_RTT_US {
+                problems.push((HealthStatus::Degraded, format!("RTT {:.0}ms", srtt as f64 / 1000.0)));
+            }
+        }
+        
+        if let Some(percent) = self.retrans_percent() {
+            if percent >= Self::DEGRADED_RETRANS_PERCENT {
+                problems.push((HealthStatus::Degraded, format!("{:.1}% retransmitted", percent)));
+            }
+        }
+        
+        if self.send_queue >= Self::DEGRADED_QUEUE_BYTES {
+            problems.push((HealthStatus::Degraded, "send queue backed up".to_string()));
+        }
+        if self.recv_queue >= Self::DEGRADED_QUEUE_BYTES {
+            problems.push((HealthStatus::Degraded, "application not reading".to_string()));
+        }
+        
+        problems
+    }
 }
 
 pub struct ConnectionTracker {
     active_connections: HashMap<String, ConnectionInfo>,
     protocol_analyzer: ProtocolAnalyzer,
+    process_resolver: ProcessResolver,
     connection_timeout: Duration,
     max_connections: usize,
 }
@@ -41,6 +139,7 @@ impl ConnectionTracker {
         Self {
             active_connections: HashMap::new(),
             protocol_analyzer: ProtocolAnalyzer::new(),
+            process_resolver: ProcessResolver::new(),
             connection_timeout: Duration::from_secs(300), // 5 minutes timeout
             max_connections: 1000,
         }
@@ -50,6 +149,7 @@ impl ConnectionTracker {
         Self {
             active_connections: HashMap::new(),
             protocol_analyzer: ProtocolAnalyzer::new(),
+            process_resolver: ProcessResolver::new(),
             connection_timeout: Duration::from_secs(timeout_secs),
             max_connections,
         }
@@ -67,6 +167,10 @@ impl ConnectionTracker {
         
         self.active_connections.retain(|key, _| proc_keys.contains(key));
         
+        // Map socket inodes to their owning processes
+        let inodes: Vec<u64> = tcp_connections.iter().map(|conn| conn.inode).collect();
+        self.process_resolver.refresh(&inodes);
+        
         // Update or add connections from /proc data
         for tcp_conn in tcp_connections {
             let key = self.connection_key(&tcp_conn.local_addr, &tcp_conn.remote_addr);
@@ -86,13 +190,34 @@ impl ConnectionTracker {
                     packets_received: 0,
                     process_id: None,
                     process_name: None,
+                    tcp_health: None,
                 }
             });
             
-            // Update connection state and last seen time
+            // Update connection state and last seen time. /proc is authoritative
+            // for which end is local, packets only know who spoke first.
+            conn_info.local_addr = tcp_conn.local_addr;
+            conn_info.remote_addr = tcp_conn.remote_addr;
             conn_info.state = Self::convert_tcp_state(&tcp_conn.state);
             conn_info.last_seen = now;
             conn_info.protocol = protocol;
+            // Queue sizes of a listening socket describe its accept backlog
+            conn_info.tcp_health = match tcp_conn.state {
+                TcpState::Listen => None,
+                _ => Some(TcpHealth::from_connection(tcp_conn)),
+            };
+            
+            // Kernel byte counters are exact; packet tracking only fills in
+            // between refreshes
+            if let Some(ref info) = tcp_conn.tcp_info {
+                conn_info.bytes_sent = info.bytes_acked;
+                conn_info.bytes_received = info.bytes_received;
+            }
+            
+            if let Some(process) = self.process_resolver.lookup(tcp_conn.inode) {
+                conn_info.process_id = Some(process.pid);
+                conn_info.process_name = Some(process.name.clone());
+            }
         }
         
         // Clean up old connections
@@ -101,41 +226,35 @@ impl ConnectionTracker {
     
     /// Track a packet and update connection information
     pub fn track_packet(&mut self, packet: &PacketInfo) {
-        if let (Some(src_ip), Some(dst_ip), Some(src_port), Some(dst_port)) = 
-            (&packet.src_ip, &packet.dst_ip, packet.src_port, packet.dst_port) {
+        // Parse addresses (IPv4 or IPv6)
+        if let Some((src_addr, dst_addr)) = packet.socket_addrs() {
+            let key = self.connection_key(&src_addr, &dst_addr);
+            let protocol = self.protocol_analyzer.analyze_packet(packet);
+            let now = packet.timestamp;
             
-            // Parse addresses
-            if let (Ok(src_addr), Ok(dst_addr)) = (
-                format!("{}:{}", src_ip, src_port).parse::<SocketAddr>(),
-                format!("{}:{}", dst_ip, dst_port).parse::<SocketAddr>()
-            ) {
-                let key = self.connection_key(&src_addr, &dst_addr);
-                let protocol = self.protocol_analyzer.analyze_packet(packet);
-                let now = SystemTime::now();
-                
-                let conn_info = self.active_connections.entry(key).or_insert_with(|| {
-                    ConnectionInfo {
-                        local_addr: src_addr,
-                        remote_addr: dst_addr,
-                        protocol: protocol.clone(),
-                        state: ConnectionState::Established,
-                        established_time: now,
-                        last_seen: now,
-                        bytes_sent: 0,
-                        bytes_received: 0,
-                        packets_sent: 0,
-                        packets_received: 0,
-                        process_id: None,
-                        process_name: None,
-                    }
-                });
-                
-                // Update packet and byte counts
-                conn_info.packets_sent += 1;
-                conn_info.bytes_sent += packet.length as u64;
-                conn_info.last_seen = now;
-                conn_info.protocol = protocol;
-            }
+            let conn_info = self.active_connections.entry(key).or_insert_with(|| {
+                ConnectionInfo {
+                    local_addr: src_addr,
+                    remote_addr: dst_addr,
+                    protocol: protocol.clone(),
+                    state: ConnectionState::Established,
+                    established_time: now,
+                    last_seen: now,
+                    bytes_sent: 0,
+                    bytes_received: 0,
+                    packets_sent: 0,
+                    packets_received: 0,
+                    process_id: None,
+                    process_name: None,
+                    tcp_health: None,
+                }
+            });
+            
+            // Update packet and byte counts
+            conn_info.packets_sent += 1;
+            conn_info.bytes_sent += packet.length as u64;
+            conn_info.last_seen = now;
+            conn_info.protocol = protocol;
         }
     }
     
@@ -158,6 +277,10 @@ impl ConnectionTracker {
             dst_ip: Some(tcp_conn.remote_addr.ip().to_string()),
             src_port: Some(tcp_conn.local_addr.port()),
             dst_port: Some(tcp_conn.remote_addr.port()),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
         };
         
         self.protocol_analyzer.analyze_packet(&dummy_packet)
@@ -248,6 +371,10 @@ impl ConnectionTracker {
     pub fn get_protocol_analyzer_mut(&mut self) -> &mut ProtocolAnalyzer {
         &mut self.protocol_analyzer
     }
+    
+    pub fn get_process_resolver(&self) -> &ProcessResolver {
+        &self.process_resolver
+    }
 }
 
 impl std::fmt::Display for ConnectionState {
@@ -266,6 +393,21 @@ impl std::fmt::Display for ConnectionState {
 mod tests {
     use super::*;
     use std::net::{IpAddr, Ipv4Addr};
+    use crate::capture::TcpInfo;
+    
+    fn tcp_connection(retransmits: u32, tcp_info: Option<TcpInfo>) -> TcpConnection {
+        TcpConnection {
+            local_addr: "192.168.1.101:48000".parse().unwrap(),
+            remote_addr: "93.184.216.34:443".parse().unwrap(),
+            state: TcpState::Established,
+            inode: 0,
+            uid: 1000,
+            tx_queue: 36,
+            rx_queue: 0,
+            retransmits,
+            tcp_info,
+        }
+    }
 
     #[test]
     fn test_connection_tracking() {
@@ -279,6 +421,10 @@ mod tests {
             dst_ip: Some("192.168.1.2".to_string()),
             src_port: Some(12345),
             dst_port: Some(80),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
         };
         
         tracker.track_packet(&packet);
@@ -305,4 +451,38 @@ mod tests {
             ConnectionState::Closed
         );
     }
+    
+    #[test]
+    fn test_tcp_health_from_proc_columns() {
+        let health = TcpHealth::from_connection(&tcp_connection(0, None));
+        assert_eq!(health.send_queue, 36);
+        assert_eq!(health.srtt_us, None);
+        assert_eq!(health.status(), HealthStatus::Healthy);
+        
+        let stalled = TcpHealth::from_connection(&tcp_connection(3, None));
+        assert_eq!(stalled.status(), HealthStatus::Unhealthy);
+    }
+    
+    #[test]
+    fn test_tcp_health_from_tcp_info() {
+        let info = TcpInfo {
+            rtt_us: 250_000,
+            rttvar_us: 40_000,
+            total_retrans: 30,
+            snd_cwnd: 4,
+            segs_out: 1000,
+            ..Default::default()
+        };
+        let health = TcpHealth::from_connection(&tcp_connection(0, Some(info)));
+        assert_eq!(health.srtt_us, Some(250_000));
+        assert_eq!(health.cwnd, Some(4));
+        assert_eq!(health.retrans_percent(), Some(3.0));
+        assert_eq!(health.status(), HealthStatus::Degraded);
+        assert_eq!(health.problems().len(), 2);
+        
+        // No RTT sample yet
+        let fresh = TcpHealth::from_connection(&tcp_connection(0, Some(TcpInfo::default())));
+        assert_eq!(fresh.srtt_us, None);
+        assert_eq!(fresh.rttvar_us, None);
+    }
 }
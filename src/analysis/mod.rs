@@ -1,7 +1,10 @@
 pub mod protocols;
+pub mod signatures;
 pub mod connections;
 pub mod statistics;
+pub mod processes;
 
 pub use protocols::{ProtocolAnalyzer, ProtocolType, ProtocolInfo};
-pub use connections::{ConnectionTracker, ConnectionInfo, ConnectionState};
+pub use connections::{ConnectionTracker, ConnectionInfo, ConnectionState, TcpHealth, HealthStatus};
 pub use statistics::{StatisticsCollector, NetworkStatistics, InterfaceMetrics};
+pub use processes::{ProcessBandwidthTracker, ProcessUsage, PeerUsage, ProcessSortKey};
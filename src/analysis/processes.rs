@@ -0,0 +1,373 @@
+use std::collections::{HashMap, HashSet};
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, Instant};
+use crate::analysis::connections::{ConnectionInfo, ConnectionTracker};
+use crate::traffic::TrafficFlow;
+use crate::utils::net::canonical_addr;
+
+/// Traffic a process exchanged with one remote host
+#[derive(Debug, Clone, PartialEq)]
+pub struct PeerUsage {
+    pub addr: IpAddr,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+}
+
+/// Per-process bandwidth summary, like a row in nethogs
+#[derive(Debug, Clone)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    pub name: String,
+    pub cmdline: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub rx_rate: f64, // bytes per second
+    pub tx_rate: f64, // bytes per second
+    pub connection_count: usize,
+    pub top_peers: Vec<PeerUsage>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ProcessSortKey {
+    Rate,
+    Received,
+    Sent,
+    Connections,
+    Name,
+}
+
+impl ProcessSortKey {
+    pub fn next(self) -> Self {
+        match self {
+            ProcessSortKey::Rate => ProcessSortKey::Received,
+            ProcessSortKey::Received => ProcessSortKey::Sent,
+            ProcessSortKey::Sent => ProcessSortKey::Connections,
+            ProcessSortKey::Connections => ProcessSortKey::Name,
+            ProcessSortKey::Name => ProcessSortKey::Rate,
+        }
+    }
+    
+    /// Busiest first, or alphabetical for `Name`
+    pub fn sort(self, processes: &mut [ProcessUsage]) {
+        match self {
+            ProcessSortKey::Rate => processes.sort_by(|a, b| {
+                (b.rx_rate + b.tx_rate).partial_cmp(&(a.rx_rate + a.tx_rate)).unwrap_or(std::cmp::Ordering::Equal)
+            }),
+            ProcessSortKey::Received => processes.sort_by_key(|p| std::cmp::Reverse(p.rx_bytes)),
+            ProcessSortKey::Sent => processes.sort_by_key(|p| std::cmp::Reverse(p.tx_bytes)),
+            ProcessSortKey::Connections => processes.sort_by_key(|p| std::cmp::Reverse(p.connection_count)),
+            ProcessSortKey::Name => processes.sort_by(|a, b| a.name.cmp(&b.name).then(a.pid.cmp(&b.pid))),
+        }
+    }
+}
+
+impl std::fmt::Display for ProcessSortKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProcessSortKey::Rate => write!(f, "rate"),
+            ProcessSortKey::Received => write!(f, "received"),
+            ProcessSortKey::Sent => write!(f, "sent"),
+            ProcessSortKey::Connections => write!(f, "connections"),
+            ProcessSortKey::Name => write!(f, "name"),
+        }
+    }
+}
+
+struct ProcessTotals {
+    name: String,
+    cmdline: String,
+    rx_bytes: u64,
+    tx_bytes: u64,
+    rx_rate: f64,
+    tx_rate: f64,
+    rate_baseline: (u64, u64), // (rx, tx) at the last rate sample
+    connection_count: usize,
+    peers: HashMap<IpAddr, (u64, u64)>, // (rx, tx)
+}
+
+/// Attributes flow bytes to processes by matching each flow's socket pair
+/// against connections whose owner the ConnectionTracker has resolved
+pub struct ProcessBandwidthTracker {
+    processes: HashMap<u32, ProcessTotals>,
+    // Per flow: owning pid and the (rx, tx) counts already attributed to it
+    flow_baselines: HashMap<String, (u32, u64, u64)>,
+    last_rate_sample: Instant,
+    rate_interval: Duration,
+    max_peers: usize,
+}
+
+impl ProcessBandwidthTracker {
+    pub fn new() -> Self {
+        Self {
+            processes: HashMap::new(),
+            flow_baselines: HashMap::new(),
+            last_rate_sample: Instant::now(),
+            rate_interval: Duration::from_secs(1),
+            max_peers: 5,
+        }
+    }
+    
+    pub fn update(&mut self, tracker: &ConnectionTracker, flows: &HashMap<String, TrafficFlow>) {
+        let resolver = tracker.get_process_resolver();
+        self.aggregate(tracker.get_active_connections(), flows, &|pid| {
+            resolver.get_process(pid).map(|process| process.cmdline.clone())
+        });
+    }
+    
+    fn aggregate(
+        &mut self,
+        connections: &HashMap<String, ConnectionInfo>,
+        flows: &HashMap<String, TrafficFlow>,
+        cmdline_of: &dyn Fn(u32) -> Option<String>,
+    ) {
+        // (local, remote) socket pairs of every connection with a known owner
+        let mut owners: HashMap<(SocketAddr, SocketAddr), u32> = HashMap::new();
+        let mut connection_counts: HashMap<u32, usize> = HashMap::new();
+        
+        for conn in connections.values() {
+            let pid = match conn.process_id {
+                Some(pid) => pid,
+                None => continue,
+            };
+            
+            owners.insert((canonical_addr(conn.local_addr), canonical_addr(conn.remote_addr)), pid);
+            let count = connection_counts.entry(pid).or_insert(0);
+            if !is_listener(conn) {
+                *count += 1;
+            }
+            
+            let name = conn.process_name.clone().unwrap_or_default();
+            let totals = self.processes.entry(pid).or_insert_with(|| ProcessTotals {
+                name: name.clone(),
+                cmdline: String::new(),
+                rx_bytes: 0,
+                tx_bytes: 0,
+                rx_rate: 0.0,
+                tx_rate: 0.0,
+                rate_baseline: (0, 0),
+                connection_count: 0,
+                peers: HashMap::new(),
+            });
+            totals.name = name;
+        }
+        
+        // Processes that no longer own any socket have exited or closed up
+        self.processes.retain(|pid, _| connection_counts.contains_key(pid));
+        for (pid, totals) in self.processes.iter_mut() {
+            totals.connection_count = connection_counts[pid];
+            if let Some(cmdline) = cmdline_of(*pid) {
+                totals.cmdline = cmdline;
+            }
+        }
+        
+        // Attribute only the bytes seen since the previous update, so totals
+        // survive flows expiring from the inspector
+        let mut seen_flows = HashSet::new();
+        for flow in flows.values() {
+            let src = canonical_addr(flow.src_addr);
+            let dst = canonical_addr(flow.dst_addr);
+            let (pid, rx, tx, peer) = if let Some(pid) = owners.get(&(src, dst)) {
+                (*pid, flow.dst_bytes, flow.src_bytes, dst.ip())
+            } else if let Some(pid) = owners.get(&(dst, src)) {
+                (*pid, flow.src_bytes, flow.dst_bytes, src.ip())
+            } else {
+                continue;
+            };
+            
+            seen_flows.insert(flow.flow_id.clone());
+            let baseline = self.flow_baselines.entry(flow.flow_id.clone()).or_insert((pid, 0, 0));
+            if baseline.0 != pid {
+                // Socket pair reused by another process
+                *baseline = (pid, 0, 0);
+            }
+            let rx_delta = rx.saturating_sub(baseline.1);
+            let tx_delta = tx.saturating_sub(baseline.2);
+            *baseline = (pid, rx, tx);
+            
+            if let Some(totals) = self.processes.get_mut(&pid) {
+                totals.rx_bytes += rx_delta;
+                totals.tx_bytes += tx_delta;
+                let peer_totals = totals.peers.entry(peer).or_insert((0, 0));
+                peer_totals.0 += rx_delta;
+                peer_totals.1 += tx_delta;
+            }
+        }
+        self.flow_baselines.retain(|flow_id, _| seen_flows.contains(flow_id));
+        
+        let elapsed = self.last_rate_sample.elapsed();
+        if elapsed >= self.rate_interval {
+            let seconds = elapsed.as_secs_f64();
+            for totals in self.processes.values_mut() {
+                totals.rx_rate = (totals.rx_bytes - totals.rate_baseline.0) as f64 / seconds;
+                totals.tx_rate = (totals.tx_bytes - totals.rate_baseline.1) as f64 / seconds;
+                totals.rate_baseline = (totals.rx_bytes, totals.tx_bytes);
+            }
+            self.last_rate_sample = Instant::now();
+        }
+    }
+    
+    pub fn get_processes(&self, sort_key: ProcessSortKey) -> Vec<ProcessUsage> {
+        let mut processes: Vec<ProcessUsage> = self.processes
+            .iter()
+            .map(|(pid, totals)| {
+                let mut top_peers: Vec<PeerUsage> = totals.peers
+                    .iter()
+                    .map(|(addr, (rx_bytes, tx_bytes))| PeerUsage {
+                        addr: *addr,
+                        rx_bytes: *rx_bytes,
+                        tx_bytes: *tx_bytes,
+                    })
+                    .collect();
+                top_peers.sort_by_key(|peer| std::cmp::Reverse(peer.rx_bytes + peer.tx_bytes));
+                top_peers.truncate(self.max_peers);
+                
+                ProcessUsage {
+                    pid: *pid,
+                    name: totals.name.clone(),
+                    cmdline: totals.cmdline.clone(),
+                    rx_bytes: totals.rx_bytes,
+                    tx_bytes: totals.tx_bytes,
+                    rx_rate: totals.rx_rate,
+                    tx_rate: totals.tx_rate,
+                    connection_count: totals.connection_count,
+                    top_peers,
+                }
+            })
+            .collect();
+        
+        sort_key.sort(&mut processes);
+        processes
+    }
+    
+    pub fn get_process_count(&self) -> usize {
+        self.processes.len()
+    }
+}
+
+impl Default for ProcessBandwidthTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn is_listener(conn: &ConnectionInfo) -> bool {
+    conn.remote_addr.port() == 0 && conn.remote_addr.ip().is_unspecified()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::SystemTime;
+    use crate::analysis::connections::ConnectionState;
+    use crate::analysis::protocols::ProtocolType;
+    use crate::traffic::FlowDirection;
+    
+    fn connection(local: &str, remote: &str, pid: u32, name: &str) -> ConnectionInfo {
+        ConnectionInfo {
+            local_addr: local.parse().unwrap(),
+            remote_addr: remote.parse().unwrap(),
+            protocol: ProtocolType::Https,
+            state: ConnectionState::Established,
+            established_time: SystemTime::now(),
+            last_seen: SystemTime::now(),
+            bytes_sent: 0,
+            bytes_received: 0,
+            packets_sent: 0,
+            packets_received: 0,
+            process_id: Some(pid),
+            process_name: Some(name.to_string()),
+            tcp_health: None,
+        }
+    }
+    
+    fn flow(src: &str, dst: &str, src_bytes: u64, dst_bytes: u64) -> TrafficFlow {
+        TrafficFlow {
+            flow_id: format!("{}:{}", src, dst),
+            src_addr: src.parse().unwrap(),
+            dst_addr: dst.parse().unwrap(),
+            protocol: ProtocolType::Https,
+            transport: "TCP".to_string(),
+            direction: FlowDirection::Outbound,
+            start_time: SystemTime::now(),
+            last_seen: SystemTime::now(),
+            packet_count: 1,
+            byte_count: src_bytes + dst_bytes,
+            src_bytes,
+            dst_bytes,
+            packets_per_second: 0.0,
+            bytes_per_second: 0.0,
+            is_active: true,
+            tls: None,
+            http: Vec::new(),
+        }
+    }
+    
+    #[test]
+    fn test_attributes_flows_to_processes() {
+        let mut connections = HashMap::new();
+        connections.insert("a".to_string(), connection("[::ffff:192.168.1.10]:51000", "[::ffff:93.184.216.34]:443", 4242, "curl"));
+        connections.insert("b".to_string(), connection("192.168.1.10:22", "10.0.0.5:60000", 812, "sshd"));
+        connections.insert("c".to_string(), connection("0.0.0.0:22", "0.0.0.0:0", 812, "sshd"));
+        
+        let mut flows = HashMap::new();
+        // Download: the first packet came from the server
+        flows.insert("f1".to_string(), flow("93.184.216.34:443", "192.168.1.10:51000", 50_000, 400));
+        flows.insert("f2".to_string(), flow("10.0.0.5:60000", "192.168.1.10:22", 100, 2_000));
+        flows.insert("f3".to_string(), flow("10.0.0.9:1234", "192.168.1.10:8080", 999, 999));
+        
+        let mut tracker = ProcessBandwidthTracker::new();
+        tracker.aggregate(&connections, &flows, &|_| None);
+        
+        let processes = tracker.get_processes(ProcessSortKey::Received);
+        assert_eq!(processes.len(), 2);
+        
+        let curl = &processes[0];
+        assert_eq!(curl.pid, 4242);
+        assert_eq!(curl.rx_bytes, 50_000);
+        assert_eq!(curl.tx_bytes, 400);
+        assert_eq!(curl.top_peers[0].addr.to_string(), "93.184.216.34");
+        
+        let sshd = &processes[1];
+        assert_eq!(sshd.rx_bytes, 100);
+        assert_eq!(sshd.tx_bytes, 2_000);
+        assert_eq!(sshd.connection_count, 1); // the listener is not a connection
+        
+        assert_eq!(tracker.get_processes(ProcessSortKey::Sent)[0].pid, 812);
+    }
+    
+    #[test]
+    fn test_totals_accumulate_across_updates() {
+        let mut connections = HashMap::new();
+        connections.insert("a".to_string(), connection("192.168.1.10:51000", "93.184.216.34:443", 4242, "curl"));
+        
+        let mut tracker = ProcessBandwidthTracker::new();
+        let mut flows = HashMap::new();
+        flows.insert("f1".to_string(), flow("192.168.1.10:51000", "93.184.216.34:443", 300, 1_000));
+        tracker.aggregate(&connections, &flows, &|_| None);
+        
+        flows.insert("f1".to_string(), flow("192.168.1.10:51000", "93.184.216.34:443", 500, 3_000));
+        tracker.aggregate(&connections, &flows, &|_| Some("curl https://example.com".to_string()));
+        
+        // The flow expired from the inspector; bytes already counted stay
+        tracker.aggregate(&connections, &HashMap::new(), &|_| None);
+        
+        let curl = &tracker.get_processes(ProcessSortKey::Rate)[0];
+        assert_eq!(curl.rx_bytes, 3_000);
+        assert_eq!(curl.tx_bytes, 500);
+        assert_eq!(curl.cmdline, "curl https://example.com");
+        
+        // Process closed its last socket
+        tracker.aggregate(&HashMap::new(), &HashMap::new(), &|_| None);
+        assert_eq!(tracker.get_process_count(), 0);
+    }
+    
+    #[test]
+    fn test_sort_key_cycle() {
+        let mut key = ProcessSortKey::Rate;
+        for _ in 0..5 {
+            key = key.next();
+        }
+        assert_eq!(key, ProcessSortKey::Rate);
+        assert_eq!(ProcessSortKey::Connections.to_string(), "connections");
+    }
+}
@@ -1,6 +1,18 @@
 use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+use crate::analysis::signatures;
 use crate::capture::PacketInfo;
 
+/// Flows whose protocol was recognised from a payload are remembered this long
+/// after their last packet
+const DETECTED_FLOW_TIMEOUT: Duration = Duration::from_secs(300);
+const MAX_DETECTED_FLOWS: usize = 8192;
+
+/// Transport and the two endpoints in a fixed order, so both directions of a
+/// flow share a key
+type FlowKey = (String, SocketAddr, SocketAddr);
+
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum ProtocolType {
     Http,
@@ -12,6 +24,12 @@ pub enum ProtocolType {
     Pop3,
     Imap,
     Telnet,
+    /// TLS on a port with no known protocol behind it
+    Tls,
+    Quic,
+    Mqtt,
+    Postgres,
+    MySql,
     Tcp(u16),  // TCP with port number
     Udp(u16),  // UDP with port number
     Icmp,
@@ -31,6 +49,8 @@ pub struct ProtocolInfo {
 pub struct ProtocolAnalyzer {
     protocol_stats: HashMap<ProtocolType, ProtocolInfo>,
     well_known_ports: HashMap<u16, ProtocolType>,
+    /// Protocols recognised from payloads, with the time of the flow's last packet
+    detected_flows: HashMap<FlowKey, (ProtocolType, SystemTime)>,
 }
 
 impl ProtocolAnalyzer {
@@ -38,6 +58,7 @@ impl ProtocolAnalyzer {
         let mut analyzer = ProtocolAnalyzer {
             protocol_stats: HashMap::new(),
             well_known_ports: HashMap::new(),
+            detected_flows: HashMap::new(),
         };
         
         analyzer.initialize_well_known_ports();
@@ -63,6 +84,7 @@ impl ProtocolAnalyzer {
         
         // Email protocols
         self.well_known_ports.insert(25, ProtocolType::Smtp);
+        self.well_known_ports.insert(465, ProtocolType::Smtp);
         self.well_known_ports.insert(587, ProtocolType::Smtp);
         self.well_known_ports.insert(110, ProtocolType::Pop3);
         self.well_known_ports.insert(995, ProtocolType::Pop3);
@@ -71,9 +93,16 @@ impl ProtocolAnalyzer {
         
         // Telnet
         self.well_known_ports.insert(23, ProtocolType::Telnet);
+        
+        // Messaging and databases
+        self.well_known_ports.insert(1883, ProtocolType::Mqtt);
+        self.well_known_ports.insert(8883, ProtocolType::Mqtt);
+        self.well_known_ports.insert(5432, ProtocolType::Postgres);
+        self.well_known_ports.insert(3306, ProtocolType::MySql);
     }
     
     pub fn analyze_packet(&mut self, packet: &PacketInfo) -> ProtocolType {
+        self.remember_detected_protocol(packet);
         let protocol_type = self.identify_protocol(packet);
         
         // Update statistics
@@ -94,7 +123,13 @@ impl ProtocolAnalyzer {
         protocol_type
     }
     
+    /// Protocol of a packet: what its flow was recognised as speaking, else
+    /// what its own payload looks like, else a guess from the ports
     pub fn identify_protocol(&self, packet: &PacketInfo) -> ProtocolType {
+        self.deep_packet_inspection(packet, Some(&packet.payload))
+    }
+    
+    fn identify_by_port(&self, packet: &PacketInfo) -> ProtocolType {
         match packet.protocol.as_str() {
             "TCP" => {
                 if let Some(dst_port) = packet.dst_port {
@@ -132,7 +167,7 @@ impl ProtocolAnalyzer {
                     ProtocolType::Unknown
                 }
             },
-            "ICMP" => ProtocolType::Icmp,
+            "ICMP" | "ICMPv6" => ProtocolType::Icmp,
             _ => ProtocolType::Unknown,
         }
     }
@@ -148,6 +183,11 @@ impl ProtocolAnalyzer {
             ProtocolType::Pop3 => "Post Office Protocol v3".to_string(),
             ProtocolType::Imap => "Internet Message Access Protocol".to_string(),
             ProtocolType::Telnet => "Telnet Protocol".to_string(),
+            ProtocolType::Tls => "Transport Layer Security".to_string(),
+            ProtocolType::Quic => "QUIC".to_string(),
+            ProtocolType::Mqtt => "MQ Telemetry Transport".to_string(),
+            ProtocolType::Postgres => "PostgreSQL".to_string(),
+            ProtocolType::MySql => "MySQL".to_string(),
             ProtocolType::Tcp(port) => format!("TCP (port {})", port),
             ProtocolType::Udp(port) => format!("UDP (port {})", port),
             ProtocolType::Icmp => "Internet Control Message Protocol".to_string(),
@@ -158,6 +198,8 @@ impl ProtocolAnalyzer {
     fn is_protocol_encrypted(protocol_type: &ProtocolType) -> bool {
         matches!(protocol_type, 
             ProtocolType::Https | 
+            ProtocolType::Tls |
+            ProtocolType::Quic |
             ProtocolType::Ssh |
             ProtocolType::Pop3 |  // Assuming POP3S on port 995
             ProtocolType::Imap    // Assuming IMAPS on port 993
@@ -175,6 +217,10 @@ impl ProtocolAnalyzer {
             ProtocolType::Pop3 => Some(110),
             ProtocolType::Imap => Some(143),
             ProtocolType::Telnet => Some(23),
+            ProtocolType::Quic => Some(443),
+            ProtocolType::Mqtt => Some(1883),
+            ProtocolType::Postgres => Some(5432),
+            ProtocolType::MySql => Some(3306),
             ProtocolType::Tcp(port) => Some(*port),
             ProtocolType::Udp(port) => Some(*port),
             _ => None,
@@ -203,11 +249,57 @@ impl ProtocolAnalyzer {
         self.protocol_stats.clear();
     }
     
-    /// Advanced protocol detection based on packet content patterns
-    pub fn deep_packet_inspection(&self, packet: &PacketInfo, _payload: Option<&[u8]>) -> ProtocolType {
-        // For now, use the basic port-based identification
-        // In the future, this could analyze packet payload for more accurate detection
-        self.identify_protocol(packet)
+    /// Advanced protocol detection based on packet content patterns. A flow
+    /// keeps the protocol its first recognisable payload showed, so a
+    /// STARTTLS upgrade or a later payload-less ACK doesn't change it.
+    pub fn deep_packet_inspection(&self, packet: &PacketInfo, payload: Option<&[u8]>) -> ProtocolType {
+        if let Some((protocol, _)) = Self::flow_key(packet).and_then(|key| self.detected_flows.get(&key)) {
+            return protocol.clone();
+        }
+        payload
+            .and_then(|payload| self.match_payload(packet, payload))
+            .unwrap_or_else(|| self.identify_by_port(packet))
+    }
+    
+    fn match_payload(&self, packet: &PacketInfo, payload: &[u8]) -> Option<ProtocolType> {
+        match signatures::match_payload(&packet.protocol, payload)? {
+            // TLS says nothing about what it carries; trust the port unless
+            // it promises plaintext HTTP
+            ProtocolType::Tls => match self.identify_by_port(packet) {
+                ProtocolType::Http | ProtocolType::Tcp(_) | ProtocolType::Unknown => Some(ProtocolType::Tls),
+                protocol => Some(protocol),
+            },
+            protocol => Some(protocol),
+        }
+    }
+    
+    fn remember_detected_protocol(&mut self, packet: &PacketInfo) {
+        let Some(key) = Self::flow_key(packet) else {
+            return;
+        };
+        let now = packet.timestamp;
+        
+        if let Some(entry) = self.detected_flows.get_mut(&key) {
+            entry.1 = now;
+            return;
+        }
+        if let Some(protocol) = self.match_payload(packet, &packet.payload) {
+            if self.detected_flows.len() >= MAX_DETECTED_FLOWS {
+                self.detected_flows.retain(|_, (_, last_seen)| {
+                    now.duration_since(*last_seen).unwrap_or_default() < DETECTED_FLOW_TIMEOUT
+                });
+                if self.detected_flows.len() >= MAX_DETECTED_FLOWS {
+                    self.detected_flows.clear();
+                }
+            }
+            self.detected_flows.insert(key, (protocol, now));
+        }
+    }
+    
+    fn flow_key(packet: &PacketInfo) -> Option<FlowKey> {
+        let (src, dst) = packet.socket_addrs()?;
+        let (low, high) = if src < dst { (src, dst) } else { (dst, src) };
+        Some((packet.protocol.clone(), low, high))
     }
 }
 
@@ -223,6 +315,11 @@ impl std::fmt::Display for ProtocolType {
             ProtocolType::Pop3 => write!(f, "POP3"),
             ProtocolType::Imap => write!(f, "IMAP"),
             ProtocolType::Telnet => write!(f, "TELNET"),
+            ProtocolType::Tls => write!(f, "TLS"),
+            ProtocolType::Quic => write!(f, "QUIC"),
+            ProtocolType::Mqtt => write!(f, "MQTT"),
+            ProtocolType::Postgres => write!(f, "POSTGRES"),
+            ProtocolType::MySql => write!(f, "MYSQL"),
             ProtocolType::Tcp(port) => write!(f, "TCP:{}", port),
             ProtocolType::Udp(port) => write!(f, "UDP:{}", port),
             ProtocolType::Icmp => write!(f, "ICMP"),
@@ -235,7 +332,7 @@ impl std::fmt::Display for ProtocolType {
 mod tests {
     use super::*;
     use std::time::SystemTime;
-
+    
     #[test]
     fn test_protocol_identification() {
         let mut analyzer = ProtocolAnalyzer::new();
@@ -249,6 +346,10 @@ mod tests {
             dst_ip: Some("192.168.1.2".to_string()),
             src_port: Some(12345),
             dst_port: Some(80),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
         };
         
         let protocol = analyzer.analyze_packet(&http_packet);
@@ -263,6 +364,10 @@ mod tests {
             dst_ip: Some("192.168.1.2".to_string()),
             src_port: Some(54321),
             dst_port: Some(443),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
         };
         
         let protocol = analyzer.analyze_packet(&https_packet);
@@ -281,6 +386,10 @@ mod tests {
             dst_ip: Some("192.168.1.2".to_string()),
             src_port: Some(12345),
             dst_port: Some(80),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
         };
         
         // Analyze the same packet multiple times
@@ -294,4 +403,45 @@ mod tests {
         assert_eq!(http_stats.packet_count, 5);
         assert_eq!(http_stats.byte_count, 500);
     }
+    
+    #[test]
+    fn test_payload_detection_sticks_to_flow() {
+        let mut analyzer = ProtocolAnalyzer::new();
+        let packet = |src: &str, src_port: u16, dst: &str, dst_port: u16, payload: &[u8]| PacketInfo {
+            timestamp: SystemTime::now(),
+            length: 60 + payload.len(),
+            protocol: "TCP".to_string(),
+            src_ip: Some(src.to_string()),
+            dst_ip: Some(dst.to_string()),
+            src_port: Some(src_port),
+            dst_port: Some(dst_port),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: payload.to_vec(),
+        };
+        
+        // SSH on a non-standard port, then bare ACKs both ways
+        let banner = packet("10.0.0.5", 2222, "10.0.0.9", 50000, b"SSH-2.0-OpenSSH_9.6\r\n");
+        assert_eq!(analyzer.analyze_packet(&banner), ProtocolType::Ssh);
+        assert_eq!(analyzer.analyze_packet(&packet("10.0.0.9", 50000, "10.0.0.5", 2222, b"")), ProtocolType::Ssh);
+        assert_eq!(analyzer.identify_protocol(&packet("10.0.0.5", 2222, "10.0.0.9", 50000, b"")), ProtocolType::Ssh);
+        
+        // Plain HTTP on the HTTPS port is HTTP; TLS on an unknown port is TLS;
+        // TLS on the IMAPS port is IMAP
+        let get = packet("10.0.0.9", 50001, "10.0.0.5", 443, b"GET / HTTP/1.1\r\n\r\n");
+        assert_eq!(analyzer.analyze_packet(&get), ProtocolType::Http);
+        let hello = [0x16, 0x03, 0x01, 0x00, 0x40, 0x01];
+        assert_eq!(analyzer.analyze_packet(&packet("10.0.0.9", 50002, "10.0.0.5", 9443, &hello)), ProtocolType::Tls);
+        assert_eq!(analyzer.analyze_packet(&packet("10.0.0.9", 50003, "10.0.0.5", 993, &hello)), ProtocolType::Imap);
+        
+        // SMTP keeps its name after STARTTLS
+        let greeting = packet("10.0.0.5", 2525, "10.0.0.9", 50004, b"220 mx.example.com ESMTP\r\n");
+        assert_eq!(analyzer.analyze_packet(&greeting), ProtocolType::Smtp);
+        assert_eq!(analyzer.analyze_packet(&packet("10.0.0.9", 50004, "10.0.0.5", 2525, &hello)), ProtocolType::Smtp);
+        
+        // Without a payload signature the port decides
+        assert_eq!(analyzer.analyze_packet(&packet("10.0.0.9", 50005, "10.0.0.5", 5432, b"")), ProtocolType::Postgres);
+        assert_eq!(analyzer.analyze_packet(&packet("10.0.0.9", 50006, "10.0.0.5", 7000, b"hello")), ProtocolType::Tcp(7000));
+    }
 }
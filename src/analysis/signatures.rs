@@ -0,0 +1,271 @@
+use crate::analysis::protocols::ProtocolType;
+
+const HTTP_METHODS: [&str; 9] = ["GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE"];
+
+/// Recognise the application protocol from the first bytes of a TCP or UDP
+/// payload. Every check anchors at the start of the payload, so only the
+/// opening message of a conversation (or a new TLS record) matches.
+///
+/// TLS is reported as `ProtocolType::Tls`; which protocol it carries is
+/// left to the caller, who knows the ports.
+pub fn match_payload(transport: &str, payload: &[u8]) -> Option<ProtocolType> {
+    if payload.is_empty() {
+        return None;
+    }
+    
+    match transport {
+        "TCP" => {
+            if payload.starts_with(b"SSH-") {
+                Some(ProtocolType::Ssh)
+            } else if is_http(payload) {
+                Some(ProtocolType::Http)
+            } else if is_tls_record(payload) {
+                Some(ProtocolType::Tls)
+            } else if is_mysql_greeting(payload) {
+                Some(ProtocolType::MySql)
+            } else if is_postgres_startup(payload) {
+                Some(ProtocolType::Postgres)
+            } else if is_mqtt_connect(payload) {
+                Some(ProtocolType::Mqtt)
+            } else if let Some(protocol) = match_text_greeting(payload) {
+                Some(protocol)
+            } else {
+                // DNS over TCP prefixes each message with its length
+                let length = u16::from_be_bytes([payload[0], *payload.get(1)?]) as usize;
+                (length == payload.len() - 2 && is_dns_message(&payload[2..])).then_some(ProtocolType::Dns)
+            }
+        },
+        "UDP" => {
+            if is_quic_long_header(payload) {
+                Some(ProtocolType::Quic)
+            } else if is_dns_message(payload) {
+                Some(ProtocolType::Dns)
+            } else {
+                None
+            }
+        },
+        _ => None,
+    }
+}
+
+/// HTTP/1.x request line (`GET /path HTTP/1.1`) or status line (`HTTP/1.1 200 OK`)
+fn is_http(payload: &[u8]) -> bool {
+    if payload.starts_with(b"HTTP/1.") {
+        return true;
+    }
+    
+    let line_end = payload.windows(2).position(|pair| pair == b"\r\n").unwrap_or(payload.len());
+    let line = &payload[..line_end];
+    HTTP_METHODS.iter().any(|method| {
+        line.starts_with(method.as_bytes())
+            && line.get(method.len()) == Some(&b' ')
+            && line.windows(8).any(|window| window == b" HTTP/1.")
+    })
+}
+
+/// TLS record header: content type (change cipher spec, alert, handshake or
+/// application data), major version 3 and a length within the record limit
+fn is_tls_record(payload: &[u8]) -> bool {
+    if payload.len() < 5 {
+        return false;
+    }
+    let length = u16::from_be_bytes([payload[3], payload[4]]);
+    (0x14..=0x17).contains(&payload[0]) && payload[1] == 3 && payload[2] <= 4 && length > 0 && length <= 18432
+}
+
+/// Initial handshake packet of the MySQL server: 3-byte length, sequence 0,
+/// protocol version 10 and a NUL-terminated version string like `8.0.36`
+fn is_mysql_greeting(payload: &[u8]) -> bool {
+    if payload.len() < 6 || payload[3] != 0 || payload[4] != 10 {
+        return false;
+    }
+    let length = u32::from_le_bytes([payload[0], payload[1], payload[2], 0]) as usize;
+    if length + 4 > payload.len() {
+        return false;
+    }
+    
+    let version = &payload[5..];
+    match version.iter().position(|byte| *byte == 0) {
+        Some(end) => (1..=64).contains(&end)
+            && version[0].is_ascii_digit()
+            && version[..end].iter().all(|byte| byte.is_ascii_graphic()),
+        None => false,
+    }
+}
+
+/// PostgreSQL StartupMessage (protocol 3.0), SSLRequest, GSSENCRequest or
+/// CancelRequest, all sent by the client before anything else
+fn is_postgres_startup(payload: &[u8]) -> bool {
+    if payload.len() < 8 {
+        return false;
+    }
+    let length = u32::from_be_bytes([payload[0], payload[1], payload[2], payload[3]]) as usize;
+    let code = u32::from_be_bytes([payload[4], payload[5], payload[6], payload[7]]);
+    match code {
+        // SSLRequest, GSSENCRequest
+        80877103 | 80877104 => length == 8,
+        // CancelRequest
+        80877102 => length == 16,
+        // StartupMessage: NUL-terminated key/value pairs
+        196608 => length == payload.len() && length <= 10000 && payload.last() == Some(&0),
+        _ => false,
+    }
+}
+
+/// MQTT CONNECT packet: type 1, a variable-length remaining length, then the
+/// protocol name `MQTT` (3.1.1 and 5) or `MQIsdp` (3.1)
+fn is_mqtt_connect(payload: &[u8]) -> bool {
+    if payload[0] != 0x10 {
+        return false;
+    }
+    
+    // Remaining length takes 1-4 bytes, high bit set on all but the last
+    let mut offset = 1;
+    while offset < 5 {
+        match payload.get(offset) {
+            Some(byte) if byte & 0x80 != 0 => offset += 1,
+            Some(_) => break,
+            None => return false,
+        }
+    }
+    let name = &payload[(offset + 1).min(payload.len())..];
+    name.starts_with(b"\x00\x04MQTT") || name.starts_with(b"\x00\x06MQIsdp")
+}
+
+/// Greetings and opening commands of the line-based mail protocols (and FTP,
+/// whose `220` greeting looks like SMTP's)
+fn match_text_greeting(payload: &[u8]) -> Option<ProtocolType> {
+    if payload.starts_with(b"+OK") {
+        return Some(ProtocolType::Pop3);
+    }
+    if payload.starts_with(b"* OK") || payload.starts_with(b"* PREAUTH") {
+        return Some(ProtocolType::Imap);
+    }
+    if payload.starts_with(b"EHLO ") || payload.starts_with(b"HELO ") {
+        return Some(ProtocolType::Smtp);
+    }
+    
+    if payload.starts_with(b"220 ") || payload.starts_with(b"220-") {
+        let line_end = payload.windows(2).position(|pair| pair == b"\r\n").unwrap_or(payload.len());
+        let line = String::from_utf8_lossy(&payload[..line_end]).to_uppercase();
+        if line.contains("SMTP") {
+            return Some(ProtocolType::Smtp);
+        }
+        if line.contains("FTP") {
+            return Some(ProtocolType::Ftp);
+        }
+    }
+    None
+}
+
+/// QUIC long header (Initial, 0-RTT, Handshake, Retry) of version 1, 2 or
+/// an IETF draft. Short-header packets carry no version and can't be told
+/// apart from random bytes.
+fn is_quic_long_header(payload: &[u8]) -> bool {
+    if payload.len() < 7 || payload[0] & 0xc0 != 0xc0 {
+        return false;
+    }
+    let version = u32::from_be_bytes([payload[1], payload[2], payload[3], payload[4]]);
+    let known_version = version == 0x0000_0001 || version == 0x6b33_43cf || version & 0xffff_ff00 == 0xff00_0000;
+    // Destination connection ID is at most 20 bytes
+    known_version && payload[5] <= 20
+}
+
+/// DNS query or response with a single, well-formed question
+fn is_dns_message(message: &[u8]) -> bool {
+    if message.len() < 17 {
+        return false;
+    }
+    let opcode = (message[2] >> 3) & 0x0f;
+    let question_count = u16::from_be_bytes([message[4], message[5]]);
+    if !matches!(opcode, 0 | 1 | 2 | 4 | 5) || question_count != 1 {
+        return false;
+    }
+    
+    // Question name: length-prefixed labels up to the root label
+    let mut offset = 12;
+    loop {
+        let label = match message.get(offset) {
+            Some(label) => *label as usize,
+            None => return false,
+        };
+        if label == 0 {
+            offset += 1;
+            break;
+        }
+        if label > 63 || offset + 1 + label > message.len() {
+            return false;
+        }
+        offset += 1 + label;
+    }
+    
+    // Class IN, CHAOS, HESIOD or ANY; mDNS sets the top bit to ask for a unicast reply
+    match message.get(offset + 2..offset + 4) {
+        Some(class) => matches!(u16::from_be_bytes([class[0], class[1]]) & 0x7fff, 1 | 3 | 4 | 255),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn dns_query() -> Vec<u8> {
+        let mut query = vec![0x12, 0x34, 0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0];
+        query.extend_from_slice(b"\x07example\x03com\x00");
+        query.extend_from_slice(&[0, 1, 0, 1]);
+        query
+    }
+    
+    #[test]
+    fn test_text_protocols() {
+        assert_eq!(match_payload("TCP", b"GET /index.html HTTP/1.1\r\nHost: example.com\r\n\r\n"), Some(ProtocolType::Http));
+        assert_eq!(match_payload("TCP", b"HTTP/1.1 404 Not Found\r\n"), Some(ProtocolType::Http));
+        assert_eq!(match_payload("TCP", b"SSH-2.0-OpenSSH_9.6\r\n"), Some(ProtocolType::Ssh));
+        assert_eq!(match_payload("TCP", b"220 mail.example.com ESMTP Postfix\r\n"), Some(ProtocolType::Smtp));
+        assert_eq!(match_payload("TCP", b"220 (vsFTPd 3.0.5)\r\n"), Some(ProtocolType::Ftp));
+        assert_eq!(match_payload("TCP", b"* OK [CAPABILITY IMAP4rev1] Dovecot ready.\r\n"), Some(ProtocolType::Imap));
+        assert_eq!(match_payload("TCP", b"+OK POP3 server ready\r\n"), Some(ProtocolType::Pop3));
+        
+        // A method name alone isn't enough
+        assert_eq!(match_payload("TCP", b"GET something"), None);
+        assert_eq!(match_payload("TCP", b"220 ready\r\n"), None);
+        assert_eq!(match_payload("UDP", b"SSH-2.0-OpenSSH_9.6\r\n"), None);
+    }
+    
+    #[test]
+    fn test_binary_protocols() {
+        // TLS 1.2 record carrying a ClientHello
+        assert_eq!(match_payload("TCP", &[0x16, 0x03, 0x01, 0x02, 0x00, 0x01, 0x00, 0x01, 0xfc]), Some(ProtocolType::Tls));
+        
+        let mut mysql = vec![0x4a, 0, 0, 0, 10];
+        mysql.extend_from_slice(b"8.0.36\0");
+        mysql.resize(0x4a + 4, 0);
+        assert_eq!(match_payload("TCP", &mysql), Some(ProtocolType::MySql));
+        
+        assert_eq!(match_payload("TCP", &[0, 0, 0, 8, 0x04, 0xd2, 0x16, 0x2f]), Some(ProtocolType::Postgres));
+        let mut startup = vec![0, 0, 0, 0, 0, 3, 0, 0];
+        startup.extend_from_slice(b"user\0postgres\0\0");
+        startup[3] = startup.len() as u8;
+        assert_eq!(match_payload("TCP", &startup), Some(ProtocolType::Postgres));
+        
+        let mut mqtt = vec![0x10, 0x10, 0x00, 0x04];
+        mqtt.extend_from_slice(b"MQTT\x04\x02\x00\x3c\x00\x04test");
+        assert_eq!(match_payload("TCP", &mqtt), Some(ProtocolType::Mqtt));
+        
+        let mut quic = vec![0xc3, 0x00, 0x00, 0x00, 0x01, 0x08];
+        quic.extend_from_slice(&[0xab; 1200]);
+        assert_eq!(match_payload("UDP", &quic), Some(ProtocolType::Quic));
+        
+        let query = dns_query();
+        assert_eq!(match_payload("UDP", &query), Some(ProtocolType::Dns));
+        let mut over_tcp = (query.len() as u16).to_be_bytes().to_vec();
+        over_tcp.extend_from_slice(&query);
+        assert_eq!(match_payload("TCP", &over_tcp), Some(ProtocolType::Dns));
+        
+        // Truncated question, random bytes
+        assert_eq!(match_payload("UDP", &query[..20]), None);
+        assert_eq!(match_payload("UDP", &[0x42; 64]), None);
+        assert_eq!(match_payload("TCP", &[0x17, 0x03]), None);
+    }
+}
@@ -1,5 +1,13 @@
 pub mod pcap_engine;
 pub mod proc_parser;
+pub mod recorder;
+pub mod process_resolver;
+pub mod sock_diag;
+pub mod socket_table;
 
-pub use pcap_engine::{PcapEngine, PacketInfo, NetworkStats, CaptureError};
+pub use pcap_engine::{PcapEngine, PacketInfo, NetworkStats, CaptureError, ReplaySpeed};
+pub use recorder::{PcapngRecorder, CaptureCounters};
+pub use process_resolver::{ProcessResolver, ProcessInfo};
+pub use sock_diag::{SockDiag, TcpInfo};
+pub use socket_table::SocketTable;
 pub use proc_parser::{ProcNetParser, TcpConnection, InterfaceStats, TcpState};
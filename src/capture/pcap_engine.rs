@@ -1,5 +1,11 @@
-use pcap::{Capture, Device};
+use pcap::{Activated, Capture, Device, Linktype};
 use pnet::packet::{Packet, ethernet::EthernetPacket};
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::capture::recorder::{CaptureCounters, PcapngRecorder};
+use crate::config::settings::RecordConfig;
 
 use thiserror::Error;
 
@@ -16,6 +22,15 @@ pub enum CaptureError {
     
     #[error("Device error: {0}")]
     DeviceError(String),
+    
+    #[error("Cannot open capture file '{0}': {1}")]
+    FileError(String, String),
+    
+    #[error("Packet recording failed: {0}")]
+    RecordingError(String),
+    
+    #[error("Invalid BPF filter '{0}': {1}")]
+    InvalidFilter(String, String),
 }
 
 pub struct PacketInfo {
@@ -26,6 +41,28 @@ pub struct PacketInfo {
     pub dst_ip: Option<String>,
     pub src_port: Option<u16>,
     pub dst_port: Option<u16>,
+    /// Raw TCP flag bits (`pnet::packet::tcp::TcpFlags`), for TCP packets only
+    pub tcp_flags: Option<u8>,
+    /// Sequence number of the first payload byte, for TCP packets only
+    pub tcp_seq: Option<u32>,
+    /// ICMP or ICMPv6 message type, for ICMP packets only
+    pub icmp_type: Option<u8>,
+    /// Bytes after the TCP/UDP header; empty for other packets and for
+    /// connections synthesized from /proc
+    pub payload: Vec<u8>,
+}
+
+impl PacketInfo {
+    /// Source and destination socket addresses, for packets that carry ports.
+    /// Works for both IPv4 and IPv6 (which needs brackets in "ip:port" form).
+    pub fn socket_addrs(&self) -> Option<(SocketAddr, SocketAddr)> {
+        let src_ip: IpAddr = self.src_ip.as_ref()?.parse().ok()?;
+        let dst_ip: IpAddr = self.dst_ip.as_ref()?.parse().ok()?;
+        Some((
+            SocketAddr::new(src_ip, self.src_port?),
+            SocketAddr::new(dst_ip, self.dst_port?),
+        ))
+    }
 }
 
 pub struct NetworkStats {
@@ -35,10 +72,102 @@ pub struct NetworkStats {
     pub interface: String,
 }
 
+/// Pacing applied when replaying a saved capture file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ReplaySpeed {
+    /// Deliver packets with the same spacing they were captured with
+    RealTime,
+    /// Deliver packets N times faster than they were captured
+    Accelerated(f64),
+    /// Deliver packets as soon as they are read from the file
+    AsFastAsPossible,
+}
+
+impl FromStr for ReplaySpeed {
+    type Err = String;
+    
+    /// Accepts "realtime", "max"/"fast", or a multiplier such as "10x" / "2.5"
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.trim().to_lowercase();
+        match value.as_str() {
+            "realtime" | "real-time" => return Ok(ReplaySpeed::RealTime),
+            "max" | "fast" | "asap" => return Ok(ReplaySpeed::AsFastAsPossible),
+            _ => {}
+        }
+        
+        let factor: f64 = value.trim_end_matches('x').parse()
+            .map_err(|_| format!("Invalid replay speed '{}': use realtime, max or a multiplier like 10x", s))?;
+        
+        if !factor.is_finite() || factor <= 0.0 {
+            return Err(format!("Invalid replay speed '{}': multiplier must be greater than zero", s));
+        }
+        
+        if factor == 1.0 {
+            Ok(ReplaySpeed::RealTime)
+        } else {
+            Ok(ReplaySpeed::Accelerated(factor))
+        }
+    }
+}
+
+impl std::fmt::Display for ReplaySpeed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ReplaySpeed::RealTime => write!(f, "realtime"),
+            ReplaySpeed::Accelerated(factor) => write!(f, "{}x", factor),
+            ReplaySpeed::AsFastAsPossible => write!(f, "max"),
+        }
+    }
+}
+
+/// Replay clock for offline captures, mapping file timestamps onto wall time
+struct ReplayState {
+    speed: ReplaySpeed,
+    started: Instant,
+    first_timestamp: Option<SystemTime>,
+    pending: Option<PacketInfo>,
+    finished: bool,
+}
+
+impl ReplayState {
+    fn new(speed: ReplaySpeed) -> Self {
+        Self {
+            speed,
+            started: Instant::now(),
+            first_timestamp: None,
+            pending: None,
+            finished: false,
+        }
+    }
+    
+    /// Wall-clock offset from the start of the replay at which a packet
+    /// captured at `timestamp` should be delivered
+    fn delivery_offset(&mut self, timestamp: SystemTime) -> Duration {
+        let first = *self.first_timestamp.get_or_insert(timestamp);
+        let capture_offset = timestamp.duration_since(first).unwrap_or_default();
+        
+        match self.speed {
+            ReplaySpeed::RealTime => capture_offset,
+            ReplaySpeed::Accelerated(factor) => capture_offset.div_f64(factor),
+            ReplaySpeed::AsFastAsPossible => Duration::ZERO,
+        }
+    }
+    
+    fn is_due(&mut self, timestamp: SystemTime) -> bool {
+        let offset = self.delivery_offset(timestamp);
+        self.started.elapsed() >= offset
+    }
+}
+
 pub struct PcapEngine {
-    capture: Option<Capture<pcap::Active>>,
+    capture: Option<Capture<dyn Activated>>,
     interface: String,
     stats: NetworkStats,
+    linktype: Linktype,
+    replay: Option<ReplayState>,
+    recorder: Option<PcapngRecorder>,
+    last_stats_refresh: Instant,
+    filter: Option<String>,
 }
 
 impl PcapEngine {
@@ -64,10 +193,10 @@ impl PcapEngine {
         
         // CRITICAL: Handle permissions gracefully on Rocky Linux
         let device = Device::from(interface.as_str());
-        let capture = match Capture::from_device(device) {
+        let capture: Capture<dyn Activated> = match Capture::from_device(device) {
             Ok(cap) => {
                 match cap.promisc(false).timeout(1000).open() {
-                    Ok(active_cap) => Some(active_cap),
+                    Ok(active_cap) => active_cap.into(),
                     Err(e) => {
                         eprintln!("Failed to open capture device: {}", e);
                         eprintln!("Try running with sudo or setting capabilities:");
@@ -89,9 +218,43 @@ impl PcapEngine {
         };
         
         Ok(PcapEngine {
-            capture,
+            linktype: capture.get_datalink(),
+            capture: Some(capture),
             interface,
             stats,
+            replay: None,
+            recorder: None,
+            last_stats_refresh: Instant::now(),
+            filter: None,
+        })
+    }
+    
+    /// Open a saved pcap/pcapng file as the capture source. Packets are fed
+    /// through `get_next_packet` exactly like a live capture, paced according
+    /// to their original timestamps and `speed`.
+    pub fn from_file<P: AsRef<Path>>(path: P, speed: ReplaySpeed) -> Result<Self, CaptureError> {
+        let path_str = path.as_ref().display().to_string();
+        
+        let capture: Capture<dyn Activated> = Capture::from_file(path.as_ref())
+            .map_err(|e| CaptureError::FileError(path_str.clone(), e.to_string()))?
+            .into();
+        
+        let stats = NetworkStats {
+            packets_captured: 0,
+            bytes_captured: 0,
+            packets_dropped: 0,
+            interface: path_str.clone(),
+        };
+        
+        Ok(PcapEngine {
+            linktype: capture.get_datalink(),
+            capture: Some(capture),
+            interface: path_str,
+            stats,
+            replay: Some(ReplayState::new(speed)),
+            recorder: None,
+            last_stats_refresh: Instant::now(),
+            filter: None,
         })
     }
     
@@ -104,22 +267,102 @@ impl PcapEngine {
             return Err(CaptureError::CaptureError("No capture device available".to_string()));
         }
         
-        println!("Starting packet capture on interface: {}", self.interface);
+        if self.replay.is_some() {
+            println!("Starting replay of capture file: {}", self.interface);
+        } else {
+            println!("Starting packet capture on interface: {}", self.interface);
+        }
+        Ok(())
+    }
+    
+    /// Compile `expression` into a kernel BPF program so non-matching packets
+    /// are dropped before they reach userspace
+    pub fn set_filter(&mut self, expression: &str) -> Result<(), CaptureError> {
+        let capture = self.capture.as_mut()
+            .ok_or_else(|| CaptureError::CaptureError("No active capture".to_string()))?;
+        
+        apply_filter(capture, expression)?;
+        
+        self.filter = Some(expression.to_string());
         Ok(())
     }
     
+    pub fn get_filter(&self) -> Option<&str> {
+        self.filter.as_deref()
+    }
+    
+    /// Write every packet read from now on (or those matching the record
+    /// filter) to rotating pcapng files
+    pub fn start_recording(&mut self, config: &RecordConfig) -> Result<(), CaptureError> {
+        let recorder = PcapngRecorder::new(config, &self.interface, self.linktype)?;
+        println!("Recording packets to {}", config.directory);
+        self.recorder = Some(recorder);
+        Ok(())
+    }
+    
+    /// Close the current capture file, including its statistics block
+    pub fn stop_recording(&mut self) -> Result<(), CaptureError> {
+        self.refresh_capture_stats();
+        match self.recorder.take() {
+            Some(mut recorder) => recorder.finish(),
+            None => Ok(()),
+        }
+    }
+    
+    pub fn get_recorder(&self) -> Option<&PcapngRecorder> {
+        self.recorder.as_ref()
+    }
+    
     pub fn get_next_packet(&mut self) -> Result<Option<PacketInfo>, CaptureError> {
+        if self.last_stats_refresh.elapsed() >= Duration::from_secs(1) {
+            self.refresh_capture_stats();
+        }
+        
+        // A replayed packet read earlier may still be waiting for its slot
+        if let Some(ref mut replay) = self.replay {
+            if let Some(pending) = replay.pending.take() {
+                if replay.is_due(pending.timestamp) {
+                    return Ok(Some(pending));
+                }
+                replay.pending = Some(pending);
+                return Ok(None);
+            }
+            if replay.finished {
+                return Ok(None);
+            }
+        }
+        
         if let Some(ref mut capture) = self.capture {
             match capture.next_packet() {
                 Ok(packet) => {
                     self.stats.packets_captured += 1;
                     self.stats.bytes_captured += packet.data.len() as u64;
                     
-                    let packet_data = packet.data.to_vec();
-                    let packet_info = Self::parse_packet_static(&packet_data);
+                    let timestamp = Self::header_timestamp(packet.header);
+                    let packet_info = Self::parse_packet_static(packet.data, self.linktype, timestamp);
+                    
+                    if let Some(ref mut recorder) = self.recorder {
+                        // A full disk must not take the live view down with it
+                        if let Err(e) = recorder.write_packet(timestamp, packet.data, packet.header.len) {
+                            log::warn!("{}", e);
+                        }
+                    }
+                    
+                    if let Some(ref mut replay) = self.replay {
+                        if !replay.is_due(packet_info.timestamp) {
+                            replay.pending = Some(packet_info);
+                            return Ok(None);
+                        }
+                    }
                     Ok(Some(packet_info))
                 },
                 Err(pcap::Error::TimeoutExpired) => Ok(None),
+                Err(pcap::Error::NoMorePackets) if self.replay.is_some() => {
+                    if let Some(ref mut replay) = self.replay {
+                        replay.finished = true;
+                    }
+                    Ok(None)
+                },
                 Err(e) => Err(CaptureError::CaptureError(format!("Packet capture error: {}", e))),
             }
         } else {
@@ -131,84 +374,304 @@ impl PcapEngine {
         &self.stats
     }
     
-    fn parse_packet_static(data: &[u8]) -> PacketInfo {
-        let timestamp = std::time::SystemTime::now();
+    /// Whether packets come from a capture file rather than a live interface
+    pub fn is_replay(&self) -> bool {
+        self.replay.is_some()
+    }
+    
+    /// Whether a replayed capture file has been fully delivered
+    pub fn is_replay_finished(&self) -> bool {
+        self.replay.as_ref()
+            .map(|replay| replay.finished && replay.pending.is_none())
+            .unwrap_or(false)
+    }
+    
+    pub fn get_replay_speed(&self) -> Option<ReplaySpeed> {
+        self.replay.as_ref().map(|replay| replay.speed)
+    }
+    
+    /// Pull kernel drop counters from libpcap (live captures only)
+    fn refresh_capture_stats(&mut self) {
+        self.last_stats_refresh = Instant::now();
+        if self.replay.is_some() {
+            return;
+        }
+        
+        if let Some(ref mut capture) = self.capture {
+            if let Ok(stat) = capture.stats() {
+                self.stats.packets_dropped = stat.dropped as u64 + stat.if_dropped as u64;
+                if let Some(ref mut recorder) = self.recorder {
+                    recorder.update_counters(CaptureCounters {
+                        received: stat.received as u64,
+                        dropped: stat.dropped as u64,
+                        if_dropped: stat.if_dropped as u64,
+                    });
+                }
+            }
+        }
+    }
+    
+    fn header_timestamp(header: &pcap::PacketHeader) -> SystemTime {
+        let secs = header.ts.tv_sec.max(0) as u64;
+        let micros = header.ts.tv_usec.clamp(0, 999_999) as u32;
+        UNIX_EPOCH + Duration::new(secs, micros * 1000)
+    }
+    
+    fn parse_packet_static(data: &[u8], linktype: Linktype, timestamp: SystemTime) -> PacketInfo {
         let length = data.len();
         
-        // Try to parse as Ethernet frame
-        if let Some(ethernet_packet) = EthernetPacket::new(data) {
-            match ethernet_packet.get_ethertype() {
-                pnet::packet::ethernet::EtherTypes::Ipv4 => {
-                    if let Some(ipv4_packet) = pnet::packet::ipv4::Ipv4Packet::new(ethernet_packet.payload()) {
-                        let src_ip = Some(ipv4_packet.get_source().to_string());
-                        let dst_ip = Some(ipv4_packet.get_destination().to_string());
-                        
-                        match ipv4_packet.get_next_level_protocol() {
-                            pnet::packet::ip::IpNextHeaderProtocols::Tcp => {
-                                if let Some(tcp_packet) = pnet::packet::tcp::TcpPacket::new(ipv4_packet.payload()) {
-                                    return PacketInfo {
-                                        timestamp,
-                                        length,
-                                        protocol: "TCP".to_string(),
-                                        src_ip,
-                                        dst_ip,
-                                        src_port: Some(tcp_packet.get_source()),
-                                        dst_port: Some(tcp_packet.get_destination()),
-                                    };
-                                }
-                            },
-                            pnet::packet::ip::IpNextHeaderProtocols::Udp => {
-                                if let Some(udp_packet) = pnet::packet::udp::UdpPacket::new(ipv4_packet.payload()) {
-                                    return PacketInfo {
-                                        timestamp,
-                                        length,
-                                        protocol: "UDP".to_string(),
-                                        src_ip,
-                                        dst_ip,
-                                        src_port: Some(udp_packet.get_source()),
-                                        dst_port: Some(udp_packet.get_destination()),
-                                    };
-                                }
-                            },
-                            _ => {
-                                return PacketInfo {
-                                    timestamp,
-                                    length,
-                                    protocol: format!("IPv4-{}", ipv4_packet.get_next_level_protocol()),
-                                    src_ip,
-                                    dst_ip,
-                                    src_port: None,
-                                    dst_port: None,
-                                };
-                            }
-                        }
+        // Linux cooked capture (the "any" device): 16-byte header, ethertype at offset 14
+        if linktype == Linktype::LINUX_SLL {
+            if data.len() >= 16 {
+                let ethertype = u16::from_be_bytes([data[14], data[15]]);
+                return Self::parse_network_layer(ethertype, &data[16..], length, timestamp);
+            }
+        } else if linktype == Linktype::RAW || linktype == Linktype(12) {
+            // Raw IP (DLT_RAW is 12 on some platforms): version nibble selects IPv4/IPv6
+            if let Some(first) = data.first() {
+                let ethertype = match first >> 4 {
+                    4 => 0x0800,
+                    6 => 0x86DD,
+                    _ => 0,
+                };
+                return Self::parse_network_layer(ethertype, data, length, timestamp);
+            }
+        } else if let Some(ethernet_packet) = EthernetPacket::new(data) {
+            // Try to parse as Ethernet frame
+            return Self::parse_network_layer(
+                ethernet_packet.get_ethertype().0,
+                ethernet_packet.payload(),
+                length,
+                timestamp,
+            );
+        }
+        
+        // Fallback for unknown packet types
+        PacketInfo {
+            timestamp,
+            length,
+            protocol: "Unknown".to_string(),
+            src_ip: None,
+            dst_ip: None,
+            src_port: None,
+            dst_port: None,
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
+        }
+    }
+    
+    /// Decode an IPv6 packet, walking the extension header chain down to the
+    /// transport header
+    fn parse_ipv6(ipv6_packet: &pnet::packet::ipv6::Ipv6Packet, length: usize, timestamp: SystemTime) -> PacketInfo {
+        use pnet::packet::ip::IpNextHeaderProtocols;
+        
+        let src_ip = Some(ipv6_packet.get_source().to_string());
+        let dst_ip = Some(ipv6_packet.get_destination().to_string());
+        let mut next_header = ipv6_packet.get_next_header();
+        let mut payload = ipv6_packet.payload();
+        
+        loop {
+            let header_len = match next_header {
+                IpNextHeaderProtocols::Hopopt
+                | IpNextHeaderProtocols::Ipv6Route
+                | IpNextHeaderProtocols::Ipv6Opts => {
+                    // Hdr Ext Len counts 8-octet units beyond the first 8 octets
+                    payload.get(1).map(|len| (*len as usize + 1) * 8)
+                },
+                IpNextHeaderProtocols::Ipv6Frag => {
+                    // Only the first fragment carries the transport header
+                    let fragment_offset = payload.get(2..4)
+                        .map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]) >> 3);
+                    if fragment_offset != Some(0) {
+                        return PacketInfo {
+                            timestamp,
+                            length,
+                            protocol: "IPv6-Fragment".to_string(),
+                            src_ip,
+                            dst_ip,
+                            src_port: None,
+                            dst_port: None,
+                            tcp_flags: None,
+                            tcp_seq: None,
+                            icmp_type: None,
+                            payload: Vec::new(),
+                        };
                     }
+                    Some(8)
                 },
-                pnet::packet::ethernet::EtherTypes::Ipv6 => {
-                    return PacketInfo {
-                        timestamp,
-                        length,
-                        protocol: "IPv6".to_string(),
-                        src_ip: None,
-                        dst_ip: None,
-                        src_port: None,
-                        dst_port: None,
-                    };
+                IpNextHeaderProtocols::Ah => {
+                    // Payload Len counts 4-octet units, minus 2
+                    payload.get(1).map(|len| (*len as usize + 2) * 4)
+                },
+                _ => break,
+            };
+            
+            match header_len {
+                Some(header_len) if payload.len() >= header_len => {
+                    next_header = pnet::packet::ip::IpNextHeaderProtocol(payload[0]);
+                    payload = &payload[header_len..];
                 },
                 _ => {
+                    // Truncated extension header
                     return PacketInfo {
                         timestamp,
                         length,
-                        protocol: format!("Ethernet-{:?}", ethernet_packet.get_ethertype()),
-                        src_ip: None,
-                        dst_ip: None,
+                        protocol: "IPv6".to_string(),
+                        src_ip,
+                        dst_ip,
                         src_port: None,
                         dst_port: None,
+                        tcp_flags: None,
+                        tcp_seq: None,
+                        icmp_type: None,
+                        payload: Vec::new(),
                     };
                 }
             }
         }
         
+        let icmp_type = payload.first().copied().filter(|_| next_header == IpNextHeaderProtocols::Icmpv6);
+        let (protocol, src_port, dst_port, tcp_flags, tcp_seq, payload) = match next_header {
+            IpNextHeaderProtocols::Tcp => match pnet::packet::tcp::TcpPacket::new(payload) {
+                Some(tcp_packet) => (
+                    "TCP".to_string(),
+                    Some(tcp_packet.get_source()),
+                    Some(tcp_packet.get_destination()),
+                    Some(tcp_packet.get_flags()),
+                    Some(tcp_packet.get_sequence()),
+                    tcp_packet.payload().to_vec(),
+                ),
+                None => ("TCP".to_string(), None, None, None, None, Vec::new()),
+            },
+            IpNextHeaderProtocols::Udp => match pnet::packet::udp::UdpPacket::new(payload) {
+                Some(udp_packet) => (
+                    "UDP".to_string(),
+                    Some(udp_packet.get_source()),
+                    Some(udp_packet.get_destination()),
+                    None,
+                    None,
+                    udp_packet.payload().to_vec(),
+                ),
+                None => ("UDP".to_string(), None, None, None, None, Vec::new()),
+            },
+            IpNextHeaderProtocols::Icmpv6 => ("ICMPv6".to_string(), None, None, None, None, Vec::new()),
+            other => (format!("IPv6-{}", other), None, None, None, None, Vec::new()),
+        };
+        
+        PacketInfo {
+            timestamp,
+            length,
+            protocol,
+            src_ip,
+            dst_ip,
+            src_port,
+            dst_port,
+            tcp_flags,
+            tcp_seq,
+            icmp_type,
+            payload,
+        }
+    }
+    
+    fn parse_network_layer(ethertype: u16, payload: &[u8], length: usize, timestamp: SystemTime) -> PacketInfo {
+        match pnet::packet::ethernet::EtherType(ethertype) {
+            pnet::packet::ethernet::EtherTypes::Ipv4 => {
+                if let Some(ipv4_packet) = pnet::packet::ipv4::Ipv4Packet::new(payload) {
+                    let src_ip = Some(ipv4_packet.get_source().to_string());
+                    let dst_ip = Some(ipv4_packet.get_destination().to_string());
+                    
+                    match ipv4_packet.get_next_level_protocol() {
+                        pnet::packet::ip::IpNextHeaderProtocols::Tcp => {
+                            if let Some(tcp_packet) = pnet::packet::tcp::TcpPacket::new(ipv4_packet.payload()) {
+                                return PacketInfo {
+                                    timestamp,
+                                    length,
+                                    protocol: "TCP".to_string(),
+                                    src_ip,
+                                    dst_ip,
+                                    src_port: Some(tcp_packet.get_source()),
+                                    dst_port: Some(tcp_packet.get_destination()),
+                                    tcp_flags: Some(tcp_packet.get_flags()),
+                                    tcp_seq: Some(tcp_packet.get_sequence()),
+                                    icmp_type: None,
+                                    payload: tcp_packet.payload().to_vec(),
+                                };
+                            }
+                        },
+                        pnet::packet::ip::IpNextHeaderProtocols::Udp => {
+                            if let Some(udp_packet) = pnet::packet::udp::UdpPacket::new(ipv4_packet.payload()) {
+                                return PacketInfo {
+                                    timestamp,
+                                    length,
+                                    protocol: "UDP".to_string(),
+                                    src_ip,
+                                    dst_ip,
+                                    src_port: Some(udp_packet.get_source()),
+                                    dst_port: Some(udp_packet.get_destination()),
+                                    tcp_flags: None,
+                                    tcp_seq: None,
+                                    icmp_type: None,
+                                    payload: udp_packet.payload().to_vec(),
+                                };
+                            }
+                        },
+                        pnet::packet::ip::IpNextHeaderProtocols::Icmp => {
+                            return PacketInfo {
+                                timestamp,
+                                length,
+                                protocol: "ICMP".to_string(),
+                                src_ip,
+                                dst_ip,
+                                src_port: None,
+                                dst_port: None,
+                                tcp_flags: None,
+                                tcp_seq: None,
+                                icmp_type: ipv4_packet.payload().first().copied(),
+                                payload: Vec::new(),
+                            };
+                        },
+                        _ => {
+                            return PacketInfo {
+                                timestamp,
+                                length,
+                                protocol: format!("IPv4-{}", ipv4_packet.get_next_level_protocol()),
+                                src_ip,
+                                dst_ip,
+                                src_port: None,
+                                dst_port: None,
+                                tcp_flags: None,
+                                tcp_seq: None,
+                                icmp_type: None,
+                                payload: Vec::new(),
+                            };
+                        }
+                    }
+                }
+            },
+            pnet::packet::ethernet::EtherTypes::Ipv6 => {
+                if let Some(ipv6_packet) = pnet::packet::ipv6::Ipv6Packet::new(payload) {
+                    return Self::parse_ipv6(&ipv6_packet, length, timestamp);
+                }
+            },
+            other => {
+                return PacketInfo {
+                    timestamp,
+                    length,
+                    protocol: format!("Ethernet-{:?}", other),
+                    src_ip: None,
+                    dst_ip: None,
+                    src_port: None,
+                    dst_port: None,
+                    tcp_flags: None,
+                    tcp_seq: None,
+                    icmp_type: None,
+                    payload: Vec::new(),
+                };
+            }
+        }
+        
         // Fallback for unknown packet types
         PacketInfo {
             timestamp,
@@ -218,6 +681,174 @@ impl PcapEngine {
             dst_ip: None,
             src_port: None,
             dst_port: None,
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
+        }
+    }
+}
+
+/// Compile and install a BPF filter, keeping libpcap's own complaint
+fn apply_filter<T: Activated + ?Sized>(capture: &mut Capture<T>, expression: &str) -> Result<(), CaptureError> {
+    capture.filter(expression, true).map_err(|e| {
+        let message = match e {
+            pcap::Error::PcapError(message) => message,
+            other => other.to_string(),
+        };
+        CaptureError::InvalidFilter(expression.to_string(), message)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_replay_speed_parsing() {
+        assert_eq!("realtime".parse::<ReplaySpeed>().unwrap(), ReplaySpeed::RealTime);
+        assert_eq!("1x".parse::<ReplaySpeed>().unwrap(), ReplaySpeed::RealTime);
+        assert_eq!("max".parse::<ReplaySpeed>().unwrap(), ReplaySpeed::AsFastAsPossible);
+        assert_eq!("10x".parse::<ReplaySpeed>().unwrap(), ReplaySpeed::Accelerated(10.0));
+        assert_eq!("2.5".parse::<ReplaySpeed>().unwrap(), ReplaySpeed::Accelerated(2.5));
+        assert!("0x".parse::<ReplaySpeed>().is_err());
+        assert!("slow".parse::<ReplaySpeed>().is_err());
+    }
+    
+    #[test]
+    fn test_replay_delivery_offset() {
+        let base = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let later = base + Duration::from_secs(10);
+        
+        let mut realtime = ReplayState::new(ReplaySpeed::RealTime);
+        assert_eq!(realtime.delivery_offset(base), Duration::ZERO);
+        assert_eq!(realtime.delivery_offset(later), Duration::from_secs(10));
+        
+        let mut accelerated = ReplayState::new(ReplaySpeed::Accelerated(5.0));
+        accelerated.delivery_offset(base);
+        assert_eq!(accelerated.delivery_offset(later), Duration::from_secs(2));
+        
+        let mut fastest = ReplayState::new(ReplaySpeed::AsFastAsPossible);
+        fastest.delivery_offset(base);
+        assert!(fastest.is_due(later));
+    }
+    
+    #[test]
+    fn test_invalid_filter_message() {
+        let mut capture = Capture::dead(Linktype::ETHERNET).unwrap();
+        assert!(apply_filter(&mut capture, "tcp port 80").is_ok());
+        
+        match apply_filter(&mut capture, "tcp prot 80") {
+            Err(CaptureError::InvalidFilter(expression, message)) => {
+                assert_eq!(expression, "tcp prot 80");
+                assert!(message.contains("syntax error"), "unexpected message: {}", message);
+            }
+            other => panic!("expected InvalidFilter, got {:?}", other),
         }
     }
+    
+    fn ipv6_header(next_header: u8, payload_len: u16) -> Vec<u8> {
+        let mut header = vec![0x60, 0, 0, 0];
+        header.extend_from_slice(&payload_len.to_be_bytes());
+        header.push(next_header);
+        header.push(64);
+        // 2001:db8::1 -> 2001:db8::2
+        header.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1]);
+        header.extend_from_slice(&[0x20, 0x01, 0x0d, 0xb8, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 2]);
+        header
+    }
+    
+    #[test]
+    fn test_parse_ipv6_tcp_through_extension_headers() {
+        // Hop-by-hop (8 bytes) -> destination options (16 bytes) -> TCP
+        let mut data = ipv6_header(0, 8 + 16 + 20);
+        data.extend_from_slice(&[60, 0, 0, 0, 0, 0, 0, 0]);
+        data.extend_from_slice(&[6, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        let mut tcp = vec![0u8; 20];
+        tcp[0..2].copy_from_slice(&40000u16.to_be_bytes());
+        tcp[2..4].copy_from_slice(&443u16.to_be_bytes());
+        tcp[4..8].copy_from_slice(&3_000_000_000u32.to_be_bytes());
+        tcp[12] = 0x50;
+        data.extend_from_slice(&tcp);
+        
+        let info = PcapEngine::parse_packet_static(&data, Linktype::RAW, UNIX_EPOCH);
+        assert_eq!(info.protocol, "TCP");
+        assert_eq!(info.src_ip.as_deref(), Some("2001:db8::1"));
+        assert_eq!(info.dst_ip.as_deref(), Some("2001:db8::2"));
+        assert_eq!(info.src_port, Some(40000));
+        assert_eq!(info.dst_port, Some(443));
+        assert_eq!(info.tcp_seq, Some(3_000_000_000));
+        
+        let (src, dst) = info.socket_addrs().unwrap();
+        assert_eq!(src.to_string(), "[2001:db8::1]:40000");
+        assert_eq!(dst.port(), 443);
+    }
+    
+    #[test]
+    fn test_parse_keeps_transport_payload() {
+        // IPv4 + UDP DNS query, followed by Ethernet-style trailing padding
+        let query = b"\x12\x34\x01\x00\x00\x01\x00\x00\x00\x00\x00\x00\x03www\x00\x00\x01\x00\x01";
+        let mut data = vec![0x45, 0, 0, 0, 0, 0, 0, 0, 64, 17, 0, 0, 10, 0, 0, 1, 10, 0, 0, 53];
+        data[2..4].copy_from_slice(&((20 + 8 + query.len()) as u16).to_be_bytes());
+        data.extend_from_slice(&[0xc3, 0x50, 0x00, 0x35]);
+        data.extend_from_slice(&((8 + query.len()) as u16).to_be_bytes());
+        data.extend_from_slice(&[0, 0]);
+        data.extend_from_slice(query);
+        data.extend_from_slice(&[0; 6]);
+        
+        let info = PcapEngine::parse_packet_static(&data, Linktype::RAW, UNIX_EPOCH);
+        assert_eq!(info.protocol, "UDP");
+        assert_eq!(info.dst_port, Some(53));
+        assert_eq!(info.payload, query.to_vec());
+    }
+    
+    #[test]
+    fn test_parse_ipv6_fragments() {
+        // First fragment (offset 0) still carries the UDP header
+        let mut first = ipv6_header(44, 8 + 8);
+        first.extend_from_slice(&[17, 0, 0x00, 0x01, 0, 0, 0, 7]);
+        first.extend_from_slice(&[0x14, 0xe9, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]);
+        let info = PcapEngine::parse_packet_static(&first, Linktype::RAW, UNIX_EPOCH);
+        assert_eq!(info.protocol, "UDP");
+        assert_eq!(info.src_port, Some(5353));
+        assert_eq!(info.dst_port, Some(53));
+        
+        // Later fragments have no transport header
+        let mut later = ipv6_header(44, 8 + 8);
+        later.extend_from_slice(&[17, 0, 0x00, 0xb8, 0, 0, 0, 7]);
+        later.extend_from_slice(&[0u8; 8]);
+        let info = PcapEngine::parse_packet_static(&later, Linktype::RAW, UNIX_EPOCH);
+        assert_eq!(info.protocol, "IPv6-Fragment");
+        assert_eq!(info.src_ip.as_deref(), Some("2001:db8::1"));
+        assert_eq!(info.src_port, None);
+    }
+    
+    #[test]
+    fn test_parse_icmpv6() {
+        let mut data = ipv6_header(58, 8);
+        data.extend_from_slice(&[128, 0, 0, 0, 0, 1, 0, 1]);
+        let info = PcapEngine::parse_packet_static(&data, Linktype::RAW, UNIX_EPOCH);
+        assert_eq!(info.protocol, "ICMPv6");
+        assert_eq!(info.icmp_type, Some(128));
+        assert_eq!(info.dst_ip.as_deref(), Some("2001:db8::2"));
+    }
+    
+    #[test]
+    fn test_parse_linux_cooked_packet() {
+        // SLL header (16 bytes) + IPv4 header (20 bytes) + UDP header (8 bytes)
+        let mut data = vec![0u8; 14];
+        data.extend_from_slice(&[0x08, 0x00]);
+        data.extend_from_slice(&[
+            0x45, 0x00, 0x00, 0x1c, 0x00, 0x00, 0x00, 0x00, 0x40, 0x11, 0x00, 0x00,
+            10, 0, 0, 1, 10, 0, 0, 2,
+        ]);
+        data.extend_from_slice(&[0x30, 0x39, 0x00, 0x35, 0x00, 0x08, 0x00, 0x00]);
+        
+        let timestamp = UNIX_EPOCH + Duration::from_secs(42);
+        let info = PcapEngine::parse_packet_static(&data, Linktype::LINUX_SLL, timestamp);
+        assert_eq!(info.protocol, "UDP");
+        assert_eq!(info.src_ip.as_deref(), Some("10.0.0.1"));
+        assert_eq!(info.dst_port, Some(53));
+        assert_eq!(info.timestamp, timestamp);
+    }
 }
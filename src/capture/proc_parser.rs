@@ -1,6 +1,7 @@
 use std::fs;
 
-use std::net::{Ipv4Addr, SocketAddr, SocketAddrV4};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, SocketAddrV4, SocketAddrV6};
+use crate::capture::sock_diag::TcpInfo;
 
 #[derive(Debug, Clone)]
 pub struct TcpConnection {
@@ -9,6 +10,13 @@ pub struct TcpConnection {
     pub state: TcpState,
     pub inode: u64,
     pub uid: u32,
+    /// Bytes queued for sending (not yet acked) and received but unread
+    pub tx_queue: u32,
+    pub rx_queue: u32,
+    /// Unrecovered retransmission timeouts on the connection right now
+    pub retransmits: u32,
+    /// Kernel tcp_info, only available from the netlink sock_diag backend
+    pub tcp_info: Option<TcpInfo>,
 }
 
 #[derive(Debug, Clone)]
@@ -43,19 +51,34 @@ pub struct InterfaceStats {
 pub struct ProcNetParser;
 
 impl ProcNetParser {
-    /// Parse /proc/net/tcp for connection info - WORKS ON KERNEL 5.x
+    /// Parse /proc/net/tcp and /proc/net/tcp6 for connection info - WORKS ON KERNEL 5.x
     pub fn get_tcp_connections() -> Result<Vec<TcpConnection>, std::io::Error> {
         let content = fs::read_to_string("/proc/net/tcp")?;
-        let mut connections = Vec::new();
+        let mut connections = Self::parse_socket_table(&content);
         
-        for line in content.lines().skip(1) {
-            if let Some(conn) = Self::parse_tcp_line(line) {
-                connections.push(conn);
-            }
+        if let Some(content_v6) = Self::read_optional_table("/proc/net/tcp6")? {
+            connections.extend(Self::parse_socket_table(&content_v6));
         }
         Ok(connections)
     }
     
+    /// Parse the body of a /proc/net/{tcp,udp}[6] table, skipping the header line
+    pub fn parse_socket_table(content: &str) -> Vec<TcpConnection> {
+        content.lines()
+            .skip(1)
+            .filter_map(Self::parse_tcp_line)
+            .collect()
+    }
+    
+    /// Read an IPv6 table, which is absent when IPv6 is disabled on the host
+    fn read_optional_table(path: &str) -> Result<Option<String>, std::io::Error> {
+        match fs::read_to_string(path) {
+            Ok(content) => Ok(Some(content)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    
     /// Parse interface statistics - RELIABLE ON ALL KERNELS
     pub fn get_interface_stats(interface: &str) -> Result<InterfaceStats, std::io::Error> {
         let base_path = format!("/sys/class/net/{}/statistics", interface);
@@ -117,6 +140,14 @@ impl ProcNetParser {
         let state_num = u8::from_str_radix(fields[3], 16).ok()?;
         let state = Self::parse_tcp_state(state_num);
         
+        // Parse send/receive queue sizes (format: XXXXXXXX:XXXX)
+        let (tx_queue, rx_queue) = fields[4].split_once(':')?;
+        let tx_queue = u32::from_str_radix(tx_queue, 16).ok()?;
+        let rx_queue = u32::from_str_radix(rx_queue, 16).ok()?;
+        
+        // Parse outstanding retransmits
+        let retransmits = u32::from_str_radix(fields[6], 16).ok()?;
+        
         // Parse inode
         let inode = fields[9].parse().ok()?;
         
@@ -129,10 +160,14 @@ impl ProcNetParser {
             state,
             inode,
             uid,
+            tx_queue,
+            rx_queue,
+            retransmits,
+            tcp_info: None,
         })
     }
     
-    /// Parse address from hex format (XXXXXXXX:XXXX)
+    /// Parse address from hex format (XXXXXXXX:XXXX for IPv4, 32 hex digits for IPv6)
     pub fn parse_address(addr_str: &str) -> Option<SocketAddr> {
         let parts: Vec<&str> = addr_str.split(':').collect();
         if parts.len() != 2 {
@@ -141,6 +176,9 @@ impl ProcNetParser {
         
         // Parse IP address (little-endian hex)
         let ip_hex = parts[0];
+        if ip_hex.len() == 32 {
+            return Self::parse_ipv6_address(ip_hex, parts[1]);
+        }
         if ip_hex.len() != 8 {
             return None;
         }
@@ -159,6 +197,20 @@ impl ProcNetParser {
         Some(SocketAddr::V4(SocketAddrV4::new(ip, port)))
     }
     
+    /// Parse an IPv6 address: four 32-bit words, each printed in host
+    /// (little-endian) byte order
+    fn parse_ipv6_address(ip_hex: &str, port_hex: &str) -> Option<SocketAddr> {
+        let mut octets = [0u8; 16];
+        for word in 0..4 {
+            let value = u32::from_str_radix(ip_hex.get(word * 8..word * 8 + 8)?, 16).ok()?;
+            octets[word * 4..word * 4 + 4].copy_from_slice(&value.to_le_bytes());
+        }
+        
+        let port = u16::from_str_radix(port_hex, 16).ok()?;
+        
+        Some(SocketAddr::V6(SocketAddrV6::new(Ipv6Addr::from(octets), port, 0, 0)))
+    }
+    
     /// Convert numeric TCP state to enum
     pub fn parse_tcp_state(state: u8) -> TcpState {
         match state {
@@ -188,17 +240,18 @@ impl ProcNetParser {
         })
     }
     
-    /// Get UDP connections from /proc/net/udp
+    /// Get UDP connections from /proc/net/udp and /proc/net/udp6
     pub fn get_udp_connections() -> Result<Vec<TcpConnection>, std::io::Error> {
         let content = fs::read_to_string("/proc/net/udp")?;
-        let mut connections = Vec::new();
+        let mut connections = Self::parse_socket_table(&content);
         
-        for line in content.lines().skip(1) {
-            if let Some(mut conn) = Self::parse_tcp_line(line) {
-                // UDP connections don't have traditional states, mark as Listen
-                conn.state = TcpState::Listen;
-                connections.push(conn);
-            }
+        if let Some(content_v6) = Self::read_optional_table("/proc/net/udp6")? {
+            connections.extend(Self::parse_socket_table(&content_v6));
+        }
+        
+        // UDP connections don't have traditional states, mark as Listen
+        for conn in &mut connections {
+            conn.state = TcpState::Listen;
         }
         Ok(connections)
     }
@@ -250,4 +303,56 @@ mod tests {
             panic!("Failed to parse valid address");
         }
     }
+    
+    #[test]
+    fn test_parse_ipv6_address() {
+        let addr = ProcNetParser::parse_address("00000000000000000000000001000000:0277").unwrap();
+        assert_eq!(addr.to_string(), "[::1]:631");
+        
+        let addr = ProcNetParser::parse_address("B80D0120000000000000000005000000:01BB").unwrap();
+        assert_eq!(addr.to_string(), "[2001:db8::5]:443");
+        
+        // Wrong lengths are still rejected
+        assert!(ProcNetParser::parse_address("B80D01200000000000000000050000:01BB").is_none());
+    }
+    
+    #[test]
+    fn test_parse_tcp_fixture() {
+        let connections = ProcNetParser::parse_socket_table(include_str!("../../tests/fixtures/proc_net_tcp"));
+        assert_eq!(connections.len(), 4);
+        assert_eq!(connections[2].local_addr.to_string(), "192.168.1.101:22");
+        assert_eq!(connections[2].remote_addr.to_string(), "192.168.1.10:54321");
+        assert!(matches!(connections[2].state, TcpState::Established));
+        assert_eq!(connections[3].uid, 1000);
+        assert_eq!(connections[3].inode, 51017);
+        assert_eq!(connections[3].tx_queue, 36);
+        assert_eq!(connections[3].rx_queue, 0);
+        assert_eq!(connections[3].retransmits, 3);
+    }
+    
+    #[test]
+    fn test_parse_tcp6_fixture() {
+        let connections = ProcNetParser::parse_socket_table(include_str!("../../tests/fixtures/proc_net_tcp6"));
+        assert_eq!(connections.len(), 4);
+        
+        assert_eq!(connections[0].local_addr.to_string(), "[::]:22");
+        assert!(matches!(connections[0].state, TcpState::Listen));
+        
+        assert_eq!(connections[2].local_addr.to_string(), "[2001:db8::5]:443");
+        assert_eq!(connections[2].remote_addr.to_string(), "[2001:db8::a]:59044");
+        assert_eq!(connections[2].uid, 48);
+        assert_eq!(connections[2].inode, 61523);
+        
+        // IPv4-mapped addresses on dual-stack sockets
+        assert_eq!(connections[3].local_addr.to_string(), "[::ffff:127.0.0.1]:8080");
+        assert!(matches!(connections[3].state, TcpState::TimeWait));
+    }
+    
+    #[test]
+    fn test_parse_udp6_fixture() {
+        let connections = ProcNetParser::parse_socket_table(include_str!("../../tests/fixtures/proc_net_udp6"));
+        assert_eq!(connections.len(), 2);
+        assert_eq!(connections[1].local_addr.to_string(), "[fe80::5054:ff:fe7a:1a2c]:546");
+        assert_eq!(connections[1].uid, 993);
+    }
 }
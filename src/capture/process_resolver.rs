@@ -0,0 +1,195 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// Owning process of a socket
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProcessInfo {
+    pub pid: u32,
+    pub name: String,
+    pub cmdline: String,
+}
+
+/// Maps socket inodes to processes by scanning /proc/<pid>/fd, like `ss -p`
+pub struct ProcessResolver {
+    proc_root: PathBuf,
+    socket_owners: HashMap<u64, u32>,
+    processes: HashMap<u32, ProcessInfo>,
+    last_scan: Option<Instant>,
+    scan_interval: Duration,
+}
+
+impl ProcessResolver {
+    pub fn new() -> Self {
+        Self::with_proc_root("/proc")
+    }
+    
+    pub fn with_proc_root<P: Into<PathBuf>>(proc_root: P) -> Self {
+        Self {
+            proc_root: proc_root.into(),
+            socket_owners: HashMap::new(),
+            processes: HashMap::new(),
+            last_scan: None,
+            scan_interval: Duration::from_secs(2),
+        }
+    }
+    
+    /// Rescan fd tables if any of `inodes` is unknown and the last scan is
+    /// older than the scan interval
+    pub fn refresh(&mut self, inodes: &[u64]) {
+        let has_unknown = inodes.iter().any(|inode| *inode != 0 && !self.socket_owners.contains_key(inode));
+        let scan_due = self.last_scan
+            .map(|last| last.elapsed() >= self.scan_interval)
+            .unwrap_or(true);
+        
+        if has_unknown && scan_due {
+            self.scan();
+        }
+    }
+    
+    /// Walk every /proc/<pid>/fd directory and record socket:[inode] links.
+    /// Process names are cached and only read for pids not seen before.
+    pub fn scan(&mut self) {
+        self.last_scan = Some(Instant::now());
+        
+        let entries = match fs::read_dir(&self.proc_root) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        
+        let mut socket_owners = HashMap::new();
+        let mut live_pids = HashSet::new();
+        
+        for entry in entries.flatten() {
+            let pid: u32 = match entry.file_name().to_str().and_then(|name| name.parse().ok()) {
+                Some(pid) => pid,
+                None => continue,
+            };
+            
+            // Other users' fd tables need root or CAP_SYS_PTRACE; skip silently
+            let fds = match fs::read_dir(entry.path().join("fd")) {
+                Ok(fds) => fds,
+                Err(_) => continue,
+            };
+            
+            let mut owns_socket = false;
+            for fd in fds.flatten() {
+                if let Ok(target) = fs::read_link(fd.path()) {
+                    if let Some(inode) = Self::parse_socket_link(&target.to_string_lossy()) {
+                        socket_owners.entry(inode).or_insert(pid);
+                        owns_socket = true;
+                    }
+                }
+            }
+            
+            if owns_socket {
+                live_pids.insert(pid);
+            }
+        }
+        
+        // Drop cached names of processes that exited (or whose pid was reused
+        // by a process without sockets)
+        self.processes.retain(|pid, _| live_pids.contains(pid));
+        for pid in live_pids {
+            if !self.processes.contains_key(&pid) {
+                if let Some(info) = self.read_process(pid) {
+                    self.processes.insert(pid, info);
+                }
+            }
+        }
+        
+        self.socket_owners = socket_owners;
+    }
+    
+    pub fn lookup(&self, inode: u64) -> Option<&ProcessInfo> {
+        if inode == 0 {
+            return None;
+        }
+        let pid = self.socket_owners.get(&inode)?;
+        self.processes.get(pid)
+    }
+    
+    pub fn get_process(&self, pid: u32) -> Option<&ProcessInfo> {
+        self.processes.get(&pid)
+    }
+    
+    /// Parse an fd symlink target of the form "socket:[12345]"
+    pub fn parse_socket_link(target: &str) -> Option<u64> {
+        target.strip_prefix("socket:[")?
+            .strip_suffix(']')?
+            .parse()
+            .ok()
+    }
+    
+    fn read_process(&self, pid: u32) -> Option<ProcessInfo> {
+        let process_dir = self.proc_root.join(pid.to_string());
+        let name = fs::read_to_string(process_dir.join("comm")).ok()?.trim().to_string();
+        
+        // cmdline is NUL-separated; kernel threads have an empty one
+        let cmdline = fs::read(process_dir.join("cmdline"))
+            .map(|raw| {
+                raw.split(|byte| *byte == 0)
+                    .filter(|arg| !arg.is_empty())
+                    .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .unwrap_or_default();
+        
+        Some(ProcessInfo { pid, name, cmdline })
+    }
+}
+
+impl Default for ProcessResolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+    
+    fn fake_process(root: &std::path::Path, pid: u32, comm: &str, cmdline: &[u8], sockets: &[u64]) {
+        let dir = root.join(pid.to_string());
+        fs::create_dir_all(dir.join("fd")).unwrap();
+        fs::write(dir.join("comm"), format!("{}\n", comm)).unwrap();
+        fs::write(dir.join("cmdline"), cmdline).unwrap();
+        for (fd, inode) in sockets.iter().enumerate() {
+            symlink(format!("socket:[{}]", inode), dir.join("fd").join((fd + 3).to_string())).unwrap();
+        }
+        symlink("/dev/null", dir.join("fd").join("0")).unwrap();
+    }
+    
+    #[test]
+    fn test_parse_socket_link() {
+        assert_eq!(ProcessResolver::parse_socket_link("socket:[21839]"), Some(21839));
+        assert_eq!(ProcessResolver::parse_socket_link("pipe:[21839]"), None);
+        assert_eq!(ProcessResolver::parse_socket_link("/dev/null"), None);
+    }
+    
+    #[test]
+    fn test_resolve_socket_owners() {
+        let root = std::env::temp_dir().join(format!("network-monitor-proc-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fake_process(&root, 812, "sshd", b"/usr/sbin/sshd\0-D\0", &[21839]);
+        fake_process(&root, 4242, "curl", b"curl\0https://example.com\0", &[51017, 51018]);
+        fs::create_dir_all(root.join("self")).unwrap();
+        
+        let mut resolver = ProcessResolver::with_proc_root(&root);
+        resolver.refresh(&[21839, 51017]);
+        
+        let sshd = resolver.lookup(21839).unwrap();
+        assert_eq!(sshd.pid, 812);
+        assert_eq!(sshd.name, "sshd");
+        assert_eq!(sshd.cmdline, "/usr/sbin/sshd -D");
+        
+        assert_eq!(resolver.lookup(51018).unwrap().name, "curl");
+        assert!(resolver.lookup(0).is_none());
+        assert!(resolver.lookup(99999).is_none());
+        
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
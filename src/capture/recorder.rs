@@ -0,0 +1,355 @@
+use pcap::{BpfProgram, Capture, Linktype};
+use std::collections::VecDeque;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use crate::config::settings::RecordConfig;
+use super::pcap_engine::CaptureError;
+
+// pcapng block types
+const BLOCK_SECTION_HEADER: u32 = 0x0A0D_0D0A;
+const BLOCK_INTERFACE_DESCRIPTION: u32 = 0x0000_0001;
+const BLOCK_INTERFACE_STATISTICS: u32 = 0x0000_0005;
+const BLOCK_ENHANCED_PACKET: u32 = 0x0000_0006;
+const BYTE_ORDER_MAGIC: u32 = 0x1A2B_3C4D;
+
+// pcapng option codes
+const OPT_END: u16 = 0;
+const OPT_COMMENT: u16 = 1;
+const SHB_USERAPPL: u16 = 4;
+const IF_NAME: u16 = 2;
+const IF_DESCRIPTION: u16 = 3;
+const IF_TSRESOL: u16 = 9;
+const ISB_STARTTIME: u16 = 2;
+const ISB_ENDTIME: u16 = 3;
+const ISB_IFRECV: u16 = 4;
+const ISB_IFDROP: u16 = 5;
+const ISB_FILTERACCEPT: u16 = 6;
+const ISB_OSDROP: u16 = 7;
+
+const SNAPLEN: u32 = 65535;
+
+/// Counters reported by libpcap, written into interface statistics blocks
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct CaptureCounters {
+    pub received: u64,
+    pub dropped: u64,
+    pub if_dropped: u64,
+}
+
+/// Writes captured packets to size/time rotated pcapng files
+pub struct PcapngRecorder {
+    config: RecordConfig,
+    interface: String,
+    linktype: Linktype,
+    filter: Option<BpfProgram>,
+    writer: Option<BufWriter<File>>,
+    current_path: Option<PathBuf>,
+    current_size: u64,
+    opened_at: Instant,
+    file_start: SystemTime,
+    file_packets: u64,
+    files: VecDeque<PathBuf>,
+    sequence: u32,
+    counters: CaptureCounters,
+    packets_written: u64,
+}
+
+impl PcapngRecorder {
+    pub fn new(config: &RecordConfig, interface: &str, linktype: Linktype) -> Result<Self, CaptureError> {
+        let filter = match config.filter.as_deref().map(str::trim) {
+            Some(expression) if !expression.is_empty() => {
+                // A dead handle is enough to compile a filter for this link type
+                let compiler = Capture::dead(linktype)
+                    .map_err(|e| CaptureError::RecordingError(format!("Cannot compile record filter: {}", e)))?;
+                let program = compiler.compile(expression, true)
+                    .map_err(|e| CaptureError::RecordingError(format!("Invalid record filter '{}': {}", expression, e)))?;
+                Some(program)
+            },
+            _ => None,
+        };
+        
+        fs::create_dir_all(&config.directory)
+            .map_err(|e| CaptureError::RecordingError(format!("Cannot create '{}': {}", config.directory, e)))?;
+        
+        Ok(Self {
+            config: config.clone(),
+            interface: interface.to_string(),
+            linktype,
+            filter,
+            writer: None,
+            current_path: None,
+            current_size: 0,
+            opened_at: Instant::now(),
+            file_start: SystemTime::now(),
+            file_packets: 0,
+            files: VecDeque::new(),
+            sequence: 0,
+            counters: CaptureCounters::default(),
+            packets_written: 0,
+        })
+    }
+    
+    /// Record a packet if it passes the record filter, rotating files as needed
+    pub fn write_packet(&mut self, timestamp: SystemTime, data: &[u8], original_len: u32) -> Result<(), CaptureError> {
+        if let Some(ref filter) = self.filter {
+            if !filter.filter(data) {
+                return Ok(());
+            }
+        }
+        
+        if self.writer.is_none() || self.should_rotate() {
+            self.rotate()?;
+        }
+        
+        let micros = timestamp.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64;
+        let mut body = Vec::with_capacity(20 + data.len() + 3);
+        body.extend_from_slice(&0u32.to_le_bytes()); // interface id
+        body.extend_from_slice(&((micros >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(micros as u32).to_le_bytes());
+        body.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        body.extend_from_slice(&original_len.to_le_bytes());
+        body.extend_from_slice(data);
+        pad_to_32(&mut body);
+        
+        self.write_block(BLOCK_ENHANCED_PACKET, &body)?;
+        self.file_packets += 1;
+        self.packets_written += 1;
+        Ok(())
+    }
+    
+    /// Latest libpcap counters, written into the next statistics block
+    pub fn update_counters(&mut self, counters: CaptureCounters) {
+        self.counters = counters;
+    }
+    
+    /// Close the current file, writing its interface statistics block
+    pub fn finish(&mut self) -> Result<(), CaptureError> {
+        if self.writer.is_some() {
+            self.write_statistics()?;
+            if let Some(mut writer) = self.writer.take() {
+                writer.flush().map_err(|e| self.io_error(e))?;
+            }
+        }
+        Ok(())
+    }
+    
+    pub fn get_current_file(&self) -> Option<&PathBuf> {
+        self.current_path.as_ref()
+    }
+    
+    pub fn get_files(&self) -> &VecDeque<PathBuf> {
+        &self.files
+    }
+    
+    pub fn get_packets_written(&self) -> u64 {
+        self.packets_written
+    }
+    
+    fn should_rotate(&self) -> bool {
+        let max_size = self.config.max_file_size_mb.saturating_mul(1024 * 1024);
+        if max_size > 0 && self.current_size >= max_size {
+            return true;
+        }
+        
+        self.config.rotate_interval_secs > 0
+            && self.opened_at.elapsed() >= Duration::from_secs(self.config.rotate_interval_secs)
+    }
+    
+    fn rotate(&mut self) -> Result<(), CaptureError> {
+        self.finish()?;
+        
+        self.sequence += 1;
+        let started = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let path = PathBuf::from(&self.config.directory)
+            .join(format!("{}-{}-{:04}.pcapng", self.config.file_prefix, started, self.sequence));
+        
+        let file = File::create(&path)
+            .map_err(|e| CaptureError::RecordingError(format!("Cannot create '{}': {}", path.display(), e)))?;
+        
+        self.writer = Some(BufWriter::new(file));
+        self.current_path = Some(path.clone());
+        self.current_size = 0;
+        self.opened_at = Instant::now();
+        self.file_start = SystemTime::now();
+        self.file_packets = 0;
+        
+        self.write_section_header()?;
+        self.write_interface_description()?;
+        
+        // Enforce the maximum file count, oldest first
+        self.files.push_back(path);
+        while self.config.max_files > 0 && self.files.len() > self.config.max_files {
+            if let Some(oldest) = self.files.pop_front() {
+                if let Err(e) = fs::remove_file(&oldest) {
+                    log::warn!("Failed to remove old capture file {}: {}", oldest.display(), e);
+                }
+            }
+        }
+        
+        Ok(())
+    }
+    
+    fn write_section_header(&mut self) -> Result<(), CaptureError> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&BYTE_ORDER_MAGIC.to_le_bytes());
+        body.extend_from_slice(&1u16.to_le_bytes()); // major version
+        body.extend_from_slice(&0u16.to_le_bytes()); // minor version
+        body.extend_from_slice(&(-1i64).to_le_bytes()); // section length unknown
+        push_option(&mut body, SHB_USERAPPL, format!("network-monitor {}", env!("CARGO_PKG_VERSION")).as_bytes());
+        push_option(&mut body, OPT_END, &[]);
+        self.write_block(BLOCK_SECTION_HEADER, &body)
+    }
+    
+    fn write_interface_description(&mut self) -> Result<(), CaptureError> {
+        let mut body = Vec::new();
+        body.extend_from_slice(&(self.linktype.0 as u16).to_le_bytes());
+        body.extend_from_slice(&0u16.to_le_bytes()); // reserved
+        body.extend_from_slice(&SNAPLEN.to_le_bytes());
+        push_option(&mut body, IF_NAME, self.interface.as_bytes());
+        if let Some(ref filter) = self.config.filter {
+            push_option(&mut body, IF_DESCRIPTION, format!("record filter: {}", filter).as_bytes());
+        }
+        push_option(&mut body, IF_TSRESOL, &[6]); // microseconds
+        push_option(&mut body, OPT_END, &[]);
+        self.write_block(BLOCK_INTERFACE_DESCRIPTION, &body)
+    }
+    
+    fn write_statistics(&mut self) -> Result<(), CaptureError> {
+        let now = timestamp_micros(SystemTime::now());
+        let mut body = Vec::new();
+        body.extend_from_slice(&0u32.to_le_bytes()); // interface id
+        body.extend_from_slice(&((now >> 32) as u32).to_le_bytes());
+        body.extend_from_slice(&(now as u32).to_le_bytes());
+        push_option(&mut body, ISB_STARTTIME, &split_timestamp(timestamp_micros(self.file_start)));
+        push_option(&mut body, ISB_ENDTIME, &split_timestamp(now));
+        push_option(&mut body, ISB_IFRECV, &self.counters.received.to_le_bytes());
+        push_option(&mut body, ISB_IFDROP, &self.counters.if_dropped.to_le_bytes());
+        push_option(&mut body, ISB_FILTERACCEPT, &self.file_packets.to_le_bytes());
+        push_option(&mut body, ISB_OSDROP, &self.counters.dropped.to_le_bytes());
+        push_option(&mut body, OPT_COMMENT, b"counters are cumulative since capture start");
+        push_option(&mut body, OPT_END, &[]);
+        self.write_block(BLOCK_INTERFACE_STATISTICS, &body)
+    }
+    
+    fn write_block(&mut self, block_type: u32, body: &[u8]) -> Result<(), CaptureError> {
+        let total_length = (body.len() + 12) as u32;
+        let result = match self.writer {
+            Some(ref mut writer) => writer.write_all(&block_type.to_le_bytes())
+                .and_then(|_| writer.write_all(&total_length.to_le_bytes()))
+                .and_then(|_| writer.write_all(body))
+                .and_then(|_| writer.write_all(&total_length.to_le_bytes())),
+            None => return Err(CaptureError::RecordingError("No capture file open".to_string())),
+        };
+        result.map_err(|e| self.io_error(e))?;
+        self.current_size += total_length as u64;
+        Ok(())
+    }
+    
+    fn io_error(&self, error: std::io::Error) -> CaptureError {
+        let path = self.current_path.as_ref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_default();
+        CaptureError::RecordingError(format!("Write to '{}' failed: {}", path, error))
+    }
+}
+
+impl Drop for PcapngRecorder {
+    fn drop(&mut self) {
+        if let Err(e) = self.finish() {
+            log::warn!("Failed to finalize capture file: {}", e);
+        }
+    }
+}
+
+fn push_option(body: &mut Vec<u8>, code: u16, value: &[u8]) {
+    body.extend_from_slice(&code.to_le_bytes());
+    body.extend_from_slice(&(value.len() as u16).to_le_bytes());
+    body.extend_from_slice(value);
+    pad_to_32(body);
+}
+
+fn pad_to_32(body: &mut Vec<u8>) {
+    body.resize(body.len().next_multiple_of(4), 0);
+}
+
+fn timestamp_micros(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH).unwrap_or_default().as_micros() as u64
+}
+
+fn split_timestamp(micros: u64) -> [u8; 8] {
+    let mut bytes = [0u8; 8];
+    bytes[..4].copy_from_slice(&((micros >> 32) as u32).to_le_bytes());
+    bytes[4..].copy_from_slice(&(micros as u32).to_le_bytes());
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn test_config(name: &str) -> RecordConfig {
+        let directory = std::env::temp_dir()
+            .join(format!("network-monitor-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        RecordConfig {
+            enabled: true,
+            directory: directory.display().to_string(),
+            file_prefix: "test".to_string(),
+            filter: None,
+            max_file_size_mb: 0,
+            rotate_interval_secs: 0,
+            max_files: 2,
+        }
+    }
+    
+    fn block_types(data: &[u8]) -> Vec<u32> {
+        let mut types = Vec::new();
+        let mut offset = 0;
+        while offset + 8 <= data.len() {
+            let block_type = u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap());
+            let length = u32::from_le_bytes(data[offset + 4..offset + 8].try_into().unwrap()) as usize;
+            types.push(block_type);
+            offset += length;
+        }
+        types
+    }
+    
+    #[test]
+    fn test_pcapng_block_layout() {
+        let config = test_config("layout");
+        let mut recorder = PcapngRecorder::new(&config, "eth0", Linktype::ETHERNET).unwrap();
+        recorder.write_packet(SystemTime::now(), &[0xAA; 61], 61).unwrap();
+        recorder.update_counters(CaptureCounters { received: 5, dropped: 1, if_dropped: 0 });
+        let path = recorder.get_current_file().unwrap().clone();
+        recorder.finish().unwrap();
+        
+        let data = fs::read(&path).unwrap();
+        assert_eq!(data.len(), data.len().next_multiple_of(4));
+        assert_eq!(
+            block_types(&data),
+            vec![BLOCK_SECTION_HEADER, BLOCK_INTERFACE_DESCRIPTION, BLOCK_ENHANCED_PACKET, BLOCK_INTERFACE_STATISTICS]
+        );
+        fs::remove_dir_all(&config.directory).unwrap();
+    }
+    
+    #[test]
+    fn test_rotation_keeps_max_files() {
+        let mut config = test_config("rotate");
+        config.max_file_size_mb = 1;
+        let mut recorder = PcapngRecorder::new(&config, "eth0", Linktype::ETHERNET).unwrap();
+        
+        // ~3 MB of packets forces several rotations with a 1 MB limit
+        let packet = vec![0u8; 1500];
+        for _ in 0..2200 {
+            recorder.write_packet(SystemTime::now(), &packet, 1500).unwrap();
+        }
+        recorder.finish().unwrap();
+        
+        assert_eq!(recorder.get_files().len(), 2);
+        assert_eq!(fs::read_dir(&config.directory).unwrap().count(), 2);
+        assert_eq!(recorder.get_packets_written(), 2200);
+        fs::remove_dir_all(&config.directory).unwrap();
+    }
+}
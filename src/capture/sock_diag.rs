@@ -0,0 +1,381 @@
+use std::io;
+use std::mem;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use crate::capture::proc_parser::{ProcNetParser, TcpConnection};
+
+// Netlink and inet_diag constants (linux/netlink.h, linux/sock_diag.h, linux/inet_diag.h)
+const NLMSG_HDR_LEN: usize = 16;
+const NLMSG_ERROR: u16 = 2;
+const NLMSG_DONE: u16 = 3;
+const NLM_F_REQUEST: u16 = 0x001;
+const NLM_F_DUMP: u16 = 0x300;
+const SOCK_DIAG_BY_FAMILY: u16 = 20;
+const INET_DIAG_REQ_V2_LEN: usize = 56;
+const INET_DIAG_MSG_LEN: usize = 72;
+const INET_DIAG_INFO: u16 = 2;
+const RTA_HDR_LEN: usize = 4;
+
+/// Kernel `struct tcp_info` fields useful for spotting unhealthy connections
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TcpInfo {
+    /// Smoothed round trip time in microseconds
+    pub rtt_us: u32,
+    /// Round trip time variance in microseconds
+    pub rttvar_us: u32,
+    /// Segments retransmitted over the connection's lifetime
+    pub total_retrans: u32,
+    /// Congestion window in segments
+    pub snd_cwnd: u32,
+    pub segs_out: u32,
+    pub bytes_acked: u64,
+    pub bytes_received: u64,
+}
+
+impl TcpInfo {
+    /// Decode the INET_DIAG_INFO attribute payload. Older kernels send a
+    /// shorter struct; fields past its end are left at zero.
+    pub fn parse(data: &[u8]) -> Self {
+        Self {
+            rtt_us: read_u32(data, 68).unwrap_or(0),
+            rttvar_us: read_u32(data, 72).unwrap_or(0),
+            snd_cwnd: read_u32(data, 80).unwrap_or(0),
+            total_retrans: read_u32(data, 100).unwrap_or(0),
+            bytes_acked: read_u64(data, 120).unwrap_or(0),
+            bytes_received: read_u64(data, 128).unwrap_or(0),
+            segs_out: read_u32(data, 136).unwrap_or(0),
+        }
+    }
+}
+
+/// Socket table source using NETLINK_SOCK_DIAG, the interface behind `ss`.
+/// Sockets come back as binary records, so large tables are far cheaper
+/// than formatting and parsing /proc/net/tcp, and each carries tcp_info.
+pub struct SockDiag {
+    socket: OwnedFd,
+    sequence: u32,
+}
+
+impl SockDiag {
+    pub fn open() -> io::Result<Self> {
+        let fd = unsafe {
+            libc::socket(libc::AF_NETLINK, libc::SOCK_RAW | libc::SOCK_CLOEXEC, libc::NETLINK_SOCK_DIAG)
+        };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let socket = unsafe { OwnedFd::from_raw_fd(fd) };
+        
+        // A dump never blocks for long; don't let a wedged kernel stall the UI
+        let timeout = libc::timeval { tv_sec: 1, tv_usec: 0 };
+        let result = unsafe {
+            libc::setsockopt(
+                socket.as_raw_fd(),
+                libc::SOL_SOCKET,
+                libc::SO_RCVTIMEO,
+                &timeout as *const libc::timeval as *const libc::c_void,
+                mem::size_of::<libc::timeval>() as libc::socklen_t,
+            )
+        };
+        if result < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        
+        Ok(Self { socket, sequence: 0 })
+    }
+    
+    /// Dump all IPv4 and IPv6 TCP sockets
+    pub fn get_tcp_connections(&mut self) -> io::Result<Vec<TcpConnection>> {
+        let mut connections = self.dump(libc::AF_INET as u8)?;
+        connections.extend(self.dump(libc::AF_INET6 as u8)?);
+        Ok(connections)
+    }
+    
+    fn dump(&mut self, family: u8) -> io::Result<Vec<TcpConnection>> {
+        self.sequence = self.sequence.wrapping_add(1);
+        let request = Self::build_request(family, self.sequence);
+        
+        let mut address: libc::sockaddr_nl = unsafe { mem::zeroed() };
+        address.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        let sent = unsafe {
+            libc::sendto(
+                self.socket.as_raw_fd(),
+                request.as_ptr() as *const libc::c_void,
+                request.len(),
+                0,
+                &address as *const libc::sockaddr_nl as *const libc::sockaddr,
+                mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+            )
+        };
+        if sent < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        
+        let mut connections = Vec::new();
+        let mut buffer = vec![0u8; 64 * 1024];
+        loop {
+            let received = unsafe {
+                libc::recv(self.socket.as_raw_fd(), buffer.as_mut_ptr() as *mut libc::c_void, buffer.len(), 0)
+            };
+            if received < 0 {
+                let error = io::Error::last_os_error();
+                if error.kind() == io::ErrorKind::Interrupted {
+                    continue;
+                }
+                return Err(error);
+            }
+            if received == 0 {
+                break;
+            }
+            
+            if Self::parse_messages(&buffer[..received as usize], self.sequence, &mut connections)? {
+                break;
+            }
+        }
+        
+        Ok(connections)
+    }
+    
+    /// nlmsghdr followed by an inet_diag_req_v2 asking for every TCP state
+    /// of `family`, with tcp_info attached
+    pub fn build_request(family: u8, sequence: u32) -> Vec<u8> {
+        let length = NLMSG_HDR_LEN + INET_DIAG_REQ_V2_LEN;
+        let mut request = Vec::with_capacity(length);
+        request.extend_from_slice(&(length as u32).to_ne_bytes());
+        request.extend_from_slice(&SOCK_DIAG_BY_FAMILY.to_ne_bytes());
+        request.extend_from_slice(&(NLM_F_REQUEST | NLM_F_DUMP).to_ne_bytes());
+        request.extend_from_slice(&sequence.to_ne_bytes());
+        request.extend_from_slice(&0u32.to_ne_bytes()); // port id: kernel
+        
+        request.push(family);
+        request.push(libc::IPPROTO_TCP as u8);
+        request.push(1 << (INET_DIAG_INFO - 1)); // extensions bitmask
+        request.push(0);
+        request.extend_from_slice(&u32::MAX.to_ne_bytes()); // all TCP states
+        request.resize(length, 0); // zeroed inet_diag_sockid: no filtering
+        request
+    }
+    
+    /// Parse one recv() worth of netlink messages. Returns true once the
+    /// dump is complete.
+    pub fn parse_messages(data: &[u8], sequence: u32, connections: &mut Vec<TcpConnection>) -> io::Result<bool> {
+        let mut offset = 0;
+        while offset + NLMSG_HDR_LEN <= data.len() {
+            let length = read_u32(data, offset).unwrap_or(0) as usize;
+            if length < NLMSG_HDR_LEN || offset + length > data.len() {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated netlink message"));
+            }
+            
+            let message_type = read_u16(data, offset + 4).unwrap_or(0);
+            let message_sequence = read_u32(data, offset + 8).unwrap_or(0);
+            let payload = &data[offset + NLMSG_HDR_LEN..offset + length];
+            offset += length.next_multiple_of(4);
+            
+            if message_sequence != sequence {
+                continue;
+            }
+            
+            match message_type {
+                NLMSG_DONE => return Ok(true),
+                NLMSG_ERROR => {
+                    // Payload is a negative errno (0 for a plain ack)
+                    let errno = read_u32(payload, 0).unwrap_or(0) as i32;
+                    if errno != 0 {
+                        return Err(io::Error::from_raw_os_error(-errno));
+                    }
+                    return Ok(true);
+                }
+                SOCK_DIAG_BY_FAMILY => {
+                    if let Some(connection) = Self::parse_diag_message(payload) {
+                        connections.push(connection);
+                    }
+                }
+                _ => {}
+            }
+        }
+        
+        Ok(false)
+    }
+    
+    /// Decode an inet_diag_msg and its attributes
+    fn parse_diag_message(payload: &[u8]) -> Option<TcpConnection> {
+        if payload.len() < INET_DIAG_MSG_LEN {
+            return None;
+        }
+        
+        let family = payload[0];
+        let state = ProcNetParser::parse_tcp_state(payload[1]);
+        
+        // inet_diag_sockid: ports and addresses are in network byte order
+        let local_port = u16::from_be_bytes([payload[4], payload[5]]);
+        let remote_port = u16::from_be_bytes([payload[6], payload[7]]);
+        let local_ip = Self::parse_ip(family, &payload[8..24])?;
+        let remote_ip = Self::parse_ip(family, &payload[24..40])?;
+        
+        let retransmits = payload[3] as u32;
+        let rx_queue = read_u32(payload, 56)?;
+        let tx_queue = read_u32(payload, 60)?;
+        let uid = read_u32(payload, 64)?;
+        let inode = read_u32(payload, 68)? as u64;
+        
+        let mut tcp_info = None;
+        let mut offset = INET_DIAG_MSG_LEN;
+        while offset + RTA_HDR_LEN <= payload.len() {
+            let attr_length = read_u16(payload, offset)? as usize;
+            let attr_type = read_u16(payload, offset + 2)?;
+            if attr_length < RTA_HDR_LEN || offset + attr_length > payload.len() {
+                break;
+            }
+            
+            if attr_type == INET_DIAG_INFO {
+                tcp_info = Some(TcpInfo::parse(&payload[offset + RTA_HDR_LEN..offset + attr_length]));
+            }
+            offset += attr_length.next_multiple_of(4);
+        }
+        
+        Some(TcpConnection {
+            local_addr: SocketAddr::new(local_ip, local_port),
+            remote_addr: SocketAddr::new(remote_ip, remote_port),
+            state,
+            inode,
+            uid,
+            tx_queue,
+            rx_queue,
+            retransmits,
+            tcp_info,
+        })
+    }
+    
+    fn parse_ip(family: u8, bytes: &[u8]) -> Option<IpAddr> {
+        if family == libc::AF_INET as u8 {
+            Some(IpAddr::V4(Ipv4Addr::new(bytes[0], bytes[1], bytes[2], bytes[3])))
+        } else if family == libc::AF_INET6 as u8 {
+            let octets: [u8; 16] = bytes.try_into().ok()?;
+            Some(IpAddr::V6(Ipv6Addr::from(octets)))
+        } else {
+            None
+        }
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_ne_bytes(data.get(offset..offset + 2)?.try_into().ok()?))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_ne_bytes(data.get(offset..offset + 4)?.try_into().ok()?))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_ne_bytes(data.get(offset..offset + 8)?.try_into().ok()?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::capture::TcpState;
+    
+    fn netlink_message(message_type: u16, sequence: u32, payload: &[u8]) -> Vec<u8> {
+        let length = NLMSG_HDR_LEN + payload.len();
+        let mut message = Vec::new();
+        message.extend_from_slice(&(length as u32).to_ne_bytes());
+        message.extend_from_slice(&message_type.to_ne_bytes());
+        message.extend_from_slice(&2u16.to_ne_bytes()); // NLM_F_MULTI
+        message.extend_from_slice(&sequence.to_ne_bytes());
+        message.extend_from_slice(&0u32.to_ne_bytes());
+        message.extend_from_slice(payload);
+        message.resize(length.next_multiple_of(4), 0);
+        message
+    }
+    
+    fn diag_message(tcp_info: &[u8]) -> Vec<u8> {
+        let mut payload = vec![0u8; INET_DIAG_MSG_LEN];
+        payload[0] = libc::AF_INET as u8;
+        payload[1] = 0x01; // ESTABLISHED
+        payload[3] = 1; // idiag_retrans
+        payload[4..6].copy_from_slice(&51000u16.to_be_bytes());
+        payload[6..8].copy_from_slice(&443u16.to_be_bytes());
+        payload[8..12].copy_from_slice(&[192, 168, 1, 10]);
+        payload[24..28].copy_from_slice(&[93, 184, 216, 34]);
+        payload[56..60].copy_from_slice(&120u32.to_ne_bytes());
+        payload[60..64].copy_from_slice(&4096u32.to_ne_bytes());
+        payload[64..68].copy_from_slice(&1000u32.to_ne_bytes());
+        payload[68..72].copy_from_slice(&51017u32.to_ne_bytes());
+        
+        payload.extend_from_slice(&((RTA_HDR_LEN + tcp_info.len()) as u16).to_ne_bytes());
+        payload.extend_from_slice(&INET_DIAG_INFO.to_ne_bytes());
+        payload.extend_from_slice(tcp_info);
+        payload
+    }
+    
+    #[test]
+    fn test_build_request() {
+        let request = SockDiag::build_request(libc::AF_INET6 as u8, 7);
+        assert_eq!(request.len(), 72);
+        assert_eq!(read_u32(&request, 0), Some(72));
+        assert_eq!(read_u16(&request, 4), Some(SOCK_DIAG_BY_FAMILY));
+        assert_eq!(read_u32(&request, 8), Some(7));
+        assert_eq!(request[16], libc::AF_INET6 as u8);
+        assert_eq!(request[17], 6); // IPPROTO_TCP
+        assert_eq!(request[18], 0b10); // INET_DIAG_INFO
+    }
+    
+    #[test]
+    fn test_parse_dump() {
+        let mut tcp_info = vec![0u8; 232];
+        tcp_info[68..72].copy_from_slice(&23_500u32.to_ne_bytes());
+        tcp_info[72..76].copy_from_slice(&4_200u32.to_ne_bytes());
+        tcp_info[80..84].copy_from_slice(&10u32.to_ne_bytes());
+        tcp_info[100..104].copy_from_slice(&3u32.to_ne_bytes());
+        tcp_info[120..128].copy_from_slice(&1_048_576u64.to_ne_bytes());
+        tcp_info[128..136].copy_from_slice(&52_000u64.to_ne_bytes());
+        tcp_info[136..140].copy_from_slice(&900u32.to_ne_bytes());
+        
+        let mut data = netlink_message(SOCK_DIAG_BY_FAMILY, 1, &diag_message(&tcp_info));
+        data.extend(netlink_message(NLMSG_DONE, 1, &0i32.to_ne_bytes()));
+        
+        let mut connections = Vec::new();
+        assert!(SockDiag::parse_messages(&data, 1, &mut connections).unwrap());
+        assert_eq!(connections.len(), 1);
+        
+        let conn = &connections[0];
+        assert_eq!(conn.local_addr.to_string(), "192.168.1.10:51000");
+        assert_eq!(conn.remote_addr.to_string(), "93.184.216.34:443");
+        assert!(matches!(conn.state, TcpState::Established));
+        assert_eq!(conn.uid, 1000);
+        assert_eq!(conn.inode, 51017);
+        assert_eq!(conn.rx_queue, 120);
+        assert_eq!(conn.tx_queue, 4096);
+        assert_eq!(conn.retransmits, 1);
+        
+        let info = conn.tcp_info.as_ref().unwrap();
+        assert_eq!(info.rtt_us, 23_500);
+        assert_eq!(info.rttvar_us, 4_200);
+        assert_eq!(info.snd_cwnd, 10);
+        assert_eq!(info.total_retrans, 3);
+        assert_eq!(info.bytes_acked, 1_048_576);
+        assert_eq!(info.bytes_received, 52_000);
+        assert_eq!(info.segs_out, 900);
+    }
+    
+    #[test]
+    fn test_short_tcp_info_from_old_kernel() {
+        let mut tcp_info = vec![0u8; 104];
+        tcp_info[68..72].copy_from_slice(&800u32.to_ne_bytes());
+        
+        let data = netlink_message(SOCK_DIAG_BY_FAMILY, 1, &diag_message(&tcp_info));
+        let mut connections = Vec::new();
+        assert!(!SockDiag::parse_messages(&data, 1, &mut connections).unwrap());
+        
+        let info = connections[0].tcp_info.as_ref().unwrap();
+        assert_eq!(info.rtt_us, 800);
+        assert_eq!(info.bytes_acked, 0);
+    }
+    
+    #[test]
+    fn test_netlink_error() {
+        let data = netlink_message(NLMSG_ERROR, 1, &(-libc::EPERM).to_ne_bytes());
+        let error = SockDiag::parse_messages(&data, 1, &mut Vec::new()).unwrap_err();
+        assert_eq!(error.raw_os_error(), Some(libc::EPERM));
+    }
+}
@@ -0,0 +1,67 @@
+use crate::capture::proc_parser::{ProcNetParser, TcpConnection};
+use crate::capture::sock_diag::SockDiag;
+use crate::config::SocketBackend;
+
+/// Reads the TCP socket table from the backend chosen in the config
+pub struct SocketTable {
+    backend: SocketBackend,
+    sock_diag: Option<SockDiag>,
+}
+
+impl SocketTable {
+    pub fn new(backend: SocketBackend) -> Self {
+        let mut table = Self {
+            backend,
+            sock_diag: None,
+        };
+        
+        if backend != SocketBackend::Proc {
+            match SockDiag::open() {
+                Ok(sock_diag) => table.sock_diag = Some(sock_diag),
+                Err(e) => {
+                    log::warn!("sock_diag netlink unavailable, using /proc/net/tcp: {}", e);
+                }
+            }
+        }
+        
+        table
+    }
+    
+    pub fn get_tcp_connections(&mut self) -> Result<Vec<TcpConnection>, std::io::Error> {
+        if let Some(ref mut sock_diag) = self.sock_diag {
+            match sock_diag.get_tcp_connections() {
+                Ok(connections) => return Ok(connections),
+                Err(e) if self.backend == SocketBackend::Auto => {
+                    // e.g. inet_diag not loaded or blocked by a seccomp/LSM policy
+                    log::warn!("sock_diag dump failed, falling back to /proc/net/tcp: {}", e);
+                    self.sock_diag = None;
+                }
+                Err(e) => return Err(e),
+            }
+        } else if self.backend == SocketBackend::Netlink {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::Unsupported,
+                "sock_diag netlink socket could not be opened",
+            ));
+        }
+        
+        ProcNetParser::get_tcp_connections()
+    }
+    
+    /// Name of the backend currently in use, for display
+    pub fn active_backend(&self) -> &'static str {
+        if self.sock_diag.is_some() {
+            "netlink"
+        } else if self.backend == SocketBackend::Netlink {
+            "netlink (unavailable)"
+        } else {
+            "/proc"
+        }
+    }
+}
+
+impl Default for SocketTable {
+    fn default() -> Self {
+        Self::new(SocketBackend::default())
+    }
+}
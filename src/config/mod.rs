@@ -1,5 +1,5 @@
 pub mod settings;
 pub mod advanced_features;
 
-pub use settings::{Config, CaptureConfig, UiConfig, SystemConfig};
+pub use settings::{Config, CaptureConfig, FirewallConfig, HostnameConfig, RecordConfig, SocketBackend, UiConfig, SystemConfig};
 pub use advanced_features::AdvancedFeatures;
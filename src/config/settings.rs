@@ -6,6 +6,10 @@ pub struct Config {
     pub capture: CaptureConfig,
     pub ui: UiConfig,
     pub system: SystemConfig,
+    #[serde(default)]
+    pub firewall: FirewallConfig,
+    #[serde(default)]
+    pub hostnames: HostnameConfig,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -14,6 +18,69 @@ pub struct CaptureConfig {
     pub buffer_size: usize,
     pub timeout_ms: u32,
     pub promiscuous: bool,
+    /// BPF expression compiled into the kernel capture filter
+    #[serde(default)]
+    pub bpf_filter: Option<String>,
+    #[serde(default)]
+    pub record: RecordConfig,
+    /// Where the TCP socket table is read from
+    #[serde(default)]
+    pub socket_backend: SocketBackend,
+}
+
+/// `auto` tries netlink sock_diag and falls back to /proc/net/tcp
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SocketBackend {
+    #[default]
+    Auto,
+    Netlink,
+    Proc,
+}
+
+/// `[capture.record]`: write captured packets to rotating pcapng files
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct RecordConfig {
+    pub enabled: bool,
+    pub directory: String,
+    pub file_prefix: String,
+    /// Optional BPF expression; only matching packets are recorded
+    pub filter: Option<String>,
+    /// Rotate once the current file reaches this size (0 = never)
+    pub max_file_size_mb: u64,
+    /// Rotate after this many seconds (0 = never)
+    pub rotate_interval_secs: u64,
+    /// Delete the oldest files beyond this count (0 = keep all)
+    pub max_files: usize,
+}
+
+/// `[firewall]`: where the firewall ruleset is kept between runs
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct FirewallConfig {
+    /// JSON ruleset loaded at startup
+    pub ruleset_file: String,
+    /// Save rule changes back to `ruleset_file` as they are made
+    pub autosave: bool,
+    /// Numbered backups (`<ruleset_file>.~N~`) to keep (0 = none)
+    pub max_backups: usize,
+    /// Start from the built-in rule templates when there is no ruleset file yet
+    pub load_default_rules: bool,
+}
+
+/// `[hostnames]`: names shown in place of addresses, learned from DNS
+/// responses on the wire and optionally from PTR lookups
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default)]
+pub struct HostnameConfig {
+    /// Start with hostnames shown ('h' toggles)
+    pub show_hostnames: bool,
+    /// DNS server asked for the PTR record of addresses no captured response
+    /// named, as "ip" or "ip:port"; unset keeps the cache passive
+    pub ptr_resolver: Option<String>,
+    /// Upper bound on PTR queries sent per second
+    pub ptr_lookups_per_second: u32,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
@@ -38,6 +105,44 @@ impl Default for CaptureConfig {
             buffer_size: 65536,
             timeout_ms: 1000,
             promiscuous: false,
+            bpf_filter: None,
+            record: RecordConfig::default(),
+            socket_backend: SocketBackend::default(),
+        }
+    }
+}
+
+impl Default for RecordConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            directory: "captures".to_string(),
+            file_prefix: "network-monitor".to_string(),
+            filter: None,
+            max_file_size_mb: 100,
+            rotate_interval_secs: 3600,
+            max_files: 10,
+        }
+    }
+}
+
+impl Default for FirewallConfig {
+    fn default() -> Self {
+        Self {
+            ruleset_file: crate::firewall::persistence::DEFAULT_RULESET_FILE.to_string(),
+            autosave: true,
+            max_backups: 10,
+            load_default_rules: true,
+        }
+    }
+}
+
+impl Default for HostnameConfig {
+    fn default() -> Self {
+        Self {
+            show_hostnames: true,
+            ptr_resolver: None,
+            ptr_lookups_per_second: 5,
         }
     }
 }
@@ -59,12 +164,14 @@ impl Config {
             .output()
             .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string())
             .unwrap_or_else(|_| "unknown".to_string());
-            
+        
         let is_kernel_5x = kernel_version.starts_with("5.");
         
         Config {
             capture: CaptureConfig::default(),
             ui: UiConfig::default(),
+            firewall: FirewallConfig::default(),
+            hostnames: HostnameConfig::default(),
             system: SystemConfig {
                 kernel_version,
                 use_ebpf_fallback: is_kernel_5x,
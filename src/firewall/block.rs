@@ -0,0 +1,346 @@
+use std::net::SocketAddr;
+use crossterm::event::KeyCode;
+use ratatui::{
+    widgets::{Block, Borders, Clear, Paragraph, Wrap},
+    layout::Rect,
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    Frame,
+};
+use crate::capture::TcpConnection;
+use crate::firewall::engine::FirewallEngine;
+use crate::firewall::rules::{FirewallRule, RuleAction, RuleDirection, RuleProtocol};
+use crate::traffic::inspector::{FlowDirection, TrafficFlow};
+use crate::utils::net::canonical_addr;
+
+/// Above the default allow rules, so the block wins over them
+const BLOCK_PRIORITY: u8 = 255;
+
+/// What a rule created from a connection blocks
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlockScope {
+    /// All traffic to and from the remote address
+    RemoteIp,
+    /// All traffic to the service port, from anyone
+    ServicePort,
+    /// Just this connection: both addresses, both ports and the protocol
+    FiveTuple,
+}
+
+impl BlockScope {
+    const ALL: [BlockScope; 3] = [BlockScope::RemoteIp, BlockScope::ServicePort, BlockScope::FiveTuple];
+    
+    fn label(&self) -> &'static str {
+        match self {
+            BlockScope::RemoteIp => "Remote IP",
+            BlockScope::ServicePort => "Port",
+            BlockScope::FiveTuple => "This connection",
+        }
+    }
+}
+
+/// A connection seen in the Connections or Protocols tab, from this host's
+/// point of view
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlockTarget {
+    pub local: SocketAddr,
+    pub remote: SocketAddr,
+    pub protocol: RuleProtocol,
+    /// Whether the remote side opened the connection
+    pub inbound: bool,
+}
+
+impl BlockTarget {
+    /// The flow's first packet came from the side that opened it
+    pub fn from_flow(flow: &TrafficFlow) -> Self {
+        let inbound = match flow.direction {
+            FlowDirection::Inbound => true,
+            FlowDirection::Outbound => false,
+            // Same guess as the firewall engine makes per packet
+            FlowDirection::Internal | FlowDirection::Unknown => !FirewallEngine::is_local_ip(&flow.src_addr.ip()),
+        };
+        let (local, remote) = if inbound { (flow.dst_addr, flow.src_addr) } else { (flow.src_addr, flow.dst_addr) };
+        let protocol = match flow.transport.as_str() {
+            "TCP" => RuleProtocol::TCP,
+            "UDP" => RuleProtocol::UDP,
+            "ICMP" | "ICMPv6" => RuleProtocol::ICMP,
+            _ => RuleProtocol::Any,
+        };
+        Self { local, remote, protocol, inbound }
+    }
+    
+    /// `inbound` when the local port is one this host listens on. IPv4
+    /// peers of dual-stack sockets get IPv4 rules, which is what their
+    /// packets carry.
+    pub fn from_tcp_connection(connection: &TcpConnection, inbound: bool) -> Self {
+        Self {
+            local: canonical_addr(connection.local_addr),
+            remote: canonical_addr(connection.remote_addr),
+            protocol: RuleProtocol::TCP,
+            inbound,
+        }
+    }
+    
+    /// Port scopes need a protocol with ports
+    pub fn scopes(&self) -> Vec<BlockScope> {
+        let has_ports = matches!(self.protocol, RuleProtocol::TCP | RuleProtocol::UDP);
+        BlockScope::ALL
+            .into_iter()
+            .filter(|scope| has_ports || *scope == BlockScope::RemoteIp)
+            .collect()
+    }
+    
+    /// The rules that block the target; the engine assigns their ids
+    pub fn build_rules(&self, scope: BlockScope) -> Vec<FirewallRule> {
+        let block = |name: String, direction: RuleDirection, protocol: RuleProtocol| {
+            FirewallRule::new(0, name, RuleAction::Block, direction, protocol)
+                .with_priority(BLOCK_PRIORITY)
+        };
+        // The side that was connected to
+        let (client, server, direction) = if self.inbound {
+            (self.remote, self.local, RuleDirection::Inbound)
+        } else {
+            (self.local, self.remote, RuleDirection::Outbound)
+        };
+        
+        match scope {
+            // Rules match sources and destinations together, so each
+            // direction needs its own rule
+            BlockScope::RemoteIp => vec![
+                block(format!("Block from {}", self.remote.ip()), RuleDirection::Inbound, RuleProtocol::Any)
+                    .with_source_ip(self.remote.ip())
+                    .with_description(format!("Blocks all traffic from {}", self.remote.ip())),
+                block(format!("Block to {}", self.remote.ip()), RuleDirection::Outbound, RuleProtocol::Any)
+                    .with_destination_ip(self.remote.ip())
+                    .with_description(format!("Blocks all traffic to {}", self.remote.ip())),
+            ],
+            BlockScope::ServicePort => vec![
+                block(format!("Block port {}/{:?}", server.port(), self.protocol), direction, self.protocol.clone())
+                    .with_destination_port(server.port())
+                    .with_description(format!("Blocks {} connections to port {}", direction_word(self.inbound), server.port())),
+            ],
+            BlockScope::FiveTuple => vec![
+                block(format!("Block {} → {}/{:?}", client, server, self.protocol), direction, self.protocol.clone())
+                    .with_source_ip(client.ip())
+                    .with_source_port(client.port())
+                    .with_destination_ip(server.ip())
+                    .with_destination_port(server.port())
+                    .with_description(format!("Blocks the connection from {} to {}", client, server)),
+            ],
+        }
+    }
+}
+
+fn direction_word(inbound: bool) -> &'static str {
+    if inbound { "incoming" } else { "outgoing" }
+}
+
+/// What the caller should do after the dialog handled a key
+#[derive(Debug, Clone)]
+pub enum BlockDialogAction {
+    Continue,
+    /// Add these rules to the engine
+    Confirm(Vec<FirewallRule>),
+    Cancel,
+}
+
+/// Confirmation dialog for blocking a connection picked in another tab
+pub struct BlockDialog {
+    target: BlockTarget,
+    scopes: Vec<BlockScope>,
+    selected_scope: usize,
+}
+
+impl BlockDialog {
+    pub fn new(target: BlockTarget) -> Self {
+        let scopes = target.scopes();
+        Self { target, scopes, selected_scope: 0 }
+    }
+    
+    pub fn selected_scope(&self) -> BlockScope {
+        self.scopes[self.selected_scope]
+    }
+    
+    pub fn handle_key(&mut self, key: KeyCode) -> BlockDialogAction {
+        match key {
+            KeyCode::Esc | KeyCode::Char('n') | KeyCode::Char('q') => return BlockDialogAction::Cancel,
+            KeyCode::Enter | KeyCode::Char('y') => {
+                return BlockDialogAction::Confirm(self.target.build_rules(self.selected_scope()));
+            }
+            KeyCode::Left | KeyCode::Up | KeyCode::BackTab => {
+                self.selected_scope = (self.selected_scope + self.scopes.len() - 1) % self.scopes.len();
+            }
+            KeyCode::Right | KeyCode::Down | KeyCode::Tab => {
+                self.selected_scope = (self.selected_scope + 1) % self.scopes.len();
+            }
+            KeyCode::Char(c) => {
+                if let Some(index) = c.to_digit(10).filter(|digit| (1..=self.scopes.len() as u32).contains(digit)) {
+                    self.selected_scope = index as usize - 1;
+                }
+            }
+            _ => {}
+        }
+        BlockDialogAction::Continue
+    }
+    
+    /// Drawn over the middle of `area`
+    pub fn render(&self, f: &mut Frame, area: Rect) {
+        let width = area.width.min(72);
+        let height = area.height.min(14);
+        let popup = Rect::new(
+            area.x + (area.width - width) / 2,
+            area.y + (area.height - height) / 2,
+            width,
+            height,
+        );
+        
+        let heading = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+        let arrow = if self.target.inbound { "←" } else { "→" };
+        let mut lines = vec![
+            Line::from(vec![
+                Span::styled("Connection: ", heading),
+                Span::raw(format!("{} {} {} ({:?})", self.target.local, arrow, self.target.remote, self.target.protocol)),
+            ]),
+            Line::from(""),
+        ];
+        
+        let mut choices = vec![Span::styled("Block: ", heading)];
+        for (i, scope) in self.scopes.iter().enumerate() {
+            let style = if i == self.selected_scope {
+                Style::default().fg(Color::Black).bg(Color::Red).add_modifier(Modifier::BOLD)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            choices.push(Span::styled(format!(" {} {} ", i + 1, scope.label()), style));
+            choices.push(Span::raw(" "));
+        }
+        lines.push(Line::from(choices));
+        lines.push(Line::from(""));
+        
+        lines.push(Line::from(Span::styled("Rules to add:", heading)));
+        for rule in self.target.build_rules(self.selected_scope()) {
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {} ", rule.name), Style::default().fg(Color::Red)),
+                Span::styled(format!("(priority {})", rule.priority), Style::default().fg(Color::DarkGray)),
+            ]));
+            lines.push(Line::from(Span::styled(format!("    {}", rule.description), Style::default().fg(Color::Gray))));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(
+            format!("←/→ or 1-{}: choose | Enter/y: add to firewall | Esc/n: cancel", self.scopes.len()),
+            Style::default().fg(Color::Gray),
+        )));
+        
+        let dialog = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title("Block Connection").border_style(Style::default().fg(Color::Red)))
+            .wrap(Wrap { trim: false });
+        
+        f.render_widget(Clear, popup);
+        f.render_widget(dialog, popup);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+    use std::time::SystemTime;
+    use crate::analysis::protocols::ProtocolType;
+    
+    fn flow(src: &str, dst: &str, transport: &str, direction: FlowDirection) -> TrafficFlow {
+        TrafficFlow {
+            flow_id: format!("{}-{}", src, dst),
+            src_addr: src.parse().unwrap(),
+            dst_addr: dst.parse().unwrap(),
+            protocol: ProtocolType::Ssh,
+            transport: transport.to_string(),
+            direction,
+            start_time: SystemTime::now(),
+            last_seen: SystemTime::now(),
+            packet_count: 10,
+            byte_count: 1000,
+            src_bytes: 600,
+            dst_bytes: 400,
+            packets_per_second: 1.0,
+            bytes_per_second: 100.0,
+            is_active: true,
+            tls: None,
+            http: Vec::new(),
+        }
+    }
+    
+    #[test]
+    fn test_rules_from_inbound_flow() {
+        let target = BlockTarget::from_flow(&flow("203.0.113.9:40000", "192.168.1.10:22", "TCP", FlowDirection::Inbound));
+        assert_eq!(target.remote, "203.0.113.9:40000".parse().unwrap());
+        assert!(target.inbound);
+        
+        let attacker: IpAddr = "203.0.113.9".parse().unwrap();
+        let local: IpAddr = "192.168.1.10".parse().unwrap();
+        let rules = target.build_rules(BlockScope::RemoteIp);
+        assert_eq!(rules.len(), 2);
+        assert!(rules[0].matches_packet(&attacker, &local, 40000, 22, &RuleProtocol::TCP, &RuleDirection::Inbound));
+        assert!(rules[1].matches_packet(&local, &attacker, 5000, 443, &RuleProtocol::UDP, &RuleDirection::Outbound));
+        assert!(!rules[0].matches_packet(&local, &attacker, 22, 40000, &RuleProtocol::TCP, &RuleDirection::Outbound));
+        
+        let port = &target.build_rules(BlockScope::ServicePort)[0];
+        assert_eq!(port.name, "Block port 22/TCP");
+        assert!(port.matches_packet(&"198.51.100.1".parse().unwrap(), &local, 50000, 22, &RuleProtocol::TCP, &RuleDirection::Inbound));
+        
+        let tuple = &target.build_rules(BlockScope::FiveTuple)[0];
+        assert!(tuple.matches_packet(&attacker, &local, 40000, 22, &RuleProtocol::TCP, &RuleDirection::Inbound));
+        assert!(!tuple.matches_packet(&attacker, &local, 40001, 22, &RuleProtocol::TCP, &RuleDirection::Inbound));
+        
+        for scope in target.scopes() {
+            for rule in target.build_rules(scope) {
+                assert!(crate::firewall::nftables::NftablesBackend::compile_rule(&rule).is_ok(), "{}", rule.name);
+            }
+        }
+    }
+    
+    #[test]
+    fn test_rules_from_dual_stack_connection() {
+        let connection = TcpConnection {
+            local_addr: "[::ffff:192.168.1.10]:22".parse().unwrap(),
+            remote_addr: "[::ffff:203.0.113.9]:40000".parse().unwrap(),
+            state: crate::capture::TcpState::Established,
+            inode: 0,
+            uid: 0,
+            tx_queue: 0,
+            rx_queue: 0,
+            retransmits: 0,
+            tcp_info: None,
+        };
+        let target = BlockTarget::from_tcp_connection(&connection, true);
+        assert_eq!(target.remote, "203.0.113.9:40000".parse().unwrap());
+        
+        let attacker: IpAddr = "203.0.113.9".parse().unwrap();
+        let local: IpAddr = "192.168.1.10".parse().unwrap();
+        let rules = target.build_rules(BlockScope::FiveTuple);
+        assert!(rules[0].matches_packet(&attacker, &local, 40000, 22, &RuleProtocol::TCP, &RuleDirection::Inbound));
+    }
+    
+    #[test]
+    fn test_dialog_keys() {
+        // Outbound to a remote server: the port is the remote one
+        let target = BlockTarget::from_flow(&flow("192.168.1.10:51000", "198.51.100.7:6667", "TCP", FlowDirection::Unknown));
+        assert!(!target.inbound);
+        let mut dialog = BlockDialog::new(target);
+        assert!(matches!(dialog.handle_key(KeyCode::Right), BlockDialogAction::Continue));
+        assert_eq!(dialog.selected_scope(), BlockScope::ServicePort);
+        match dialog.handle_key(KeyCode::Enter) {
+            BlockDialogAction::Confirm(rules) => {
+                assert_eq!(rules[0].direction, RuleDirection::Outbound);
+                assert!(rules[0].destination_ports.as_ref().unwrap().contains(&6667));
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+        assert!(matches!(dialog.handle_key(KeyCode::Esc), BlockDialogAction::Cancel));
+        
+        // ICMP has no ports to block
+        let mut dialog = BlockDialog::new(BlockTarget::from_flow(&flow("203.0.113.9:0", "192.168.1.10:0", "ICMP", FlowDirection::Inbound)));
+        dialog.handle_key(KeyCode::Char('3'));
+        dialog.handle_key(KeyCode::Right);
+        assert_eq!(dialog.selected_scope(), BlockScope::RemoteIp);
+    }
+}
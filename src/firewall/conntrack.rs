@@ -0,0 +1,506 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::time::{Duration, SystemTime};
+use pnet::packet::tcp::TcpFlags;
+use serde::{Serialize, Deserialize};
+use crate::firewall::rules::RuleProtocol;
+
+// Idle timeouts, following the Linux nf_conntrack defaults
+const TCP_SYN_SENT_TIMEOUT: Duration = Duration::from_secs(120);
+const TCP_SYN_RECV_TIMEOUT: Duration = Duration::from_secs(60);
+const TCP_ESTABLISHED_TIMEOUT: Duration = Duration::from_secs(5 * 24 * 3600);
+const TCP_CLOSING_TIMEOUT: Duration = Duration::from_secs(120);
+const TCP_CLOSED_TIMEOUT: Duration = Duration::from_secs(10);
+const UDP_UNREPLIED_TIMEOUT: Duration = Duration::from_secs(30);
+const UDP_REPLIED_TIMEOUT: Duration = Duration::from_secs(180);
+const GENERIC_TIMEOUT: Duration = Duration::from_secs(30);
+
+const DEFAULT_MAX_ENTRIES: usize = 65536;
+
+const PROTOCOL_ICMP: u8 = 1;
+
+/// Connection state of a packet, with the same meaning as nftables' `ct state`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ConnState {
+    /// First packets of a connection, before anything came back
+    New,
+    /// Packets of a connection that has seen traffic in both directions
+    Established,
+    /// ICMP errors about a tracked connection
+    Related,
+    /// Packets that fit no connection or carry impossible TCP flags
+    Invalid,
+}
+
+impl ConnState {
+    pub const ALL: [ConnState; 4] = [ConnState::New, ConnState::Established, ConnState::Related, ConnState::Invalid];
+    
+    /// Keyword used by `nft ct state`
+    pub fn nft_keyword(&self) -> &'static str {
+        match self {
+            ConnState::New => "new",
+            ConnState::Established => "established",
+            ConnState::Related => "related",
+            ConnState::Invalid => "invalid",
+        }
+    }
+}
+
+impl fmt::Display for ConnState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.nft_keyword())
+    }
+}
+
+impl std::str::FromStr for ConnState {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "new" => Ok(ConnState::New),
+            "established" => Ok(ConnState::Established),
+            "related" => Ok(ConnState::Related),
+            "invalid" => Ok(ConnState::Invalid),
+            other => Err(format!("unknown connection state '{}'", other)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcpState {
+    SynSent,
+    SynReceived,
+    Established,
+    /// FIN seen in at least one direction
+    Closing,
+    /// FIN seen in both directions, or reset
+    Closed,
+}
+
+/// Endpoints in a fixed order, so both directions of a flow share a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct FlowKey {
+    protocol: u8,
+    low: SocketAddr,
+    high: SocketAddr,
+}
+
+impl FlowKey {
+    fn new(protocol: u8, a: SocketAddr, b: SocketAddr) -> Self {
+        let (low, high) = if a <= b { (a, b) } else { (b, a) };
+        Self { protocol, low, high }
+    }
+    
+    fn hosts(&self) -> (IpAddr, IpAddr) {
+        (self.low.ip(), self.high.ip())
+    }
+}
+
+fn host_pair(a: IpAddr, b: IpAddr) -> (IpAddr, IpAddr) {
+    if a <= b { (a, b) } else { (b, a) }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConntrackEntry {
+    pub protocol: RuleProtocol,
+    /// Endpoint that sent the first packet
+    pub original_src: SocketAddr,
+    pub original_dst: SocketAddr,
+    pub seen_reply: bool,
+    pub tcp_state: Option<TcpState>,
+    pub packets: u64,
+    pub first_seen: SystemTime,
+    pub last_seen: SystemTime,
+    fin_original: bool,
+    fin_reply: bool,
+}
+
+impl ConntrackEntry {
+    fn new(protocol: &RuleProtocol, src: SocketAddr, dst: SocketAddr, now: SystemTime) -> Self {
+        Self {
+            protocol: protocol.clone(),
+            original_src: src,
+            original_dst: dst,
+            seen_reply: false,
+            tcp_state: None,
+            packets: 0,
+            first_seen: now,
+            last_seen: now,
+            fin_original: false,
+            fin_reply: false,
+        }
+    }
+    
+    fn timeout(&self) -> Duration {
+        match (self.tcp_state, &self.protocol) {
+            (Some(TcpState::SynSent), _) => TCP_SYN_SENT_TIMEOUT,
+            (Some(TcpState::SynReceived), _) => TCP_SYN_RECV_TIMEOUT,
+            (Some(TcpState::Established), _) => TCP_ESTABLISHED_TIMEOUT,
+            (Some(TcpState::Closing), _) => TCP_CLOSING_TIMEOUT,
+            (Some(TcpState::Closed), _) => TCP_CLOSED_TIMEOUT,
+            (None, RuleProtocol::UDP) if self.seen_reply => UDP_REPLIED_TIMEOUT,
+            (None, RuleProtocol::UDP) => UDP_UNREPLIED_TIMEOUT,
+            (None, _) => GENERIC_TIMEOUT,
+        }
+    }
+    
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        now.duration_since(self.last_seen).unwrap_or_default() > self.timeout()
+    }
+    
+    fn state(&self) -> ConnState {
+        if self.seen_reply { ConnState::Established } else { ConnState::New }
+    }
+}
+
+/// Connection tracking table keyed on the 5-tuple.
+///
+/// TCP connections follow the SYN / SYN-ACK / FIN / RST flags; other protocols
+/// become established once a reply is seen and expire after a period of idleness.
+/// Packets are timed by their capture timestamp, so replayed captures behave the
+/// same as live traffic.
+pub struct ConnTracker {
+    entries: HashMap<FlowKey, ConntrackEntry>,
+    /// Non-ICMP flows by the pair of hosts they connect, for matching ICMP errors
+    host_pairs: HashMap<(IpAddr, IpAddr), HashSet<FlowKey>>,
+    max_entries: usize,
+}
+
+impl ConnTracker {
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_MAX_ENTRIES)
+    }
+    
+    /// Limit the table size; once full, new flows are judged but not tracked
+    pub fn with_capacity(max_entries: usize) -> Self {
+        Self {
+            entries: HashMap::new(),
+            host_pairs: HashMap::new(),
+            max_entries,
+        }
+    }
+    
+    /// Update the table with a packet and return the packet's connection state.
+    /// Packets without ports (ICMP) use port 0.
+    pub fn track(
+        &mut self,
+        src: SocketAddr,
+        dst: SocketAddr,
+        protocol: &RuleProtocol,
+        tcp_flags: Option<u8>,
+        icmp_type: Option<u8>,
+        now: SystemTime,
+    ) -> ConnState {
+        let icmp_error = icmp_type.is_some_and(|kind| is_icmp_error(src.ip(), kind));
+        if *protocol == RuleProtocol::ICMP && icmp_error && self.has_flow_between(src.ip(), dst.ip(), now) {
+            return ConnState::Related;
+        }
+        
+        if let Some(flags) = tcp_flags {
+            if !valid_tcp_flags(flags) {
+                return ConnState::Invalid;
+            }
+        }
+        
+        let key = FlowKey::new(protocol_number(protocol), src, dst);
+        
+        if self.entries.get(&key).is_some_and(|entry| entry.is_expired(now)) {
+            self.remove_entry(&key);
+        }
+        
+        if let Some(entry) = self.entries.get_mut(&key) {
+            if *protocol == RuleProtocol::TCP {
+                // A fresh SYN reuses the tuple of a connection that has finished
+                let flags = tcp_flags.unwrap_or(TcpFlags::ACK);
+                if is_syn(flags) && entry.tcp_state == Some(TcpState::Closed) {
+                    self.remove_entry(&key);
+                    return self.start_connection(key, protocol, src, dst, tcp_flags, now);
+                }
+            }
+            
+            entry.packets += 1;
+            entry.last_seen = now;
+            let is_reply = src != entry.original_src;
+            
+            if *protocol != RuleProtocol::TCP {
+                entry.seen_reply |= is_reply;
+                return entry.state();
+            }
+            return update_tcp(entry, tcp_flags.unwrap_or(TcpFlags::ACK), is_reply);
+        }
+        
+        self.start_connection(key, protocol, src, dst, tcp_flags, now)
+    }
+    
+    fn start_connection(
+        &mut self,
+        key: FlowKey,
+        protocol: &RuleProtocol,
+        src: SocketAddr,
+        dst: SocketAddr,
+        tcp_flags: Option<u8>,
+        now: SystemTime,
+    ) -> ConnState {
+        let mut entry = ConntrackEntry::new(protocol, src, dst, now);
+        entry.packets = 1;
+        
+        if *protocol == RuleProtocol::TCP {
+            // Without flag information (e.g. connections read from /proc) the
+            // packet is treated as mid-stream
+            let flags = tcp_flags.unwrap_or(TcpFlags::ACK);
+            if is_syn(flags) {
+                entry.tcp_state = Some(TcpState::SynSent);
+            } else if flags & (TcpFlags::SYN | TcpFlags::RST | TcpFlags::FIN) == 0 {
+                // Pick up connections that were open before tracking began,
+                // like nf_conntrack_tcp_loose
+                entry.tcp_state = Some(TcpState::Established);
+                entry.seen_reply = true;
+            } else {
+                // SYN-ACK, FIN or RST for a connection we know nothing about
+                return ConnState::Invalid;
+            }
+        }
+        
+        let state = entry.state();
+        if self.entries.len() >= self.max_entries {
+            self.expire(now);
+        }
+        if self.entries.len() < self.max_entries {
+            self.insert_entry(key, entry);
+        }
+        state
+    }
+    
+    fn insert_entry(&mut self, key: FlowKey, entry: ConntrackEntry) {
+        if key.protocol != PROTOCOL_ICMP {
+            self.host_pairs.entry(key.hosts()).or_default().insert(key);
+        }
+        self.entries.insert(key, entry);
+    }
+    
+    fn remove_entry(&mut self, key: &FlowKey) {
+        if let Some(keys) = self.host_pairs.get_mut(&key.hosts()) {
+            keys.remove(key);
+            if keys.is_empty() {
+                self.host_pairs.remove(&key.hosts());
+            }
+        }
+        self.entries.remove(key);
+    }
+    
+    fn has_flow_between(&self, a: IpAddr, b: IpAddr, now: SystemTime) -> bool {
+        self.host_pairs.get(&host_pair(a, b)).is_some_and(|keys| {
+            keys.iter().any(|key| self.entries.get(key).is_some_and(|entry| !entry.is_expired(now)))
+        })
+    }
+    
+    /// Drop idle connections, returning how many were removed
+    pub fn expire(&mut self, now: SystemTime) -> usize {
+        let expired: Vec<FlowKey> = self.entries.iter()
+            .filter(|(_, entry)| entry.is_expired(now))
+            .map(|(key, _)| *key)
+            .collect();
+        for key in &expired {
+            self.remove_entry(key);
+        }
+        expired.len()
+    }
+    
+    pub fn get_entries(&self) -> impl Iterator<Item = &ConntrackEntry> {
+        self.entries.values()
+    }
+    
+    pub fn established_count(&self) -> usize {
+        self.entries.values().filter(|entry| entry.seen_reply).count()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.entries.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+    
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.host_pairs.clear();
+    }
+}
+
+impl Default for ConnTracker {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn update_tcp(entry: &mut ConntrackEntry, flags: u8, is_reply: bool) -> ConnState {
+    let state = entry.tcp_state.unwrap_or(TcpState::Established);
+    
+    if flags & TcpFlags::RST != 0 {
+        entry.tcp_state = Some(TcpState::Closed);
+        return entry.state();
+    }
+    
+    if flags & TcpFlags::SYN != 0 {
+        let syn_ack = flags & TcpFlags::ACK != 0;
+        return match (state, is_reply, syn_ack) {
+            // Retransmitted SYN
+            (TcpState::SynSent, false, false) => ConnState::New,
+            (TcpState::SynSent | TcpState::SynReceived, true, true) => {
+                entry.tcp_state = Some(TcpState::SynReceived);
+                entry.seen_reply = true;
+                ConnState::Established
+            }
+            _ => ConnState::Invalid,
+        };
+    }
+    
+    if is_reply {
+        entry.seen_reply = true;
+    }
+    
+    if flags & TcpFlags::FIN != 0 {
+        if is_reply {
+            entry.fin_reply = true;
+        } else {
+            entry.fin_original = true;
+        }
+        entry.tcp_state = Some(if entry.fin_original && entry.fin_reply {
+            TcpState::Closed
+        } else {
+            TcpState::Closing
+        });
+    } else if state == TcpState::SynReceived && !is_reply {
+        // Final ACK of the handshake
+        entry.tcp_state = Some(TcpState::Established);
+    }
+    
+    entry.state()
+}
+
+fn is_syn(flags: u8) -> bool {
+    flags & (TcpFlags::SYN | TcpFlags::ACK) == TcpFlags::SYN
+}
+
+/// Flag combinations a real TCP stack can send (see `tcp_valid_flags` in Linux)
+fn valid_tcp_flags(flags: u8) -> bool {
+    const SYN: u8 = TcpFlags::SYN;
+    const ACK: u8 = TcpFlags::ACK;
+    const RST: u8 = TcpFlags::RST;
+    const FIN: u8 = TcpFlags::FIN;
+    const URG: u8 = TcpFlags::URG;
+    
+    let flags = flags & !(TcpFlags::PSH | TcpFlags::ECE | TcpFlags::CWR);
+    [SYN, SYN | URG, SYN | ACK, RST, RST | ACK, FIN | ACK, FIN | ACK | URG, ACK, ACK | URG].contains(&flags)
+}
+
+/// Error messages, which nftables relates to the flow they report on.
+/// Queries such as echo requests are flows of their own.
+fn is_icmp_error(ip: IpAddr, icmp_type: u8) -> bool {
+    match ip {
+        // Destination unreachable, time exceeded, parameter problem
+        IpAddr::V4(_) => matches!(icmp_type, 3 | 11 | 12),
+        // Destination unreachable, packet too big, time exceeded, parameter problem
+        IpAddr::V6(_) => matches!(icmp_type, 1..=4),
+    }
+}
+
+fn protocol_number(protocol: &RuleProtocol) -> u8 {
+    match protocol {
+        RuleProtocol::TCP => 6,
+        RuleProtocol::UDP => 17,
+        RuleProtocol::ICMP => PROTOCOL_ICMP,
+        RuleProtocol::Any => 0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    
+    const CLIENT: IpAddr = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 10));
+    const SERVER: IpAddr = IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34));
+    
+    fn addr(ip: IpAddr, port: u16) -> SocketAddr {
+        SocketAddr::new(ip, port)
+    }
+    
+    fn tcp(tracker: &mut ConnTracker, from_client: bool, flags: u8, now: SystemTime) -> ConnState {
+        let (client, server) = (addr(CLIENT, 51000), addr(SERVER, 443));
+        if from_client {
+            tracker.track(client, server, &RuleProtocol::TCP, Some(flags), None, now)
+        } else {
+            tracker.track(server, client, &RuleProtocol::TCP, Some(flags), None, now)
+        }
+    }
+    
+    #[test]
+    fn test_tcp_handshake_and_close() {
+        let mut tracker = ConnTracker::new();
+        let now = SystemTime::now();
+        
+        assert_eq!(tcp(&mut tracker, true, TcpFlags::SYN, now), ConnState::New);
+        assert_eq!(tcp(&mut tracker, false, TcpFlags::SYN | TcpFlags::ACK, now), ConnState::Established);
+        assert_eq!(tcp(&mut tracker, true, TcpFlags::ACK, now), ConnState::Established);
+        assert_eq!(tcp(&mut tracker, true, TcpFlags::PSH | TcpFlags::ACK, now), ConnState::Established);
+        assert_eq!(tracker.established_count(), 1);
+        
+        assert_eq!(tcp(&mut tracker, true, TcpFlags::FIN | TcpFlags::ACK, now), ConnState::Established);
+        assert_eq!(tcp(&mut tracker, false, TcpFlags::FIN | TcpFlags::ACK, now), ConnState::Established);
+        assert_eq!(tracker.get_entries().next().unwrap().tcp_state, Some(TcpState::Closed));
+        
+        // The port pair can be reused by a new connection
+        assert_eq!(tcp(&mut tracker, true, TcpFlags::SYN, now), ConnState::New);
+        assert_eq!(tracker.len(), 1);
+        
+        // Closed connections go away quickly
+        assert_eq!(tcp(&mut tracker, true, TcpFlags::RST, now), ConnState::New);
+        assert_eq!(tracker.expire(now + Duration::from_secs(11)), 1);
+    }
+    
+    #[test]
+    fn test_invalid_tcp_packets() {
+        let mut tracker = ConnTracker::new();
+        let now = SystemTime::now();
+        
+        // Xmas scan and a SYN-ACK nobody asked for
+        let xmas = TcpFlags::FIN | TcpFlags::PSH | TcpFlags::URG;
+        assert_eq!(tcp(&mut tracker, false, xmas, now), ConnState::Invalid);
+        assert_eq!(tcp(&mut tracker, false, TcpFlags::SYN | TcpFlags::ACK, now), ConnState::Invalid);
+        assert_eq!(tcp(&mut tracker, false, TcpFlags::SYN | TcpFlags::FIN, now), ConnState::Invalid);
+        assert!(tracker.is_empty());
+        
+        // Mid-stream traffic is picked up as established
+        assert_eq!(tcp(&mut tracker, false, TcpFlags::ACK, now), ConnState::Established);
+        assert_eq!(tracker.track(addr(CLIENT, 40000), addr(SERVER, 22), &RuleProtocol::TCP, None, None, now), ConnState::Established);
+    }
+    
+    #[test]
+    fn test_udp_and_related_icmp() {
+        let mut tracker = ConnTracker::new();
+        let now = SystemTime::now();
+        let dns = IpAddr::V4(Ipv4Addr::new(1, 1, 1, 1));
+        
+        let (client, resolver) = (addr(CLIENT, 53000), addr(dns, 53));
+        
+        assert_eq!(tracker.track(client, resolver, &RuleProtocol::UDP, None, None, now), ConnState::New);
+        assert_eq!(tracker.track(client, resolver, &RuleProtocol::UDP, None, None, now), ConnState::New);
+        assert_eq!(tracker.track(resolver, client, &RuleProtocol::UDP, None, None, now), ConnState::Established);
+        
+        // Port unreachable coming back from the resolver
+        let (icmp_resolver, icmp_client) = (addr(dns, 0), addr(CLIENT, 0));
+        assert_eq!(tracker.track(icmp_resolver, icmp_client, &RuleProtocol::ICMP, None, Some(3), now), ConnState::Related);
+        
+        // A ping is a flow of its own, even between hosts that already talk
+        assert_eq!(tracker.track(icmp_client, icmp_resolver, &RuleProtocol::ICMP, None, Some(8), now), ConnState::New);
+        assert_eq!(tracker.track(icmp_resolver, icmp_client, &RuleProtocol::ICMP, None, Some(0), now), ConnState::Established);
+        
+        // Replied flows live for 180s (UDP) and 30s (ICMP) of idleness
+        assert_eq!(tracker.expire(now + Duration::from_secs(60)), 1);
+        assert_eq!(tracker.expire(now + Duration::from_secs(181)), 1);
+        assert_eq!(tracker.track(icmp_resolver, icmp_client, &RuleProtocol::ICMP, None, Some(3), now), ConnState::New);
+    }
+}
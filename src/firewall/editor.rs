@@ -0,0 +1,754 @@
+use std::collections::{BTreeSet, VecDeque};
+use std::time::{Duration, SystemTime};
+use crossterm::event::KeyCode;
+use ipnetwork::IpNetwork;
+use ratatui::{
+    widgets::{Block, Borders, List, ListItem, Paragraph, Wrap},
+    layout::{Layout, Constraint, Direction, Rect},
+    style::{Color, Modifier, Style},
+    text::{Line, Span},
+    Frame,
+};
+use crate::firewall::conntrack::ConnState;
+use crate::firewall::engine::{FirewallEvent, FirewallEventKind};
+use crate::firewall::matchers::{AddressSet, PortRange, PortSet};
+use crate::firewall::nftables::NftablesBackend;
+use crate::firewall::ratelimit::{RateLimit, RateScope};
+use crate::firewall::rules::{FirewallRule, RuleAction, RuleDirection, RuleProtocol};
+use crate::firewall::schedule::RuleSchedule;
+use crate::utils::formatting::format_duration;
+
+const ACTIONS: [RuleAction; 5] = [
+    RuleAction::Allow,
+    RuleAction::Block,
+    RuleAction::Log,
+    RuleAction::LogAndBlock,
+    RuleAction::RateLimit(RateLimit::packets_per_second(10, 5, RateScope::PerSource)),
+];
+const DIRECTIONS: [RuleDirection; 3] = [RuleDirection::Inbound, RuleDirection::Outbound, RuleDirection::Bidirectional];
+const PROTOCOLS: [RuleProtocol; 4] = [RuleProtocol::TCP, RuleProtocol::UDP, RuleProtocol::ICMP, RuleProtocol::Any];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorField {
+    Name,
+    Action,
+    RateLimit,
+    Direction,
+    Protocol,
+    SourceIps,
+    DestinationIps,
+    SourcePorts,
+    DestinationPorts,
+    States,
+    Priority,
+    Expires,
+    Schedules,
+    Description,
+}
+
+impl EditorField {
+    const ALL: [EditorField; 14] = [
+        EditorField::Name,
+        EditorField::Action,
+        EditorField::RateLimit,
+        EditorField::Direction,
+        EditorField::Protocol,
+        EditorField::SourceIps,
+        EditorField::DestinationIps,
+        EditorField::SourcePorts,
+        EditorField::DestinationPorts,
+        EditorField::States,
+        EditorField::Priority,
+        EditorField::Expires,
+        EditorField::Schedules,
+        EditorField::Description,
+    ];
+    
+    fn label(&self) -> &'static str {
+        match self {
+            EditorField::Name => "Name",
+            EditorField::Action => "Action",
+            EditorField::RateLimit => "Rate limit",
+            EditorField::Direction => "Direction",
+            EditorField::Protocol => "Protocol",
+            EditorField::SourceIps => "Source IPs",
+            EditorField::DestinationIps => "Destination IPs",
+            EditorField::SourcePorts => "Source ports",
+            EditorField::DestinationPorts => "Destination ports",
+            EditorField::States => "Conn. states",
+            EditorField::Priority => "Priority",
+            EditorField::Expires => "Expires in",
+            EditorField::Schedules => "Schedule",
+            EditorField::Description => "Description",
+        }
+    }
+    
+    fn hint(&self) -> &'static str {
+        match self {
+            EditorField::Action | EditorField::Direction | EditorField::Protocol => "←/→ or Space to change",
+            EditorField::SourceIps | EditorField::DestinationIps => {
+                "Addresses or CIDR networks, comma separated (e.g. 10.0.0.0/8, 2001:db8::1). Prefix with ! to negate, empty = any"
+            }
+            EditorField::SourcePorts | EditorField::DestinationPorts => {
+                "Ports or ranges, comma separated (e.g. 22, 8000-8100). Prefix with ! to negate, empty = any"
+            }
+            EditorField::States => "Any of new, established, related, invalid; empty = any state",
+            EditorField::RateLimit => "For RateLimit rules, e.g. 10/s burst 5 per source or 125000 bytes/s per rule",
+            EditorField::Priority => "0-255, higher numbers are checked first",
+            EditorField::Expires => "Remove the rule after e.g. 30m, 2h or 1d 12h; empty = keep it",
+            EditorField::Schedules => "Local time windows like Mon-Fri 09:00-17:00, separated by ';'; empty = always",
+            EditorField::Name | EditorField::Description => "Free text",
+        }
+    }
+    
+    fn is_choice(&self) -> bool {
+        matches!(self, EditorField::Action | EditorField::Direction | EditorField::Protocol)
+    }
+}
+
+/// A problem that prevents the form from being saved
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EditorError {
+    /// `None` for problems with the rule as a whole
+    pub field: Option<EditorField>,
+    pub message: String,
+}
+
+impl EditorError {
+    fn new(field: Option<EditorField>, message: String) -> Self {
+        Self { field, message }
+    }
+}
+
+/// What the view should do after the editor handled a key
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EditorAction {
+    Continue,
+    Save,
+    Cancel,
+}
+
+/// Form state for creating or editing a `FirewallRule`.
+///
+/// Set fields are edited as text and only parsed on validation, so a
+/// half-typed network like `10.0.` doesn't fight the user.
+pub struct RuleEditor {
+    // Rule being edited; keeps id, counters and timestamps across the edit
+    base: FirewallRule,
+    is_new: bool,
+    name: String,
+    action: RuleAction,
+    // Kept while other actions are selected, used once RateLimit is
+    rate_limit: String,
+    direction: RuleDirection,
+    protocol: RuleProtocol,
+    source_ips: String,
+    destination_ips: String,
+    source_ports: String,
+    destination_ports: String,
+    states: String,
+    priority: String,
+    expires: String,
+    // Unchanged remaining time keeps the original expiry instead of restarting it
+    initial_expires: String,
+    schedules: String,
+    description: String,
+    selected_field: usize,
+}
+
+impl RuleEditor {
+    pub fn new_rule() -> Self {
+        let mut editor = Self::edit_rule(&FirewallRule::new(
+            0,
+            String::new(),
+            RuleAction::Block,
+            RuleDirection::Inbound,
+            RuleProtocol::TCP,
+        ));
+        editor.is_new = true;
+        editor
+    }
+    
+    pub fn edit_rule(rule: &FirewallRule) -> Self {
+        let text = |value: Option<String>| value.unwrap_or_default();
+        let expires = text(rule.get_time_until_expiry().map(|remaining| format_duration(remaining.as_secs())));
+        Self {
+            base: rule.clone(),
+            is_new: false,
+            name: rule.name.clone(),
+            action: rule.action.clone(),
+            rate_limit: match &rule.action {
+                RuleAction::RateLimit(limit) => limit.to_string(),
+                _ => RateLimit::default().to_string(),
+            },
+            direction: rule.direction.clone(),
+            protocol: rule.protocol.clone(),
+            source_ips: text(rule.source_ips.as_ref().map(|ips| ips.to_string())),
+            destination_ips: text(rule.destination_ips.as_ref().map(|ips| ips.to_string())),
+            source_ports: text(rule.source_ports.as_ref().map(|ports| ports.to_string())),
+            destination_ports: text(rule.destination_ports.as_ref().map(|ports| ports.to_string())),
+            states: text(rule.states.as_ref().map(|states| {
+                states.iter().map(|state| state.nft_keyword()).collect::<Vec<_>>().join(",")
+            })),
+            priority: rule.priority.to_string(),
+            initial_expires: expires.clone(),
+            expires,
+            schedules: rule.schedules.iter().map(|schedule| schedule.to_string()).collect::<Vec<_>>().join("; "),
+            description: rule.description.clone(),
+            selected_field: 0,
+        }
+    }
+    
+    /// Id of the rule being edited, or `None` when creating a new one
+    pub fn editing_rule_id(&self) -> Option<u32> {
+        if self.is_new { None } else { Some(self.base.id) }
+    }
+    
+    pub fn selected_field(&self) -> EditorField {
+        EditorField::ALL[self.selected_field]
+    }
+    
+    pub fn handle_key(&mut self, key: KeyCode) -> EditorAction {
+        let field = self.selected_field();
+        match key {
+            KeyCode::Esc => return EditorAction::Cancel,
+            // An invalid form stays open; the errors are shown next to it
+            KeyCode::Enter if self.build().is_ok() => return EditorAction::Save,
+            KeyCode::Up | KeyCode::BackTab => {
+                self.selected_field = (self.selected_field + EditorField::ALL.len() - 1) % EditorField::ALL.len();
+            }
+            KeyCode::Down | KeyCode::Tab => {
+                self.selected_field = (self.selected_field + 1) % EditorField::ALL.len();
+            }
+            KeyCode::Left if field.is_choice() => self.cycle_choice(field, false),
+            KeyCode::Right | KeyCode::Char(' ') if field.is_choice() => self.cycle_choice(field, true),
+            KeyCode::Backspace => {
+                if let Some(text) = self.text_mut(field) {
+                    text.pop();
+                }
+            }
+            KeyCode::Char(c) => {
+                if let Some(text) = self.text_mut(field) {
+                    text.push(c);
+                }
+            }
+            _ => {}
+        }
+        EditorAction::Continue
+    }
+    
+    fn cycle_choice(&mut self, field: EditorField, forward: bool) {
+        match field {
+            EditorField::Action => self.action = cycle(&ACTIONS, &self.action, forward),
+            EditorField::Direction => self.direction = cycle(&DIRECTIONS, &self.direction, forward),
+            EditorField::Protocol => self.protocol = cycle(&PROTOCOLS, &self.protocol, forward),
+            _ => {}
+        }
+    }
+    
+    fn text_mut(&mut self, field: EditorField) -> Option<&mut String> {
+        match field {
+            EditorField::Name => Some(&mut self.name),
+            EditorField::RateLimit => Some(&mut self.rate_limit),
+            EditorField::SourceIps => Some(&mut self.source_ips),
+            EditorField::DestinationIps => Some(&mut self.destination_ips),
+            EditorField::SourcePorts => Some(&mut self.source_ports),
+            EditorField::DestinationPorts => Some(&mut self.destination_ports),
+            EditorField::States => Some(&mut self.states),
+            EditorField::Priority => Some(&mut self.priority),
+            EditorField::Expires => Some(&mut self.expires),
+            EditorField::Schedules => Some(&mut self.schedules),
+            EditorField::Description => Some(&mut self.description),
+            EditorField::Action | EditorField::Direction | EditorField::Protocol => None,
+        }
+    }
+    
+    fn display_value(&self, field: EditorField) -> String {
+        match field {
+            EditorField::Name => self.name.clone(),
+            // The limit itself has its own field
+            EditorField::Action => match self.action {
+                RuleAction::RateLimit(_) => "RateLimit".to_string(),
+                ref action => action.to_string(),
+            },
+            EditorField::RateLimit => self.rate_limit.clone(),
+            EditorField::Direction => format!("{:?}", self.direction),
+            EditorField::Protocol => format!("{:?}", self.protocol),
+            EditorField::SourceIps => self.source_ips.clone(),
+            EditorField::DestinationIps => self.destination_ips.clone(),
+            EditorField::SourcePorts => self.source_ports.clone(),
+            EditorField::DestinationPorts => self.destination_ports.clone(),
+            EditorField::States => self.states.clone(),
+            EditorField::Priority => self.priority.clone(),
+            EditorField::Expires => self.expires.clone(),
+            EditorField::Schedules => self.schedules.clone(),
+            EditorField::Description => self.description.clone(),
+        }
+    }
+    
+    /// Validate the form and assemble the rule it describes
+    pub fn build(&self) -> Result<FirewallRule, Vec<EditorError>> {
+        let mut errors = Vec::new();
+        let mut rule = self.base.clone();
+        
+        rule.name = self.name.trim().to_string();
+        if rule.name.is_empty() {
+            errors.push(EditorError::new(Some(EditorField::Name), "Name is required".to_string()));
+        }
+        rule.action = self.action.clone();
+        if let RuleAction::RateLimit(_) = rule.action {
+            match self.rate_limit.parse::<RateLimit>() {
+                Ok(limit) => rule.action = RuleAction::RateLimit(limit),
+                Err(message) => errors.push(EditorError::new(Some(EditorField::RateLimit), message)),
+            }
+        }
+        rule.direction = self.direction.clone();
+        rule.protocol = self.protocol.clone();
+        rule.description = self.description.trim().to_string();
+        
+        rule.source_ips = field_value(&mut errors, EditorField::SourceIps, parse_addresses(&self.source_ips));
+        rule.destination_ips = field_value(&mut errors, EditorField::DestinationIps, parse_addresses(&self.destination_ips));
+        rule.source_ports = field_value(&mut errors, EditorField::SourcePorts, parse_ports(&self.source_ports));
+        rule.destination_ports = field_value(&mut errors, EditorField::DestinationPorts, parse_ports(&self.destination_ports));
+        rule.states = field_value(&mut errors, EditorField::States, parse_states(&self.states));
+        rule.schedules = field_value(&mut errors, EditorField::Schedules, parse_schedules(&self.schedules)).unwrap_or_default();
+        if self.expires != self.initial_expires {
+            rule.expires_at = field_value(&mut errors, EditorField::Expires, parse_expiry(&self.expires))
+                .map(|remaining| SystemTime::now() + remaining);
+        }
+        
+        match self.priority.trim().parse::<u8>() {
+            Ok(priority) => rule.priority = priority,
+            Err(_) => errors.push(EditorError::new(
+                Some(EditorField::Priority),
+                format!("Priority must be a number from 0 to 255, got '{}'", self.priority.trim()),
+            )),
+        }
+        
+        // Rules that the kernel backend can't express would fail once enforced
+        if errors.is_empty() {
+            if let Err(message) = NftablesBackend::compile_rule(&rule) {
+                errors.push(EditorError::new(None, message));
+            }
+        }
+        
+        if errors.is_empty() { Ok(rule) } else { Err(errors) }
+    }
+    
+    /// Recent events the rule would have matched, newest first, or `None`
+    /// while the form is invalid
+    pub fn preview_matches<'a>(&self, events: &'a VecDeque<FirewallEvent>) -> Option<Vec<&'a FirewallEvent>> {
+        let mut rule = self.build().ok()?;
+        rule.enabled = true;
+        Some(
+            events
+                .iter()
+                .rev()
+                .filter(|event| {
+                    event.kind != FirewallEventKind::RuleExpired && rule.matches_packet(
+                        &event.src_ip,
+                        &event.dst_ip,
+                        event.src_port,
+                        event.dst_port,
+                        &event.protocol,
+                        &event.direction,
+                    ) && rule.matches_state(event.state)
+                })
+                .collect(),
+        )
+    }
+    
+    pub fn render(&self, f: &mut Frame, area: Rect, events: &VecDeque<FirewallEvent>) {
+        let chunks = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([
+                Constraint::Percentage(60), // Form
+                Constraint::Percentage(40), // Validation and preview
+            ])
+            .split(area);
+        
+        let errors = self.build().err().unwrap_or_default();
+        self.render_form(f, chunks[0], &errors);
+        
+        let side_chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([
+                Constraint::Length(6), // Validation
+                Constraint::Min(4),    // Preview
+            ])
+            .split(chunks[1]);
+        
+        self.render_validation(f, side_chunks[0], &errors);
+        self.render_preview(f, side_chunks[1], events);
+    }
+    
+    fn render_form(&self, f: &mut Frame, area: Rect, errors: &[EditorError]) {
+        let mut lines: Vec<Line> = EditorField::ALL
+            .iter()
+            .enumerate()
+            .map(|(i, field)| {
+                let selected = i == self.selected_field;
+                let has_error = errors.iter().any(|error| error.field == Some(*field));
+                let value = self.display_value(*field);
+                let value = if field.is_choice() {
+                    format!("◀ {} ▶", value)
+                } else if selected {
+                    format!("{}▏", value)
+                } else {
+                    value
+                };
+                
+                let label_style = if has_error {
+                    Style::default().fg(Color::Red)
+                } else {
+                    Style::default().fg(Color::Yellow)
+                };
+                let mut line = Line::from(vec![
+                    Span::raw(if selected { "> " } else { "  " }),
+                    Span::styled(format!("{:<18}", field.label()), label_style),
+                    Span::styled(value, Style::default().fg(Color::White)),
+                ]);
+                if selected {
+                    line.patch_style(Style::default().bg(Color::DarkGray).add_modifier(Modifier::BOLD));
+                }
+                line
+            })
+            .collect();
+        
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled(self.selected_field().hint(), Style::default().fg(Color::Gray))));
+        
+        let title = match self.editing_rule_id() {
+            Some(id) => format!("Edit Rule {} (Enter: save, Esc: cancel, ↑↓/Tab: field)", id),
+            None => "New Rule (Enter: save, Esc: cancel, ↑↓/Tab: field)".to_string(),
+        };
+        let form = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .wrap(Wrap { trim: false });
+        
+        f.render_widget(form, area);
+    }
+    
+    fn render_validation(&self, f: &mut Frame, area: Rect, errors: &[EditorError]) {
+        let lines: Vec<Line> = if errors.is_empty() {
+            vec![Line::from(Span::styled("✓ Rule is valid", Style::default().fg(Color::Green)))]
+        } else {
+            errors
+                .iter()
+                .map(|error| {
+                    let text = match error.field {
+                        Some(field) => format!("✗ {}: {}", field.label(), error.message),
+                        None => format!("✗ {}", error.message),
+                    };
+                    Line::from(Span::styled(text, Style::default().fg(Color::Red)))
+                })
+                .collect()
+        };
+        
+        let validation = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title("Validation"))
+            .wrap(Wrap { trim: true });
+        
+        f.render_widget(validation, area);
+    }
+    
+    fn render_preview(&self, f: &mut Frame, area: Rect, events: &VecDeque<FirewallEvent>) {
+        let (title, items) = match self.preview_matches(events) {
+            Some(matches) => {
+                let title = format!("Would have matched {} of {} recent events", matches.len(), events.len());
+                let items: Vec<ListItem> = matches
+                    .iter()
+                    .take(area.height.saturating_sub(2) as usize)
+                    .map(|event| ListItem::new(event.format_summary()))
+                    .collect();
+                (title, items)
+            }
+            None => ("Match Preview (fix the form first)".to_string(), Vec::new()),
+        };
+        
+        let preview = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .style(Style::default().fg(Color::White));
+        
+        f.render_widget(preview, area);
+    }
+}
+
+/// Next or previous option, wrapping around
+/// Options are compared by variant, so a RateLimit with any limit is found
+fn cycle<T: Clone>(options: &[T], current: &T, forward: bool) -> T {
+    let index = options
+        .iter()
+        .position(|option| std::mem::discriminant(option) == std::mem::discriminant(current))
+        .unwrap_or(0);
+    let next = if forward {
+        (index + 1) % options.len()
+    } else {
+        (index + options.len() - 1) % options.len()
+    };
+    options[next].clone()
+}
+
+/// Parsed value of a set field, recording the error if it doesn't parse
+fn field_value<T>(errors: &mut Vec<EditorError>, field: EditorField, result: Result<Option<T>, String>) -> Option<T> {
+    result.unwrap_or_else(|message| {
+        errors.push(EditorError::new(Some(field), message));
+        None
+    })
+}
+
+/// Split a set field into its entries and whether it starts with `!`
+fn split_set(text: &str) -> (Vec<&str>, bool) {
+    let text = text.trim();
+    let (text, negated) = match text.strip_prefix('!') {
+        Some(rest) => (rest, true),
+        None => (text, false),
+    };
+    let entries = text
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|entry| !entry.is_empty())
+        .collect();
+    (entries, negated)
+}
+
+fn parse_addresses(text: &str) -> Result<Option<AddressSet>, String> {
+    let (entries, negated) = split_set(text);
+    if entries.is_empty() {
+        return if negated { Err("'!' needs addresses to exclude".to_string()) } else { Ok(None) };
+    }
+    let mut set = entries
+        .iter()
+        .map(|entry| entry.parse::<IpNetwork>().map_err(|_| format!("'{}' is not an address or CIDR network", entry)))
+        .collect::<Result<AddressSet, _>>()?;
+    set.set_negated(negated);
+    Ok(Some(set))
+}
+
+fn parse_ports(text: &str) -> Result<Option<PortSet>, String> {
+    let (entries, negated) = split_set(text);
+    if entries.is_empty() {
+        return if negated { Err("'!' needs ports to exclude".to_string()) } else { Ok(None) };
+    }
+    let mut set = entries
+        .iter()
+        .map(|entry| entry.parse::<PortRange>())
+        .collect::<Result<PortSet, _>>()?;
+    set.set_negated(negated);
+    Ok(Some(set))
+}
+
+fn parse_states(text: &str) -> Result<Option<BTreeSet<ConnState>>, String> {
+    let (entries, negated) = split_set(text);
+    if negated {
+        return Err("connection states can't be negated; list the states to match".to_string());
+    }
+    if entries.is_empty() {
+        return Ok(None);
+    }
+    entries.iter().map(|entry| entry.parse::<ConnState>()).collect::<Result<_, _>>().map(Some)
+}
+
+/// A duration like `90s`, `30m`, `2h` or `1d 12h`; empty for no expiry
+fn parse_expiry(text: &str) -> Result<Option<Duration>, String> {
+    let invalid = || format!("'{}' is not a duration like 30m, 2h or 1d 12h", text.trim());
+    let mut total = 0u64;
+    let mut number = String::new();
+    for c in text.chars().filter(|c| !c.is_whitespace()) {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            'd' => 86400,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = number.parse().map_err(|_| invalid())?;
+        total = total.saturating_add(value.saturating_mul(unit));
+        number.clear();
+    }
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+    Ok(if total == 0 { None } else { Some(Duration::from_secs(total)) })
+}
+
+fn parse_schedules(text: &str) -> Result<Option<Vec<RuleSchedule>>, String> {
+    let schedules = text
+        .split(';')
+        .map(str::trim)
+        .filter(|schedule| !schedule.is_empty())
+        .map(|schedule| schedule.parse::<RuleSchedule>())
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(Some(schedules))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::IpAddr;
+    use std::time::SystemTime;
+    
+    fn type_text(editor: &mut RuleEditor, text: &str) {
+        for c in text.chars() {
+            editor.handle_key(KeyCode::Char(c));
+        }
+    }
+    
+    fn select(editor: &mut RuleEditor, field: EditorField) {
+        while editor.selected_field() != field {
+            editor.handle_key(KeyCode::Down);
+        }
+    }
+    
+    #[test]
+    fn test_build_from_form() {
+        let mut editor = RuleEditor::new_rule();
+        type_text(&mut editor, "Block LAN RDP");
+        select(&mut editor, EditorField::SourceIps);
+        type_text(&mut editor, "192.168.0.0/16, 10.0.0.1");
+        select(&mut editor, EditorField::DestinationPorts);
+        type_text(&mut editor, "3389,5900-5910");
+        select(&mut editor, EditorField::States);
+        type_text(&mut editor, "new");
+        
+        assert_eq!(editor.handle_key(KeyCode::Enter), EditorAction::Save);
+        let rule = editor.build().unwrap();
+        assert_eq!(rule.name, "Block LAN RDP");
+        assert_eq!(rule.action, RuleAction::Block);
+        assert_eq!(rule.source_ips.as_ref().unwrap().len(), 2);
+        assert!(rule.destination_ports.as_ref().unwrap().contains(&5905));
+        assert!(rule.matches_state(ConnState::New));
+        assert!(!rule.matches_state(ConnState::Established));
+    }
+    
+    #[test]
+    fn test_validation_errors() {
+        let mut editor = RuleEditor::new_rule();
+        select(&mut editor, EditorField::SourceIps);
+        type_text(&mut editor, "10.0.0.300");
+        select(&mut editor, EditorField::Priority);
+        editor.handle_key(KeyCode::Backspace);
+        type_text(&mut editor, "99");
+        
+        let errors = editor.build().unwrap_err();
+        let fields: Vec<_> = errors.iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec![Some(EditorField::Name), Some(EditorField::SourceIps), Some(EditorField::Priority)]);
+        assert_eq!(editor.handle_key(KeyCode::Enter), EditorAction::Continue);
+        
+        // Rule-level check: ICMP has no ports
+        let mut editor = RuleEditor::edit_rule(&crate::firewall::rules::RuleTemplates::allow_ssh());
+        select(&mut editor, EditorField::Protocol);
+        editor.handle_key(KeyCode::Right);
+        editor.handle_key(KeyCode::Right);
+        let errors = editor.build().unwrap_err();
+        assert_eq!(errors, vec![EditorError::new(None, "ICMP has no ports".to_string())]);
+    }
+    
+    #[test]
+    fn test_edit_round_trip() {
+        let rule = FirewallRule::new(42, "Web".to_string(), RuleAction::Allow, RuleDirection::Inbound, RuleProtocol::TCP)
+            .with_source_network("10.0.0.0/8".parse().unwrap())
+            .negate_source_ips()
+            .with_destination_ports(vec![80, 443]);
+        
+        let editor = RuleEditor::edit_rule(&rule);
+        assert_eq!(editor.editing_rule_id(), Some(42));
+        assert_eq!(editor.source_ips, "!10.0.0.0/8");
+        assert_eq!(editor.destination_ports, "80,443");
+        
+        let rebuilt = editor.build().unwrap();
+        assert_eq!(rebuilt.id, 42);
+        assert_eq!(rebuilt.source_ips, rule.source_ips);
+        assert_eq!(rebuilt.destination_ports, rule.destination_ports);
+    }
+    
+    #[test]
+    fn test_expiry_and_schedules() {
+        let mut editor = RuleEditor::new_rule();
+        type_text(&mut editor, "Incident block");
+        select(&mut editor, EditorField::Expires);
+        type_text(&mut editor, "1h 30m");
+        select(&mut editor, EditorField::Schedules);
+        type_text(&mut editor, "Mon-Fri 09:00-17:00; sat 10:00-12:00");
+        
+        let rule = editor.build().unwrap();
+        let remaining = rule.get_time_until_expiry().unwrap().as_secs();
+        assert!((5390..=5400).contains(&remaining), "{}", remaining);
+        assert_eq!(rule.schedules.len(), 2);
+        assert_eq!(rule.schedules[1].to_string(), "Sat 10:00-12:00");
+        
+        // Re-opening shows the remaining time and keeps the deadline unless it is changed
+        let editor = RuleEditor::edit_rule(&rule);
+        assert_eq!(editor.expires, "1h 29m");
+        assert_eq!(editor.schedules, "Mon-Fri 09:00-17:00; Sat 10:00-12:00");
+        assert_eq!(editor.build().unwrap().expires_at, rule.expires_at);
+        
+        let mut editor = RuleEditor::new_rule();
+        type_text(&mut editor, "x");
+        select(&mut editor, EditorField::Expires);
+        type_text(&mut editor, "2 weeks");
+        select(&mut editor, EditorField::Schedules);
+        type_text(&mut editor, "Mon 9-17");
+        let fields: Vec<_> = editor.build().unwrap_err().iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec![Some(EditorField::Schedules), Some(EditorField::Expires)]);
+    }
+    
+    #[test]
+    fn test_rate_limit_action() {
+        let mut editor = RuleEditor::new_rule();
+        type_text(&mut editor, "Throttle DNS");
+        select(&mut editor, EditorField::Action);
+        editor.handle_key(KeyCode::Left);
+        editor.handle_key(KeyCode::Left);
+        assert_eq!(editor.display_value(EditorField::Action), "RateLimit");
+        select(&mut editor, EditorField::RateLimit);
+        for _ in 0..editor.rate_limit.len() {
+            editor.handle_key(KeyCode::Backspace);
+        }
+        type_text(&mut editor, "50/s burst 20 per rule");
+        
+        let rule = editor.build().unwrap();
+        assert_eq!(rule.action, RuleAction::RateLimit(RateLimit::packets_per_second(50, 20, RateScope::PerRule)));
+        
+        // Cycling keeps the place of a limit that differs from the default
+        let mut editor = RuleEditor::edit_rule(&rule);
+        assert_eq!(editor.rate_limit, "50/s burst 20 per rule");
+        select(&mut editor, EditorField::Action);
+        editor.handle_key(KeyCode::Right);
+        assert_eq!(editor.action, RuleAction::Allow);
+        
+        let mut editor = RuleEditor::edit_rule(&rule);
+        select(&mut editor, EditorField::RateLimit);
+        type_text(&mut editor, "x");
+        let fields: Vec<_> = editor.build().unwrap_err().iter().map(|error| error.field).collect();
+        assert_eq!(fields, vec![Some(EditorField::RateLimit)]);
+    }
+    
+    #[test]
+    fn test_preview_matches() {
+        let event = |dst_port: u16| FirewallEvent {
+            timestamp: SystemTime::now(),
+            kind: FirewallEventKind::Match,
+            rule_id: 1,
+            rule_name: "Log All Connections".to_string(),
+            action: RuleAction::Log,
+            src_ip: "203.0.113.8".parse::<IpAddr>().unwrap(),
+            dst_ip: "192.168.1.2".parse::<IpAddr>().unwrap(),
+            src_port: 40000,
+            dst_port,
+            protocol: RuleProtocol::TCP,
+            direction: RuleDirection::Inbound,
+            state: ConnState::New,
+            packet_size: 60,
+        };
+        let events: VecDeque<FirewallEvent> = [event(22), event(80), event(22)].into_iter().collect();
+        
+        let editor = RuleEditor::edit_rule(&crate::firewall::rules::RuleTemplates::allow_ssh());
+        assert_eq!(editor.preview_matches(&events).unwrap().len(), 2);
+        
+        assert!(RuleEditor::new_rule().preview_matches(&events).is_none());
+    }
+}
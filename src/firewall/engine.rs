@@ -1,7 +1,11 @@
-use std::collections::VecDeque;
-use std::net::IpAddr;
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
 use std::time::{Duration, SystemTime};
+use crate::firewall::conntrack::{ConnState, ConnTracker};
 use crate::firewall::rules::{FirewallRule, RuleAction, RuleDirection, RuleProtocol};
+use crate::firewall::nftables::{EnforcementError, NftablesBackend};
+use crate::firewall::persistence::RulesetStore;
+use crate::firewall::ratelimit::RateLimiter;
 use crate::capture::PacketInfo;
 
 #[derive(Debug, Clone)]
@@ -10,6 +14,10 @@ pub struct FirewallStats {
     pub packets_allowed: u64,
     pub packets_blocked: u64,
     pub packets_logged: u64,
+    /// Packets dropped for going over a rate limit; also counted as blocked
+    pub packets_rate_limited: u64,
+    /// Over-limit packets by rate-limit rule id
+    pub rate_limited_by_rule: HashMap<u32, u64>,
     pub rules_matched: u64,
     pub active_rules: usize,
     pub enabled_rules: usize,
@@ -23,6 +31,8 @@ impl FirewallStats {
             packets_allowed: 0,
             packets_blocked: 0,
             packets_logged: 0,
+            packets_rate_limited: 0,
+            rate_limited_by_rule: HashMap::new(),
             rules_matched: 0,
             active_rules: 0,
             enabled_rules: 0,
@@ -51,9 +61,21 @@ impl FirewallStats {
     }
 }
 
+/// What a `FirewallEvent` records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FirewallEventKind {
+    /// A packet matched the rule
+    Match,
+    /// The rule reached its expiry time and was removed; the packet fields are unset
+    RuleExpired,
+    /// A packet matched a rate-limit rule while over its limit and was dropped
+    RateLimited,
+}
+
 #[derive(Debug, Clone)]
 pub struct FirewallEvent {
     pub timestamp: SystemTime,
+    pub kind: FirewallEventKind,
     pub rule_id: u32,
     pub rule_name: String,
     pub action: RuleAction,
@@ -63,6 +85,7 @@ pub struct FirewallEvent {
     pub dst_port: u16,
     pub protocol: RuleProtocol,
     pub direction: RuleDirection,
+    pub state: ConnState,
     pub packet_size: usize,
 }
 
@@ -72,9 +95,14 @@ impl FirewallEvent {
     }
     
     pub fn format_summary(&self) -> String {
+        if self.kind == FirewallEventKind::RuleExpired {
+            return format!("Rule {} ({}) expired and was removed", self.rule_id, self.rule_name);
+        }
+        let over_limit = if self.kind == FirewallEventKind::RateLimited { " (over limit)" } else { "" };
         format!(
-            "{:?} {} {}:{} → {}:{} ({})",
+            "{}{} {} {}:{} → {}:{} ({})",
             self.action,
+            over_limit,
             self.protocol_str(),
             self.src_ip,
             self.src_port,
@@ -102,6 +130,20 @@ pub struct FirewallEngine {
     last_cleanup: SystemTime,
     rule_counter: u32,
     enabled: bool,
+    conntrack: ConnTracker,
+    rate_limiter: RateLimiter,
+    // Kernel enforcement: ruleset changes bump the version and are pushed
+    // to nftables on the next sync
+    enforcer: Option<NftablesBackend>,
+    rules_version: u64,
+    enforced_version: u64,
+    enforcement_error: Option<String>,
+    // Autosave: same versioning, written to the ruleset file
+    store: Option<RulesetStore>,
+    saved_version: u64,
+    save_error: Option<String>,
+    // Ids of scheduled rules in effect at the last timer update
+    scheduled_in_effect: Vec<u32>,
 }
 
 impl FirewallEngine {
@@ -114,6 +156,16 @@ impl FirewallEngine {
             last_cleanup: SystemTime::now(),
             rule_counter: 0,
             enabled: true,
+            conntrack: ConnTracker::new(),
+            rate_limiter: RateLimiter::new(),
+            enforcer: None,
+            rules_version: 0,
+            enforced_version: 0,
+            enforcement_error: None,
+            store: None,
+            saved_version: 0,
+            save_error: None,
+            scheduled_in_effect: Vec::new(),
         }
     }
     
@@ -121,6 +173,39 @@ impl FirewallEngine {
         self.rule_counter += 1;
         rule.id = self.rule_counter;
         
+        self.insert_by_priority(rule);
+        self.update_stats();
+        self.rules_version += 1;
+        self.rule_counter
+    }
+    
+    /// Replace a rule, keeping its id; it moves if its priority changed
+    pub fn update_rule(&mut self, rule_id: u32, mut rule: FirewallRule) -> bool {
+        if let Some(pos) = self.rules.iter().position(|r| r.id == rule_id) {
+            self.rules.remove(pos);
+            rule.id = rule_id;
+            self.insert_by_priority(rule);
+            self.update_stats();
+            self.rules_version += 1;
+            true
+        } else {
+            false
+        }
+    }
+    
+    /// Swap in a whole ruleset (loaded from disk or a backup), keeping rule ids
+    pub fn replace_rules(&mut self, rules: Vec<FirewallRule>) {
+        self.rules.clear();
+        self.rule_counter = 0;
+        for rule in rules {
+            self.rule_counter = self.rule_counter.max(rule.id);
+            self.insert_by_priority(rule);
+        }
+        self.update_stats();
+        self.rules_version += 1;
+    }
+    
+    fn insert_by_priority(&mut self, rule: FirewallRule) {
         // Insert rule in priority order (higher priority first)
         let insert_pos = self.rules
             .iter()
@@ -128,14 +213,13 @@ impl FirewallEngine {
             .unwrap_or(self.rules.len());
         
         self.rules.insert(insert_pos, rule);
-        self.update_stats();
-        self.rule_counter
     }
     
     pub fn remove_rule(&mut self, rule_id: u32) -> bool {
         if let Some(pos) = self.rules.iter().position(|r| r.id == rule_id) {
             self.rules.remove(pos);
             self.update_stats();
+            self.rules_version += 1;
             true
         } else {
             false
@@ -146,6 +230,7 @@ impl FirewallEngine {
         if let Some(rule) = self.rules.iter_mut().find(|r| r.id == rule_id) {
             rule.enabled = true;
             self.update_stats();
+            self.rules_version += 1;
             true
         } else {
             false
@@ -156,6 +241,7 @@ impl FirewallEngine {
         if let Some(rule) = self.rules.iter_mut().find(|r| r.id == rule_id) {
             rule.enabled = false;
             self.update_stats();
+            self.rules_version += 1;
             true
         } else {
             false
@@ -166,14 +252,15 @@ impl FirewallEngine {
         self.rules.iter().find(|r| r.id == rule_id)
     }
     
-    pub fn get_rule_mut(&mut self, rule_id: u32) -> Option<&mut FirewallRule> {
-        self.rules.iter_mut().find(|r| r.id == rule_id)
-    }
-    
     pub fn get_rules(&self) -> &[FirewallRule] {
         &self.rules
     }
     
+    /// Changes whenever the ruleset does, for caching anything derived from it
+    pub fn get_rules_version(&self) -> u64 {
+        self.rules_version
+    }
+    
     pub fn get_stats(&self) -> &FirewallStats {
         &self.stats
     }
@@ -182,6 +269,10 @@ impl FirewallEngine {
         &self.recent_events
     }
     
+    pub fn get_conntrack(&self) -> &ConnTracker {
+        &self.conntrack
+    }
+    
     pub fn clear_events(&mut self) {
         self.recent_events.clear();
     }
@@ -192,12 +283,153 @@ impl FirewallEngine {
     
     pub fn set_enabled(&mut self, enabled: bool) {
         self.enabled = enabled;
+        self.rules_version += 1;
     }
     
     pub fn is_enabled(&self) -> bool {
         self.enabled
     }
     
+    /// Start enforcing the rules in the kernel; they are loaded immediately
+    pub fn enable_enforcement(&mut self, mut backend: NftablesBackend) -> Result<(), EnforcementError> {
+        backend.apply(&self.enforced_rules())?;
+        self.enforcer = Some(backend);
+        self.enforced_version = self.rules_version;
+        self.enforcement_error = None;
+        Ok(())
+    }
+    
+    /// Stop enforcing and restore the nftables state from before
+    pub fn disable_enforcement(&mut self) -> Result<(), EnforcementError> {
+        match self.enforcer.take() {
+            Some(mut backend) => backend.rollback(),
+            None => Ok(()),
+        }
+    }
+    
+    /// Push rule changes made since the last sync to the kernel
+    pub fn sync_enforcement(&mut self) {
+        if self.enforced_version == self.rules_version {
+            return;
+        }
+        
+        if let Some(mut backend) = self.enforcer.take() {
+            match backend.apply(&self.enforced_rules()) {
+                Ok(()) => self.enforcement_error = None,
+                Err(e) => {
+                    log::error!("Failed to apply firewall rules: {}", e);
+                    self.enforcement_error = Some(e.to_string());
+                }
+            }
+            self.enforcer = Some(backend);
+        }
+        self.enforced_version = self.rules_version;
+    }
+    
+    /// Save rule changes to this store from now on
+    pub fn set_ruleset_store(&mut self, store: RulesetStore) {
+        self.store = Some(store);
+        self.saved_version = self.rules_version;
+    }
+    
+    pub fn get_ruleset_store(&self) -> Option<&RulesetStore> {
+        self.store.as_ref()
+    }
+    
+    /// Write rule changes made since the last save to the ruleset file
+    pub fn autosave(&mut self) {
+        if self.saved_version == self.rules_version {
+            return;
+        }
+        
+        if let Some(ref store) = self.store {
+            match store.save(&self.rules) {
+                Ok(()) => self.save_error = None,
+                Err(e) => {
+                    log::error!("{}", e);
+                    self.save_error = Some(e.to_string());
+                }
+            }
+        }
+        self.saved_version = self.rules_version;
+    }
+    
+    pub fn get_save_error(&self) -> Option<&str> {
+        self.save_error.as_deref()
+    }
+    
+    /// The nft script enforcement would load for the current rules
+    pub fn preview_ruleset(&self) -> String {
+        match self.enforcer {
+            Some(ref backend) => backend.generate_ruleset(&self.enforced_rules()),
+            None => NftablesBackend::new().generate_ruleset(&self.enforced_rules()),
+        }
+    }
+    
+    pub fn is_enforcing(&self) -> bool {
+        self.enforcer.is_some()
+    }
+    
+    pub fn get_enforcement_error(&self) -> Option<&str> {
+        self.enforcement_error.as_deref()
+    }
+    
+    /// Rules the kernel should have right now; timed rules come and go
+    /// through `update_timed_rules`
+    fn enforced_rules(&self) -> Vec<FirewallRule> {
+        if !self.enabled {
+            return Vec::new();
+        }
+        let now = SystemTime::now();
+        self.rules.iter().filter(|rule| rule.is_in_effect(now)).cloned().collect()
+    }
+    
+    /// Remove rules that have expired, recording an event for each, and
+    /// re-sync enforcement when a scheduled rule starts or stops applying.
+    /// Call periodically with the current time.
+    pub fn update_timed_rules(&mut self, now: SystemTime) {
+        let (expired, rules): (Vec<FirewallRule>, Vec<FirewallRule>) = std::mem::take(&mut self.rules)
+            .into_iter()
+            .partition(|rule| rule.is_expired(now));
+        self.rules = rules;
+        
+        if !expired.is_empty() {
+            for rule in expired {
+                log::info!("Firewall rule {} ({}) expired and was removed", rule.id, rule.name);
+                self.recent_events.push_back(FirewallEvent {
+                    timestamp: now,
+                    kind: FirewallEventKind::RuleExpired,
+                    rule_id: rule.id,
+                    rule_name: rule.name,
+                    action: rule.action,
+                    src_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                    dst_ip: IpAddr::V4(Ipv4Addr::UNSPECIFIED),
+                    src_port: 0,
+                    dst_port: 0,
+                    protocol: rule.protocol,
+                    direction: rule.direction,
+                    state: ConnState::New,
+                    packet_size: 0,
+                });
+                if self.recent_events.len() > self.max_events {
+                    self.recent_events.pop_front();
+                }
+            }
+            self.update_stats();
+            self.rules_version += 1;
+        }
+        
+        let scheduled_in_effect: Vec<u32> = self.rules
+            .iter()
+            .filter(|rule| !rule.schedules.is_empty() && rule.is_in_effect(now))
+            .map(|rule| rule.id)
+            .collect();
+        if scheduled_in_effect != self.scheduled_in_effect {
+            self.scheduled_in_effect = scheduled_in_effect;
+            self.rules_version += 1;
+        }
+    }
+    
     pub fn process_packet(&mut self, packet: &PacketInfo) -> RuleAction {
         if !self.enabled {
             return RuleAction::Allow;
@@ -222,26 +454,47 @@ impl FirewallEngine {
         let protocol = match packet.protocol.as_str() {
             "TCP" => RuleProtocol::TCP,
             "UDP" => RuleProtocol::UDP,
-            "ICMP" => RuleProtocol::ICMP,
+            "ICMP" | "ICMPv6" => RuleProtocol::ICMP,
             _ => RuleProtocol::Any,
         };
         
         // Determine direction (simplified - in real implementation this would be more complex)
-        let direction = if self.is_local_ip(&src_ip) {
+        let direction = if Self::is_local_ip(&src_ip) {
             RuleDirection::Outbound
         } else {
             RuleDirection::Inbound
         };
         
+        let state = self.conntrack.track(
+            SocketAddr::new(src_ip, src_port),
+            SocketAddr::new(dst_ip, dst_port),
+            &protocol,
+            packet.tcp_flags,
+            packet.icmp_type,
+            packet.timestamp,
+        );
+        self.expire_connections(packet.timestamp);
+        
         // Check rules in priority order
         for rule in &mut self.rules {
-            if rule.matches_packet(&src_ip, &dst_ip, src_port, dst_port, &protocol, &direction) {
+            if rule.matches_packet(&src_ip, &dst_ip, src_port, dst_port, &protocol, &direction)
+                && rule.matches_state(state)
+                && rule.is_in_effect(packet.timestamp)
+            {
                 rule.record_match();
                 self.stats.rules_matched += 1;
                 
+                let within_limit = match &rule.action {
+                    RuleAction::RateLimit(limit) => {
+                        self.rate_limiter.admit(rule.id, limit, src_ip, packet.length, packet.timestamp)
+                    }
+                    _ => true,
+                };
+                
                 // Create event
                 let event = FirewallEvent {
                     timestamp: SystemTime::now(),
+                    kind: if within_limit { FirewallEventKind::Match } else { FirewallEventKind::RateLimited },
                     rule_id: rule.id,
                     rule_name: rule.name.clone(),
                     action: rule.action.clone(),
@@ -251,6 +504,7 @@ impl FirewallEngine {
                     dst_port,
                     protocol: protocol.clone(),
                     direction: direction.clone(),
+                    state,
                     packet_size: packet.length,
                 };
                 
@@ -279,6 +533,16 @@ impl FirewallEngine {
                         self.stats.packets_blocked += 1;
                         return RuleAction::LogAndBlock;
                     }
+                    RuleAction::RateLimit(_) if within_limit => {
+                        self.stats.packets_allowed += 1;
+                        return RuleAction::Allow;
+                    }
+                    RuleAction::RateLimit(_) => {
+                        self.stats.packets_rate_limited += 1;
+                        *self.stats.rate_limited_by_rule.entry(rule.id).or_default() += 1;
+                        self.stats.packets_blocked += 1;
+                        return RuleAction::Block;
+                    }
                 }
             }
         }
@@ -288,7 +552,17 @@ impl FirewallEngine {
         RuleAction::Allow
     }
     
-    fn is_local_ip(&self, ip: &IpAddr) -> bool {
+    fn expire_connections(&mut self, now: SystemTime) {
+        let since_cleanup = now.duration_since(self.last_cleanup).unwrap_or_default();
+        if since_cleanup >= Duration::from_secs(10) {
+            self.conntrack.expire(now);
+            self.rate_limiter.expire(now);
+            self.last_cleanup = now;
+        }
+    }
+    
+    /// Addresses treated as this host's side of a connection
+    pub(crate) fn is_local_ip(ip: &IpAddr) -> bool {
         match ip {
             IpAddr::V4(ipv4) => {
                 ipv4.is_loopback() || 
@@ -345,7 +619,7 @@ impl Default for FirewallEngine {
 mod tests {
     use super::*;
     use crate::firewall::rules::RuleTemplates;
-
+    
     #[test]
     fn test_firewall_engine_creation() {
         let engine = FirewallEngine::new();
@@ -405,6 +679,10 @@ mod tests {
             dst_ip: Some("192.168.1.1".to_string()),
             src_port: Some(12345),
             dst_port: Some(22),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
         };
         
         let action = engine.process_packet(&ssh_packet);
@@ -412,4 +690,137 @@ mod tests {
         assert_eq!(engine.stats.total_packets_processed, 1);
         assert_eq!(engine.stats.packets_allowed, 1);
     }
+    
+    #[test]
+    fn test_replace_rules_keeps_ids() {
+        let mut engine = FirewallEngine::new();
+        let mut low = RuleTemplates::log_all_connections();
+        low.id = 40;
+        let mut high = RuleTemplates::allow_localhost();
+        high.id = 7;
+        
+        engine.replace_rules(vec![low, high]);
+        let ids: Vec<u32> = engine.get_rules().iter().map(|r| r.id).collect();
+        assert_eq!(ids, vec![7, 40]);
+        
+        // New rules continue after the highest loaded id
+        assert_eq!(engine.add_rule(RuleTemplates::allow_ssh()), 41);
+    }
+    
+    #[test]
+    fn test_stateful_block_incoming() {
+        use pnet::packet::tcp::TcpFlags;
+        
+        let mut engine = FirewallEngine::new();
+        engine.add_rule(RuleTemplates::block_all_incoming());
+        
+        let packet = |src: &str, dst: &str, src_port: u16, dst_port: u16, flags: u8| PacketInfo {
+            timestamp: SystemTime::now(),
+            length: 60,
+            protocol: "TCP".to_string(),
+            src_ip: Some(src.to_string()),
+            dst_ip: Some(dst.to_string()),
+            src_port: Some(src_port),
+            dst_port: Some(dst_port),
+            tcp_flags: Some(flags),
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
+        };
+        
+        // Outbound session: the server's replies come back in
+        let syn = packet("192.168.1.10", "203.0.113.5", 50000, 443, TcpFlags::SYN);
+        let syn_ack = packet("203.0.113.5", "192.168.1.10", 443, 50000, TcpFlags::SYN | TcpFlags::ACK);
+        let data = packet("203.0.113.5", "192.168.1.10", 443, 50000, TcpFlags::PSH | TcpFlags::ACK);
+        assert_eq!(engine.process_packet(&syn), RuleAction::Allow);
+        assert_eq!(engine.process_packet(&syn_ack), RuleAction::Allow);
+        assert_eq!(engine.process_packet(&data), RuleAction::Allow);
+        
+        // A connection attempt from outside is still blocked
+        let inbound_syn = packet("198.51.100.7", "192.168.1.10", 40000, 22, TcpFlags::SYN);
+        assert_eq!(engine.process_packet(&inbound_syn), RuleAction::Block);
+        assert_eq!(engine.get_recent_events().back().unwrap().state, ConnState::New);
+        assert_eq!(engine.get_conntrack().len(), 2);
+    }
+    
+    #[test]
+    fn test_expiring_rules() {
+        let mut engine = FirewallEngine::new();
+        let now = SystemTime::now();
+        let block = FirewallRule::new(0, "Incident block".to_string(), RuleAction::Block, RuleDirection::Inbound, RuleProtocol::TCP)
+            .with_destination_port(22)
+            .with_priority(255)
+            .with_expiry(now + Duration::from_secs(3600));
+        let block_id = engine.add_rule(block);
+        engine.add_rule(RuleTemplates::allow_ssh());
+        
+        let packet = |timestamp: SystemTime| PacketInfo {
+            timestamp,
+            length: 64,
+            protocol: "TCP".to_string(),
+            src_ip: Some("203.0.113.9".to_string()),
+            dst_ip: Some("192.168.1.1".to_string()),
+            src_port: Some(40000),
+            dst_port: Some(22),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
+        };
+        assert_eq!(engine.process_packet(&packet(now)), RuleAction::Block);
+        // Past its expiry the rule no longer applies, even before it is removed
+        assert_eq!(engine.process_packet(&packet(now + Duration::from_secs(3601))), RuleAction::Allow);
+        
+        engine.update_timed_rules(now + Duration::from_secs(60));
+        assert!(engine.get_rule(block_id).is_some());
+        
+        engine.update_timed_rules(now + Duration::from_secs(3600));
+        assert!(engine.get_rule(block_id).is_none());
+        assert_eq!(engine.stats.active_rules, 1);
+        let event = engine.get_recent_events().back().unwrap();
+        assert_eq!(event.kind, FirewallEventKind::RuleExpired);
+        assert_eq!(event.rule_id, block_id);
+        assert_eq!(event.format_summary(), format!("Rule {} (Incident block) expired and was removed", block_id));
+    }
+    
+    #[test]
+    fn test_rate_limited_ssh() {
+        use pnet::packet::tcp::TcpFlags;
+        
+        let mut engine = FirewallEngine::new();
+        let limit_id = engine.add_rule(RuleTemplates::rate_limit_ssh());
+        engine.add_rule(RuleTemplates::allow_ssh());
+        
+        let start = SystemTime::now();
+        let attempt = |src: &str, src_port: u16, offset: Duration| PacketInfo {
+            timestamp: start + offset,
+            length: 60,
+            protocol: "TCP".to_string(),
+            src_ip: Some(src.to_string()),
+            dst_ip: Some("192.168.1.1".to_string()),
+            src_port: Some(src_port),
+            dst_port: Some(22),
+            tcp_flags: Some(TcpFlags::SYN),
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
+        };
+        
+        // 1/s with a burst of 5: six attempts at once get through, then drops
+        let verdicts: Vec<RuleAction> = (0..8)
+            .map(|i| engine.process_packet(&attempt("203.0.113.9", 40000 + i, Duration::ZERO)))
+            .collect();
+        assert_eq!(verdicts.iter().filter(|verdict| **verdict == RuleAction::Allow).count(), 6);
+        assert_eq!(verdicts[6..], [RuleAction::Block, RuleAction::Block]);
+        assert_eq!(engine.get_recent_events().back().unwrap().kind, FirewallEventKind::RateLimited);
+        
+        // Other sources have their own budget, and tokens come back over time
+        assert_eq!(engine.process_packet(&attempt("198.51.100.4", 40000, Duration::ZERO)), RuleAction::Allow);
+        assert_eq!(engine.process_packet(&attempt("203.0.113.9", 41000, Duration::from_secs(1))), RuleAction::Allow);
+        
+        assert_eq!(engine.stats.packets_rate_limited, 2);
+        assert_eq!(engine.stats.rate_limited_by_rule.get(&limit_id), Some(&2));
+        assert_eq!(engine.stats.packets_blocked, 2);
+        assert_eq!(engine.stats.packets_allowed, 8);
+    }
 }
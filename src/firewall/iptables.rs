@@ -0,0 +1,599 @@
+use std::collections::{BTreeSet, HashMap};
+use ipnetwork::IpNetwork;
+use crate::firewall::conntrack::ConnState;
+use crate::firewall::matchers::{AddressSet, PortRange, PortSet};
+use crate::firewall::nftables::NftablesBackend;
+use crate::firewall::ratelimit::{RateLimit, RateScope, RateUnit};
+use crate::firewall::rules::{FirewallRule, RuleAction, RuleDirection, RuleProtocol};
+use crate::firewall::translate::{
+    family_addresses, import_priority, name_from_comment, rule_comment, rule_location,
+    AddressMatch, RulesetFormat, TranslateError, Translation, TranslationIssue, POLICY_PRIORITY,
+};
+
+/// A multiport match takes at most 15 ports, a range counting as two
+const MULTIPORT_MAX: usize = 15;
+
+/// Match modules whose options are understood; loading them matches nothing by itself
+const SUPPORTED_MODULES: [&str; 8] = ["tcp", "udp", "icmp", "icmp6", "multiport", "conntrack", "state", "comment"];
+
+/// Parse `iptables-save` / `ip6tables-save` output (with or without `-c`
+/// counters, which become match counts). Only the INPUT and OUTPUT chains of
+/// the `filter` table map onto rules; a DROP policy becomes a catch-all
+/// block rule below everything else.
+///
+/// Rules without addresses apply to both IP families once imported.
+pub fn import(content: &str) -> Result<Translation<Vec<FirewallRule>>, TranslateError> {
+    let malformed = |line_no: usize, message: String| {
+        TranslateError::Malformed(RulesetFormat::Iptables, format!("line {}: {}", line_no, message))
+    };
+    let mut translation: Translation<Vec<FirewallRule>> = Translation::default();
+    let mut table: Option<String> = None;
+    let mut drop_policies: Vec<(RuleDirection, String, usize)> = Vec::new();
+    let mut chain_positions: HashMap<String, usize> = HashMap::new();
+    
+    for (index, line) in content.lines().enumerate() {
+        let line_no = index + 1;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let location = format!("line {}", line_no);
+        
+        if let Some(name) = line.strip_prefix('*') {
+            table = Some(name.to_string());
+            continue;
+        }
+        let table_name = match table {
+            Some(ref name) => name.clone(),
+            None => return Err(malformed(line_no, format!("'{}' is outside of a table (expected iptables-save output)", line))),
+        };
+        
+        if line == "COMMIT" {
+            // Policies apply after every rule of the chain
+            for (direction, chain, policy_line) in drop_policies.drain(..) {
+                let rules = &mut translation.output;
+                let rule = FirewallRule::new(rules.len() as u32 + 1, format!("{} policy DROP", chain), RuleAction::Block, direction, RuleProtocol::Any)
+                    .with_priority(POLICY_PRIORITY)
+                    .with_description(format!("Imported from iptables line {}: default policy of chain {}", policy_line, chain));
+                rules.push(rule);
+            }
+            table = None;
+            continue;
+        }
+        
+        if let Some(declaration) = line.strip_prefix(':') {
+            // ":INPUT DROP [0:0]"; user-defined chains have policy "-"
+            let mut fields = declaration.split_whitespace();
+            let (chain, policy) = match (fields.next(), fields.next()) {
+                (Some(chain), Some(policy)) => (chain, policy),
+                _ => return Err(malformed(line_no, format!("malformed chain declaration '{}'", line))),
+            };
+            if table_name == "filter" && policy == "DROP" {
+                match chain_direction(chain) {
+                    Some(direction) => drop_policies.push((direction, chain.to_string(), line_no)),
+                    None => translation.issues.push(TranslationIssue::skipped(location, format!("DROP policy of chain {} is not supported", chain))),
+                }
+            }
+            continue;
+        }
+        
+        // Counters from `iptables-save -c` come first: "[packets:bytes] -A ..."
+        let (packets, rule_text) = match line.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+            Some((counters, rest)) => (counters.split(':').next().and_then(|packets| packets.parse::<u64>().ok()), rest.trim()),
+            None => (None, line),
+        };
+        let args = tokenize(rule_text).map_err(|message| malformed(line_no, message))?;
+        let chain = match args.as_slice() {
+            [append, chain, ..] if append == "-A" => chain.clone(),
+            _ => return Err(malformed(line_no, format!("expected '-A <chain> ...', got '{}'", rule_text))),
+        };
+        
+        if table_name != "filter" {
+            translation.issues.push(TranslationIssue::skipped(location, format!("rules in the '{}' table are not supported", table_name)));
+            continue;
+        }
+        let direction = match chain_direction(&chain) {
+            Some(direction) => direction,
+            None => {
+                translation.issues.push(TranslationIssue::skipped(location, format!("chain {} is not supported, only INPUT and OUTPUT", chain)));
+                continue;
+            }
+        };
+        let position = chain_positions.entry(chain.clone()).or_insert(0);
+        *position += 1;
+        
+        match parse_rule(&args[2..], direction) {
+            Ok((mut rule, approximations)) => {
+                let rules = &mut translation.output;
+                rule.id = rules.len() as u32 + 1;
+                rule.priority = import_priority(rules.len());
+                if rule.name.is_empty() {
+                    rule.name = format!("{} #{}", chain, position);
+                }
+                rule.description = format!("Imported from iptables line {}: {}", line_no, rule_text);
+                rule.match_count = packets.unwrap_or(0);
+                rules.push(rule);
+                
+                for message in approximations {
+                    translation.issues.push(TranslationIssue::approximated(location.clone(), message));
+                }
+            }
+            Err(message) => translation.issues.push(TranslationIssue::skipped(location, message)),
+        }
+    }
+    
+    if table.is_some() {
+        return Err(TranslateError::Malformed(RulesetFormat::Iptables, "missing COMMIT at end of input".to_string()));
+    }
+    Ok(translation)
+}
+
+/// Write rules of one IP family as `iptables-save` text for `iptables-restore`
+/// (or `ip6tables-restore` when `ipv6` is set)
+pub fn export(rules: &[FirewallRule], ipv6: bool) -> Translation<String> {
+    let mut issues = Vec::new();
+    let mut input = Vec::new();
+    let mut output = Vec::new();
+    
+    for rule in rules.iter().filter(|rule| rule.enabled) {
+        let lines = match export_rule(rule, ipv6) {
+            Ok(lines) => lines,
+            Err(message) => {
+                issues.push(TranslationIssue::skipped(rule_location(rule), message));
+                continue;
+            }
+        };
+        
+        if matches!(rule.direction, RuleDirection::Inbound | RuleDirection::Bidirectional) {
+            input.extend(lines.iter().cloned());
+        }
+        if matches!(rule.direction, RuleDirection::Outbound | RuleDirection::Bidirectional) {
+            output.extend(lines);
+        }
+    }
+    
+    let tool = if ipv6 { "ip6tables-save" } else { "iptables-save" };
+    let mut text = format!("# Generated by network-monitor in {} format\n*filter\n", tool);
+    text.push_str(":INPUT ACCEPT [0:0]\n:FORWARD ACCEPT [0:0]\n:OUTPUT ACCEPT [0:0]\n");
+    for (chain, lines) in [("INPUT", &input), ("OUTPUT", &output)] {
+        for line in lines {
+            text.push_str(&format!("-A {} {}\n", chain, line));
+        }
+    }
+    text.push_str("COMMIT\n");
+    
+    Translation { output: text, issues }
+}
+
+fn chain_direction(chain: &str) -> Option<RuleDirection> {
+    match chain {
+        "INPUT" => Some(RuleDirection::Inbound),
+        "OUTPUT" => Some(RuleDirection::Outbound),
+        _ => None,
+    }
+}
+
+/// Build a rule from the arguments after `-A <chain>`. Errors name the
+/// construct that can't be represented; the second value lists the ones
+/// that were approximated.
+fn parse_rule(args: &[String], direction: RuleDirection) -> Result<(FirewallRule, Vec<String>), String> {
+    let mut approximations = Vec::new();
+    let mut protocol = RuleProtocol::Any;
+    let mut source_ips = None;
+    let mut destination_ips = None;
+    let mut source_ports = None;
+    let mut destination_ports = None;
+    let mut states = None;
+    let mut comment = None;
+    let mut action = None;
+    let mut negate = false;
+    
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if arg == "!" {
+            negate = true;
+            continue;
+        }
+        let negated = std::mem::take(&mut negate);
+        
+        match arg.as_str() {
+            "-s" | "--source" => source_ips = Some(parse_addresses(next_value(&mut args, arg)?, negated)?),
+            "-d" | "--destination" => destination_ips = Some(parse_addresses(next_value(&mut args, arg)?, negated)?),
+            "-p" | "--protocol" => {
+                if negated {
+                    return Err("negated protocol matches are not supported".to_string());
+                }
+                protocol = parse_protocol(next_value(&mut args, arg)?)?;
+            }
+            "-m" | "--match" => {
+                let module = next_value(&mut args, arg)?;
+                if !SUPPORTED_MODULES.contains(&module.as_str()) {
+                    return Err(format!("match module '{}' is not supported", module));
+                }
+            }
+            "--sport" | "--source-port" | "--sports" | "--source-ports" => {
+                source_ports = Some(parse_ports(next_value(&mut args, arg)?, negated)?);
+            }
+            "--dport" | "--destination-port" | "--dports" | "--destination-ports" => {
+                destination_ports = Some(parse_ports(next_value(&mut args, arg)?, negated)?);
+            }
+            "--ctstate" | "--state" => states = Some(parse_states(next_value(&mut args, arg)?, negated)?),
+            "--comment" => comment = Some(next_value(&mut args, arg)?.clone()),
+            "-j" | "--jump" => {
+                action = Some(match next_value(&mut args, arg)?.as_str() {
+                    "ACCEPT" => RuleAction::Allow,
+                    "DROP" => RuleAction::Block,
+                    "REJECT" => {
+                        approximations.push("REJECT imported as a silent drop".to_string());
+                        RuleAction::Block
+                    }
+                    "LOG" => RuleAction::Log,
+                    target => return Err(format!("target '{}' is not supported", target)),
+                });
+            }
+            // Target options that don't affect what is matched
+            "--reject-with" | "--log-prefix" | "--log-level" => {
+                next_value(&mut args, arg)?;
+            }
+            "--log-uid" | "--log-tcp-sequence" | "--log-tcp-options" | "--log-ip-options" => {}
+            "-i" | "--in-interface" | "-o" | "--out-interface" => {
+                return Err("interface matches are not supported".to_string());
+            }
+            other => return Err(format!("option '{}' is not supported", other)),
+        }
+    }
+    
+    let action = action.ok_or("rule has no target (it only counts packets)")?;
+    let has_ports = source_ports.is_some() || destination_ports.is_some();
+    if has_ports && !matches!(protocol, RuleProtocol::TCP | RuleProtocol::UDP) {
+        return Err("port match without -p tcp or -p udp".to_string());
+    }
+    
+    let mut rule = FirewallRule::new(0, comment.map(|comment| name_from_comment(&comment)).unwrap_or_default(), action, direction, protocol);
+    rule.source_ips = source_ips;
+    rule.destination_ips = destination_ips;
+    rule.source_ports = source_ports;
+    rule.destination_ports = destination_ports;
+    rule.states = states;
+    Ok((rule, approximations))
+}
+
+fn next_value<'a>(args: &mut std::slice::Iter<'a, String>, option: &str) -> Result<&'a String, String> {
+    args.next().ok_or_else(|| format!("option '{}' needs a value", option))
+}
+
+fn parse_addresses(value: &str, negated: bool) -> Result<AddressSet, String> {
+    let mut set: AddressSet = value
+        .split(',')
+        .map(|entry| entry.parse::<IpNetwork>().map_err(|_| format!("invalid address '{}'", entry)))
+        .collect::<Result<_, _>>()?;
+    set.set_negated(negated);
+    Ok(set)
+}
+
+fn parse_protocol(value: &str) -> Result<RuleProtocol, String> {
+    match value.to_lowercase().as_str() {
+        "tcp" | "6" => Ok(RuleProtocol::TCP),
+        "udp" | "17" => Ok(RuleProtocol::UDP),
+        "icmp" | "1" | "ipv6-icmp" | "icmpv6" | "58" => Ok(RuleProtocol::ICMP),
+        "all" | "0" => Ok(RuleProtocol::Any),
+        other => Err(format!("protocol '{}' is not supported", other)),
+    }
+}
+
+/// "22", "1024:65535", open-ended "1024:", or a multiport list of those
+fn parse_ports(value: &str, negated: bool) -> Result<PortSet, String> {
+    let parse = |port: &str, default: u16| {
+        if port.is_empty() {
+            Ok(default)
+        } else {
+            port.parse::<u16>().map_err(|_| format!("invalid port '{}'", port))
+        }
+    };
+    let mut set: PortSet = value
+        .split(',')
+        .map(|entry| match entry.split_once(':') {
+            Some((start, end)) => Ok(PortRange::new(parse(start, 0)?, parse(end, u16::MAX)?)),
+            None => Ok(PortRange::single(parse(entry, 0)?)),
+        })
+        .collect::<Result<_, String>>()?;
+    set.set_negated(negated);
+    Ok(set)
+}
+
+fn parse_states(value: &str, negated: bool) -> Result<BTreeSet<ConnState>, String> {
+    let listed: BTreeSet<ConnState> = value
+        .split(',')
+        .map(|state| state.parse::<ConnState>().map_err(|_| format!("connection state '{}' is not supported", state)))
+        .collect::<Result<_, _>>()?;
+    if !negated {
+        return Ok(listed);
+    }
+    
+    let states: BTreeSet<ConnState> = ConnState::ALL.into_iter().filter(|state| !listed.contains(state)).collect();
+    if states.is_empty() {
+        return Err("negated state match excludes every connection state".to_string());
+    }
+    Ok(states)
+}
+
+/// Split a rule line into arguments like iptables-restore does: double
+/// quotes group words, a backslash escapes the next character
+fn tokenize(line: &str) -> Result<Vec<String>, String> {
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    let mut in_token = false;
+    let mut quoted = false;
+    
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                current.push(chars.next().unwrap_or('\\'));
+                in_token = true;
+            }
+            '"' => {
+                quoted = !quoted;
+                in_token = true;
+            }
+            c if c.is_whitespace() && !quoted => {
+                if in_token {
+                    tokens.push(std::mem::take(&mut current));
+                    in_token = false;
+                }
+            }
+            c => {
+                current.push(c);
+                in_token = true;
+            }
+        }
+    }
+    
+    if quoted {
+        return Err("unterminated quote".to_string());
+    }
+    if in_token {
+        tokens.push(current);
+    }
+    Ok(tokens)
+}
+
+/// A packet burst counts the packets allowed at once, a byte burst only
+/// those on top of the rate
+fn hashlimit_above(rule_id: u32, limit: &RateLimit) -> String {
+    let (rate, burst) = match limit.unit {
+        RateUnit::Packets => (format!("{}/sec", limit.rate), limit.rate.saturating_add(limit.burst)),
+        RateUnit::Bytes => (format!("{}b/s", limit.rate), limit.burst),
+    };
+    let mode = match limit.scope {
+        RateScope::PerSource => " --hashlimit-mode srcip",
+        RateScope::PerRule => "",
+    };
+    format!("-m hashlimit --hashlimit-above {} --hashlimit-burst {}{} --hashlimit-name nm_ratelimit_{}", rate, burst, mode, rule_id)
+}
+
+/// The arguments after `-A <chain>` for one rule; a rule can need several
+/// lines (address lists, TCP-or-UDP, log-and-drop) or none at all when it
+/// can't match this IP family
+fn export_rule(rule: &FirewallRule, ipv6: bool) -> Result<Vec<String>, String> {
+    // Same validity checks as enforcement, e.g. ICMP with ports
+    NftablesBackend::compile_rule(rule)?;
+    let addresses = match family_addresses(rule, ipv6) {
+        Some(addresses) => addresses,
+        None => return Ok(Vec::new()),
+    };
+    
+    let sources = address_args("-s", &addresses.source)?;
+    let destinations = address_args("-d", &addresses.destination)?;
+    
+    let has_ports = rule.source_ports.is_some() || rule.destination_ports.is_some();
+    let protocols: Vec<&str> = match rule.protocol {
+        RuleProtocol::TCP => vec!["tcp"],
+        RuleProtocol::UDP => vec!["udp"],
+        RuleProtocol::ICMP if ipv6 => vec!["ipv6-icmp"],
+        RuleProtocol::ICMP => vec!["icmp"],
+        RuleProtocol::Any if has_ports => vec!["tcp", "udp"],
+        RuleProtocol::Any => vec![""],
+    };
+    
+    // Single ports go with the protocol module, lists need multiport
+    let mut protocol_options = Vec::new();
+    let mut multiport_options = Vec::new();
+    for (option, ports) in [("sport", &rule.source_ports), ("dport", &rule.destination_ports)] {
+        let ports = match ports {
+            Some(ports) if !ports.is_empty() => ports,
+            _ => continue, // an empty negated set excludes nothing
+        };
+        let negation = if ports.is_negated() { "! " } else { "" };
+        let ranges: Vec<String> = ports.iter().map(|range| format!("{}", range).replace('-', ":")).collect();
+        if ports.len() == 1 {
+            protocol_options.push(format!("{}--{} {}", negation, option, ranges[0]));
+        } else {
+            let port_count: usize = ports.iter().map(|range| if range.is_single() { 1 } else { 2 }).sum();
+            if port_count > MULTIPORT_MAX {
+                return Err(format!("{} ports need more than the {} a multiport match allows", option, MULTIPORT_MAX));
+            }
+            multiport_options.push(format!("{}--{}s {}", negation, option, ranges.join(",")));
+        }
+    }
+    
+    let mut common = Vec::new();
+    if !multiport_options.is_empty() {
+        common.push(format!("-m multiport {}", multiport_options.join(" ")));
+    }
+    if let Some(ref states) = rule.states {
+        let states: Vec<String> = states.iter().map(|state| state.nft_keyword().to_uppercase()).collect();
+        common.push(format!("-m conntrack --ctstate {}", states.join(",")));
+    }
+    common.push(format!("-m comment --comment {}", quote(&rule_comment(rule))));
+    
+    let log = format!("-j LOG --log-prefix {}", quote(&format!("nm rule {}: ", rule.id)));
+    let targets: Vec<String> = match rule.action {
+        RuleAction::Allow => vec!["-j ACCEPT".to_string()],
+        RuleAction::Block => vec!["-j DROP".to_string()],
+        RuleAction::Log => vec![log],
+        RuleAction::LogAndBlock => vec![log, "-j DROP".to_string()],
+        // hashlimit drops what is over the limit, the rest is accepted
+        RuleAction::RateLimit(limit) => vec![
+            format!("{} -j DROP", hashlimit_above(rule.id, &limit)),
+            "-j ACCEPT".to_string(),
+        ],
+    };
+    
+    let mut lines = Vec::new();
+    for source in &sources {
+        for destination in &destinations {
+            for protocol in &protocols {
+                for target in &targets {
+                    let mut parts = vec![source.clone(), destination.clone()];
+                    if !protocol.is_empty() {
+                        parts.push(format!("-p {}", protocol));
+                        if !protocol_options.is_empty() {
+                            parts.push(format!("-m {} {}", protocol, protocol_options.join(" ")));
+                        }
+                    }
+                    parts.extend(common.iter().cloned());
+                    parts.push(target.clone());
+                    parts.retain(|part| !part.is_empty());
+                    lines.push(parts.join(" "));
+                }
+            }
+        }
+    }
+    Ok(lines)
+}
+
+/// One alternative per address, since iptables takes one network per rule
+/// (`iptables-save` lists `-s a,b` as two rules too)
+fn address_args(flag: &str, addresses: &Option<AddressMatch>) -> Result<Vec<String>, String> {
+    let addresses = match addresses {
+        Some(addresses) => addresses,
+        None => return Ok(vec![String::new()]),
+    };
+    if addresses.negated && addresses.networks.len() > 1 {
+        return Err(format!("iptables can only negate a single network, not {}", addresses.networks.len()));
+    }
+    
+    let negation = if addresses.negated { "! " } else { "" };
+    Ok(addresses.networks.iter().map(|network| format!("{}{} {}", negation, flag, network)).collect())
+}
+
+fn quote(text: &str) -> String {
+    format!("\"{}\"", text.replace('\\', "\\\\").replace('"', "\\\""))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firewall::rules::RuleTemplates;
+    use std::net::{IpAddr, Ipv4Addr};
+    
+    const SAVED: &str = r#"# Generated by iptables-save v1.8.8
+*nat
+:PREROUTING ACCEPT [0:0]
+-A PREROUTING -p tcp --dport 8080 -j REDIRECT --to-ports 80
+COMMIT
+*filter
+:INPUT DROP [0:0]
+:FORWARD DROP [0:0]
+:OUTPUT ACCEPT [0:0]
+[120:9600] -A INPUT -m conntrack --ctstate RELATED,ESTABLISHED -j ACCEPT
+[3:180] -A INPUT -s 10.0.0.0/8 -p tcp -m tcp --dport 22 -m comment --comment "ssh \"admins\"" -j ACCEPT
+-A INPUT -p udp -m multiport ! --dports 53,1024:65535 -j REJECT --reject-with icmp-port-unreachable
+-A INPUT -i eth1 -j DROP
+-A FORWARD -j ACCEPT
+COMMIT
+"#;
+
+    #[test]
+    fn test_import_iptables_save() {
+        let translation = import(SAVED).unwrap();
+        let rules = &translation.output;
+        assert_eq!(rules.len(), 4);
+        
+        assert_eq!(rules[0].name, "INPUT #1");
+        assert_eq!(rules[0].match_count, 120);
+        assert_eq!(rules[0].states, Some([ConnState::Established, ConnState::Related].into_iter().collect()));
+        
+        let ssh = &rules[1];
+        assert_eq!(ssh.name, "ssh \"admins\"");
+        assert!(ssh.priority < rules[0].priority);
+        assert!(ssh.matches_packet(
+            &IpAddr::V4(Ipv4Addr::new(10, 2, 3, 4)),
+            &IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1)),
+            40000,
+            22,
+            &RuleProtocol::TCP,
+            &RuleDirection::Inbound,
+        ));
+        
+        let ports = rules[2].destination_ports.as_ref().unwrap();
+        assert!(ports.is_negated());
+        assert_eq!(ports.to_string(), "!53,1024-65535");
+        assert_eq!(rules[2].action, RuleAction::Block);
+        
+        // The DROP policy comes last, below every rule
+        assert_eq!(rules[3].priority, POLICY_PRIORITY);
+        assert_eq!(rules[3].action, RuleAction::Block);
+        
+        let issues: Vec<String> = translation.issues.iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(issues, vec![
+            "line 4: rules in the 'nat' table are not supported (skipped)",
+            "line 8: DROP policy of chain FORWARD is not supported (skipped)",
+            "line 12: REJECT imported as a silent drop (approximated)",
+            "line 13: interface matches are not supported (skipped)",
+            "line 14: chain FORWARD is not supported, only INPUT and OUTPUT (skipped)",
+        ]);
+        assert_eq!(translation.skipped_count(), 4);
+        
+        assert!(import("-A INPUT -j ACCEPT\n").is_err());
+        assert!(import("*filter\n-A INPUT -m comment --comment \"open\n").is_err());
+    }
+    
+    #[test]
+    fn test_export_round_trip() {
+        let mut rules = vec![
+            RuleTemplates::allow_established(),
+            RuleTemplates::allow_ssh().with_source_network("192.168.0.0/16".parse().unwrap()),
+            RuleTemplates::block_suspicious_ports(),
+            FirewallRule::new(20, "not internal".to_string(), RuleAction::LogAndBlock, RuleDirection::Inbound, RuleProtocol::Any)
+                .with_source_network("10.0.0.0/8".parse().unwrap())
+                .with_source_network("172.16.0.0/12".parse().unwrap())
+                .negate_source_ips(),
+        ];
+        rules[0].id = 1;
+        rules[1].id = 2;
+        rules[2].id = 3;
+        
+        let translation = export(&rules, false);
+        assert_eq!(translation.issues.len(), 1);
+        assert_eq!(translation.issues[0].location, "rule 20 (not internal)");
+        assert!(translation.output.contains("-A INPUT -s 192.168.0.0/16 -p tcp -m tcp --dport 22 -m comment --comment \"rule 2: Allow SSH\" -j ACCEPT\n"));
+        
+        // Log-and-block needs a LOG and a DROP line, for TCP and UDP each
+        let imported = import(&translation.output).unwrap();
+        assert!(imported.issues.is_empty());
+        let names: Vec<&str> = imported.output.iter().map(|rule| rule.name.as_str()).take(4).collect();
+        assert_eq!(names, vec!["Allow Established/Related", "Allow SSH", "Block Suspicious Ports", "Block Suspicious Ports"]);
+        assert_eq!(imported.output.len(), 11);
+        assert_eq!(imported.output[0].states, rules[0].states);
+        assert_eq!(imported.output[2].action, RuleAction::Log);
+        assert_eq!(imported.output[3].action, RuleAction::Block);
+        assert_eq!(imported.output[3].destination_ports, rules[2].destination_ports);
+        
+        // The same rules have nothing to say about IPv6 sources in 192.168.0.0/16
+        let ipv6 = export(&rules[1..2], true);
+        assert!(!ipv6.output.contains("-A INPUT"));
+    }
+    
+    #[test]
+    fn test_export_rate_limit() {
+        let translation = export(&[RuleTemplates::rate_limit_ssh()], false);
+        assert!(translation.issues.is_empty());
+        let lines: Vec<&str> = translation.output.lines().filter(|line| line.starts_with("-A INPUT")).collect();
+        assert_eq!(lines, vec![
+            "-A INPUT -p tcp -m tcp --dport 22 -m conntrack --ctstate NEW -m comment --comment \"rule 9: Rate Limit SSH\" -m hashlimit --hashlimit-above 1/sec --hashlimit-burst 6 --hashlimit-mode srcip --hashlimit-name nm_ratelimit_9 -j DROP",
+            "-A INPUT -p tcp -m tcp --dport 22 -m conntrack --ctstate NEW -m comment --comment \"rule 9: Rate Limit SSH\" -j ACCEPT",
+        ]);
+    }
+}
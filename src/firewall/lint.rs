@@ -0,0 +1,388 @@
+use std::fmt;
+use ipnetwork::IpNetwork;
+use crate::firewall::conntrack::ConnState;
+use crate::firewall::matchers::{AddressSet, PortSet};
+use crate::firewall::rules::{FirewallRule, RuleAction, RuleDirection, RuleProtocol};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    /// A higher-priority rule with a different action takes all of the rule's traffic
+    Shadowed,
+    /// A higher-priority rule already does the same thing to all of the rule's traffic
+    Redundant,
+    /// Overlapping allow and block rules at the same priority, decided only by insertion order
+    Conflict,
+}
+
+impl fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            FindingKind::Shadowed => write!(f, "shadowed"),
+            FindingKind::Redundant => write!(f, "redundant"),
+            FindingKind::Conflict => write!(f, "conflict"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RuleFinding {
+    pub kind: FindingKind,
+    /// The rule the finding is about (the later one in evaluation order)
+    pub rule_id: u32,
+    /// The earlier rule that shadows or conflicts with it
+    pub other_rule_id: u32,
+    pub message: String,
+}
+
+impl fmt::Display for RuleFinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.kind, self.message)
+    }
+}
+
+/// Check rules in evaluation order (as `FirewallEngine::get_rules()` returns
+/// them) for ones that can never take effect or that contradict each other.
+///
+/// Disabled rules are ignored. Log rules never shadow anything, since
+/// evaluation continues past them, and neither do expiring or scheduled
+/// rules, which step aside at other times.
+pub fn check_rules(rules: &[FirewallRule]) -> Vec<RuleFinding> {
+    let enabled: Vec<(&FirewallRule, MatchSpace)> = rules
+        .iter()
+        .filter(|rule| rule.enabled)
+        .map(|rule| (rule, MatchSpace::of(rule)))
+        .collect();
+    let mut findings = Vec::new();
+    
+    for (i, (rule, space)) in enabled.iter().enumerate() {
+        // A rule that matches nothing is covered by everything; don't pin it on any one rule
+        if space.is_empty() {
+            continue;
+        }
+        let earlier = &enabled[..i];
+        
+        let covering = earlier.iter().find(|(other, other_space)| {
+            !other.is_timed() && other_space.covers(space) && (is_terminal(&other.action) || other.action == rule.action)
+        });
+        if let Some((other, _)) = covering {
+            findings.push(covered_finding(rule, other));
+            continue;
+        }
+        
+        let conflicting = earlier.iter().filter(|(other, other_space)| {
+            other.priority == rule.priority
+                && is_terminal(&other.action)
+                && is_terminal(&rule.action)
+                && is_blocking(&other.action) != is_blocking(&rule.action)
+                && other_space.overlaps(space)
+        });
+        for (other, _) in conflicting {
+            findings.push(RuleFinding {
+                kind: FindingKind::Conflict,
+                rule_id: rule.id,
+                other_rule_id: other.id,
+                message: format!(
+                    "{} and {} overlap with opposite actions at the same priority ({}); {} wins only because it comes first",
+                    describe(rule),
+                    describe(other),
+                    rule.priority,
+                    describe(other)
+                ),
+            });
+        }
+    }
+    findings
+}
+
+fn covered_finding(rule: &FirewallRule, other: &FirewallRule) -> RuleFinding {
+    // Same verdict and no logging lost: removing the rule changes nothing.
+    // A rate limit on either side makes the verdicts differ for some traffic.
+    let rate_limited = matches!(rule.action, RuleAction::RateLimit(_)) || matches!(other.action, RuleAction::RateLimit(_));
+    let redundant = rule.action == other.action
+        || (is_blocking(&rule.action) == is_blocking(&other.action)
+            && is_terminal(&rule.action)
+            && rule.action != RuleAction::LogAndBlock
+            && !rate_limited);
+    
+    let (kind, message) = if redundant {
+        let verb = match other.action {
+            RuleAction::Allow => "allows",
+            RuleAction::Block | RuleAction::LogAndBlock => "blocks",
+            RuleAction::Log => "logs",
+            RuleAction::RateLimit(_) => "rate-limits",
+        };
+        (
+            FindingKind::Redundant,
+            format!("{} duplicates {} (priority {}), which already {} all of its traffic", describe(rule), describe(other), other.priority, verb),
+        )
+    } else {
+        (
+            FindingKind::Shadowed,
+            format!("{} can never match: {} (priority {}) takes all of its traffic first", describe(rule), describe(other), other.priority),
+        )
+    };
+    RuleFinding { kind, rule_id: rule.id, other_rule_id: other.id, message }
+}
+
+fn describe(rule: &FirewallRule) -> String {
+    format!("#{} '{}'", rule.id, rule.name)
+}
+
+/// Whether evaluation stops at the rule
+fn is_terminal(action: &RuleAction) -> bool {
+    *action != RuleAction::Log
+}
+
+fn is_blocking(action: &RuleAction) -> bool {
+    matches!(action, RuleAction::Block | RuleAction::LogAndBlock)
+}
+
+/// Sorted, merged, inclusive intervals of a field's values
+type Intervals = Vec<(u128, u128)>;
+
+const IPV4_MAX: u128 = u32::MAX as u128;
+const IPV6_MAX: u128 = u128::MAX;
+const PORT_MAX: u128 = u16::MAX as u128;
+
+/// Everything a rule can match, with protocol, direction and state as bit sets
+struct MatchSpace {
+    protocols: u8,
+    directions: u8,
+    states: u8,
+    sources: [Intervals; 2],
+    destinations: [Intervals; 2],
+    source_ports: Intervals,
+    destination_ports: Intervals,
+}
+
+impl MatchSpace {
+    fn of(rule: &FirewallRule) -> Self {
+        let protocols = match rule.protocol {
+            RuleProtocol::TCP => 0b0001,
+            RuleProtocol::UDP => 0b0010,
+            RuleProtocol::ICMP => 0b0100,
+            // Packets of any other protocol only match `Any`
+            RuleProtocol::Any => 0b1111,
+        };
+        let directions = match rule.direction {
+            RuleDirection::Inbound => 0b01,
+            RuleDirection::Outbound => 0b10,
+            RuleDirection::Bidirectional => 0b11,
+        };
+        let states = ConnState::ALL
+            .iter()
+            .enumerate()
+            .filter(|(_, state)| rule.matches_state(**state))
+            .fold(0, |bits, (i, _)| bits | 1 << i);
+        
+        Self {
+            protocols,
+            directions,
+            states,
+            sources: address_space(rule.source_ips.as_ref()),
+            destinations: address_space(rule.destination_ips.as_ref()),
+            source_ports: port_space(rule.source_ports.as_ref()),
+            destination_ports: port_space(rule.destination_ports.as_ref()),
+        }
+    }
+    
+    fn is_empty(&self) -> bool {
+        self.protocols == 0
+            || self.directions == 0
+            || self.states == 0
+            || self.sources.iter().all(|family| family.is_empty())
+            || self.destinations.iter().all(|family| family.is_empty())
+            || self.source_ports.is_empty()
+            || self.destination_ports.is_empty()
+    }
+    
+    /// Whether every packet `other` matches is also matched by `self`
+    fn covers(&self, other: &MatchSpace) -> bool {
+        other.protocols & !self.protocols == 0
+            && other.directions & !self.directions == 0
+            && other.states & !self.states == 0
+            && (0..2).all(|family| is_subset(&other.sources[family], &self.sources[family]))
+            && (0..2).all(|family| is_subset(&other.destinations[family], &self.destinations[family]))
+            && is_subset(&other.source_ports, &self.source_ports)
+            && is_subset(&other.destination_ports, &self.destination_ports)
+    }
+    
+    /// Whether some packet could match both
+    fn overlaps(&self, other: &MatchSpace) -> bool {
+        self.protocols & other.protocols != 0
+            && self.directions & other.directions != 0
+            && self.states & other.states != 0
+            && (0..2).any(|family| intersects(&self.sources[family], &other.sources[family]))
+            && (0..2).any(|family| intersects(&self.destinations[family], &other.destinations[family]))
+            && intersects(&self.source_ports, &other.source_ports)
+            && intersects(&self.destination_ports, &other.destination_ports)
+    }
+}
+
+/// IPv4 and IPv6 addresses an address field admits
+fn address_space(set: Option<&AddressSet>) -> [Intervals; 2] {
+    let set = match set {
+        Some(set) => set,
+        None => return [vec![(0, IPV4_MAX)], vec![(0, IPV6_MAX)]],
+    };
+    
+    let mut v4 = Vec::new();
+    let mut v6 = Vec::new();
+    for network in set.iter() {
+        match network {
+            IpNetwork::V4(network) => {
+                let start = u32::from(network.network()) as u128;
+                v4.push((start, start | (IPV4_MAX >> network.prefix())));
+            }
+            IpNetwork::V6(network) => {
+                let start = u128::from(network.network());
+                v6.push((start, start | IPV6_MAX.checked_shr(network.prefix() as u32).unwrap_or(0)));
+            }
+        }
+    }
+    
+    let (v4, v6) = (merge(v4), merge(v6));
+    if set.is_negated() {
+        [complement(&v4, IPV4_MAX), complement(&v6, IPV6_MAX)]
+    } else {
+        [v4, v6]
+    }
+}
+
+fn port_space(set: Option<&PortSet>) -> Intervals {
+    let set = match set {
+        Some(set) => set,
+        None => return vec![(0, PORT_MAX)],
+    };
+    
+    let ranges = merge(set.iter().map(|range| (range.start as u128, range.end as u128)).collect());
+    if set.is_negated() {
+        complement(&ranges, PORT_MAX)
+    } else {
+        ranges
+    }
+}
+
+fn merge(mut intervals: Intervals) -> Intervals {
+    intervals.sort_unstable();
+    let mut merged: Intervals = Vec::with_capacity(intervals.len());
+    for (start, end) in intervals {
+        match merged.last_mut() {
+            Some(last) if start <= last.1.saturating_add(1) => last.1 = last.1.max(end),
+            _ => merged.push((start, end)),
+        }
+    }
+    merged
+}
+
+fn complement(intervals: &Intervals, max: u128) -> Intervals {
+    let mut gaps = Vec::new();
+    let mut next = Some(0u128);
+    for &(start, end) in intervals {
+        if let Some(gap_start) = next {
+            if gap_start < start {
+                gaps.push((gap_start, start - 1));
+            }
+        }
+        next = end.checked_add(1);
+    }
+    if let Some(gap_start) = next.filter(|gap_start| *gap_start <= max) {
+        gaps.push((gap_start, max));
+    }
+    gaps
+}
+
+/// Both sides merged, so each inner interval has to fit inside a single outer one
+fn is_subset(inner: &Intervals, outer: &Intervals) -> bool {
+    inner.iter().all(|&(start, end)| outer.iter().any(|&(outer_start, outer_end)| outer_start <= start && end <= outer_end))
+}
+
+fn intersects(a: &Intervals, b: &Intervals) -> bool {
+    a.iter().any(|&(a_start, a_end)| b.iter().any(|&(b_start, b_end)| a_start <= b_end && b_start <= a_end))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firewall::engine::FirewallEngine;
+    use crate::firewall::rules::RuleTemplates;
+    
+    fn rule(id: u32, action: RuleAction, protocol: RuleProtocol, priority: u8) -> FirewallRule {
+        FirewallRule::new(id, format!("rule {}", id), action, RuleDirection::Inbound, protocol).with_priority(priority)
+    }
+    
+    fn kinds(rules: Vec<FirewallRule>) -> Vec<(FindingKind, u32, u32)> {
+        let mut engine = FirewallEngine::new();
+        engine.replace_rules(rules);
+        check_rules(engine.get_rules())
+            .into_iter()
+            .map(|finding| (finding.kind, finding.rule_id, finding.other_rule_id))
+            .collect()
+    }
+    
+    #[test]
+    fn test_default_rules_are_clean() {
+        let mut engine = FirewallEngine::new();
+        engine.load_default_rules();
+        let findings = check_rules(engine.get_rules());
+        assert!(findings.is_empty(), "{:?}", findings);
+    }
+    
+    #[test]
+    fn test_shadowed_redundant_and_conflicting_rules() {
+        let rules = vec![
+            // Blocks all TCP from outside 10.0.0.0/8, so the allow below never sees any
+            rule(1, RuleAction::Block, RuleProtocol::TCP, 200)
+                .with_source_network("10.0.0.0/8".parse().unwrap())
+                .negate_source_ips(),
+            rule(2, RuleAction::Allow, RuleProtocol::TCP, 150)
+                .with_source_network("198.51.100.0/24".parse().unwrap())
+                .with_destination_port(443),
+            rule(3, RuleAction::Allow, RuleProtocol::Any, 120)
+                .with_source_network("10.0.0.0/9".parse().unwrap())
+                .with_destination_port_range(7000, 9000),
+            rule(4, RuleAction::Allow, RuleProtocol::TCP, 100)
+                .with_source_network("10.1.0.0/16".parse().unwrap())
+                .with_source_network("10.2.0.0/16".parse().unwrap())
+                .with_destination_port_range(8000, 8100),
+            // Same priority, overlapping ports, opposite actions
+            rule(5, RuleAction::Allow, RuleProtocol::UDP, 50).with_destination_port_range(5000, 6000),
+            rule(6, RuleAction::Block, RuleProtocol::UDP, 50).with_destination_port_range(5900, 6100),
+            rule(7, RuleAction::Block, RuleProtocol::UDP, 50).with_destination_port(53),
+            // Log rules are passed through, so nothing above shadows this one
+            rule(8, RuleAction::Log, RuleProtocol::Any, 1),
+        ];
+        
+        assert_eq!(
+            kinds(rules),
+            vec![
+                (FindingKind::Shadowed, 2, 1),
+                (FindingKind::Redundant, 4, 3),
+                (FindingKind::Conflict, 6, 5),
+            ]
+        );
+    }
+    
+    #[test]
+    fn test_duplicates_and_disabled_rules() {
+        let mut disabled = RuleTemplates::allow_ssh();
+        disabled.id = 10;
+        disabled.enabled = false;
+        let mut duplicate = RuleTemplates::allow_ssh();
+        duplicate.id = 11;
+        let mut log_twice = RuleTemplates::log_all_connections();
+        log_twice.id = 12;
+        
+        let rules = vec![
+            RuleTemplates::allow_ssh(),
+            disabled,
+            duplicate,
+            RuleTemplates::log_all_connections(),
+            log_twice,
+        ];
+        assert_eq!(
+            kinds(rules),
+            vec![(FindingKind::Redundant, 11, 2), (FindingKind::Redundant, 12, 6)]
+        );
+    }
+}
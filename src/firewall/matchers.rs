@@ -0,0 +1,367 @@
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use ipnetwork::IpNetwork;
+use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+/// Addresses and CIDR networks matched by a rule field.
+///
+/// Serialized as a JSON array of strings (plain addresses for single hosts, so
+/// rule files written before CIDR support still load), or as `{"not": [...]}`
+/// when the match is negated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AddressSet {
+    networks: Vec<IpNetwork>,
+    negated: bool,
+}
+
+impl AddressSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn insert(&mut self, network: IpNetwork) {
+        // Store the network address so `10.0.0.7/24` and `10.0.0.0/24` are the same entry
+        let network = IpNetwork::new(network.network(), network.prefix()).unwrap_or(network);
+        if let Err(index) = self.networks.binary_search(&network) {
+            self.networks.insert(index, network);
+        }
+    }
+    
+    /// Whether `ip` falls inside any of the networks, ignoring negation
+    pub fn contains(&self, ip: &IpAddr) -> bool {
+        self.networks.iter().any(|network| network.contains(*ip))
+    }
+    
+    /// Whether the field matches `ip`, taking negation into account
+    pub fn matches(&self, ip: &IpAddr) -> bool {
+        self.contains(ip) != self.negated
+    }
+    
+    pub fn set_negated(&mut self, negated: bool) {
+        self.negated = negated;
+    }
+    
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+    
+    /// Networks in sorted order
+    pub fn iter(&self) -> impl Iterator<Item = &IpNetwork> {
+        self.networks.iter()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.networks.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.networks.is_empty()
+    }
+}
+
+impl FromIterator<IpNetwork> for AddressSet {
+    fn from_iter<I: IntoIterator<Item = IpNetwork>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for network in iter {
+            set.insert(network);
+        }
+        set
+    }
+}
+
+impl FromIterator<IpAddr> for AddressSet {
+    fn from_iter<I: IntoIterator<Item = IpAddr>>(iter: I) -> Self {
+        iter.into_iter().map(IpNetwork::from).collect()
+    }
+}
+
+impl fmt::Display for AddressSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let networks: Vec<String> = self.networks.iter().map(format_network).collect();
+        if self.negated {
+            write!(f, "!")?;
+        }
+        write!(f, "{}", networks.join(","))
+    }
+}
+
+/// Single hosts are written without a prefix length
+pub fn format_network(network: &IpNetwork) -> String {
+    let host_prefix = if network.is_ipv4() { 32 } else { 128 };
+    if network.prefix() == host_prefix {
+        network.ip().to_string()
+    } else {
+        network.to_string()
+    }
+}
+
+/// Inclusive port range; a single port has `start == end`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct PortRange {
+    pub start: u16,
+    pub end: u16,
+}
+
+impl PortRange {
+    pub fn new(start: u16, end: u16) -> Self {
+        Self {
+            start: start.min(end),
+            end: start.max(end),
+        }
+    }
+    
+    pub fn single(port: u16) -> Self {
+        Self::new(port, port)
+    }
+    
+    pub fn contains(&self, port: u16) -> bool {
+        self.start <= port && port <= self.end
+    }
+    
+    pub fn is_single(&self) -> bool {
+        self.start == self.end
+    }
+}
+
+impl From<u16> for PortRange {
+    fn from(port: u16) -> Self {
+        Self::single(port)
+    }
+}
+
+impl fmt::Display for PortRange {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_single() {
+            write!(f, "{}", self.start)
+        } else {
+            write!(f, "{}-{}", self.start, self.end)
+        }
+    }
+}
+
+impl FromStr for PortRange {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parse = |port: &str| port.trim().parse::<u16>().map_err(|_| format!("invalid port '{}'", port.trim()));
+        match s.split_once('-') {
+            Some((start, end)) => Ok(Self::new(parse(start)?, parse(end)?)),
+            None => Ok(Self::single(parse(s)?)),
+        }
+    }
+}
+
+/// Ports and inclusive port ranges matched by a rule field.
+///
+/// Serialized as a JSON array whose entries are port numbers or `"start-end"`
+/// strings, or as `{"not": [...]}` when the match is negated.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PortSet {
+    ranges: Vec<PortRange>,
+    negated: bool,
+}
+
+impl PortSet {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    pub fn insert(&mut self, range: PortRange) {
+        if let Err(index) = self.ranges.binary_search(&range) {
+            self.ranges.insert(index, range);
+        }
+    }
+    
+    /// Whether `port` falls inside any of the ranges, ignoring negation
+    pub fn contains(&self, port: &u16) -> bool {
+        self.ranges.iter().any(|range| range.contains(*port))
+    }
+    
+    /// Whether the field matches `port`, taking negation into account
+    pub fn matches(&self, port: u16) -> bool {
+        self.contains(&port) != self.negated
+    }
+    
+    pub fn set_negated(&mut self, negated: bool) {
+        self.negated = negated;
+    }
+    
+    pub fn is_negated(&self) -> bool {
+        self.negated
+    }
+    
+    /// Ranges in sorted order
+    pub fn iter(&self) -> impl Iterator<Item = &PortRange> {
+        self.ranges.iter()
+    }
+    
+    pub fn len(&self) -> usize {
+        self.ranges.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.ranges.is_empty()
+    }
+}
+
+impl FromIterator<PortRange> for PortSet {
+    fn from_iter<I: IntoIterator<Item = PortRange>>(iter: I) -> Self {
+        let mut set = Self::new();
+        for range in iter {
+            set.insert(range);
+        }
+        set
+    }
+}
+
+impl FromIterator<u16> for PortSet {
+    fn from_iter<I: IntoIterator<Item = u16>>(iter: I) -> Self {
+        iter.into_iter().map(PortRange::single).collect()
+    }
+}
+
+impl fmt::Display for PortSet {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ranges: Vec<String> = self.ranges.iter().map(|range| range.to_string()).collect();
+        if self.negated {
+            write!(f, "!")?;
+        }
+        write!(f, "{}", ranges.join(","))
+    }
+}
+
+/// On-disk shape shared by both sets: a bare list, or a list wrapped in `not`
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum MatchRepr<T> {
+    Any(Vec<T>),
+    Not { not: Vec<T> },
+}
+
+impl<T> MatchRepr<T> {
+    fn new(entries: Vec<T>, negated: bool) -> Self {
+        if negated {
+            MatchRepr::Not { not: entries }
+        } else {
+            MatchRepr::Any(entries)
+        }
+    }
+    
+    fn into_parts(self) -> (Vec<T>, bool) {
+        match self {
+            MatchRepr::Any(entries) => (entries, false),
+            MatchRepr::Not { not } => (not, true),
+        }
+    }
+}
+
+/// Port entries are numbers, except ranges which need a string
+#[derive(Serialize, Deserialize)]
+#[serde(untagged)]
+enum PortRepr {
+    Single(u16),
+    Range(String),
+}
+
+impl Serialize for AddressSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entries: Vec<String> = self.networks.iter().map(format_network).collect();
+        MatchRepr::new(entries, self.negated).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for AddressSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (entries, negated) = MatchRepr::<String>::deserialize(deserializer)?.into_parts();
+        let mut set: AddressSet = entries
+            .iter()
+            .map(|entry| entry.parse::<IpNetwork>().map_err(serde::de::Error::custom))
+            .collect::<Result<_, _>>()?;
+        set.negated = negated;
+        Ok(set)
+    }
+}
+
+impl Serialize for PortSet {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let entries: Vec<PortRepr> = self.ranges
+            .iter()
+            .map(|range| {
+                if range.is_single() {
+                    PortRepr::Single(range.start)
+                } else {
+                    PortRepr::Range(range.to_string())
+                }
+            })
+            .collect();
+        MatchRepr::new(entries, self.negated).serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for PortSet {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (entries, negated) = MatchRepr::<PortRepr>::deserialize(deserializer)?.into_parts();
+        let mut set: PortSet = entries
+            .into_iter()
+            .map(|entry| match entry {
+                PortRepr::Single(port) => Ok(PortRange::single(port)),
+                PortRepr::Range(range) => range.parse::<PortRange>().map_err(serde::de::Error::custom),
+            })
+            .collect::<Result<_, _>>()?;
+        set.negated = negated;
+        Ok(set)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::net::Ipv4Addr;
+    
+    #[test]
+    fn test_address_set_cidr() {
+        let set: AddressSet = ["10.1.2.77/24".parse::<IpNetwork>().unwrap(), "2001:db8::/32".parse().unwrap()]
+            .into_iter()
+            .collect();
+        
+        assert!(set.contains(&IpAddr::V4(Ipv4Addr::new(10, 1, 2, 200))));
+        assert!(!set.contains(&IpAddr::V4(Ipv4Addr::new(10, 1, 3, 1))));
+        assert!(set.contains(&"2001:db8:1::5".parse().unwrap()));
+        assert_eq!(set.to_string(), "10.1.2.0/24,2001:db8::/32");
+    }
+    
+    #[test]
+    fn test_port_range_parsing() {
+        assert_eq!("1024-65535".parse::<PortRange>().unwrap(), PortRange::new(1024, 65535));
+        assert_eq!("443".parse::<PortRange>().unwrap(), PortRange::single(443));
+        assert_eq!("9000-8000".parse::<PortRange>().unwrap(), PortRange::new(8000, 9000));
+        assert!("80-http".parse::<PortRange>().is_err());
+    }
+    
+    #[test]
+    fn test_negated_sets() {
+        let mut ports: PortSet = [PortRange::new(1024, 65535)].into_iter().collect();
+        assert!(ports.matches(40000));
+        ports.set_negated(true);
+        assert!(!ports.matches(40000));
+        assert!(ports.matches(22));
+    }
+    
+    #[test]
+    fn test_serialization_compatible() {
+        // Format written before ranges and CIDR existed
+        let ips: AddressSet = serde_json::from_str(r#"["127.0.0.1", "::1"]"#).unwrap();
+        let ports: PortSet = serde_json::from_str("[22, 80]").unwrap();
+        assert_eq!(serde_json::to_string(&ips).unwrap(), r#"["127.0.0.1","::1"]"#);
+        assert_eq!(serde_json::to_string(&ports).unwrap(), "[22,80]");
+        
+        let ports: PortSet = serde_json::from_str(r#"{"not": [53, "1024-2047"]}"#).unwrap();
+        assert!(ports.is_negated());
+        assert_eq!(serde_json::to_string(&ports).unwrap(), r#"{"not":[53,"1024-2047"]}"#);
+        
+        let ips: AddressSet = serde_json::from_str(r#"["192.168.0.0/16"]"#).unwrap();
+        assert!(ips.matches(&IpAddr::V4(Ipv4Addr::new(192, 168, 40, 2))));
+        assert!(serde_json::from_str::<AddressSet>(r#"["not-an-ip"]"#).is_err());
+    }
+}
@@ -1,7 +1,31 @@
 pub mod rules;
+pub mod matchers;
+pub mod conntrack;
+pub mod schedule;
+pub mod ratelimit;
 pub mod engine;
 pub mod ui;
+pub mod editor;
+pub mod persistence;
+pub mod nftables;
+pub mod translate;
+pub mod iptables;
+pub mod nft_json;
+pub mod simulation;
+pub mod lint;
+pub mod block;
 
 pub use rules::{FirewallRule, RuleAction, RuleDirection, RuleProtocol};
+pub use matchers::{AddressSet, PortRange, PortSet};
+pub use conntrack::{ConnState, ConnTracker};
+pub use schedule::{RuleSchedule, Weekday};
+pub use ratelimit::{RateLimit, RateLimiter, RateScope, RateUnit};
 pub use engine::{FirewallEngine, FirewallStats};
 pub use ui::FirewallView;
+pub use editor::RuleEditor;
+pub use persistence::{RulesetBackup, RulesetError, RulesetStore};
+pub use nftables::{NftablesBackend, EnforcementError};
+pub use translate::{RulesetFormat, TranslateError, Translation, TranslationIssue};
+pub use simulation::{Simulation, SimulationReport};
+pub use lint::{check_rules, FindingKind, RuleFinding};
+pub use block::{BlockDialog, BlockDialogAction, BlockScope, BlockTarget};
@@ -0,0 +1,640 @@
+use std::collections::{BTreeSet, HashMap};
+use ipnetwork::IpNetwork;
+use serde_json::{json, Value};
+use crate::firewall::conntrack::ConnState;
+use crate::firewall::matchers::{AddressSet, PortRange, PortSet};
+use crate::firewall::nftables::{log_prefix, NftablesBackend, DEFAULT_TABLE};
+use crate::firewall::ratelimit::{RateLimit, RateScope, RateUnit};
+use crate::firewall::rules::{FirewallRule, RuleAction, RuleDirection, RuleProtocol};
+use crate::firewall::translate::{
+    family_addresses, import_priority, name_from_comment, rule_comment, rule_location,
+    RulesetFormat, TranslateError, Translation, TranslationIssue, POLICY_PRIORITY,
+};
+
+/// Schema version of the JSON written by `nft -j`
+const JSON_SCHEMA_VERSION: u32 = 1;
+
+/// Parse `nft -j list ruleset` output. Only rules in `filter` base chains
+/// hooked to input or output map onto rules; a drop policy becomes a
+/// catch-all block rule below everything else.
+///
+/// Rules of `ip` and `ip6` tables without addresses apply to both IP
+/// families once imported.
+pub fn import(content: &str) -> Result<Translation<Vec<FirewallRule>>, TranslateError> {
+    let malformed = |message: String| TranslateError::Malformed(RulesetFormat::NftJson, message);
+    let document: Value = serde_json::from_str(content).map_err(|e| malformed(e.to_string()))?;
+    let objects = document
+        .get("nftables")
+        .and_then(Value::as_array)
+        .ok_or_else(|| malformed("expected an object with an \"nftables\" array, as written by nft -j list ruleset".to_string()))?;
+    
+    let mut translation: Translation<Vec<FirewallRule>> = Translation::default();
+    // Chains are listed before their rules; `None` marks chains that aren't imported
+    let mut chains: HashMap<String, Option<RuleDirection>> = HashMap::new();
+    let mut drop_policies: Vec<(RuleDirection, String)> = Vec::new();
+    let mut chain_positions: HashMap<String, usize> = HashMap::new();
+    
+    for object in objects {
+        if let Some(chain) = object.get("chain") {
+            let location = chain_location(chain, &chain["name"]);
+            let direction = match (chain["type"].as_str(), chain["hook"].as_str()) {
+                (Some("filter"), Some("input")) => Some(RuleDirection::Inbound),
+                (Some("filter"), Some("output")) => Some(RuleDirection::Outbound),
+                _ => None,
+            };
+            if chain["policy"].as_str() == Some("drop") {
+                match direction {
+                    Some(ref direction) => drop_policies.push((direction.clone(), location.clone())),
+                    None => translation.issues.push(TranslationIssue::skipped(location.clone(), "drop policy of this chain is not supported")),
+                }
+            }
+            chains.insert(location, direction);
+        } else if let Some(rule) = object.get("rule") {
+            let chain = chain_location(rule, &rule["chain"]);
+            let position = chain_positions.entry(chain.clone()).or_insert(0);
+            *position += 1;
+            // Listings have handles, hand-written input may not
+            let id = match rule["handle"].as_u64() {
+                Some(handle) => format!("handle {}", handle),
+                None => format!("#{}", position),
+            };
+            let location = format!("{} {}", chain, id);
+            let direction = match chains.get(&chain) {
+                Some(Some(direction)) => direction.clone(),
+                Some(None) => {
+                    translation.issues.push(TranslationIssue::skipped(location, "chain is not an input or output filter base chain"));
+                    continue;
+                }
+                None => return Err(malformed(format!("{}: rule of an undeclared chain", location))),
+            };
+            
+            match parse_rule(&rule["expr"], direction) {
+                Ok((mut rule_out, approximations)) => {
+                    let rules = &mut translation.output;
+                    rule_out.id = rules.len() as u32 + 1;
+                    rule_out.priority = import_priority(rules.len());
+                    rule_out.name = match rule["comment"].as_str() {
+                        Some(comment) => name_from_comment(comment),
+                        None => format!("{} {}", rule["chain"].as_str().unwrap_or_default(), id),
+                    };
+                    rule_out.description = format!("Imported from nft {}", location);
+                    rules.push(rule_out);
+                    
+                    for message in approximations {
+                        translation.issues.push(TranslationIssue::approximated(location.clone(), message));
+                    }
+                }
+                Err(message) => translation.issues.push(TranslationIssue::skipped(location, message)),
+            }
+        }
+    }
+    
+    for (direction, chain) in drop_policies {
+        let rules = &mut translation.output;
+        let rule = FirewallRule::new(rules.len() as u32 + 1, format!("{} policy drop", chain), RuleAction::Block, direction, RuleProtocol::Any)
+            .with_priority(POLICY_PRIORITY)
+            .with_description(format!("Imported from nft: default policy of chain {}", chain));
+        rules.push(rule);
+    }
+    Ok(translation)
+}
+
+/// Write the rules as an `inet` table for `nft -j -f`, in the same shape as
+/// `nft -j list ruleset` (objects without a command are added)
+pub fn export(rules: &[FirewallRule]) -> Translation<String> {
+    let mut issues = Vec::new();
+    let mut objects = vec![
+        json!({"metainfo": {"json_schema_version": JSON_SCHEMA_VERSION}}),
+        json!({"table": {"family": "inet", "name": DEFAULT_TABLE}}),
+    ];
+    for chain in ["input", "output"] {
+        objects.push(json!({"chain": {
+            "family": "inet", "table": DEFAULT_TABLE, "name": chain,
+            "type": "filter", "hook": chain, "prio": 0, "policy": "accept",
+        }}));
+    }
+    
+    // Listed chain by chain, like nft does
+    let mut input = Vec::new();
+    let mut output = Vec::new();
+    for rule in rules.iter().filter(|rule| rule.enabled) {
+        let variants = match export_rule(rule) {
+            Ok(variants) => variants,
+            Err(message) => {
+                issues.push(TranslationIssue::skipped(rule_location(rule), message));
+                continue;
+            }
+        };
+        
+        let rule_object = |chain: &str, expr: &Value| json!({"rule": {
+            "family": "inet", "table": DEFAULT_TABLE, "chain": chain,
+            "comment": rule_comment(rule), "expr": expr,
+        }});
+        if matches!(rule.direction, RuleDirection::Inbound | RuleDirection::Bidirectional) {
+            input.extend(variants.iter().map(|expr| rule_object("input", expr)));
+        }
+        if matches!(rule.direction, RuleDirection::Outbound | RuleDirection::Bidirectional) {
+            output.extend(variants.iter().map(|expr| rule_object("output", expr)));
+        }
+    }
+    objects.extend(input);
+    objects.extend(output);
+    
+    let output = serde_json::to_string_pretty(&json!({"nftables": objects})).unwrap_or_default();
+    Translation { output, issues }
+}
+
+/// "inet filter input"
+fn chain_location(object: &Value, chain: &Value) -> String {
+    format!(
+        "{} {} {}",
+        object["family"].as_str().unwrap_or_default(),
+        object["table"].as_str().unwrap_or_default(),
+        chain.as_str().unwrap_or_default()
+    )
+}
+
+/// Matches collected from a rule's expressions
+#[derive(Default)]
+struct RuleMatches {
+    protocol: Option<RuleProtocol>,
+    source_ips: Option<AddressSet>,
+    destination_ips: Option<AddressSet>,
+    source_ports: Option<PortSet>,
+    destination_ports: Option<PortSet>,
+    states: Option<BTreeSet<ConnState>>,
+    /// `meta nfproto`; true for ipv6
+    family: Option<bool>,
+}
+
+/// Build a rule from its `expr` list. Errors name the construct that can't
+/// be represented; the second value lists the ones that were approximated.
+fn parse_rule(expressions: &Value, direction: RuleDirection) -> Result<(FirewallRule, Vec<String>), String> {
+    let expressions = expressions.as_array().ok_or("rule has no expressions")?;
+    let mut approximations = Vec::new();
+    let mut matches = RuleMatches::default();
+    let mut packets = 0;
+    let mut action = None;
+    
+    for expression in expressions {
+        let (kind, body) = expression
+            .as_object()
+            .and_then(|object| object.iter().next())
+            .ok_or("unrecognised expression")?;
+        
+        match kind.as_str() {
+            "match" => parse_match(body, &mut matches)?,
+            "counter" => packets = body["packets"].as_u64().unwrap_or(0),
+            "log" => action = Some(RuleAction::Log),
+            "accept" => {
+                if action == Some(RuleAction::Log) {
+                    approximations.push("logging of accepted packets is not imported".to_string());
+                }
+                action = Some(RuleAction::Allow);
+            }
+            "drop" | "reject" => {
+                if kind == "reject" {
+                    approximations.push("reject imported as a silent drop".to_string());
+                }
+                action = Some(match action {
+                    Some(RuleAction::Log) => RuleAction::LogAndBlock,
+                    _ => RuleAction::Block,
+                });
+            }
+            other => return Err(format!("'{}' statements are not supported", other)),
+        }
+        if matches!(action, Some(RuleAction::Allow | RuleAction::Block | RuleAction::LogAndBlock)) {
+            break; // nothing after a verdict is evaluated
+        }
+    }
+    
+    let action = action.ok_or("rule has no verdict (it only counts packets)")?;
+    let has_ports = matches.source_ports.is_some() || matches.destination_ports.is_some();
+    let protocol = match matches.protocol {
+        Some(RuleProtocol::TCP) => RuleProtocol::TCP,
+        Some(RuleProtocol::UDP) => RuleProtocol::UDP,
+        Some(RuleProtocol::Any) => RuleProtocol::Any,
+        _ if has_ports => return Err("port match without a tcp or udp protocol match".to_string()),
+        Some(protocol) => protocol,
+        None => RuleProtocol::Any,
+    };
+    if let Some(ipv6) = matches.family {
+        // The family is implied by addresses of only that family
+        let implied = [&matches.source_ips, &matches.destination_ips].into_iter().flatten().any(|ips| {
+            !ips.is_negated() && ips.iter().all(|network| network.is_ipv6() == ipv6)
+        });
+        if !implied {
+            let family = if ipv6 { "ipv6" } else { "ipv4" };
+            return Err(format!("rule is limited to {} without matching {} addresses", family, family));
+        }
+    }
+    
+    let mut rule = FirewallRule::new(0, String::new(), action, direction, protocol);
+    rule.source_ips = matches.source_ips;
+    rule.destination_ips = matches.destination_ips;
+    rule.source_ports = matches.source_ports;
+    rule.destination_ports = matches.destination_ports;
+    rule.states = matches.states;
+    rule.match_count = packets;
+    Ok((rule, approximations))
+}
+
+fn parse_match(body: &Value, matches: &mut RuleMatches) -> Result<(), String> {
+    let negated = match body["op"].as_str() {
+        Some("==") | Some("in") | None => false,
+        Some("!=") => true,
+        Some(op) => return Err(format!("'{}' comparisons are not supported", op)),
+    };
+    let (left, right) = (&body["left"], &body["right"]);
+    
+    if let Some(payload) = left.get("payload") {
+        let protocol = payload["protocol"].as_str().unwrap_or_default();
+        let field = payload["field"].as_str().unwrap_or_default();
+        match (protocol, field) {
+            ("ip" | "ip6", "saddr") => matches.source_ips = Some(parse_addresses(right, negated)?),
+            ("ip" | "ip6", "daddr") => matches.destination_ips = Some(parse_addresses(right, negated)?),
+            ("ip", "protocol") | ("ip6", "nexthdr") => matches.protocol = Some(parse_protocol(right, negated)?),
+            ("tcp" | "udp" | "th", "sport" | "dport") => {
+                match protocol {
+                    "tcp" => matches.protocol = Some(RuleProtocol::TCP),
+                    "udp" => matches.protocol = Some(RuleProtocol::UDP),
+                    _ => {}
+                }
+                let ports = Some(parse_ports(right, negated)?);
+                if field == "sport" {
+                    matches.source_ports = ports;
+                } else {
+                    matches.destination_ports = ports;
+                }
+            }
+            _ => return Err(format!("{} {} matches are not supported", protocol, field)),
+        }
+        return Ok(());
+    }
+    
+    match (left.pointer("/meta/key"), left.pointer("/ct/key")) {
+        (Some(key), _) if key == "l4proto" => matches.protocol = Some(parse_protocol(right, negated)?),
+        (Some(key), _) if key == "nfproto" && !negated => {
+            matches.family = Some(match right.as_str() {
+                Some("ipv4") => false,
+                Some("ipv6") => true,
+                _ => return Err(format!("meta nfproto {} is not supported", right)),
+            });
+        }
+        (Some(key), _) => return Err(format!("meta {} matches are not supported", key.as_str().unwrap_or_default())),
+        (_, Some(key)) if key == "state" => matches.states = Some(parse_states(right, negated)?),
+        (_, Some(key)) => return Err(format!("ct {} matches are not supported", key.as_str().unwrap_or_default())),
+        _ => return Err(format!("matches on {} are not supported", left)),
+    }
+    Ok(())
+}
+
+/// What a match compares against: a value, a list (flags) or an anonymous set
+fn elements(right: &Value) -> Result<Vec<&Value>, String> {
+    let values: Vec<&Value> = match right {
+        Value::Array(values) => values.iter().collect(),
+        Value::Object(object) if object.contains_key("set") => match object["set"] {
+            Value::Array(ref values) => values.iter().collect(),
+            ref value => vec![value],
+        },
+        Value::String(name) if name.starts_with('@') => return Err(format!("named set {} is not supported", name)),
+        value => vec![value],
+    };
+    // Set elements with options (timeouts, counters) wrap the value
+    Ok(values.into_iter().map(|value| value.pointer("/elem/val").unwrap_or(value)).collect())
+}
+
+fn parse_addresses(right: &Value, negated: bool) -> Result<AddressSet, String> {
+    let mut set = AddressSet::new();
+    for element in elements(right)? {
+        let network = match (element.as_str(), element.get("prefix")) {
+            (Some(address), _) => address.parse::<IpNetwork>().ok(),
+            (_, Some(prefix)) => prefix["addr"]
+                .as_str()
+                .and_then(|address| address.parse().ok())
+                .zip(prefix["len"].as_u64())
+                .and_then(|(address, len)| IpNetwork::new(address, len as u8).ok()),
+            _ => None,
+        };
+        set.insert(network.ok_or_else(|| format!("address {} is not supported", element))?);
+    }
+    set.set_negated(negated);
+    Ok(set)
+}
+
+fn parse_ports(right: &Value, negated: bool) -> Result<PortSet, String> {
+    let port = |value: &Value| -> Option<u16> {
+        match value {
+            Value::Number(number) => number.as_u64().and_then(|port| u16::try_from(port).ok()),
+            Value::String(port) => port.parse().ok(),
+            _ => None,
+        }
+    };
+    
+    let mut set = PortSet::new();
+    for element in elements(right)? {
+        let range = match element.get("range") {
+            Some(range) => port(&range[0]).zip(port(&range[1])).map(|(start, end)| PortRange::new(start, end)),
+            None => port(element).map(PortRange::single),
+        };
+        set.insert(range.ok_or_else(|| format!("port {} is not supported", element))?);
+    }
+    set.set_negated(negated);
+    Ok(set)
+}
+
+fn parse_protocol(right: &Value, negated: bool) -> Result<RuleProtocol, String> {
+    if negated {
+        return Err("negated protocol matches are not supported".to_string());
+    }
+    
+    let mut names = BTreeSet::new();
+    for element in elements(right)? {
+        let name = match (element.as_str(), element.as_u64()) {
+            (Some(name), _) => name.to_string(),
+            (_, Some(6)) => "tcp".to_string(),
+            (_, Some(17)) => "udp".to_string(),
+            (_, Some(1)) => "icmp".to_string(),
+            (_, Some(58)) => "ipv6-icmp".to_string(),
+            _ => return Err(format!("protocol {} is not supported", element)),
+        };
+        names.insert(name);
+    }
+    
+    let names: Vec<&str> = names.iter().map(String::as_str).collect();
+    match names.as_slice() {
+        ["tcp"] => Ok(RuleProtocol::TCP),
+        ["udp"] => Ok(RuleProtocol::UDP),
+        ["icmp"] | ["ipv6-icmp"] | ["icmp", "ipv6-icmp"] => Ok(RuleProtocol::ICMP),
+        // Only written together with ports, which already limit it to TCP and UDP
+        ["tcp", "udp"] => Ok(RuleProtocol::Any),
+        _ => Err(format!("protocol {} is not supported", names.join(","))),
+    }
+}
+
+fn parse_states(right: &Value, negated: bool) -> Result<BTreeSet<ConnState>, String> {
+    let listed: BTreeSet<ConnState> = elements(right)?
+        .into_iter()
+        .map(|element| {
+            element
+                .as_str()
+                .and_then(|state| state.parse::<ConnState>().ok())
+                .ok_or_else(|| format!("connection state {} is not supported", element))
+        })
+        .collect::<Result<_, _>>()?;
+    if !negated {
+        return Ok(listed);
+    }
+    
+    let states: BTreeSet<ConnState> = ConnState::ALL.into_iter().filter(|state| !listed.contains(state)).collect();
+    if states.is_empty() {
+        return Err("negated state match excludes every connection state".to_string());
+    }
+    Ok(states)
+}
+
+/// The `expr` lists for one rule, one per address family when it matches on
+/// addresses of both, like `NftablesBackend::compile_rule`
+fn export_rule(rule: &FirewallRule) -> Result<Vec<Value>, String> {
+    // Same validity checks as enforcement, e.g. ICMP with ports
+    NftablesBackend::compile_rule(rule)?;
+    
+    // Per-source meters are keyed by a family's address, so they always need one
+    let per_source_limit = matches!(rule.action, RuleAction::RateLimit(limit) if limit.scope == RateScope::PerSource);
+    let families: Vec<(&str, Vec<Value>)> = if rule.source_ips.is_none() && rule.destination_ips.is_none() {
+        if per_source_limit {
+            [("ip", "ipv4"), ("ip6", "ipv6")]
+                .into_iter()
+                .map(|(protocol, nfproto)| {
+                    (protocol, vec![match_expression(json!({"meta": {"key": "nfproto"}}), false, json!(nfproto))])
+                })
+                .collect()
+        } else {
+            vec![("", Vec::new())]
+        }
+    } else {
+        [false, true]
+            .into_iter()
+            .filter_map(|ipv6| {
+                let addresses = family_addresses(rule, ipv6)?;
+                let (protocol, nfproto) = if ipv6 { ("ip6", "ipv6") } else { ("ip", "ipv4") };
+                let mut matches = Vec::new();
+                for (field, address_match) in [("saddr", &addresses.source), ("daddr", &addresses.destination)] {
+                    if let Some(address_match) = address_match {
+                        let values = address_match.networks.iter().map(address_value).collect();
+                        matches.push(match_expression(payload(protocol, field), address_match.negated, set_value(values)));
+                    }
+                }
+                if matches.is_empty() {
+                    matches.push(match_expression(json!({"meta": {"key": "nfproto"}}), false, json!(nfproto)));
+                }
+                Some((protocol, matches))
+            })
+            .collect()
+    };
+    
+    let has_ports = rule.source_ports.is_some() || rule.destination_ports.is_some();
+    let (port_protocol, l4_match) = match rule.protocol {
+        RuleProtocol::TCP => ("tcp", Some(json!("tcp"))),
+        RuleProtocol::UDP => ("udp", Some(json!("udp"))),
+        RuleProtocol::ICMP => ("", Some(json!({"set": ["icmp", "ipv6-icmp"]}))),
+        RuleProtocol::Any if has_ports => ("th", Some(json!({"set": ["tcp", "udp"]}))),
+        RuleProtocol::Any => ("", None),
+    };
+    
+    let mut common = Vec::new();
+    if let Some(l4_match) = l4_match {
+        common.push(match_expression(json!({"meta": {"key": "l4proto"}}), false, l4_match));
+    }
+    for (field, ports) in [("sport", &rule.source_ports), ("dport", &rule.destination_ports)] {
+        if let Some(ports) = ports.as_ref().filter(|ports| !ports.is_empty()) {
+            let values = ports
+                .iter()
+                .map(|range| if range.is_single() { json!(range.start) } else { json!({"range": [range.start, range.end]}) })
+                .collect();
+            common.push(match_expression(payload(port_protocol, field), ports.is_negated(), set_value(values)));
+        }
+    }
+    
+    // Rate limits drop what is over the limit first and accept the rest with
+    // a second rule
+    let counter = json!({"counter": {"packets": 0, "bytes": 0}});
+    let log = json!({"log": {"prefix": log_prefix(rule)}});
+    let verdicts = |family: &str| -> Vec<Vec<Value>> {
+        match rule.action {
+            RuleAction::Allow => vec![vec![counter.clone(), json!({"accept": null})]],
+            RuleAction::Block => vec![vec![counter.clone(), json!({"drop": null})]],
+            RuleAction::Log => vec![vec![counter.clone(), log.clone()]],
+            RuleAction::LogAndBlock => vec![vec![counter.clone(), log.clone(), json!({"drop": null})]],
+            RuleAction::RateLimit(limit) => {
+                let over_limit = match limit.scope {
+                    RateScope::PerSource => json!({"meter": {
+                        "name": format!("nm_ratelimit_{}_{}", rule.id, family),
+                        "key": payload(family, "saddr"),
+                        "stmt": limit_statement(&limit),
+                    }}),
+                    RateScope::PerRule => limit_statement(&limit),
+                };
+                vec![
+                    vec![over_limit, counter.clone(), json!({"drop": null})],
+                    vec![counter.clone(), json!({"accept": null})],
+                ]
+            }
+        }
+    };
+    
+    let state_match = rule.states.as_ref().map(|states| {
+        let states: Vec<&str> = states.iter().map(|state| state.nft_keyword()).collect();
+        json!({"match": {"op": "in", "left": {"ct": {"key": "state"}}, "right": states}})
+    });
+    
+    Ok(families
+        .into_iter()
+        .flat_map(|(family, address_matches)| {
+            verdicts(family)
+                .into_iter()
+                .map(|verdict| {
+                    let mut expressions = Vec::new();
+                    expressions.extend(state_match.clone());
+                    expressions.extend(address_matches.iter().cloned());
+                    expressions.extend(common.iter().cloned());
+                    expressions.extend(verdict);
+                    Value::Array(expressions)
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect())
+}
+
+/// `limit rate over`, as `inv`
+fn limit_statement(limit: &RateLimit) -> Value {
+    let unit = match limit.unit {
+        RateUnit::Packets => "packets",
+        RateUnit::Bytes => "bytes",
+    };
+    json!({"limit": {
+        "rate": limit.rate, "rate_unit": unit, "per": "second",
+        "burst": limit.burst, "burst_unit": unit, "inv": true,
+    }})
+}
+
+fn payload(protocol: &str, field: &str) -> Value {
+    json!({"payload": {"protocol": protocol, "field": field}})
+}
+
+fn match_expression(left: Value, negated: bool, right: Value) -> Value {
+    json!({"match": {"op": if negated { "!=" } else { "==" }, "left": left, "right": right}})
+}
+
+/// Single value, or an anonymous set
+fn set_value(mut values: Vec<Value>) -> Value {
+    if values.len() == 1 {
+        values.remove(0)
+    } else {
+        json!({"set": values})
+    }
+}
+
+/// Single hosts as a bare address, networks as a prefix
+fn address_value(network: &IpNetwork) -> Value {
+    let host_prefix = if network.is_ipv4() { 32 } else { 128 };
+    if network.prefix() == host_prefix {
+        json!(network.ip().to_string())
+    } else {
+        json!({"prefix": {"addr": network.network().to_string(), "len": network.prefix()}})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firewall::rules::RuleTemplates;
+    
+    const LISTED: &str = r#"{"nftables": [
+        {"metainfo": {"version": "1.0.4", "release_name": "Lester Gooch #3", "json_schema_version": 1}},
+        {"table": {"family": "inet", "name": "filter", "handle": 1}},
+        {"chain": {"family": "inet", "table": "filter", "name": "input", "handle": 1, "type": "filter", "hook": "input", "prio": 0, "policy": "drop"}},
+        {"chain": {"family": "inet", "table": "filter", "name": "forward", "handle": 2, "type": "filter", "hook": "forward", "prio": 0, "policy": "accept"}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 4, "expr": [
+            {"match": {"op": "in", "left": {"ct": {"key": "state"}}, "right": ["established", "related"]}},
+            {"accept": null}]}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 5, "comment": "ssh", "expr": [
+            {"match": {"op": "==", "left": {"payload": {"protocol": "ip", "field": "saddr"}}, "right": {"prefix": {"addr": "10.0.0.0", "len": 8}}}},
+            {"match": {"op": "==", "left": {"payload": {"protocol": "tcp", "field": "dport"}}, "right": 22}},
+            {"counter": {"packets": 7, "bytes": 420}},
+            {"accept": null}]}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 6, "expr": [
+            {"match": {"op": "==", "left": {"meta": {"key": "iifname"}}, "right": "lo"}},
+            {"accept": null}]}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "input", "handle": 7, "expr": [
+            {"match": {"op": "==", "left": {"meta": {"key": "l4proto"}}, "right": {"set": ["tcp", "udp"]}}},
+            {"match": {"op": "!=", "left": {"payload": {"protocol": "th", "field": "dport"}}, "right": {"set": [53, {"range": [1024, 65535]}]}}},
+            {"reject": null}]}},
+        {"rule": {"family": "inet", "table": "filter", "chain": "forward", "handle": 8, "expr": [{"drop": null}]}}
+    ]}"#;
+    
+    #[test]
+    fn test_import_nft_json() {
+        let translation = import(LISTED).unwrap();
+        let rules = &translation.output;
+        assert_eq!(rules.len(), 4);
+        
+        assert_eq!(rules[0].name, "input handle 4");
+        assert_eq!(rules[0].states, Some([ConnState::Established, ConnState::Related].into_iter().collect()));
+        
+        assert_eq!(rules[1].name, "ssh");
+        assert_eq!(rules[1].protocol, RuleProtocol::TCP);
+        assert_eq!(rules[1].match_count, 7);
+        assert_eq!(rules[1].source_ips.as_ref().unwrap().to_string(), "10.0.0.0/8");
+        
+        assert_eq!(rules[2].protocol, RuleProtocol::Any);
+        assert_eq!(rules[2].destination_ports.as_ref().unwrap().to_string(), "!53,1024-65535");
+        assert_eq!(rules[3].priority, POLICY_PRIORITY);
+        
+        let issues: Vec<String> = translation.issues.iter().map(|issue| issue.to_string()).collect();
+        assert_eq!(issues, vec![
+            "inet filter input handle 6: meta iifname matches are not supported (skipped)",
+            "inet filter input handle 7: reject imported as a silent drop (approximated)",
+            "inet filter forward handle 8: chain is not an input or output filter base chain (skipped)",
+        ]);
+        
+        assert!(import("[]").is_err());
+        assert!(import("{\"nftables\": [").is_err());
+    }
+    
+    #[test]
+    fn test_export_round_trip() {
+        let mut ssh = RuleTemplates::allow_ssh()
+            .with_source_network("192.168.0.0/16".parse().unwrap())
+            .with_source_network("fd00::/8".parse().unwrap());
+        ssh.id = 2;
+        let mut rules = vec![RuleTemplates::allow_established(), ssh, RuleTemplates::block_suspicious_ports()];
+        rules.push(
+            FirewallRule::new(30, "ICMP ports".to_string(), RuleAction::Block, RuleDirection::Inbound, RuleProtocol::ICMP)
+                .with_destination_port(7),
+        );
+        
+        let translation = export(&rules);
+        assert_eq!(translation.issues.len(), 1);
+        assert_eq!(translation.issues[0].location, "rule 30 (ICMP ports)");
+        
+        // Input: established, ssh for each family, suspicious ports; output: established, suspicious ports
+        let imported = import(&translation.output).unwrap();
+        assert!(imported.issues.is_empty(), "{:?}", imported.issues);
+        let names: Vec<&str> = imported.output.iter().map(|rule| rule.name.as_str()).collect();
+        assert_eq!(names, vec![
+            "Allow Established/Related", "Allow SSH", "Allow SSH", "Block Suspicious Ports",
+            "Allow Established/Related", "Block Suspicious Ports",
+        ]);
+        
+        assert_eq!(imported.output[1].source_ips.as_ref().unwrap().to_string(), "192.168.0.0/16");
+        assert_eq!(imported.output[2].source_ips.as_ref().unwrap().to_string(), "fd00::/8");
+        assert_eq!(imported.output[1].destination_ports, rules[1].destination_ports);
+        
+        let suspicious = &imported.output[3];
+        assert_eq!(suspicious.action, RuleAction::LogAndBlock);
+        assert_eq!(suspicious.protocol, RuleProtocol::Any);
+        assert_eq!(suspicious.destination_ports, rules[2].destination_ports);
+        assert_eq!(suspicious.direction, RuleDirection::Inbound);
+    }
+}
@@ -0,0 +1,540 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::os::fd::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use thiserror::Error;
+use crate::firewall::matchers::format_network;
+use crate::firewall::ratelimit::{RateLimit, RateScope, RateUnit};
+use crate::firewall::rules::{FirewallRule, RuleAction, RuleDirection, RuleProtocol};
+
+pub const DEFAULT_TABLE: &str = "network_monitor";
+pub const DEFAULT_STATE_FILE: &str = "/run/network-monitor/nftables-rollback.nft";
+
+#[derive(Error, Debug)]
+pub enum EnforcementError {
+    #[error("Cannot run nft (is nftables installed?): {0}")]
+    NftUnavailable(std::io::Error),
+    
+    #[error("nft rejected the ruleset: {0}")]
+    NftFailed(String),
+    
+    #[error("Cannot write rollback state '{0}': {1}")]
+    StateFile(String, std::io::Error),
+    
+    #[error("nftables table '{0}' is already enforced by another running instance")]
+    InUse(String),
+}
+
+/// Enforces firewall rules in the kernel by compiling them into a dedicated
+/// nftables table. Each apply replaces the whole table in one `nft -f`
+/// transaction, so the kernel never sees a half-loaded ruleset.
+///
+/// Before the first apply the previous contents of the table (usually none)
+/// are saved to a rollback script on disk. Rollback runs on `Drop`, and if
+/// the process dies without unwinding, `recover` replays the script on the
+/// next start. The enforcing process holds a lock on the script, so a
+/// script is only replayed once its owner is gone.
+pub struct NftablesBackend {
+    table: String,
+    state_file: PathBuf,
+    applied: bool,
+    // Open rollback script, locked while the rules are applied
+    lock: Option<File>,
+}
+
+impl NftablesBackend {
+    pub fn new() -> Self {
+        Self::with_table(DEFAULT_TABLE, DEFAULT_STATE_FILE)
+    }
+    
+    pub fn with_table<P: Into<PathBuf>>(table: &str, state_file: P) -> Self {
+        Self {
+            table: table.to_string(),
+            state_file: state_file.into(),
+            applied: false,
+            lock: None,
+        }
+    }
+    
+    pub fn is_applied(&self) -> bool {
+        self.applied
+    }
+    
+    pub fn get_table(&self) -> &str {
+        &self.table
+    }
+    
+    /// The nft script `apply` would load; used for dry runs
+    pub fn generate_ruleset(&self, rules: &[FirewallRule]) -> String {
+        let mut input = Vec::new();
+        let mut output = Vec::new();
+        
+        for rule in rules.iter().filter(|rule| rule.enabled) {
+            let statements = match Self::compile_rule(rule) {
+                Ok(statements) => statements,
+                Err(reason) => {
+                    let comment = format!("# rule {} ({}) skipped: {}", rule.id, sanitize(&rule.name), reason);
+                    input.push(comment.clone());
+                    output.push(comment);
+                    continue;
+                }
+            };
+            
+            if matches!(rule.direction, RuleDirection::Inbound | RuleDirection::Bidirectional) {
+                input.extend(statements.iter().cloned());
+            }
+            if matches!(rule.direction, RuleDirection::Outbound | RuleDirection::Bidirectional) {
+                output.extend(statements);
+            }
+        }
+        
+        // "table; delete table" makes the delete succeed whether or not the
+        // table exists yet, all within the same transaction as the reload
+        let mut script = self.delete_script();
+        script.push_str(&format!("table inet {} {{\n", self.table));
+        for (chain, hook, statements) in [("input", "input", &input), ("output", "output", &output)] {
+            script.push_str(&format!("    chain {} {{\n", chain));
+            script.push_str(&format!("        type filter hook {} priority 0; policy accept;\n", hook));
+            for statement in statements {
+                script.push_str(&format!("        {}\n", statement));
+            }
+            script.push_str("    }\n");
+        }
+        script.push_str("}\n");
+        script
+    }
+    
+    /// Validate the generated ruleset with `nft -c` without touching the kernel
+    pub fn check(&self, rules: &[FirewallRule]) -> Result<String, EnforcementError> {
+        let script = self.generate_ruleset(rules);
+        Self::run_nft(&["-c", "-f", "-"], Some(&script))?;
+        Ok(script)
+    }
+    
+    /// Atomically replace the table with the compiled rules
+    pub fn apply(&mut self, rules: &[FirewallRule]) -> Result<(), EnforcementError> {
+        if !self.applied {
+            self.save_rollback_state()?;
+        }
+        
+        let script = self.generate_ruleset(rules);
+        if let Err(e) = Self::run_nft(&["-f", "-"], Some(&script)) {
+            if !self.applied {
+                // Nothing was loaded, so there is nothing to roll back
+                let _ = fs::remove_file(&self.state_file);
+                self.lock = None;
+            }
+            return Err(e);
+        }
+        
+        self.applied = true;
+        Ok(())
+    }
+    
+    /// Restore the table to what it was before the first apply
+    pub fn rollback(&mut self) -> Result<(), EnforcementError> {
+        if !self.applied {
+            return Ok(());
+        }
+        
+        let script = fs::read_to_string(&self.state_file).unwrap_or_else(|_| self.delete_script());
+        Self::run_nft(&["-f", "-"], Some(&script))?;
+        let _ = fs::remove_file(&self.state_file);
+        self.lock = None;
+        self.applied = false;
+        Ok(())
+    }
+    
+    /// Replay a rollback script left behind by a previous run that did not
+    /// exit cleanly. Returns true if one was found and applied; a script
+    /// whose owner is still running is left alone.
+    pub fn recover<P: AsRef<Path>>(state_file: P) -> Result<bool, EnforcementError> {
+        let state_file = state_file.as_ref();
+        let state_error = |e| EnforcementError::StateFile(state_file.display().to_string(), e);
+        let mut file = match File::open(state_file) {
+            Ok(file) => file,
+            Err(_) => return Ok(false),
+        };
+        if !try_lock(&file).map_err(state_error)? {
+            return Ok(false);
+        }
+        let mut script = String::new();
+        file.read_to_string(&mut script).map_err(state_error)?;
+        
+        Self::run_nft(&["-f", "-"], Some(&script))?;
+        let _ = fs::remove_file(state_file);
+        Ok(true)
+    }
+    
+    fn save_rollback_state(&mut self) -> Result<(), EnforcementError> {
+        let state_error = |e| EnforcementError::StateFile(self.state_file.display().to_string(), e);
+        if let Some(parent) = self.state_file.parent() {
+            fs::create_dir_all(parent).map_err(state_error)?;
+        }
+        let mut file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&self.state_file)
+            .map_err(state_error)?;
+        if !try_lock(&file).map_err(state_error)? {
+            return Err(EnforcementError::InUse(self.table.clone()));
+        }
+        
+        // An existing table of the same name is restored verbatim on rollback
+        let mut script = self.delete_script();
+        if let Ok(previous) = Self::run_nft(&["list", "table", "inet", &self.table], None) {
+            script.push_str(&previous);
+        }
+        file.set_len(0).map_err(state_error)?;
+        file.write_all(script.as_bytes()).map_err(state_error)?;
+        self.lock = Some(file);
+        Ok(())
+    }
+    
+    fn delete_script(&self) -> String {
+        format!("table inet {0}\ndelete table inet {0}\n", self.table)
+    }
+    
+    fn run_nft(args: &[&str], stdin: Option<&str>) -> Result<String, EnforcementError> {
+        let mut child = Command::new("nft")
+            .args(args)
+            .stdin(if stdin.is_some() { Stdio::piped() } else { Stdio::null() })
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .spawn()
+            .map_err(EnforcementError::NftUnavailable)?;
+        
+        if let (Some(script), Some(mut pipe)) = (stdin, child.stdin.take()) {
+            pipe.write_all(script.as_bytes()).map_err(EnforcementError::NftUnavailable)?;
+        }
+        
+        let output = child.wait_with_output().map_err(EnforcementError::NftUnavailable)?;
+        if !output.status.success() {
+            return Err(EnforcementError::NftFailed(String::from_utf8_lossy(&output.stderr).trim().to_string()));
+        }
+        Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+    }
+    
+    /// Translate one rule into nft statements, one per address family when
+    /// it matches on addresses of both families
+    pub(crate) fn compile_rule(rule: &FirewallRule) -> Result<Vec<String>, String> {
+        let has_ports = rule.source_ports.is_some() || rule.destination_ports.is_some();
+        let (l4_match, port_prefix) = match rule.protocol {
+            RuleProtocol::TCP => ("meta l4proto tcp", "tcp"),
+            RuleProtocol::UDP => ("meta l4proto udp", "udp"),
+            RuleProtocol::ICMP if has_ports => return Err("ICMP has no ports".to_string()),
+            RuleProtocol::ICMP => ("meta l4proto { icmp, ipv6-icmp }", ""),
+            RuleProtocol::Any if has_ports => ("meta l4proto { tcp, udp }", "th"),
+            RuleProtocol::Any => ("", ""),
+        };
+        
+        let mut port_matches = Vec::new();
+        for (field, ports) in [("sport", &rule.source_ports), ("dport", &rule.destination_ports)] {
+            if let Some(ports) = ports {
+                if ports.is_empty() {
+                    if ports.is_negated() {
+                        continue; // excludes nothing
+                    }
+                    return Err(format!("empty {} set can never match", field));
+                }
+                let ranges: Vec<String> = ports.iter().map(|range| range.to_string()).collect();
+                port_matches.push(format!("{} {} {}{}", port_prefix, field, negation(ports.is_negated()), nft_set(ranges)));
+            }
+        }
+        
+        let state_match = match rule.states {
+            Some(ref states) if states.is_empty() => return Err("empty connection state set can never match".to_string()),
+            Some(ref states) => Some(format!("ct state {}", nft_set(states.iter().map(|state| state.nft_keyword().to_string()).collect()))),
+            None => None,
+        };
+        
+        let per_source_limit = match rule.action {
+            RuleAction::RateLimit(limit) if limit.scope == RateScope::PerSource => {
+                if rule.direction == RuleDirection::Bidirectional {
+                    return Err("per-source rate limits need an inbound or outbound direction".to_string());
+                }
+                true
+            }
+            _ => false,
+        };
+        
+        // Address matches by family; per-source meters are keyed by a
+        // family's address, so they always need one
+        let families: Vec<(&str, Vec<String>)> = if rule.source_ips.is_none() && rule.destination_ips.is_none() {
+            if per_source_limit {
+                vec![("ip", vec!["meta nfproto ipv4".to_string()]), ("ip6", vec!["meta nfproto ipv6".to_string()])]
+            } else {
+                vec![("", Vec::new())]
+            }
+        } else {
+            [("ip", "ipv4", true), ("ip6", "ipv6", false)]
+                .into_iter()
+                .filter_map(|(family, nfproto, v4)| {
+                    let mut matches = Vec::new();
+                    for (field, ips) in [("saddr", &rule.source_ips), ("daddr", &rule.destination_ips)] {
+                        if let Some(ips) = ips {
+                            let networks: Vec<String> = ips.iter()
+                                .filter(|network| network.is_ipv4() == v4)
+                                .map(format_network)
+                                .collect();
+                            if networks.is_empty() {
+                                if ips.is_negated() {
+                                    continue; // nothing of this family is excluded
+                                }
+                                return None; // this family can never match the rule
+                            }
+                            matches.push(format!("{} {} {}{}", family, field, negation(ips.is_negated()), nft_set(networks)));
+                        }
+                    }
+                    if matches.is_empty() {
+                        matches.push(format!("meta nfproto {}", nfproto));
+                    }
+                    Some((family, matches))
+                })
+                .collect()
+        };
+        
+        if families.is_empty() {
+            return Err("source and destination addresses are of different IP families".to_string());
+        }
+        
+        // Each entry becomes a statement; rate limits drop what is over the
+        // limit first and accept the rest with a second one
+        let verdicts = |family: &str| -> Vec<String> {
+            match rule.action {
+                RuleAction::Allow => vec!["counter accept".to_string()],
+                RuleAction::Block => vec!["counter drop".to_string()],
+                RuleAction::Log => vec![format!("counter log prefix \"{}\"", log_prefix(rule))],
+                RuleAction::LogAndBlock => vec![format!("counter log prefix \"{}\" drop", log_prefix(rule))],
+                RuleAction::RateLimit(limit) => {
+                    let over_limit = match limit.scope {
+                        RateScope::PerSource => format!(
+                            "meter nm_ratelimit_{}_{} {{ {} saddr {} }}",
+                            rule.id,
+                            family,
+                            family,
+                            nft_limit(&limit)
+                        ),
+                        RateScope::PerRule => nft_limit(&limit),
+                    };
+                    vec![format!("{} counter drop", over_limit), "counter accept".to_string()]
+                }
+            }
+        };
+        let comment = format!("comment \"rule {}: {}\"", rule.id, sanitize(&rule.name));
+        
+        Ok(families
+            .into_iter()
+            .flat_map(|(family, address_matches)| {
+                let mut parts: Vec<String> = Vec::new();
+                parts.extend(state_match.clone());
+                parts.extend(address_matches);
+                if !l4_match.is_empty() {
+                    parts.push(l4_match.to_string());
+                }
+                parts.extend(port_matches.iter().cloned());
+                let matches = parts.join(" ");
+                verdicts(family)
+                    .into_iter()
+                    .map(|verdict| [matches.as_str(), verdict.as_str(), comment.as_str()].join(" ").trim_start().to_string())
+                    .collect::<Vec<_>>()
+            })
+            .collect())
+    }
+}
+
+impl Default for NftablesBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for NftablesBackend {
+    fn drop(&mut self) {
+        if let Err(e) = self.rollback() {
+            log::error!("Failed to roll back nftables table '{}': {}", self.table, e);
+        }
+    }
+}
+
+/// Take an exclusive lock on `file` without waiting; `false` if another
+/// process holds it. The kernel drops the lock when its holder exits.
+fn try_lock(file: &File) -> io::Result<bool> {
+    // SAFETY: flock only operates on the descriptor, which `file` keeps open
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == 0 {
+        return Ok(true);
+    }
+    let error = io::Error::last_os_error();
+    if error.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(error)
+    }
+}
+
+/// Single value, or an anonymous set `{ a, b }`; the matcher sets are kept sorted
+fn nft_set(values: Vec<String>) -> String {
+    if values.len() == 1 {
+        values[0].clone()
+    } else {
+        format!("{{ {} }}", values.join(", "))
+    }
+}
+
+/// `limit rate over` matches the traffic beyond the limit
+fn nft_limit(limit: &RateLimit) -> String {
+    let unit = match limit.unit {
+        RateUnit::Packets => ("", "packets"),
+        RateUnit::Bytes => (" bytes", "bytes"),
+    };
+    format!("limit rate over {}{}/second burst {} {}", limit.rate, unit.0, limit.burst, unit.1)
+}
+
+fn negation(negated: bool) -> &'static str {
+    if negated { "!= " } else { "" }
+}
+
+/// Rule names end up inside quoted nft strings
+fn sanitize(name: &str) -> String {
+    name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() || " -_.:/".contains(c) { c } else { '_' })
+        .collect()
+}
+
+pub(crate) fn log_prefix(rule: &FirewallRule) -> String {
+    let mut prefix = format!("network-monitor {}: ", sanitize(&rule.name));
+    prefix.truncate(64);
+    prefix
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firewall::rules::RuleTemplates;
+    use std::net::{IpAddr, Ipv4Addr};
+    
+    #[test]
+    fn test_generate_ruleset() {
+        let backend = NftablesBackend::with_table("nm_test", "/nonexistent/state.nft");
+        let mut ssh = RuleTemplates::allow_ssh();
+        ssh.id = 2;
+        let mut suspicious = RuleTemplates::block_suspicious_ports();
+        suspicious.id = 4;
+        let mut disabled = RuleTemplates::block_all_incoming();
+        disabled.enabled = false;
+        
+        let script = backend.generate_ruleset(&[ssh, suspicious, disabled]);
+        
+        assert!(script.starts_with("table inet nm_test\ndelete table inet nm_test\ntable inet nm_test {\n"));
+        assert!(script.contains("type filter hook input priority 0; policy accept;"));
+        assert!(script.contains(
+            "meta l4proto tcp tcp dport 22 counter accept comment \"rule 2: Allow SSH\""
+        ));
+        // Bidirectional rules land in both chains, Inbound only in input
+        assert_eq!(script.matches("rule 4: Block Suspicious Ports").count(), 2);
+        assert_eq!(script.matches("rule 2: Allow SSH").count(), 1);
+        assert!(script.contains("meta l4proto { tcp, udp } th dport { 21, 23, 135, 139, 445, 1433, 1521, 3306, 5432 }"));
+        assert!(script.contains("log prefix \"network-monitor Block Suspicious Ports: \" drop"));
+        assert!(!script.contains("Block All Incoming"));
+    }
+    
+    #[test]
+    fn test_connection_states() {
+        let statements = NftablesBackend::compile_rule(&RuleTemplates::allow_established()).unwrap();
+        assert_eq!(statements, vec![
+            "ct state { established, related } counter accept comment \"rule 7: Allow Established/Related\"".to_string(),
+        ]);
+        
+        let statements = NftablesBackend::compile_rule(&RuleTemplates::block_all_incoming()).unwrap();
+        assert!(statements[0].starts_with("ct state { new, invalid } counter drop"));
+    }
+    
+    #[test]
+    fn test_mixed_family_addresses() {
+        let rule = FirewallRule::new(7, "Block \"bad\" hosts".to_string(), RuleAction::Block, RuleDirection::Inbound, RuleProtocol::Any)
+            .with_source_ips(vec![
+                IpAddr::V4(Ipv4Addr::new(203, 0, 113, 9)),
+                "2001:db8::9".parse().unwrap(),
+            ]);
+        
+        let statements = NftablesBackend::compile_rule(&rule).unwrap();
+        assert_eq!(statements, vec![
+            "ip saddr 203.0.113.9 counter drop comment \"rule 7: Block _bad_ hosts\"".to_string(),
+            "ip6 saddr 2001:db8::9 counter drop comment \"rule 7: Block _bad_ hosts\"".to_string(),
+        ]);
+        
+        let mut v4_only = rule.with_destination_ip("2001:db8::1".parse().unwrap());
+        v4_only.source_ips = Some([IpAddr::V4(Ipv4Addr::new(203, 0, 113, 9))].into_iter().collect());
+        assert!(NftablesBackend::compile_rule(&v4_only).is_err());
+    }
+    
+    #[test]
+    fn test_networks_ranges_and_negation() {
+        let rule = FirewallRule::new(8, "Ephemeral from outside".to_string(), RuleAction::Block, RuleDirection::Inbound, RuleProtocol::TCP)
+            .with_source_network("10.0.0.0/8".parse().unwrap())
+            .with_source_network("192.168.0.0/16".parse().unwrap())
+            .negate_source_ips()
+            .with_destination_port_range(32768, 60999)
+            .with_destination_port(22);
+        
+        let statements = NftablesBackend::compile_rule(&rule).unwrap();
+        assert_eq!(statements, vec![
+            "ip saddr != { 10.0.0.0/8, 192.168.0.0/16 } meta l4proto tcp tcp dport { 22, 32768-60999 } counter drop comment \"rule 8: Ephemeral from outside\"".to_string(),
+            // No IPv6 network is excluded, so every IPv6 source matches
+            "meta nfproto ipv6 meta l4proto tcp tcp dport { 22, 32768-60999 } counter drop comment \"rule 8: Ephemeral from outside\"".to_string(),
+        ]);
+    }
+    
+    #[test]
+    fn test_rate_limits() {
+        let statements = NftablesBackend::compile_rule(&RuleTemplates::rate_limit_ssh()).unwrap();
+        assert_eq!(statements, vec![
+            "ct state new meta nfproto ipv4 meta l4proto tcp tcp dport 22 meter nm_ratelimit_9_ip { ip saddr limit rate over 1/second burst 5 packets } counter drop comment \"rule 9: Rate Limit SSH\"".to_string(),
+            "ct state new meta nfproto ipv4 meta l4proto tcp tcp dport 22 counter accept comment \"rule 9: Rate Limit SSH\"".to_string(),
+            "ct state new meta nfproto ipv6 meta l4proto tcp tcp dport 22 meter nm_ratelimit_9_ip6 { ip6 saddr limit rate over 1/second burst 5 packets } counter drop comment \"rule 9: Rate Limit SSH\"".to_string(),
+            "ct state new meta nfproto ipv6 meta l4proto tcp tcp dport 22 counter accept comment \"rule 9: Rate Limit SSH\"".to_string(),
+        ]);
+        
+        let mut rule = FirewallRule::new(
+            3,
+            "Backup upload".to_string(),
+            RuleAction::RateLimit(RateLimit::bytes_per_second(125000, 0, RateScope::PerRule)),
+            RuleDirection::Bidirectional,
+            RuleProtocol::Any,
+        );
+        let statements = NftablesBackend::compile_rule(&rule).unwrap();
+        assert_eq!(statements, vec![
+            "limit rate over 125000 bytes/second burst 0 bytes counter drop comment \"rule 3: Backup upload\"".to_string(),
+            "counter accept comment \"rule 3: Backup upload\"".to_string(),
+        ]);
+        
+        rule.action = RuleAction::RateLimit(RateLimit::packets_per_second(10, 5, RateScope::PerSource));
+        assert!(NftablesBackend::compile_rule(&rule).is_err());
+    }
+    
+    #[test]
+    fn test_recover_without_state_file() {
+        let state_file = std::env::temp_dir().join(format!("nm-no-state-{}.nft", std::process::id()));
+        assert!(!NftablesBackend::recover(&state_file).unwrap());
+    }
+    
+    #[test]
+    fn test_recover_leaves_running_owner_alone() {
+        let state_file = std::env::temp_dir().join(format!("nm-owned-state-{}.nft", std::process::id()));
+        fs::write(&state_file, "delete table inet network_monitor\n").unwrap();
+        
+        // Stands in for the instance that is still enforcing
+        let owner = File::open(&state_file).unwrap();
+        assert!(try_lock(&owner).unwrap());
+        assert!(!NftablesBackend::recover(&state_file).unwrap());
+        assert!(state_file.exists());
+        
+        let mut backend = NftablesBackend::with_table("network_monitor", &state_file);
+        assert!(matches!(backend.save_rollback_state(), Err(EnforcementError::InUse(_))));
+        
+        drop(owner);
+        fs::remove_file(&state_file).unwrap();
+    }
+}
@@ -0,0 +1,248 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use thiserror::Error;
+use crate::firewall::rules::FirewallRule;
+
+pub const DEFAULT_RULESET_FILE: &str = "/var/lib/network-monitor/firewall-rules.json";
+
+#[derive(Error, Debug)]
+pub enum RulesetError {
+    #[error("Cannot read firewall ruleset '{0}': {1}")]
+    Read(String, io::Error),
+    
+    #[error("Malformed firewall ruleset '{0}': {1}")]
+    Malformed(String, String),
+    
+    #[error("Cannot save firewall ruleset '{0}': {1}")]
+    Write(String, io::Error),
+}
+
+/// A numbered copy of an earlier ruleset, `<file>.~N~` like `cp --backup=numbered`
+#[derive(Debug, Clone)]
+pub struct RulesetBackup {
+    pub version: u32,
+    pub path: PathBuf,
+    pub modified: Option<SystemTime>,
+    /// `None` if the backup itself no longer parses
+    pub rule_count: Option<usize>,
+}
+
+/// JSON ruleset file on disk, with numbered backups of what each save replaced
+pub struct RulesetStore {
+    path: PathBuf,
+    max_backups: usize,
+}
+
+impl RulesetStore {
+    pub fn new<P: AsRef<Path>>(path: P, max_backups: usize) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            max_backups,
+        }
+    }
+    
+    pub fn get_path(&self) -> &Path {
+        &self.path
+    }
+    
+    /// The saved rules, or `None` if no ruleset has been saved yet
+    pub fn load(&self) -> Result<Option<Vec<FirewallRule>>, RulesetError> {
+        match fs::read_to_string(&self.path) {
+            Ok(content) => parse_ruleset(&content)
+                .map(Some)
+                .map_err(|message| RulesetError::Malformed(self.display_path(), message)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(RulesetError::Read(self.display_path(), e)),
+        }
+    }
+    
+    /// Write the rules, first backing up the file being replaced
+    pub fn save(&self, rules: &[FirewallRule]) -> Result<(), RulesetError> {
+        let write_error = |e: io::Error| RulesetError::Write(self.display_path(), e);
+        let content = serde_json::to_string_pretty(rules).map_err(|e| write_error(io::Error::other(e)))?;
+        
+        match fs::read_to_string(&self.path) {
+            // Nothing changed (e.g. a rule toggled twice), don't churn backups
+            Ok(existing) if existing == content => return Ok(()),
+            Ok(_) => self.backup_current().map_err(write_error)?,
+            Err(_) => {}
+        }
+        
+        if let Some(parent) = self.path.parent().filter(|parent| !parent.as_os_str().is_empty()) {
+            fs::create_dir_all(parent).map_err(write_error)?;
+        }
+        // Write then rename, so a crash never leaves a half-written ruleset
+        let temp_path = self.sibling(".tmp");
+        fs::write(&temp_path, content).map_err(write_error)?;
+        fs::rename(&temp_path, &self.path).map_err(write_error)?;
+        Ok(())
+    }
+    
+    /// Backups, newest first
+    pub fn list_backups(&self) -> Vec<RulesetBackup> {
+        let mut backups: Vec<RulesetBackup> = self.backup_versions()
+            .into_iter()
+            .map(|version| {
+                let path = self.backup_path(version);
+                let modified = fs::metadata(&path).and_then(|metadata| metadata.modified()).ok();
+                let rule_count = fs::read_to_string(&path)
+                    .ok()
+                    .and_then(|content| parse_ruleset(&content).ok())
+                    .map(|rules| rules.len());
+                RulesetBackup { version, path, modified, rule_count }
+            })
+            .collect();
+        backups.sort_by_key(|backup| std::cmp::Reverse(backup.version));
+        backups
+    }
+    
+    pub fn load_backup(&self, version: u32) -> Result<Vec<FirewallRule>, RulesetError> {
+        let path = self.backup_path(version);
+        let content = fs::read_to_string(&path)
+            .map_err(|e| RulesetError::Read(path.display().to_string(), e))?;
+        parse_ruleset(&content).map_err(|message| RulesetError::Malformed(path.display().to_string(), message))
+    }
+    
+    fn backup_current(&self) -> io::Result<()> {
+        if self.max_backups == 0 {
+            return Ok(());
+        }
+        
+        let versions = self.backup_versions();
+        let next_version = versions.iter().max().map_or(1, |version| version + 1);
+        fs::copy(&self.path, self.backup_path(next_version))?;
+        
+        // Drop the oldest beyond the limit, counting the one just made
+        let mut versions = versions;
+        versions.sort_unstable();
+        let excess = (versions.len() + 1).saturating_sub(self.max_backups);
+        for version in versions.into_iter().take(excess) {
+            if let Err(e) = fs::remove_file(self.backup_path(version)) {
+                log::warn!("Cannot remove old ruleset backup ~{}~: {}", version, e);
+            }
+        }
+        Ok(())
+    }
+    
+    fn backup_versions(&self) -> Vec<u32> {
+        let file_name = match self.path.file_name() {
+            Some(name) => name.to_string_lossy().into_owned(),
+            None => return Vec::new(),
+        };
+        let directory = match self.path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let prefix = format!("{}.~", file_name);
+        
+        fs::read_dir(directory)
+            .map(|entries| {
+                entries
+                    .filter_map(|entry| entry.ok())
+                    .filter_map(|entry| {
+                        let name = entry.file_name().to_string_lossy().into_owned();
+                        name.strip_prefix(&prefix)?.strip_suffix('~')?.parse().ok()
+                    })
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+    
+    fn backup_path(&self, version: u32) -> PathBuf {
+        self.sibling(&format!(".~{}~", version))
+    }
+    
+    fn sibling(&self, suffix: &str) -> PathBuf {
+        let mut name = self.path.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    }
+    
+    fn display_path(&self) -> String {
+        self.path.display().to_string()
+    }
+}
+
+/// Parse rules one at a time so an error can say which rule is broken
+fn parse_ruleset(content: &str) -> Result<Vec<FirewallRule>, String> {
+    let values: Vec<serde_json::Value> = serde_json::from_str(content)
+        .map_err(|e| format!("expected a JSON array of rules: {}", e))?;
+    
+    values
+        .into_iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let name = value.get("name")
+                .and_then(|name| name.as_str())
+                .map(|name| format!(" (\"{}\")", name))
+                .unwrap_or_default();
+            serde_json::from_value(value).map_err(|e| format!("rule {}{}: {}", i + 1, name, e))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firewall::rules::RuleTemplates;
+    
+    /// Fixed timestamp, so saving the same rules twice writes identical files
+    fn ssh_rule() -> FirewallRule {
+        let mut rule = RuleTemplates::allow_ssh();
+        rule.created_at = std::time::UNIX_EPOCH;
+        rule
+    }
+    
+    fn temp_store(name: &str, max_backups: usize) -> (PathBuf, RulesetStore) {
+        let directory = std::env::temp_dir().join(format!("nm-ruleset-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&directory);
+        let store = RulesetStore::new(directory.join("rules.json"), max_backups);
+        (directory, store)
+    }
+    
+    #[test]
+    fn test_save_load_and_backups() {
+        let (directory, store) = temp_store("backups", 2);
+        assert!(store.load().unwrap().is_none());
+        
+        store.save(&[ssh_rule()]).unwrap();
+        store.save(&[ssh_rule()]).unwrap(); // unchanged, no backup
+        assert!(store.list_backups().is_empty());
+        
+        for count in 2..=4 {
+            let rules: Vec<FirewallRule> = (0..count).map(|_| ssh_rule()).collect();
+            store.save(&rules).unwrap();
+        }
+        
+        assert_eq!(store.load().unwrap().unwrap().len(), 4);
+        let backups = store.list_backups();
+        let versions: Vec<u32> = backups.iter().map(|backup| backup.version).collect();
+        assert_eq!(versions, vec![3, 2]);
+        assert_eq!(backups[0].rule_count, Some(3));
+        assert_eq!(store.load_backup(2).unwrap().len(), 2);
+        
+        fs::remove_dir_all(directory).unwrap();
+    }
+    
+    #[test]
+    fn test_malformed_rule_error() {
+        let (directory, store) = temp_store("malformed", 5);
+        fs::create_dir_all(&directory).unwrap();
+        
+        let mut rules = serde_json::to_value([RuleTemplates::allow_ssh(), RuleTemplates::allow_http_https()]).unwrap();
+        rules[1]["action"] = serde_json::json!("Blok");
+        fs::write(store.get_path(), rules.to_string()).unwrap();
+        
+        let message = store.load().unwrap_err().to_string();
+        assert!(message.starts_with("Malformed firewall ruleset"), "{}", message);
+        assert!(message.contains("rule 2 (\"Allow HTTP/HTTPS\"): unknown variant `Blok`"), "{}", message);
+        
+        fs::write(store.get_path(), "{ not json").unwrap();
+        let message = store.load().unwrap_err().to_string();
+        assert!(message.contains("expected a JSON array of rules") && message.contains("line 1"), "{}", message);
+        
+        fs::remove_dir_all(directory).unwrap();
+    }
+}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::str::FromStr;
+use std::time::{Duration, SystemTime};
+use serde::{Serialize, Deserialize};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RateUnit {
+    Packets,
+    Bytes,
+}
+
+/// Whose traffic shares a token bucket
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum RateScope {
+    /// Each source address has its own limit
+    PerSource,
+    /// All traffic matching the rule shares one limit
+    PerRule,
+}
+
+/// Limit of a `RuleAction::RateLimit` rule, written like
+/// `10/s burst 5 per source` or `125000 bytes/s burst 0 per rule`.
+///
+/// Traffic within the limit is allowed and the rest dropped. Up to
+/// `rate + burst` units pass at once, then `rate` per second, the same as
+/// nftables' `limit rate ... burst ...`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct RateLimit {
+    /// Sustained units per second
+    pub rate: u32,
+    /// Extra units allowed at once on top of the rate
+    pub burst: u32,
+    pub unit: RateUnit,
+    pub scope: RateScope,
+}
+
+impl RateLimit {
+    pub const fn packets_per_second(rate: u32, burst: u32, scope: RateScope) -> Self {
+        Self { rate, burst, unit: RateUnit::Packets, scope }
+    }
+    
+    pub const fn bytes_per_second(rate: u32, burst: u32, scope: RateScope) -> Self {
+        Self { rate, burst, unit: RateUnit::Bytes, scope }
+    }
+    
+    fn capacity(&self) -> f64 {
+        self.rate as f64 + self.burst as f64
+    }
+}
+
+impl Default for RateLimit {
+    fn default() -> Self {
+        Self::packets_per_second(10, 5, RateScope::PerSource)
+    }
+}
+
+impl fmt::Display for RateLimit {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let unit = match self.unit {
+            RateUnit::Packets => "",
+            RateUnit::Bytes => " bytes",
+        };
+        let scope = match self.scope {
+            RateScope::PerSource => "source",
+            RateScope::PerRule => "rule",
+        };
+        write!(f, "{}{}/s burst {} per {}", self.rate, unit, self.burst, scope)
+    }
+}
+
+impl FromStr for RateLimit {
+    type Err = String;
+    
+    /// `<rate>[ packets| bytes]/s [burst <n>] [per source|per rule]`; the
+    /// burst defaults to 0 and the scope to per source
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text = s.trim().to_lowercase();
+        let (rate, rest) = text
+            .split_once("/s")
+            .ok_or_else(|| format!("expected a rate like 10/s or 1000 bytes/s, got '{}'", s.trim()))?;
+        let (rate, unit) = match rate.trim().split_once(char::is_whitespace) {
+            Some((rate, "packets" | "pkts")) => (rate, RateUnit::Packets),
+            Some((rate, "bytes" | "b")) => (rate, RateUnit::Bytes),
+            Some((_, unit)) => return Err(format!("unknown rate unit '{}', expected packets or bytes", unit.trim())),
+            None => (rate.trim(), RateUnit::Packets),
+        };
+        let rate: u32 = rate.parse().map_err(|_| format!("invalid rate '{}'", rate))?;
+        if rate == 0 {
+            return Err("rate must be at least 1 per second".to_string());
+        }
+        
+        let mut limit = Self { rate, burst: 0, unit, scope: RateScope::PerSource };
+        let mut words = rest.split_whitespace();
+        while let Some(word) = words.next() {
+            match (word, words.next()) {
+                ("burst", Some(burst)) => {
+                    limit.burst = burst.parse().map_err(|_| format!("invalid burst '{}'", burst))?;
+                }
+                ("per", Some("source" | "src" | "ip")) => limit.scope = RateScope::PerSource,
+                ("per", Some("rule")) => limit.scope = RateScope::PerRule,
+                (word, _) => return Err(format!("unexpected '{}' in rate limit", word)),
+            }
+        }
+        Ok(limit)
+    }
+}
+
+struct TokenBucket {
+    tokens: f64,
+    updated: SystemTime,
+    /// When the bucket will be full again, after which it can be dropped
+    full_at: SystemTime,
+}
+
+/// Token buckets of the rate-limit rules, keyed by rule and (for per-source
+/// limits) source address. Time comes from the packets, so replays are
+/// limited at their recorded pace.
+#[derive(Default)]
+pub struct RateLimiter {
+    buckets: HashMap<(u32, Option<IpAddr>), TokenBucket>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Take a packet's worth of tokens; `false` if the packet is over the limit
+    pub fn admit(&mut self, rule_id: u32, limit: &RateLimit, source: IpAddr, packet_size: usize, now: SystemTime) -> bool {
+        let key = match limit.scope {
+            RateScope::PerSource => (rule_id, Some(source)),
+            RateScope::PerRule => (rule_id, None),
+        };
+        let cost = match limit.unit {
+            RateUnit::Packets => 1.0,
+            RateUnit::Bytes => packet_size as f64,
+        };
+        let capacity = limit.capacity();
+        let rate = limit.rate.max(1) as f64;
+        
+        let bucket = self.buckets.entry(key).or_insert(TokenBucket { tokens: capacity, updated: now, full_at: now });
+        let elapsed = now.duration_since(bucket.updated).unwrap_or_default().as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated = now.max(bucket.updated);
+        
+        let admitted = bucket.tokens >= cost;
+        if admitted {
+            bucket.tokens -= cost;
+        }
+        bucket.full_at = bucket.updated + Duration::from_secs_f64((capacity - bucket.tokens) / rate);
+        admitted
+    }
+    
+    /// Forget buckets that have refilled, so idle sources don't pile up
+    pub fn expire(&mut self, now: SystemTime) {
+        self.buckets.retain(|_, bucket| bucket.full_at > now);
+    }
+    
+    pub fn len(&self) -> usize {
+        self.buckets.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.buckets.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_and_format() {
+        let limit: RateLimit = "10/s burst 5".parse().unwrap();
+        assert_eq!(limit, RateLimit::packets_per_second(10, 5, RateScope::PerSource));
+        assert_eq!(limit.to_string(), "10/s burst 5 per source");
+        
+        let limit: RateLimit = "125000 bytes/s per rule".parse().unwrap();
+        assert_eq!(limit, RateLimit::bytes_per_second(125000, 0, RateScope::PerRule));
+        assert_eq!(limit.to_string().parse::<RateLimit>().unwrap(), limit);
+        
+        assert!("0/s".parse::<RateLimit>().is_err());
+        assert!("10 kbit/s".parse::<RateLimit>().is_err());
+        assert!("10/s burst".parse::<RateLimit>().is_err());
+        assert!("10 per second".parse::<RateLimit>().is_err());
+    }
+    
+    #[test]
+    fn test_token_buckets() {
+        let mut limiter = RateLimiter::new();
+        let limit = RateLimit::packets_per_second(2, 1, RateScope::PerSource);
+        let start = SystemTime::UNIX_EPOCH + Duration::from_secs(1_000_000);
+        let attacker: IpAddr = "203.0.113.7".parse().unwrap();
+        let other: IpAddr = "198.51.100.1".parse().unwrap();
+        
+        // rate + burst at once, then nothing until tokens come back
+        let admitted: Vec<bool> = (0..5).map(|_| limiter.admit(1, &limit, attacker, 60, start)).collect();
+        assert_eq!(admitted, vec![true, true, true, false, false]);
+        assert!(limiter.admit(1, &limit, other, 60, start));
+        assert!(limiter.admit(1, &limit, attacker, 60, start + Duration::from_millis(500)));
+        assert!(!limiter.admit(1, &limit, attacker, 60, start + Duration::from_millis(500)));
+        
+        // Bytes: a large packet needs more tokens than a small one
+        let bytes = RateLimit::bytes_per_second(1000, 500, RateScope::PerRule);
+        assert!(limiter.admit(2, &bytes, attacker, 1200, start));
+        assert!(!limiter.admit(2, &bytes, other, 400, start));
+        assert!(limiter.admit(2, &bytes, other, 300, start));
+        
+        limiter.expire(start + Duration::from_secs(1));
+        assert_eq!(limiter.len(), 2);
+        limiter.expire(start + Duration::from_secs(3));
+        assert!(limiter.is_empty());
+    }
+}
@@ -1,6 +1,12 @@
+use std::collections::BTreeSet;
+use std::fmt;
 use std::net::{IpAddr, Ipv4Addr};
-use std::collections::HashSet;
+use ipnetwork::IpNetwork;
 use serde::{Serialize, Deserialize};
+use crate::firewall::conntrack::ConnState;
+use crate::firewall::matchers::{AddressSet, PortRange, PortSet};
+use crate::firewall::ratelimit::{RateLimit, RateScope};
+use crate::firewall::schedule::RuleSchedule;
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum RuleAction {
@@ -8,6 +14,17 @@ pub enum RuleAction {
     Block,
     Log,
     LogAndBlock,
+    /// Allow traffic within the limit and drop the rest
+    RateLimit(RateLimit),
+}
+
+impl fmt::Display for RuleAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            RuleAction::RateLimit(limit) => write!(f, "RateLimit {}", limit),
+            action => write!(f, "{:?}", action),
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
@@ -33,15 +50,24 @@ pub struct FirewallRule {
     pub action: RuleAction,
     pub direction: RuleDirection,
     pub protocol: RuleProtocol,
-    pub source_ips: Option<HashSet<IpAddr>>,
-    pub destination_ips: Option<HashSet<IpAddr>>,
-    pub source_ports: Option<HashSet<u16>>,
-    pub destination_ports: Option<HashSet<u16>>,
+    pub source_ips: Option<AddressSet>,
+    pub destination_ips: Option<AddressSet>,
+    pub source_ports: Option<PortSet>,
+    pub destination_ports: Option<PortSet>,
+    /// Connection states the rule applies to; `None` matches any state
+    #[serde(default)]
+    pub states: Option<BTreeSet<ConnState>>,
     pub priority: u8, // 0-255, higher number = higher priority
     pub description: String,
     pub created_at: std::time::SystemTime,
     pub last_matched: Option<std::time::SystemTime>,
     pub match_count: u64,
+    /// The engine removes the rule once this has passed; `None` never expires
+    #[serde(default)]
+    pub expires_at: Option<std::time::SystemTime>,
+    /// Local time windows the rule applies in; empty applies at all times
+    #[serde(default)]
+    pub schedules: Vec<RuleSchedule>,
 }
 
 impl FirewallRule {
@@ -63,62 +89,122 @@ impl FirewallRule {
             destination_ips: None,
             source_ports: None,
             destination_ports: None,
+            states: None,
             priority: 128, // Default medium priority
             description: String::new(),
             created_at: std::time::SystemTime::now(),
             last_matched: None,
             match_count: 0,
+            expires_at: None,
+            schedules: Vec::new(),
         }
     }
     
     pub fn with_source_ip(mut self, ip: IpAddr) -> Self {
-        self.source_ips.get_or_insert_with(HashSet::new).insert(ip);
+        self.source_ips.get_or_insert_with(AddressSet::new).insert(IpNetwork::from(ip));
         self
     }
     
     pub fn with_source_ips(mut self, ips: Vec<IpAddr>) -> Self {
-        let set = self.source_ips.get_or_insert_with(HashSet::new);
+        let set = self.source_ips.get_or_insert_with(AddressSet::new);
         for ip in ips {
-            set.insert(ip);
+            set.insert(IpNetwork::from(ip));
         }
         self
     }
     
+    pub fn with_source_network(mut self, network: IpNetwork) -> Self {
+        self.source_ips.get_or_insert_with(AddressSet::new).insert(network);
+        self
+    }
+    
     pub fn with_destination_ip(mut self, ip: IpAddr) -> Self {
-        self.destination_ips.get_or_insert_with(HashSet::new).insert(ip);
+        self.destination_ips.get_or_insert_with(AddressSet::new).insert(IpNetwork::from(ip));
         self
     }
     
     pub fn with_destination_ips(mut self, ips: Vec<IpAddr>) -> Self {
-        let set = self.destination_ips.get_or_insert_with(HashSet::new);
+        let set = self.destination_ips.get_or_insert_with(AddressSet::new);
         for ip in ips {
-            set.insert(ip);
+            set.insert(IpNetwork::from(ip));
         }
         self
     }
     
+    pub fn with_destination_network(mut self, network: IpNetwork) -> Self {
+        self.destination_ips.get_or_insert_with(AddressSet::new).insert(network);
+        self
+    }
+    
     pub fn with_source_port(mut self, port: u16) -> Self {
-        self.source_ports.get_or_insert_with(HashSet::new).insert(port);
+        self.source_ports.get_or_insert_with(PortSet::new).insert(PortRange::single(port));
         self
     }
     
     pub fn with_source_ports(mut self, ports: Vec<u16>) -> Self {
-        let set = self.source_ports.get_or_insert_with(HashSet::new);
+        let set = self.source_ports.get_or_insert_with(PortSet::new);
         for port in ports {
-            set.insert(port);
+            set.insert(PortRange::single(port));
         }
         self
     }
     
+    pub fn with_source_port_range(mut self, start: u16, end: u16) -> Self {
+        self.source_ports.get_or_insert_with(PortSet::new).insert(PortRange::new(start, end));
+        self
+    }
+    
     pub fn with_destination_port(mut self, port: u16) -> Self {
-        self.destination_ports.get_or_insert_with(HashSet::new).insert(port);
+        self.destination_ports.get_or_insert_with(PortSet::new).insert(PortRange::single(port));
         self
     }
     
     pub fn with_destination_ports(mut self, ports: Vec<u16>) -> Self {
-        let set = self.destination_ports.get_or_insert_with(HashSet::new);
+        let set = self.destination_ports.get_or_insert_with(PortSet::new);
         for port in ports {
-            set.insert(port);
+            set.insert(PortRange::single(port));
+        }
+        self
+    }
+    
+    pub fn with_destination_port_range(mut self, start: u16, end: u16) -> Self {
+        self.destination_ports.get_or_insert_with(PortSet::new).insert(PortRange::new(start, end));
+        self
+    }
+    
+    /// Match packets whose source is *not* in the configured addresses
+    pub fn negate_source_ips(mut self) -> Self {
+        self.source_ips.get_or_insert_with(AddressSet::new).set_negated(true);
+        self
+    }
+    
+    /// Match packets whose destination is *not* in the configured addresses
+    pub fn negate_destination_ips(mut self) -> Self {
+        self.destination_ips.get_or_insert_with(AddressSet::new).set_negated(true);
+        self
+    }
+    
+    /// Match packets whose source port is *not* in the configured ports
+    pub fn negate_source_ports(mut self) -> Self {
+        self.source_ports.get_or_insert_with(PortSet::new).set_negated(true);
+        self
+    }
+    
+    /// Match packets whose destination port is *not* in the configured ports
+    pub fn negate_destination_ports(mut self) -> Self {
+        self.destination_ports.get_or_insert_with(PortSet::new).set_negated(true);
+        self
+    }
+    
+    pub fn with_state(mut self, state: ConnState) -> Self {
+        self.states.get_or_insert_with(BTreeSet::new).insert(state);
+        self
+    }
+    
+    pub fn with_states(mut self, states: Vec<ConnState>) -> Self {
+        let set = self.states.get_or_insert_with(BTreeSet::new);
+        for state in states {
+            set.insert(state);
         }
         self
     }
@@ -133,6 +219,16 @@ impl FirewallRule {
         self
     }
     
+    pub fn with_expiry(mut self, expires_at: std::time::SystemTime) -> Self {
+        self.expires_at = Some(expires_at);
+        self
+    }
+    
+    pub fn with_schedule(mut self, schedule: RuleSchedule) -> Self {
+        self.schedules.push(schedule);
+        self
+    }
+    
     pub fn matches_packet(
         &self,
         src_ip: &IpAddr,
@@ -160,28 +256,28 @@ impl FirewallRule {
         
         // Check source IPs
         if let Some(ref allowed_ips) = self.source_ips {
-            if !allowed_ips.contains(src_ip) {
+            if !allowed_ips.matches(src_ip) {
                 return false;
             }
         }
         
         // Check destination IPs
         if let Some(ref allowed_ips) = self.destination_ips {
-            if !allowed_ips.contains(dst_ip) {
+            if !allowed_ips.matches(dst_ip) {
                 return false;
             }
         }
         
         // Check source ports
         if let Some(ref allowed_ports) = self.source_ports {
-            if !allowed_ports.contains(&src_port) {
+            if !allowed_ports.matches(src_port) {
                 return false;
             }
         }
         
         // Check destination ports
         if let Some(ref allowed_ports) = self.destination_ports {
-            if !allowed_ports.contains(&dst_port) {
+            if !allowed_ports.matches(dst_port) {
                 return false;
             }
         }
@@ -189,6 +285,32 @@ impl FirewallRule {
         true
     }
     
+    /// Check the rule's connection state condition, which `matches_packet`
+    /// leaves out since it needs the connection tracker
+    pub fn matches_state(&self, state: ConnState) -> bool {
+        match self.states {
+            Some(ref states) => states.contains(&state),
+            None => true,
+        }
+    }
+    
+    pub fn is_expired(&self, now: std::time::SystemTime) -> bool {
+        self.expires_at.is_some_and(|expires_at| expires_at <= now)
+    }
+    
+    /// Whether the rule applies at `time`: not expired, and inside one of its
+    /// schedules if it has any. Like `matches_state`, this is separate from
+    /// `matches_packet`.
+    pub fn is_in_effect(&self, time: std::time::SystemTime) -> bool {
+        !self.is_expired(time)
+            && (self.schedules.is_empty() || self.schedules.iter().any(|schedule| schedule.is_active_at(time)))
+    }
+    
+    /// Whether the rule only applies for a while or at certain times
+    pub fn is_timed(&self) -> bool {
+        self.expires_at.is_some() || !self.schedules.is_empty()
+    }
+    
     pub fn record_match(&mut self) {
         self.last_matched = Some(std::time::SystemTime::now());
         self.match_count += 1;
@@ -201,6 +323,11 @@ impl FirewallRule {
     pub fn get_time_since_last_match(&self) -> Option<Duration> {
         self.last_matched?.elapsed().ok()
     }
+    
+    /// Time left before the rule expires, zero once it has
+    pub fn get_time_until_expiry(&self) -> Option<Duration> {
+        Some(self.expires_at?.duration_since(std::time::SystemTime::now()).unwrap_or_default())
+    }
 }
 
 use std::time::Duration;
@@ -229,10 +356,38 @@ impl RuleTemplates {
             RuleDirection::Inbound,
             RuleProtocol::Any,
         )
+        // Replies to outbound connections are still let in
+        .with_states(vec![ConnState::New, ConnState::Invalid])
         .with_description("Block all incoming connections".to_string())
         .with_priority(200)
     }
     
+    pub fn allow_established() -> FirewallRule {
+        FirewallRule::new(
+            7,
+            "Allow Established/Related".to_string(),
+            RuleAction::Allow,
+            RuleDirection::Bidirectional,
+            RuleProtocol::Any,
+        )
+        .with_states(vec![ConnState::Established, ConnState::Related])
+        .with_description("Allow traffic of connections that are already open".to_string())
+        .with_priority(254)
+    }
+    
+    pub fn block_invalid() -> FirewallRule {
+        FirewallRule::new(
+            8,
+            "Block Invalid".to_string(),
+            RuleAction::Block,
+            RuleDirection::Bidirectional,
+            RuleProtocol::Any,
+        )
+        .with_state(ConnState::Invalid)
+        .with_description("Drop packets that belong to no valid connection".to_string())
+        .with_priority(253)
+    }
+    
     pub fn allow_ssh() -> FirewallRule {
         FirewallRule::new(
             2,
@@ -246,6 +401,21 @@ impl RuleTemplates {
         .with_priority(250)
     }
     
+    pub fn rate_limit_ssh() -> FirewallRule {
+        FirewallRule::new(
+            9,
+            "Rate Limit SSH".to_string(),
+            RuleAction::RateLimit(RateLimit::packets_per_second(1, 5, RateScope::PerSource)),
+            RuleDirection::Inbound,
+            RuleProtocol::TCP,
+        )
+        .with_destination_port(22)
+        // Only connection attempts count, so open sessions aren't throttled
+        .with_state(ConnState::New)
+        .with_description("Limit new SSH connections per source against brute-force attempts".to_string())
+        .with_priority(251)
+    }
+    
     pub fn allow_http_https() -> FirewallRule {
         FirewallRule::new(
             3,
@@ -306,7 +476,7 @@ impl RuleTemplates {
 #[cfg(test)]
 mod tests {
     use super::*;
-
+    
     #[test]
     fn test_rule_creation() {
         let rule = FirewallRule::new(
@@ -366,5 +536,53 @@ mod tests {
         let block_rule = RuleTemplates::block_all_incoming();
         assert_eq!(block_rule.action, RuleAction::Block);
         assert_eq!(block_rule.direction, RuleDirection::Inbound);
+        assert!(block_rule.matches_state(ConnState::New));
+        assert!(!block_rule.matches_state(ConnState::Established));
+    }
+    
+    #[test]
+    fn test_cidr_range_and_negation_matching() {
+        let rule = FirewallRule::new(
+            1,
+            "Block LAN to high ports".to_string(),
+            RuleAction::Block,
+            RuleDirection::Inbound,
+            RuleProtocol::TCP,
+        )
+        .with_source_network("192.168.1.0/24".parse().unwrap())
+        .with_destination_port_range(1024, 65535);
+        
+        let lan = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 100));
+        let wan = IpAddr::V4(Ipv4Addr::new(198, 51, 100, 7));
+        let local = IpAddr::V4(Ipv4Addr::new(192, 168, 1, 1));
+        
+        assert!(rule.matches_packet(&lan, &local, 51000, 8080, &RuleProtocol::TCP, &RuleDirection::Inbound));
+        assert!(!rule.matches_packet(&lan, &local, 51000, 443, &RuleProtocol::TCP, &RuleDirection::Inbound));
+        assert!(!rule.matches_packet(&wan, &local, 51000, 8080, &RuleProtocol::TCP, &RuleDirection::Inbound));
+        
+        let not_lan = rule.clone().negate_source_ips();
+        assert!(not_lan.matches_packet(&wan, &local, 51000, 8080, &RuleProtocol::TCP, &RuleDirection::Inbound));
+        assert!(!not_lan.matches_packet(&lan, &local, 51000, 8080, &RuleProtocol::TCP, &RuleDirection::Inbound));
+    }
+    
+    #[test]
+    fn test_legacy_rule_json() {
+        let json = r#"{
+            "id": 7, "name": "Legacy", "enabled": true, "action": "Block",
+            "direction": "Inbound", "protocol": "TCP",
+            "source_ips": ["10.0.0.1", "10.0.0.2"], "destination_ips": null,
+            "source_ports": null, "destination_ports": [22, 3389],
+            "priority": 128, "description": "",
+            "created_at": {"secs_since_epoch": 0, "nanos_since_epoch": 0},
+            "last_matched": null, "match_count": 0
+        }"#;
+        let rule: FirewallRule = serde_json::from_str(json).unwrap();
+        
+        assert!(rule.source_ips.as_ref().unwrap().contains(&IpAddr::V4(Ipv4Addr::new(10, 0, 0, 2))));
+        assert!(rule.destination_ports.as_ref().unwrap().contains(&3389));
+        
+        let reserialized = serde_json::to_value(&rule).unwrap();
+        assert_eq!(reserialized["source_ips"], serde_json::json!(["10.0.0.1", "10.0.0.2"]));
+        assert_eq!(reserialized["destination_ports"], serde_json::json!([22, 3389]));
     }
 }
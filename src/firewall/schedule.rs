@@ -0,0 +1,259 @@
+use std::collections::BTreeSet;
+use std::fmt;
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::{Serialize, Deserialize};
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Weekday {
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+    Sun,
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+        Weekday::Sun,
+    ];
+    
+    /// Day for `tm_wday`, which counts from Sunday = 0
+    fn from_tm_wday(wday: i32) -> Self {
+        Self::ALL[(wday + 6).rem_euclid(7) as usize]
+    }
+    
+    fn index(&self) -> usize {
+        *self as usize
+    }
+    
+    fn previous(&self) -> Self {
+        Self::ALL[(self.index() + 6) % 7]
+    }
+}
+
+impl fmt::Display for Weekday {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl FromStr for Weekday {
+    type Err = String;
+    
+    /// Three-letter or full English day names, any case
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        const NAMES: [&str; 7] = ["monday", "tuesday", "wednesday", "thursday", "friday", "saturday", "sunday"];
+        let name = s.trim().to_lowercase();
+        Self::ALL
+            .iter()
+            .zip(NAMES)
+            .find(|(_, full_name)| name.len() >= 3 && full_name.starts_with(&name))
+            .map(|(day, _)| *day)
+            .ok_or_else(|| format!("unknown day '{}'", s.trim()))
+    }
+}
+
+/// A weekly local-time window in which a rule is in effect, written like
+/// `Mon-Fri 09:00-17:00`, `Sat,Sun 22:00-06:00` or just `08:00-18:00`
+/// (every day).
+///
+/// A window that ends before it starts runs past midnight into the next
+/// day; the days name the day it starts on.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct RuleSchedule {
+    /// Empty means every day
+    days: BTreeSet<Weekday>,
+    /// Minutes since local midnight
+    start: u16,
+    /// Minutes since local midnight, up to 24:00
+    end: u16,
+}
+
+impl RuleSchedule {
+    pub fn new<I: IntoIterator<Item = Weekday>>(days: I, start: (u8, u8), end: (u8, u8)) -> Result<Self, String> {
+        let start = time_of_day(start.0, start.1, false)?;
+        let end = time_of_day(end.0, end.1, true)?;
+        if start == end {
+            return Err("time window is empty".to_string());
+        }
+        Ok(Self { days: days.into_iter().collect(), start, end })
+    }
+    
+    /// Whether the window covers the given local day and minute of the day
+    pub fn contains(&self, day: Weekday, minute: u16) -> bool {
+        let on = |day: Weekday| self.days.is_empty() || self.days.contains(&day);
+        if self.start < self.end {
+            on(day) && self.start <= minute && minute < self.end
+        } else {
+            (on(day) && minute >= self.start) || (on(day.previous()) && minute < self.end)
+        }
+    }
+    
+    /// Whether the window covers `time` in the local time zone
+    pub fn is_active_at(&self, time: SystemTime) -> bool {
+        let (day, minute) = local_day_and_minute(time);
+        self.contains(day, minute)
+    }
+}
+
+impl fmt::Display for RuleSchedule {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.days.is_empty() {
+            write!(f, "{} ", format_days(&self.days))?;
+        }
+        write!(f, "{}-{}", format_time(self.start), format_time(self.end))
+    }
+}
+
+impl FromStr for RuleSchedule {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let (days, window) = match s.rsplit_once(char::is_whitespace) {
+            Some((days, window)) => (parse_days(days)?, window),
+            None => (BTreeSet::new(), s),
+        };
+        let (start, end) = window
+            .split_once('-')
+            .ok_or_else(|| format!("expected a time window like 09:00-17:00, got '{}'", window))?;
+        Self::new(days, parse_time(start)?, parse_time(end)?)
+    }
+}
+
+impl TryFrom<String> for RuleSchedule {
+    type Error = String;
+    
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<RuleSchedule> for String {
+    fn from(schedule: RuleSchedule) -> Self {
+        schedule.to_string()
+    }
+}
+
+fn time_of_day(hour: u8, minute: u8, allow_end_of_day: bool) -> Result<u16, String> {
+    let minutes = hour as u16 * 60 + minute as u16;
+    if minute >= 60 || minutes > MINUTES_PER_DAY || (minutes == MINUTES_PER_DAY && !allow_end_of_day) {
+        return Err(format!("invalid time {:02}:{:02}", hour, minute));
+    }
+    Ok(minutes)
+}
+
+fn parse_time(text: &str) -> Result<(u8, u8), String> {
+    let invalid = || format!("invalid time '{}', expected HH:MM", text.trim());
+    let (hour, minute) = text.trim().split_once(':').ok_or_else(invalid)?;
+    Ok((hour.parse().map_err(|_| invalid())?, minute.parse().map_err(|_| invalid())?))
+}
+
+fn format_time(minutes: u16) -> String {
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// Comma-separated days and day ranges, e.g. `Mon-Fri` or `Fri-Mon,Wed`
+fn parse_days(text: &str) -> Result<BTreeSet<Weekday>, String> {
+    let mut days = BTreeSet::new();
+    for item in text.split(',').map(str::trim).filter(|item| !item.is_empty()) {
+        match item.split_once('-') {
+            Some((first, last)) => {
+                let (first, last) = (first.parse::<Weekday>()?, last.parse::<Weekday>()?);
+                let count = (last.index() + 7 - first.index()) % 7 + 1;
+                days.extend((0..count).map(|offset| Weekday::ALL[(first.index() + offset) % 7]));
+            }
+            None => {
+                days.insert(item.parse()?);
+            }
+        }
+    }
+    Ok(days)
+}
+
+/// Runs of three or more consecutive days are written as ranges
+fn format_days(days: &BTreeSet<Weekday>) -> String {
+    let mut parts = Vec::new();
+    let days: Vec<Weekday> = days.iter().copied().collect();
+    let mut i = 0;
+    while i < days.len() {
+        let mut j = i;
+        while j + 1 < days.len() && days[j + 1].index() == days[j].index() + 1 {
+            j += 1;
+        }
+        if j - i >= 2 {
+            parts.push(format!("{}-{}", days[i], days[j]));
+        } else {
+            parts.extend(days[i..=j].iter().map(|day| day.to_string()));
+        }
+        i = j + 1;
+    }
+    parts.join(",")
+}
+
+fn local_day_and_minute(time: SystemTime) -> (Weekday, u16) {
+    let seconds = time.duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs()) as libc::time_t;
+    // SAFETY: `tm` is plain data for which all zeroes is valid, and both
+    // pointers come from references that outlive the call
+    let mut tm: libc::tm = unsafe { std::mem::zeroed() };
+    if unsafe { libc::localtime_r(&seconds, &mut tm) }.is_null() {
+        // Out of range for the C library; UTC beats guessing at a time zone
+        let seconds = seconds.max(0) as u64;
+        // The epoch was a Thursday
+        let weekday = Weekday::from_tm_wday(((seconds / 86400 + 4) % 7) as i32);
+        return (weekday, (seconds % 86400 / 60) as u16);
+    }
+    (Weekday::from_tm_wday(tm.tm_wday), (tm.tm_hour * 60 + tm.tm_min) as u16)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_parse_and_format() {
+        let schedule: RuleSchedule = "mon-fri 09:00-17:30".parse().unwrap();
+        assert_eq!(schedule.to_string(), "Mon-Fri 09:00-17:30");
+        assert_eq!("Sat,Sunday 22:00-06:00".parse::<RuleSchedule>().unwrap().to_string(), "Sat,Sun 22:00-06:00");
+        assert_eq!("Fri-Mon 00:00-24:00".parse::<RuleSchedule>().unwrap().to_string(), "Mon,Fri-Sun 00:00-24:00");
+        assert_eq!("08:00-18:00".parse::<RuleSchedule>().unwrap().to_string(), "08:00-18:00");
+        
+        assert!("Mon 09:00-09:00".parse::<RuleSchedule>().is_err());
+        assert!("Mon 25:00-26:00".parse::<RuleSchedule>().is_err());
+        assert!("Funday 09:00-17:00".parse::<RuleSchedule>().is_err());
+        assert!("Mon-Fri".parse::<RuleSchedule>().is_err());
+        
+        let json = serde_json::to_string(&schedule).unwrap();
+        assert_eq!(json, "\"Mon-Fri 09:00-17:30\"");
+        assert_eq!(serde_json::from_str::<RuleSchedule>(&json).unwrap(), schedule);
+    }
+    
+    #[test]
+    fn test_windows_past_midnight() {
+        let office: RuleSchedule = "Mon-Fri 09:00-17:00".parse().unwrap();
+        assert!(office.contains(Weekday::Wed, 9 * 60));
+        assert!(!office.contains(Weekday::Wed, 17 * 60));
+        assert!(!office.contains(Weekday::Sat, 12 * 60));
+        
+        // Friday night runs into Saturday morning, Sunday night doesn't start on Monday
+        let nights: RuleSchedule = "Fri,Sat 22:00-06:00".parse().unwrap();
+        assert!(nights.contains(Weekday::Fri, 23 * 60));
+        assert!(nights.contains(Weekday::Sat, 5 * 60));
+        assert!(nights.contains(Weekday::Sun, 5 * 60 + 59));
+        assert!(!nights.contains(Weekday::Sun, 6 * 60));
+        assert!(!nights.contains(Weekday::Fri, 5 * 60));
+        assert!(!nights.contains(Weekday::Sun, 23 * 60));
+    }
+}
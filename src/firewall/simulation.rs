@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::{IpAddr, SocketAddr};
+use std::path::Path;
+use pnet::packet::tcp::TcpFlags;
+use crate::capture::{CaptureError, PacketInfo, PcapEngine, ReplaySpeed};
+use crate::firewall::engine::FirewallEngine;
+use crate::firewall::rules::{FirewallRule, RuleAction};
+use crate::traffic::inspector::TrafficFlow;
+use crate::utils::formatting::format_bytes;
+
+/// "What if" evaluation of a candidate ruleset against recorded traffic.
+///
+/// Traffic runs through two private engines, one with the candidate rules
+/// and one with the rules in force, so the live engine's statistics, events
+/// and connection tracking are never touched. Verdicts are compared as
+/// passed/dropped; a Log rule on its own does not change a verdict.
+pub struct Simulation {
+    candidate: FirewallEngine,
+    baseline: FirewallEngine,
+    rule_hits: HashMap<u32, Hits>,
+    peers: HashMap<IpAddr, PeerImpact>,
+    changes: HashMap<(String, SocketAddr, SocketAddr), VerdictChange>,
+    total: Hits,
+    per_flow: bool,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Hits {
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl Hits {
+    fn add(&mut self, other: Hits) {
+        self.packets += other.packets;
+        self.bytes += other.bytes;
+    }
+}
+
+/// Would-match count for one candidate rule
+#[derive(Debug, Clone)]
+pub struct RuleImpact {
+    pub rule_id: u32,
+    pub rule_name: String,
+    pub action: RuleAction,
+    pub enabled: bool,
+    pub hits: Hits,
+}
+
+/// Traffic of one remote peer whose verdict would change
+#[derive(Debug, Clone)]
+pub struct PeerImpact {
+    pub peer: IpAddr,
+    pub newly_blocked: Hits,
+    pub newly_allowed: Hits,
+    pub flows: usize,
+}
+
+impl PeerImpact {
+    pub fn changed_packets(&self) -> u64 {
+        self.newly_blocked.packets + self.newly_allowed.packets
+    }
+}
+
+/// A flow that the candidate rules would treat differently
+#[derive(Debug, Clone)]
+pub struct VerdictChange {
+    pub protocol: String,
+    pub src: SocketAddr,
+    pub dst: SocketAddr,
+    pub before: RuleAction,
+    pub after: RuleAction,
+    /// Candidate rule that decided the new verdict; `None` for the default allow
+    pub rule_name: Option<String>,
+    pub hits: Hits,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SimulationReport {
+    pub evaluated: Hits,
+    pub newly_blocked: Hits,
+    pub newly_allowed: Hits,
+    /// Every candidate rule, in evaluation order
+    pub rules: Vec<RuleImpact>,
+    pub top_peers: Vec<PeerImpact>,
+    pub changed_flows: Vec<VerdictChange>,
+    /// Traffic was evaluated as flow summaries rather than packet by packet
+    pub per_flow: bool,
+}
+
+impl SimulationReport {
+    /// A flow is judged by its first packet only, so a rate limit never
+    /// sees enough packets to trip
+    pub fn rate_limits_unmeasured(&self) -> bool {
+        self.per_flow && self.rules.iter().any(|rule| matches!(rule.action, RuleAction::RateLimit(_)))
+    }
+}
+
+impl Simulation {
+    pub fn new(candidate_rules: Vec<FirewallRule>, current_rules: Vec<FirewallRule>) -> Self {
+        let engine = |rules: Vec<FirewallRule>| {
+            let mut engine = FirewallEngine::new();
+            engine.replace_rules(rules);
+            engine
+        };
+        
+        Self {
+            candidate: engine(candidate_rules),
+            baseline: engine(current_rules),
+            rule_hits: HashMap::new(),
+            peers: HashMap::new(),
+            changes: HashMap::new(),
+            total: Hits::default(),
+            per_flow: false,
+        }
+    }
+    
+    pub fn evaluate_packet(&mut self, packet: &PacketInfo) {
+        self.evaluate(packet, Hits { packets: 1, bytes: packet.length as u64 });
+    }
+    
+    /// Evaluate a recorded flow as the packet that opened it, weighted by all
+    /// of the flow's traffic. Rate limits only ever see that one packet, so
+    /// they don't limit anything here.
+    pub fn evaluate_flow(&mut self, flow: &TrafficFlow) {
+        self.per_flow = true;
+        let packet = PacketInfo {
+            timestamp: flow.start_time,
+            length: 0,
+            protocol: flow.transport.clone(),
+            src_ip: Some(flow.src_addr.ip().to_string()),
+            dst_ip: Some(flow.dst_addr.ip().to_string()),
+            src_port: Some(flow.src_addr.port()),
+            dst_port: Some(flow.dst_addr.port()),
+            tcp_flags: if flow.transport == "TCP" { Some(TcpFlags::SYN) } else { None },
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
+        };
+        self.evaluate(&packet, Hits { packets: flow.packet_count, bytes: flow.byte_count });
+    }
+    
+    fn evaluate(&mut self, packet: &PacketInfo, weight: Hits) {
+        self.total.add(weight);
+        
+        let counts_before: Vec<u64> = self.candidate.get_rules().iter().map(|rule| rule.match_count).collect();
+        let after = self.candidate.process_packet(packet);
+        let before = self.baseline.process_packet(packet);
+        
+        // Every rule whose count moved matched; only the last can be terminal
+        let mut deciding_rule = None;
+        for (rule, count_before) in self.candidate.get_rules().iter().zip(counts_before) {
+            if rule.match_count > count_before {
+                self.rule_hits.entry(rule.id).or_default().add(weight);
+                if rule.action != RuleAction::Log {
+                    deciding_rule = Some(rule.name.clone());
+                }
+            }
+        }
+        
+        if is_blocking(&before) == is_blocking(&after) {
+            return;
+        }
+        let (src, dst) = match endpoints(packet) {
+            Some(endpoints) => endpoints,
+            None => return,
+        };
+        
+        // The same flow in either direction
+        let key = if src <= dst {
+            (packet.protocol.clone(), src, dst)
+        } else {
+            (packet.protocol.clone(), dst, src)
+        };
+        let is_new_flow = !self.changes.contains_key(&key);
+        self.changes
+            .entry(key)
+            .or_insert_with(|| VerdictChange {
+                protocol: packet.protocol.clone(),
+                src,
+                dst,
+                before: before.clone(),
+                after: after.clone(),
+                rule_name: deciding_rule,
+                hits: Hits::default(),
+            })
+            .hits
+            .add(weight);
+        
+        let peer = if FirewallEngine::is_local_ip(&src.ip()) { dst.ip() } else { src.ip() };
+        let impact = self.peers.entry(peer).or_insert_with(|| PeerImpact {
+            peer,
+            newly_blocked: Hits::default(),
+            newly_allowed: Hits::default(),
+            flows: 0,
+        });
+        if is_new_flow {
+            impact.flows += 1;
+        }
+        if is_blocking(&after) {
+            impact.newly_blocked.add(weight);
+        } else {
+            impact.newly_allowed.add(weight);
+        }
+    }
+    
+    /// Summary with at most `limit` peers and changed flows, largest first
+    pub fn report(&self, limit: usize) -> SimulationReport {
+        let rules = self.candidate
+            .get_rules()
+            .iter()
+            .map(|rule| RuleImpact {
+                rule_id: rule.id,
+                rule_name: rule.name.clone(),
+                action: rule.action.clone(),
+                enabled: rule.enabled,
+                hits: self.rule_hits.get(&rule.id).copied().unwrap_or_default(),
+            })
+            .collect();
+        
+        let mut newly_blocked = Hits::default();
+        let mut newly_allowed = Hits::default();
+        for peer in self.peers.values() {
+            newly_blocked.add(peer.newly_blocked);
+            newly_allowed.add(peer.newly_allowed);
+        }
+        
+        let mut top_peers: Vec<PeerImpact> = self.peers.values().cloned().collect();
+        top_peers.sort_by(|a, b| b.changed_packets().cmp(&a.changed_packets()).then(a.peer.cmp(&b.peer)));
+        top_peers.truncate(limit);
+        
+        let mut changed_flows: Vec<VerdictChange> = self.changes.values().cloned().collect();
+        changed_flows.sort_by(|a, b| b.hits.packets.cmp(&a.hits.packets).then(a.src.cmp(&b.src)));
+        changed_flows.truncate(limit);
+        
+        SimulationReport {
+            evaluated: self.total,
+            newly_blocked,
+            newly_allowed,
+            rules,
+            top_peers,
+            changed_flows,
+            per_flow: self.per_flow,
+        }
+    }
+}
+
+/// Replay a capture file through the candidate and current rules
+pub fn simulate_pcap<P: AsRef<Path>>(
+    path: P,
+    candidate_rules: Vec<FirewallRule>,
+    current_rules: Vec<FirewallRule>,
+) -> Result<Simulation, CaptureError> {
+    let mut capture = PcapEngine::from_file(path, ReplaySpeed::AsFastAsPossible)?;
+    let mut simulation = Simulation::new(candidate_rules, current_rules);
+    
+    loop {
+        match capture.get_next_packet()? {
+            Some(packet) => simulation.evaluate_packet(&packet),
+            None if capture.is_replay_finished() => break,
+            None => {}
+        }
+    }
+    Ok(simulation)
+}
+
+/// Evaluate recorded flows (e.g. the traffic inspector's active flows and history)
+pub fn simulate_flows<'a, I: IntoIterator<Item = &'a TrafficFlow>>(
+    flows: I,
+    candidate_rules: Vec<FirewallRule>,
+    current_rules: Vec<FirewallRule>,
+) -> Simulation {
+    let mut simulation = Simulation::new(candidate_rules, current_rules);
+    for flow in flows {
+        simulation.evaluate_flow(flow);
+    }
+    simulation
+}
+
+fn is_blocking(action: &RuleAction) -> bool {
+    matches!(action, RuleAction::Block | RuleAction::LogAndBlock)
+}
+
+fn endpoints(packet: &PacketInfo) -> Option<(SocketAddr, SocketAddr)> {
+    let src_ip: IpAddr = packet.src_ip.as_ref()?.parse().ok()?;
+    let dst_ip: IpAddr = packet.dst_ip.as_ref()?.parse().ok()?;
+    Some((
+        SocketAddr::new(src_ip, packet.src_port.unwrap_or(0)),
+        SocketAddr::new(dst_ip, packet.dst_port.unwrap_or(0)),
+    ))
+}
+
+impl fmt::Display for SimulationReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        writeln!(f, "Evaluated {} packets ({})", self.evaluated.packets, format_bytes(self.evaluated.bytes))?;
+        writeln!(
+            f,
+            "Verdict changes: {} packets ({}) newly blocked, {} packets ({}) newly allowed",
+            self.newly_blocked.packets,
+            format_bytes(self.newly_blocked.bytes),
+            self.newly_allowed.packets,
+            format_bytes(self.newly_allowed.bytes)
+        )?;
+        if self.rate_limits_unmeasured() {
+            writeln!(f, "Note: flows are judged by their first packet, so rate limits are not applied")?;
+        }
+        
+        writeln!(f, "\nWould-match counts:")?;
+        for rule in &self.rules {
+            let disabled = if rule.enabled { "" } else { " (disabled)" };
+            writeln!(
+                f,
+                "  #{:<4} {:<32} {:<12} {:>8} packets {:>10}{}",
+                rule.rule_id,
+                rule.rule_name,
+                rule.action.to_string(),
+                rule.hits.packets,
+                format_bytes(rule.hits.bytes),
+                disabled
+            )?;
+        }
+        
+        writeln!(f, "\nTop affected peers:")?;
+        if self.top_peers.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for peer in &self.top_peers {
+            writeln!(
+                f,
+                "  {:<39} {:>8} packets newly blocked, {:>8} newly allowed ({} flows)",
+                peer.peer.to_string(),
+                peer.newly_blocked.packets,
+                peer.newly_allowed.packets,
+                peer.flows
+            )?;
+        }
+        
+        writeln!(f, "\nFlows that would change verdict:")?;
+        if self.changed_flows.is_empty() {
+            writeln!(f, "  none")?;
+        }
+        for change in &self.changed_flows {
+            writeln!(
+                f,
+                "  {} {} → {}  {:?} → {:?} ({}), {} packets",
+                change.protocol,
+                change.src,
+                change.dst,
+                change.before,
+                change.after,
+                change.rule_name.as_deref().unwrap_or("default allow"),
+                change.hits.packets
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::firewall::rules::{RuleDirection, RuleProtocol, RuleTemplates};
+    use crate::analysis::protocols::ProtocolType;
+    use crate::traffic::inspector::FlowDirection;
+    use std::time::SystemTime;
+    
+    fn flow(src: &str, dst: &str, transport: &str, packet_count: u64) -> TrafficFlow {
+        TrafficFlow {
+            flow_id: format!("{}:{}", src, dst),
+            src_addr: src.parse().unwrap(),
+            dst_addr: dst.parse().unwrap(),
+            protocol: ProtocolType::Unknown,
+            transport: transport.to_string(),
+            direction: FlowDirection::Inbound,
+            start_time: SystemTime::now(),
+            last_seen: SystemTime::now(),
+            packet_count,
+            byte_count: packet_count * 100,
+            src_bytes: 0,
+            dst_bytes: 0,
+            packets_per_second: 0.0,
+            bytes_per_second: 0.0,
+            is_active: true,
+            tls: None,
+            http: Vec::new(),
+        }
+    }
+    
+    #[test]
+    fn test_what_if_new_block_rule() {
+        let mut current = vec![RuleTemplates::allow_ssh(), RuleTemplates::log_all_connections()];
+        current[1].id = 5;
+        let mut candidate = current.clone();
+        candidate.push(
+            FirewallRule::new(9, "Block SSH from outside".to_string(), RuleAction::Block, RuleDirection::Inbound, RuleProtocol::TCP)
+                .with_destination_port(22)
+                .with_priority(255),
+        );
+        
+        let flows = vec![
+            flow("203.0.113.5:50000", "192.168.1.2:22", "TCP", 40),
+            flow("203.0.113.5:50001", "192.168.1.2:22", "TCP", 10),
+            flow("198.51.100.7:40000", "192.168.1.2:22", "TCP", 5),
+            flow("203.0.113.5:50002", "192.168.1.2:22", "UDP", 3),
+            flow("198.51.100.7:40001", "192.168.1.2:443", "TCP", 100),
+        ];
+        let report = simulate_flows(&flows, candidate, current).report(10);
+        
+        assert_eq!(report.evaluated.packets, 158);
+        assert_eq!(report.newly_blocked.packets, 55);
+        assert_eq!(report.newly_allowed.packets, 0);
+        
+        let hits: Vec<(u32, u64)> = report.rules.iter().map(|rule| (rule.rule_id, rule.hits.packets)).collect();
+        assert_eq!(hits, vec![(9, 55), (2, 0), (5, 103)]);
+        
+        let peers: Vec<(String, u64, usize)> = report.top_peers
+            .iter()
+            .map(|peer| (peer.peer.to_string(), peer.newly_blocked.packets, peer.flows))
+            .collect();
+        assert_eq!(peers, vec![("203.0.113.5".to_string(), 50, 2), ("198.51.100.7".to_string(), 5, 1)]);
+        
+        assert_eq!(report.changed_flows.len(), 3);
+        assert_eq!(report.changed_flows[0].src, "203.0.113.5:50000".parse().unwrap());
+        assert_eq!(report.changed_flows[0].after, RuleAction::Block);
+        assert_eq!(report.changed_flows[0].rule_name.as_deref(), Some("Block SSH from outside"));
+        assert!(report.per_flow);
+        assert!(!report.rate_limits_unmeasured());
+    }
+}
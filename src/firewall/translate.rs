@@ -0,0 +1,189 @@
+use std::fmt;
+use std::str::FromStr;
+use ipnetwork::IpNetwork;
+use thiserror::Error;
+use crate::firewall::iptables;
+use crate::firewall::matchers::AddressSet;
+use crate::firewall::nft_json;
+use crate::firewall::rules::FirewallRule;
+
+#[derive(Error, Debug)]
+pub enum TranslateError {
+    #[error("Malformed {0} input: {1}")]
+    Malformed(RulesetFormat, String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RulesetFormat {
+    /// `iptables-save` output (IPv4)
+    Iptables,
+    /// `ip6tables-save` output; same syntax as `Iptables`
+    Ip6tables,
+    /// `nft -j list ruleset` output
+    NftJson,
+}
+
+impl RulesetFormat {
+    /// Guess the format of a ruleset from its content
+    pub fn detect(content: &str) -> Self {
+        if content.trim_start().starts_with('{') {
+            RulesetFormat::NftJson
+        } else {
+            RulesetFormat::Iptables
+        }
+    }
+}
+
+impl fmt::Display for RulesetFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            RulesetFormat::Iptables => "iptables",
+            RulesetFormat::Ip6tables => "ip6tables",
+            RulesetFormat::NftJson => "nft-json",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl FromStr for RulesetFormat {
+    type Err = String;
+    
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim().to_lowercase().as_str() {
+            "iptables" | "iptables-save" => Ok(RulesetFormat::Iptables),
+            "ip6tables" | "ip6tables-save" => Ok(RulesetFormat::Ip6tables),
+            "nft-json" | "nft" | "json" => Ok(RulesetFormat::NftJson),
+            other => Err(format!("Unknown ruleset format '{}': use iptables, ip6tables or nft-json", other)),
+        }
+    }
+}
+
+/// A construct that could not be translated exactly. Neither direction of a
+/// translation is lossless, so these are reported rather than dropped.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TranslationIssue {
+    /// Where it came from: an input line, an nft rule handle or a rule id
+    pub location: String,
+    pub message: String,
+    /// The rule was left out; otherwise it was translated approximately
+    pub skipped: bool,
+}
+
+impl TranslationIssue {
+    pub fn skipped<L: Into<String>, M: Into<String>>(location: L, message: M) -> Self {
+        Self { location: location.into(), message: message.into(), skipped: true }
+    }
+    
+    pub fn approximated<L: Into<String>, M: Into<String>>(location: L, message: M) -> Self {
+        Self { location: location.into(), message: message.into(), skipped: false }
+    }
+}
+
+impl fmt::Display for TranslationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let outcome = if self.skipped { "skipped" } else { "approximated" };
+        write!(f, "{}: {} ({})", self.location, self.message, outcome)
+    }
+}
+
+/// Result of a translation together with what did not carry over
+#[derive(Debug, Clone, Default)]
+pub struct Translation<T> {
+    pub output: T,
+    pub issues: Vec<TranslationIssue>,
+}
+
+impl<T> Translation<T> {
+    pub fn skipped_count(&self) -> usize {
+        self.issues.iter().filter(|issue| issue.skipped).count()
+    }
+}
+
+/// Parse an existing `iptables-save`, `ip6tables-save` or `nft -j list ruleset`
+/// policy, e.g. to visualise it and count hits in the engine
+pub fn import_rules(content: &str) -> Result<Translation<Vec<FirewallRule>>, TranslateError> {
+    match RulesetFormat::detect(content) {
+        RulesetFormat::NftJson => nft_json::import(content),
+        _ => iptables::import(content),
+    }
+}
+
+/// Write the enabled rules for deployment with `iptables-restore` or
+/// `nft -j -f`, in evaluation order
+pub fn export_rules(format: RulesetFormat, rules: &[FirewallRule]) -> Translation<String> {
+    let mut translation = match format {
+        RulesetFormat::Iptables => iptables::export(rules, false),
+        RulesetFormat::Ip6tables => iptables::export(rules, true),
+        RulesetFormat::NftJson => nft_json::export(rules),
+    };
+    
+    // Expiry and schedules are enforced by the running engine, not the exported ruleset
+    for rule in rules.iter().filter(|rule| rule.enabled && rule.is_timed()) {
+        translation.issues.push(TranslationIssue::approximated(
+            rule_location(rule),
+            "expiry and schedules are not exported, the rule applies at all times",
+        ));
+    }
+    translation
+}
+
+/// Import order becomes priority, so the first rule of a chain is checked
+/// first; the default policy goes below every imported rule
+pub(crate) fn import_priority(index: usize) -> u8 {
+    255usize.saturating_sub(index).max(1) as u8
+}
+
+pub(crate) const POLICY_PRIORITY: u8 = 0;
+
+/// One address field of a rule, restricted to a single IP family
+#[derive(Debug, Clone)]
+pub(crate) struct AddressMatch {
+    pub networks: Vec<IpNetwork>,
+    pub negated: bool,
+}
+
+/// The address fields of a rule as they apply to one IP family. `None` from
+/// `family_addresses` means the rule can never match that family.
+#[derive(Debug, Clone)]
+pub(crate) struct FamilyAddresses {
+    pub source: Option<AddressMatch>,
+    pub destination: Option<AddressMatch>,
+}
+
+pub(crate) fn family_addresses(rule: &FirewallRule, ipv6: bool) -> Option<FamilyAddresses> {
+    let field = |ips: &Option<AddressSet>| -> Result<Option<AddressMatch>, ()> {
+        let ips = match ips {
+            Some(ips) => ips,
+            None => return Ok(None),
+        };
+        let networks: Vec<IpNetwork> = ips.iter().filter(|network| network.is_ipv6() == ipv6).cloned().collect();
+        match (networks.is_empty(), ips.is_negated()) {
+            (true, true) => Ok(None), // nothing of this family is excluded
+            (true, false) => Err(()),
+            (false, negated) => Ok(Some(AddressMatch { networks, negated })),
+        }
+    };
+    
+    Some(FamilyAddresses {
+        source: field(&rule.source_ips).ok()?,
+        destination: field(&rule.destination_ips).ok()?,
+    })
+}
+
+pub(crate) fn rule_location(rule: &FirewallRule) -> String {
+    format!("rule {} ({})", rule.id, rule.name)
+}
+
+/// Rule comments written on export; import strips the prefix again
+pub(crate) fn rule_comment(rule: &FirewallRule) -> String {
+    format!("rule {}: {}", rule.id, rule.name)
+}
+
+pub(crate) fn name_from_comment(comment: &str) -> String {
+    comment
+        .strip_prefix("rule ")
+        .and_then(|rest| rest.split_once(": "))
+        .filter(|(id, _)| id.parse::<u32>().is_ok())
+        .map(|(_, name)| name.to_string())
+        .unwrap_or_else(|| comment.to_string())
+}
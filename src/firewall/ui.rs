@@ -6,12 +6,31 @@ use ratatui::{
     Frame,
 };
 use crate::firewall::{FirewallEngine, RuleAction, RuleDirection, RuleProtocol};
+use crate::firewall::engine::FirewallEventKind;
+use crate::firewall::editor::{EditorAction, RuleEditor};
+use crate::firewall::lint::{check_rules, FindingKind, RuleFinding};
+use crate::firewall::persistence::RulesetBackup;
+use crate::firewall::simulation::{simulate_flows, SimulationReport};
+use crate::traffic::inspector::TrafficFlow;
+use crate::traffic::HostnameCache;
+use crate::traffic::hostnames::{format_endpoint, format_host};
+use crate::utils::formatting::{format_bytes, format_duration};
 
 pub struct FirewallView {
     selected_rule: usize,
     selected_event: usize,
-    show_rule_editor: bool,
+    rule_editor: Option<RuleEditor>,
     show_stats: bool,
+    show_preview: bool,
+    // Ruleset backups panel; `None` when closed
+    backups: Option<Vec<RulesetBackup>>,
+    selected_backup: usize,
+    status_message: Option<String>,
+    // What-if result for the rule it was run on; `None` when closed
+    simulation: Option<(String, SimulationReport)>,
+    // Lint findings and the engine's rules version they were computed for
+    findings: Vec<RuleFinding>,
+    findings_version: Option<u64>,
     _scroll_offset: usize,
 }
 
@@ -20,13 +39,34 @@ impl FirewallView {
         Self {
             selected_rule: 0,
             selected_event: 0,
-            show_rule_editor: false,
+            rule_editor: None,
             show_stats: true,
+            show_preview: false,
+            backups: None,
+            selected_backup: 0,
+            status_message: None,
+            simulation: None,
+            findings: Vec::new(),
+            findings_version: None,
             _scroll_offset: 0,
         }
     }
     
     pub fn handle_key(&mut self, key: crossterm::event::KeyCode, engine: &mut FirewallEngine) {
+        if let Some(ref mut editor) = self.rule_editor {
+            match editor.handle_key(key) {
+                EditorAction::Save => self.save_editor(engine),
+                EditorAction::Cancel => self.close_editor(),
+                EditorAction::Continue => {}
+            }
+            return;
+        }
+        
+        if self.backups.is_some() {
+            self.handle_backups_key(key, engine);
+            return;
+        }
+        
         match key {
             crossterm::event::KeyCode::Up => {
                 if self.selected_rule > 0 {
@@ -73,7 +113,14 @@ impl FirewallView {
                 self.show_stats = !self.show_stats;
             }
             crossterm::event::KeyCode::Char('e') => {
-                self.show_rule_editor = !self.show_rule_editor;
+                // Edit the selected rule, or start a new one if there are none
+                self.open_editor(match engine.get_rules().get(self.selected_rule) {
+                    Some(rule) => RuleEditor::edit_rule(rule),
+                    None => RuleEditor::new_rule(),
+                });
+            }
+            crossterm::event::KeyCode::Char('n') => {
+                self.open_editor(RuleEditor::new_rule());
             }
             crossterm::event::KeyCode::Char('c') => {
                 engine.clear_events();
@@ -87,11 +134,173 @@ impl FirewallView {
             crossterm::event::KeyCode::Char('t') => {
                 engine.set_enabled(!engine.is_enabled());
             }
+            crossterm::event::KeyCode::Char('p') => {
+                self.show_preview = !self.show_preview;
+            }
+            crossterm::event::KeyCode::Char('b') => {
+                self.open_backups(engine);
+            }
+            crossterm::event::KeyCode::Esc => {
+                self.simulation = None;
+            }
+            _ => {}
+        }
+    }
+    
+    fn open_backups(&mut self, engine: &FirewallEngine) {
+        match engine.get_ruleset_store() {
+            Some(store) => {
+                self.backups = Some(store.list_backups());
+                self.selected_backup = 0;
+                self.status_message = None;
+            }
+            None => self.status_message = Some("Rules are not being saved (autosave is off)".to_string()),
+        }
+    }
+    
+    fn handle_backups_key(&mut self, key: crossterm::event::KeyCode, engine: &mut FirewallEngine) {
+        let backup_count = self.backups.as_ref().map_or(0, |backups| backups.len());
+        match key {
+            crossterm::event::KeyCode::Up => {
+                self.selected_backup = self.selected_backup.saturating_sub(1);
+            }
+            crossterm::event::KeyCode::Down if self.selected_backup + 1 < backup_count => {
+                self.selected_backup += 1;
+            }
+            crossterm::event::KeyCode::Enter => {
+                self.restore_backup(engine);
+            }
+            crossterm::event::KeyCode::Esc | crossterm::event::KeyCode::Char('b') => {
+                self.backups = None;
+            }
             _ => {}
         }
     }
     
-    pub fn render(&mut self, f: &mut Frame, area: Rect, engine: &FirewallEngine) {
+    fn restore_backup(&mut self, engine: &mut FirewallEngine) {
+        let version = match self.backups.as_ref().and_then(|backups| backups.get(self.selected_backup)) {
+            Some(backup) => backup.version,
+            None => return,
+        };
+        let result = match engine.get_ruleset_store() {
+            Some(store) => store.load_backup(version),
+            None => return,
+        };
+        
+        // Restoring is itself a change, so the autosave backs up what it replaced
+        self.status_message = Some(match result {
+            Ok(rules) => {
+                let rule_count = rules.len();
+                engine.replace_rules(rules);
+                self.selected_rule = 0;
+                self.backups = None;
+                format!("Restored {} rules from backup ~{}~", rule_count, version)
+            }
+            Err(e) => e.to_string(),
+        });
+    }
+    
+    /// Show how recorded flows would fare with the selected rule toggled,
+    /// without touching the engine
+    pub fn simulate_selected_rule<'a, I: IntoIterator<Item = &'a TrafficFlow>>(&mut self, engine: &FirewallEngine, flows: I) {
+        let rule = match engine.get_rules().get(self.selected_rule) {
+            Some(rule) => rule,
+            None => return,
+        };
+        let title = format!("What if '{}' were {}", rule.name, if rule.enabled { "disabled" } else { "enabled" });
+        
+        let current_rules = engine.get_rules().to_vec();
+        let mut candidate_rules = current_rules.clone();
+        candidate_rules[self.selected_rule].enabled = !rule.enabled;
+        
+        let report = simulate_flows(flows, candidate_rules, current_rules).report(10);
+        self.simulation = Some((title, report));
+    }
+    
+    /// Show how recorded flows would fare if the rule in the editor were
+    /// saved, keeping the editor open
+    pub fn simulate_draft<'a, I: IntoIterator<Item = &'a TrafficFlow>>(&mut self, engine: &FirewallEngine, flows: I) {
+        let editor = match self.rule_editor {
+            Some(ref editor) => editor,
+            None => return,
+        };
+        let mut draft = match editor.build() {
+            Ok(rule) => rule,
+            Err(_) => {
+                self.status_message = Some("Fix the highlighted fields to run the what-if".to_string());
+                return;
+            }
+        };
+        let title = format!("What if '{}' were saved", draft.name);
+        
+        let current_rules = engine.get_rules().to_vec();
+        let mut candidate_rules = current_rules.clone();
+        match editor.editing_rule_id().and_then(|rule_id| candidate_rules.iter().position(|rule| rule.id == rule_id)) {
+            Some(pos) => candidate_rules[pos] = draft,
+            None => {
+                draft.id = current_rules.iter().map(|rule| rule.id).max().unwrap_or(0) + 1;
+                candidate_rules.push(draft);
+            }
+        }
+        
+        let report = simulate_flows(flows, candidate_rules, current_rules).report(10);
+        self.simulation = Some((title, report));
+        self.status_message = None;
+    }
+    
+    /// A what-if shown next to the editor is about its draft, so it goes
+    /// with the editor
+    fn open_editor(&mut self, editor: RuleEditor) {
+        self.rule_editor = Some(editor);
+        self.simulation = None;
+    }
+    
+    fn close_editor(&mut self) {
+        self.rule_editor = None;
+        self.simulation = None;
+    }
+    
+    fn save_editor(&mut self, engine: &mut FirewallEngine) {
+        let editor = match self.rule_editor.take() {
+            Some(editor) => editor,
+            None => return,
+        };
+        self.simulation = None;
+        // The editor only reports Save for a valid form
+        let rule = match editor.build() {
+            Ok(rule) => rule,
+            Err(_) => return,
+        };
+        
+        let rule_id = match editor.editing_rule_id() {
+            Some(rule_id) => {
+                engine.update_rule(rule_id, rule);
+                rule_id
+            }
+            None => engine.add_rule(rule),
+        };
+        
+        // Keep the saved rule selected at its (possibly new) position
+        if let Some(pos) = engine.get_rules().iter().position(|r| r.id == rule_id) {
+            self.selected_rule = pos;
+        }
+    }
+    
+    /// Linting compares every pair of rules, so it's only redone after a change
+    fn refresh_findings(&mut self, engine: &FirewallEngine) {
+        if self.findings_version != Some(engine.get_rules_version()) {
+            self.findings = check_rules(engine.get_rules());
+            self.findings_version = Some(engine.get_rules_version());
+        }
+    }
+    
+    /// While the editor is open it takes all keys, including the global ones
+    pub fn is_editing(&self) -> bool {
+        self.rule_editor.is_some()
+    }
+    
+    /// Event addresses are shown by name where `names` has one
+    pub fn render(&mut self, f: &mut Frame, area: Rect, engine: &FirewallEngine, names: Option<&HostnameCache>) {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
@@ -101,9 +310,28 @@ impl FirewallView {
             ])
             .split(area);
         
+        self.refresh_findings(engine);
+        
         // Header
         self.render_header(f, chunks[0], engine);
         
+        if let Some(ref editor) = self.rule_editor {
+            // A what-if of the draft shows next to the form
+            match self.simulation {
+                Some((ref title, ref report)) => {
+                    let editor_chunks = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([Constraint::Percentage(60), Constraint::Percentage(40)])
+                        .split(chunks[1]);
+                    editor.render(f, editor_chunks[0], engine.get_recent_events());
+                    self.render_simulation_panel(f, editor_chunks[1], title, report);
+                }
+                None => editor.render(f, chunks[1], engine.get_recent_events()),
+            }
+            self.render_footer(f, chunks[2]);
+            return;
+        }
+        
         // Main content
         let main_chunks = Layout::default()
             .direction(Direction::Horizontal)
@@ -115,10 +343,16 @@ impl FirewallView {
         
         self.render_rules_panel(f, main_chunks[0], engine);
         
-        if self.show_stats {
+        if let Some(ref backups) = self.backups {
+            self.render_backups_panel(f, main_chunks[1], backups);
+        } else if let Some((ref title, ref report)) = self.simulation {
+            self.render_simulation_panel(f, main_chunks[1], title, report);
+        } else if self.show_preview {
+            self.render_preview_panel(f, main_chunks[1], engine);
+        } else if self.show_stats {
             self.render_stats_panel(f, main_chunks[1], engine);
         } else {
-            self.render_events_panel(f, main_chunks[1], engine);
+            self.render_events_panel(f, main_chunks[1], engine, names);
         }
         
         // Footer
@@ -129,16 +363,34 @@ impl FirewallView {
         let status = if engine.is_enabled() { "ACTIVE" } else { "DISABLED" };
         let status_color = if engine.is_enabled() { Color::Green } else { Color::Red };
         
+        let mode = match engine.get_enforcement_error() {
+            Some(_) => "ENFORCEMENT FAILED",
+            None if engine.is_enforcing() => "ENFORCING (nftables)",
+            None => "SIMULATION",
+        };
+        let status_color = if engine.get_enforcement_error().is_some() { Color::Red } else { status_color };
+        
         let stats = engine.get_stats();
-        let header_text = format!(
-            "Firewall: {} | Rules: {}/{} | Processed: {} | Blocked: {} ({:.1}%)",
+        let mut header_text = format!(
+            "Firewall: {} | Mode: {} | Rules: {}/{} | Processed: {} | Blocked: {} ({:.1}%)",
             status,
+            mode,
             stats.enabled_rules,
             stats.active_rules,
             stats.total_packets_processed,
             stats.packets_blocked,
             stats.get_block_rate()
         );
+        // A failed save matters more than the last status message
+        let status_color = if let Some(error) = engine.get_save_error() {
+            header_text = format!("{} | {}", header_text, error);
+            Color::Red
+        } else {
+            if let Some(ref message) = self.status_message {
+                header_text = format!("{} | {}", header_text, message);
+            }
+            status_color
+        };
         
         let header = Paragraph::new(header_text)
             .block(Block::default().borders(Borders::ALL).title("Firewall Status"))
@@ -150,6 +402,24 @@ impl FirewallView {
     
     fn render_rules_panel(&self, f: &mut Frame, area: Rect, engine: &FirewallEngine) {
         let rules = engine.get_rules();
+        let stats = engine.get_stats();
+        let findings = &self.findings;
+        let now = std::time::SystemTime::now();
+        
+        // Lint warnings get a strip under the rules while there are any
+        let area = if findings.is_empty() {
+            area
+        } else {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([
+                    Constraint::Min(5),
+                    Constraint::Length(findings.len().min(6) as u16 + 2),
+                ])
+                .split(area);
+            self.render_findings(f, chunks[1], findings);
+            chunks[0]
+        };
         
         let items: Vec<ListItem> = rules
             .iter()
@@ -161,6 +431,7 @@ impl FirewallView {
                     RuleAction::Block => Color::Red,
                     RuleAction::Log => Color::Yellow,
                     RuleAction::LogAndBlock => Color::Magenta,
+                    RuleAction::RateLimit(_) => Color::LightBlue,
                 };
                 
                 let direction_symbol = match rule.direction {
@@ -176,10 +447,10 @@ impl FirewallView {
                     RuleProtocol::Any => "ANY",
                 };
                 
-                let line = Line::from(vec![
+                let mut line = Line::from(vec![
                     Span::styled(status, Style::default().fg(if rule.enabled { Color::Green } else { Color::Red })),
                     Span::raw(" "),
-                    Span::styled(format!("{:?}", rule.action), Style::default().fg(action_color)),
+                    Span::styled(rule.action.to_string(), Style::default().fg(action_color)),
                     Span::raw(" "),
                     Span::raw(direction_symbol),
                     Span::raw(" "),
@@ -187,8 +458,28 @@ impl FirewallView {
                     Span::raw(" "),
                     Span::styled(&rule.name, Style::default().fg(Color::White)),
                     Span::raw(format!(" ({})", rule.match_count)),
+                    Span::styled(
+                        if findings.iter().any(|finding| finding.rule_id == rule.id) { " ⚠" } else { "" },
+                        Style::default().fg(Color::Yellow),
+                    ),
                 ]);
                 
+                // Temporary and scheduled rules show when they apply
+                if let Some(remaining) = rule.get_time_until_expiry() {
+                    line.spans.push(Span::styled(
+                        format!(" ⏱ {} left", format_duration(remaining.as_secs())),
+                        Style::default().fg(Color::LightRed),
+                    ));
+                }
+                if let Some(over_limit) = stats.rate_limited_by_rule.get(&rule.id) {
+                    line.spans.push(Span::styled(format!(" {} over limit", over_limit), Style::default().fg(Color::Red)));
+                }
+                if !rule.schedules.is_empty() {
+                    let schedules: Vec<String> = rule.schedules.iter().map(|schedule| schedule.to_string()).collect();
+                    let color = if rule.is_in_effect(now) { Color::LightBlue } else { Color::DarkGray };
+                    line.spans.push(Span::styled(format!(" ⏲ {}", schedules.join("; ")), Style::default().fg(color)));
+                }
+                
                 let mut item = ListItem::new(line);
                 if i == self.selected_rule {
                     item = item.style(Style::default().bg(Color::DarkGray));
@@ -204,7 +495,28 @@ impl FirewallView {
         f.render_widget(rules_list, area);
     }
     
-    fn render_events_panel(&self, f: &mut Frame, area: Rect, engine: &FirewallEngine) {
+    fn render_findings(&self, f: &mut Frame, area: Rect, findings: &[RuleFinding]) {
+        let items: Vec<ListItem> = findings
+            .iter()
+            .map(|finding| {
+                let color = match finding.kind {
+                    FindingKind::Shadowed | FindingKind::Conflict => Color::Yellow,
+                    FindingKind::Redundant => Color::Gray,
+                };
+                ListItem::new(Line::from(vec![
+                    Span::styled(format!("⚠ {}: ", finding.kind), Style::default().fg(color)),
+                    Span::raw(finding.message.as_str()),
+                ]))
+            })
+            .collect();
+        
+        let findings_list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title(format!("Rule Warnings ({})", findings.len())));
+        
+        f.render_widget(findings_list, area);
+    }
+    
+    fn render_events_panel(&self, f: &mut Frame, area: Rect, engine: &FirewallEngine, names: Option<&HostnameCache>) {
         let events = engine.get_recent_events();
         
         let items: Vec<ListItem> = events
@@ -218,6 +530,7 @@ impl FirewallView {
                     RuleAction::Block => Color::Red,
                     RuleAction::Log => Color::Yellow,
                     RuleAction::LogAndBlock => Color::Magenta,
+                    RuleAction::RateLimit(_) => Color::LightBlue,
                 };
                 
                 let age = event.get_age();
@@ -229,15 +542,37 @@ impl FirewallView {
                     format!("{}h", age.as_secs() / 3600)
                 };
                 
-                let line = Line::from(vec![
-                    Span::styled(format!("{:?}", event.action), Style::default().fg(action_color)),
-                    Span::raw(" "),
-                    Span::styled(format!("{}:{}", event.src_ip, event.src_port), Style::default().fg(Color::Cyan)),
-                    Span::raw(" → "),
-                    Span::styled(format!("{}:{}", event.dst_ip, event.dst_port), Style::default().fg(Color::Yellow)),
-                    Span::raw(" "),
-                    Span::styled(age_str, Style::default().fg(Color::Gray)),
-                ]);
+                let line = if event.kind == FirewallEventKind::RuleExpired {
+                    Line::from(vec![
+                        Span::styled("Expired", Style::default().fg(Color::Gray)),
+                        Span::raw(format!(" rule '{}' removed ", event.rule_name)),
+                        Span::styled(age_str, Style::default().fg(Color::Gray)),
+                    ])
+                } else if event.kind == FirewallEventKind::RateLimited {
+                    Line::from(vec![
+                        Span::styled("Over limit", Style::default().fg(Color::Red)),
+                        Span::raw(" "),
+                        Span::styled(format_host(event.src_ip, names), Style::default().fg(Color::Cyan)),
+                        Span::raw(format!(
+                            " → {} dropped by '{}' ",
+                            format_endpoint((event.dst_ip, event.dst_port).into(), names),
+                            event.rule_name
+                        )),
+                        Span::styled(age_str, Style::default().fg(Color::Gray)),
+                    ])
+                } else {
+                    Line::from(vec![
+                        Span::styled(event.action.to_string(), Style::default().fg(action_color)),
+                        Span::raw(" "),
+                        Span::styled(format_endpoint((event.src_ip, event.src_port).into(), names), Style::default().fg(Color::Cyan)),
+                        Span::raw(" → "),
+                        Span::styled(format_endpoint((event.dst_ip, event.dst_port).into(), names), Style::default().fg(Color::Yellow)),
+                        Span::raw(" "),
+                        Span::styled(format!("[{}]", event.state), Style::default().fg(Color::Magenta)),
+                        Span::raw(" "),
+                        Span::styled(age_str, Style::default().fg(Color::Gray)),
+                    ])
+                };
                 
                 let mut item = ListItem::new(line);
                 if i == self.selected_event {
@@ -260,21 +595,25 @@ impl FirewallView {
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([
-                Constraint::Length(8),  // Stats text
+                Constraint::Length(11), // Stats text
                 Constraint::Min(4),     // Gauges
             ])
             .split(area);
         
         // Stats text
+        let conntrack = engine.get_conntrack();
         let stats_text = format!(
-            "Total Processed: {}\nAllowed: {}\nBlocked: {}\nLogged: {}\nRules Matched: {}\nActive Rules: {}\nEnabled Rules: {}",
+            "Total Processed: {}\nAllowed: {}\nBlocked: {}\nLogged: {}\nRate Limited: {}\nRules Matched: {}\nActive Rules: {}\nEnabled Rules: {}\nTracked Connections: {} ({} established)",
             stats.total_packets_processed,
             stats.packets_allowed,
             stats.packets_blocked,
             stats.packets_logged,
+            stats.packets_rate_limited,
             stats.rules_matched,
             stats.active_rules,
-            stats.enabled_rules
+            stats.enabled_rules,
+            conntrack.len(),
+            conntrack.established_count()
         );
         
         let stats_paragraph = Paragraph::new(stats_text)
@@ -311,11 +650,140 @@ impl FirewallView {
         f.render_widget(allow_gauge, gauge_chunks[1]);
     }
     
+    fn render_preview_panel(&self, f: &mut Frame, area: Rect, engine: &FirewallEngine) {
+        let mut text = String::new();
+        if let Some(error) = engine.get_enforcement_error() {
+            text.push_str(&format!("# Last apply failed: {}\n", error));
+        }
+        text.push_str(&engine.preview_ruleset());
+        
+        let title = if engine.is_enforcing() { "nftables Ruleset (applied)" } else { "nftables Ruleset (dry run)" };
+        let preview = Paragraph::new(text)
+            .block(Block::default().borders(Borders::ALL).title(title))
+            .style(Style::default().fg(Color::White));
+        
+        f.render_widget(preview, area);
+    }
+    
+    fn render_backups_panel(&self, f: &mut Frame, area: Rect, backups: &[RulesetBackup]) {
+        let items: Vec<ListItem> = if backups.is_empty() {
+            vec![ListItem::new("No backups yet; one is made each time the rules change")]
+        } else {
+            backups
+                .iter()
+                .enumerate()
+                .map(|(i, backup)| {
+                    let age = backup.modified
+                        .and_then(|modified| modified.elapsed().ok())
+                        .map(|age| format!("{} ago", format_duration(age.as_secs())))
+                        .unwrap_or_else(|| "unknown age".to_string());
+                    let rules = match backup.rule_count {
+                        Some(count) => Span::raw(format!("{} rules", count)),
+                        None => Span::styled("malformed", Style::default().fg(Color::Red)),
+                    };
+                    
+                    let line = Line::from(vec![
+                        Span::styled(format!("~{}~", backup.version), Style::default().fg(Color::Cyan)),
+                        Span::raw(" "),
+                        Span::styled(age, Style::default().fg(Color::Gray)),
+                        Span::raw(" "),
+                        rules,
+                    ]);
+                    
+                    let mut item = ListItem::new(line);
+                    if i == self.selected_backup {
+                        item = item.style(Style::default().bg(Color::DarkGray));
+                    }
+                    item
+                })
+                .collect()
+        };
+        
+        let backups_list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Ruleset Backups (newest first)"));
+        
+        f.render_widget(backups_list, area);
+    }
+    
+    fn render_simulation_panel(&self, f: &mut Frame, area: Rect, title: &str, report: &SimulationReport) {
+        let heading = Style::default().fg(Color::Yellow);
+        let mut lines = vec![
+            Line::from(format!(
+                "{} recorded packets ({}) evaluated",
+                report.evaluated.packets,
+                format_bytes(report.evaluated.bytes)
+            )),
+            Line::from(vec![
+                Span::styled(format!("{} packets newly blocked", report.newly_blocked.packets), Style::default().fg(Color::Red)),
+                Span::raw(", "),
+                Span::styled(format!("{} newly allowed", report.newly_allowed.packets), Style::default().fg(Color::Green)),
+            ]),
+        ];
+        if report.rate_limits_unmeasured() {
+            lines.push(Line::from(Span::styled(
+                "Rate limits not applied: each flow is judged by its first packet",
+                Style::default().fg(Color::DarkGray),
+            )));
+        }
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Flows that would change verdict", heading)));
+        
+        if report.changed_flows.is_empty() {
+            lines.push(Line::from("  none"));
+        }
+        for change in &report.changed_flows {
+            let color = match change.after {
+                RuleAction::Block | RuleAction::LogAndBlock => Color::Red,
+                _ => Color::Green,
+            };
+            lines.push(Line::from(vec![
+                Span::styled(format!("  {} → {} ", change.before, change.after), Style::default().fg(color)),
+                Span::raw(format!("{} {} → {} ({} pkts)", change.protocol, change.src, change.dst, change.hits.packets)),
+            ]));
+        }
+        
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Top affected peers", heading)));
+        if report.top_peers.is_empty() {
+            lines.push(Line::from("  none"));
+        }
+        for peer in &report.top_peers {
+            lines.push(Line::from(format!(
+                "  {} -{} +{} pkts ({} flows)",
+                peer.peer,
+                peer.newly_blocked.packets,
+                peer.newly_allowed.packets,
+                peer.flows
+            )));
+        }
+        
+        lines.push(Line::from(""));
+        lines.push(Line::from(Span::styled("Would-match counts", heading)));
+        for rule in &report.rules {
+            let style = if rule.enabled { Style::default() } else { Style::default().fg(Color::DarkGray) };
+            lines.push(Line::from(Span::styled(
+                format!("  {:>6} {}", rule.hits.packets, rule.rule_name),
+                style,
+            )));
+        }
+        
+        let panel = Paragraph::new(lines)
+            .block(Block::default().borders(Borders::ALL).title(title.to_string()));
+        
+        f.render_widget(panel, area);
+    }
+    
     fn render_footer(&self, f: &mut Frame, area: Rect) {
-        let footer_text = if self.show_stats {
-            "Keys: ↑↓ Rules | Enter Toggle | Del Remove | 's' Events | 'e' Editor | 'd' Defaults | 't' Toggle Firewall | 'c' Clear | 'r' Reset"
+        let footer_text = if self.rule_editor.is_some() {
+            "Editor: ↑↓/Tab Field | ←→/Space Change option | Type to edit | Backspace Delete | F5 What-if | Enter Save | Esc Cancel"
+        } else if self.backups.is_some() {
+            "Backups: ↑↓ Select | Enter Restore | Esc/'b' Close"
+        } else if self.simulation.is_some() {
+            "What-if: ↑↓ Rules | 'w' Simulate selected rule | Esc Close"
+        } else if self.show_stats {
+            "Keys: ↑↓ Rules | Enter Toggle | Del Remove | 's' Events | 'e' Edit | 'n' New | 'd' Defaults | 't' Toggle Firewall | 'p' nft Preview | 'b' Backups | 'w' What-if | 'c' Clear | 'r' Reset"
         } else {
-            "Keys: ↑↓ Rules | ←→ Events | Enter Toggle | Del Remove | 's' Stats | 'e' Editor | 'd' Defaults | 't' Toggle Firewall | 'c' Clear"
+            "Keys: ↑↓ Rules | ←→ Events | Enter Toggle | Del Remove | 's' Stats | 'e' Edit | 'n' New | 'd' Defaults | 't' Toggle Firewall | 'p' nft Preview | 'b' Backups | 'w' What-if | 'c' Clear"
         };
         
         let footer = Paragraph::new(footer_text)
@@ -342,8 +810,12 @@ impl FirewallView {
         self.show_stats = !self.show_stats;
     }
     
+    /// Open the editor on a blank rule, or close it discarding changes
     pub fn toggle_rule_editor(&mut self) {
-        self.show_rule_editor = !self.show_rule_editor;
+        match self.rule_editor {
+            Some(_) => self.close_editor(),
+            None => self.open_editor(RuleEditor::new_rule()),
+        }
     }
 }
 
@@ -356,13 +828,13 @@ impl Default for FirewallView {
 #[cfg(test)]
 mod tests {
     use super::*;
-
+    
     #[test]
     fn test_firewall_view_creation() {
         let view = FirewallView::new();
         assert_eq!(view.selected_rule, 0);
         assert_eq!(view.selected_event, 0);
-        assert!(!view.show_rule_editor);
+        assert!(!view.is_editing());
         assert!(view.show_stats);
     }
     
@@ -383,4 +855,151 @@ mod tests {
         view.handle_key(crossterm::event::KeyCode::Char('s'), &mut engine);
         assert!(!view.show_stats);
     }
+    
+    #[test]
+    fn test_rule_editor_save_and_cancel() {
+        use crossterm::event::KeyCode;
+        
+        let mut view = FirewallView::new();
+        let mut engine = FirewallEngine::new();
+        engine.load_default_rules();
+        let rule_count = engine.get_rules().len();
+        
+        // Keys like 's' are typed into the form instead of toggling views
+        view.handle_key(KeyCode::Char('n'), &mut engine);
+        assert!(view.is_editing());
+        for c in "ssh-bastion".chars() {
+            view.handle_key(KeyCode::Char(c), &mut engine);
+        }
+        assert!(view.show_stats);
+        view.handle_key(KeyCode::Enter, &mut engine);
+        
+        assert!(!view.is_editing());
+        assert_eq!(engine.get_rules().len(), rule_count + 1);
+        assert_eq!(engine.get_rules()[view.get_selected_rule()].name, "ssh-bastion");
+        
+        // Editing keeps the id; Esc discards
+        let rule_id = engine.get_rules()[view.get_selected_rule()].id;
+        view.handle_key(KeyCode::Char('e'), &mut engine);
+        view.handle_key(KeyCode::Char('2'), &mut engine);
+        view.handle_key(KeyCode::Esc, &mut engine);
+        assert_eq!(engine.get_rule(rule_id).unwrap().name, "ssh-bastion");
+        
+        view.handle_key(KeyCode::Char('e'), &mut engine);
+        view.handle_key(KeyCode::Char('2'), &mut engine);
+        view.handle_key(KeyCode::Enter, &mut engine);
+        assert_eq!(engine.get_rule(rule_id).unwrap().name, "ssh-bastion2");
+        assert_eq!(engine.get_rules().len(), rule_count + 1);
+    }
+    
+    #[test]
+    fn test_restore_backup() {
+        use crossterm::event::KeyCode;
+        use crate::firewall::persistence::RulesetStore;
+        
+        let directory = std::env::temp_dir().join(format!("nm-firewall-view-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&directory);
+        let mut view = FirewallView::new();
+        let mut engine = FirewallEngine::new();
+        engine.load_default_rules();
+        let rule_count = engine.get_rules().len();
+        
+        // Without a store there is nothing to show
+        view.handle_key(KeyCode::Char('b'), &mut engine);
+        assert!(view.backups.is_none());
+        
+        engine.set_ruleset_store(RulesetStore::new(directory.join("rules.json"), 5));
+        view.handle_key(KeyCode::Delete, &mut engine);
+        engine.autosave();
+        view.handle_key(KeyCode::Delete, &mut engine);
+        engine.autosave();
+        assert_eq!(engine.get_rules().len(), rule_count - 2);
+        
+        // Newest backup first: the ruleset before the second delete
+        view.handle_key(KeyCode::Char('b'), &mut engine);
+        assert_eq!(view.backups.as_ref().unwrap().len(), 1);
+        view.handle_key(KeyCode::Enter, &mut engine);
+        assert!(view.backups.is_none());
+        assert_eq!(engine.get_rules().len(), rule_count - 1);
+        
+        std::fs::remove_dir_all(directory).unwrap();
+    }
+    
+    #[test]
+    fn test_simulate_selected_rule() {
+        let mut view = FirewallView::new();
+        let mut engine = FirewallEngine::new();
+        engine.load_default_rules();
+        let rule = engine.get_rules()[0].clone();
+        
+        view.simulate_selected_rule(&engine, &[]);
+        let (title, report) = view.simulation.as_ref().unwrap();
+        assert_eq!(title, &format!("What if '{}' were disabled", rule.name));
+        assert!(!report.rules[0].enabled);
+        
+        // The live engine is untouched
+        assert!(engine.get_rules()[0].enabled);
+        assert_eq!(engine.get_stats().total_packets_processed, 0);
+        
+        view.handle_key(crossterm::event::KeyCode::Esc, &mut engine);
+        assert!(view.simulation.is_none());
+    }
+    
+    #[test]
+    fn test_findings_follow_rule_changes() {
+        let mut view = FirewallView::new();
+        let mut engine = FirewallEngine::new();
+        engine.load_default_rules();
+        view.refresh_findings(&engine);
+        let findings = view.findings.len();
+        
+        // A duplicate is redundant, but only shows up once the rules change
+        let duplicate = engine.get_rules()[0].clone();
+        engine.add_rule(duplicate);
+        view.refresh_findings(&engine);
+        assert_eq!(view.findings_version, Some(engine.get_rules_version()));
+        assert!(view.findings.len() > findings);
+    }
+    
+    #[test]
+    fn test_simulate_draft() {
+        use crossterm::event::KeyCode;
+        
+        let mut view = FirewallView::new();
+        let mut engine = FirewallEngine::new();
+        engine.load_default_rules();
+        let rule_count = engine.get_rules().len();
+        
+        // An invalid form has nothing to simulate
+        view.handle_key(KeyCode::Char('n'), &mut engine);
+        view.simulate_draft(&engine, &[]);
+        assert!(view.simulation.is_none());
+        
+        for c in "block-inbound".chars() {
+            view.handle_key(KeyCode::Char(c), &mut engine);
+        }
+        view.simulate_draft(&engine, &[]);
+        let (title, report) = view.simulation.as_ref().unwrap();
+        assert_eq!(title, "What if 'block-inbound' were saved");
+        assert_eq!(report.rules.len(), rule_count + 1);
+        assert!(report.rules.iter().any(|rule| rule.rule_name == "block-inbound"));
+        
+        // The draft stays in the editor and out of the engine
+        assert!(view.is_editing());
+        assert_eq!(engine.get_rules().len(), rule_count);
+        
+        // The report goes away with the draft it describes
+        view.handle_key(KeyCode::Esc, &mut engine);
+        assert!(!view.is_editing());
+        assert!(view.simulation.is_none());
+        
+        // An earlier what-if isn't shown as if it were the draft's
+        view.simulate_selected_rule(&engine, &[]);
+        view.handle_key(KeyCode::Char('e'), &mut engine);
+        assert!(view.simulation.is_none());
+        view.simulate_draft(&engine, &[]);
+        view.handle_key(KeyCode::Enter, &mut engine);
+        assert!(!view.is_editing());
+        assert!(view.simulation.is_none());
+    }
 }
@@ -1,5 +1,7 @@
 use clap::Parser;
-use network_monitor::{app::App, settings::Config, config::AdvancedFeatures};
+use network_monitor::{app::App, settings::Config, config::{AdvancedFeatures, FirewallConfig}, capture::ReplaySpeed};
+use network_monitor::firewall::{check_rules, FirewallEngine, FirewallRule, NftablesBackend, RulesetError, RulesetFormat, RulesetStore, translate, simulation::simulate_pcap, nftables::{DEFAULT_STATE_FILE, DEFAULT_TABLE}};
+use network_monitor::utils::shutdown;
 use std::process;
 
 #[derive(Parser)]
@@ -15,6 +17,18 @@ struct Cli {
     #[arg(short, long, help = "Enable debug logging")]
     debug: bool,
     
+    #[arg(short, long, value_name = "FILE", help = "Replay a saved pcap/pcapng file instead of capturing live")]
+    read: Option<String>,
+    
+    #[arg(short, long, value_name = "EXPR", help = "BPF capture filter, e.g. \"tcp port 443\" (overrides config)")]
+    filter: Option<String>,
+    
+    #[arg(long, value_name = "ADDR", help = "Look up hostnames of unnamed addresses with PTR queries to this DNS server (overrides config)")]
+    ptr_resolver: Option<String>,
+    
+    #[arg(long, value_name = "SPEED", default_value = "realtime", help = "Replay pacing: realtime, max, or a multiplier like 10x")]
+    replay_speed: ReplaySpeed,
+    
     // Advanced features (opt-in)
     #[arg(long, help = "Enable firewall functionality (advanced)")]
     enable_firewall: bool,
@@ -27,6 +41,24 @@ struct Cli {
     
     #[arg(long, help = "Enable all advanced features")]
     enable_all_advanced: bool,
+    
+    #[arg(long, help = "Enforce firewall rules in the kernel via nftables (implies --enable-firewall, needs root)")]
+    enforce_firewall: bool,
+    
+    #[arg(long, help = "Print the nftables ruleset the firewall would load, then exit")]
+    firewall_dry_run: bool,
+    
+    #[arg(long, value_name = "FILE", help = "Replace the firewall rules for this session with an iptables-save or `nft -j list ruleset` file, without saving them (implies --enable-firewall)")]
+    import_firewall: Option<String>,
+    
+    #[arg(long, value_name = "FORMAT", help = "Print the firewall rules as iptables, ip6tables or nft-json, then exit")]
+    export_firewall: Option<RulesetFormat>,
+    
+    #[arg(long, value_name = "RULES", requires = "read", help = "Report how a candidate ruleset (JSON, iptables-save or nft JSON) would treat the --read capture compared to the current rules, then exit")]
+    what_if: Option<String>,
+    
+    #[arg(long, help = "Report shadowed, redundant and conflicting firewall rules (of --import-firewall FILE if given), then exit")]
+    check_rules: bool,
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -36,8 +68,89 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         env_logger::init();
     }
     
-    // Validate Rocky Linux 9 system compatibility
-    if let Err(error_msg) = Config::validate_rocky_linux_9() {
+    // Show what --enforce-firewall would load without touching the kernel,
+    // or hand the rules to the host's own firewall tooling
+    if cli.firewall_dry_run || cli.export_firewall.is_some() || cli.what_if.is_some() || cli.check_rules {
+        // Falling back to defaults here would judge or export the wrong rules
+        let firewall_config = match cli.config {
+            Some(ref path) => match Config::load_from_file(path) {
+                Ok(config) => config.firewall,
+                Err(e) => {
+                    eprintln!("Error: Failed to load config file '{}': {}", path, e);
+                    process::exit(1);
+                }
+            },
+            None => Default::default(),
+        };
+        let mut engine = FirewallEngine::new();
+        if let Err(e) = load_firewall_rules(&mut engine, &firewall_config) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        
+        if cli.check_rules {
+            if let Some(ref path) = cli.import_firewall {
+                if let Err(e) = import_firewall_rules(&mut engine, path) {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            }
+            let findings = check_rules(engine.get_rules());
+            for finding in &findings {
+                println!("{}", finding);
+            }
+            if !findings.is_empty() {
+                eprintln!("{} problems in {} rules", findings.len(), engine.get_rules().len());
+                process::exit(1);
+            }
+            println!("No problems found in {} rules", engine.get_rules().len());
+            return Ok(());
+        }
+        
+        if let Some(format) = cli.export_firewall {
+            let translation = translate::export_rules(format, engine.get_rules());
+            print!("{}", translation.output);
+            for issue in &translation.issues {
+                eprintln!("Warning: {}", issue);
+            }
+            return Ok(());
+        }
+        
+        if let (Some(rules_path), Some(capture_file)) = (&cli.what_if, &cli.read) {
+            let candidate_rules = match load_candidate_rules(rules_path) {
+                Ok(rules) => rules,
+                Err(e) => {
+                    eprintln!("Error: {}", e);
+                    process::exit(1);
+                }
+            };
+            match simulate_pcap(capture_file, candidate_rules, engine.get_rules().to_vec()) {
+                Ok(simulation) => print!("{}", simulation.report(10)),
+                Err(e) => {
+                    eprintln!("Error: Cannot replay '{}': {}", capture_file, e);
+                    process::exit(1);
+                }
+            }
+            return Ok(());
+        }
+        
+        let backend = NftablesBackend::new();
+        print!("{}", backend.generate_ruleset(engine.get_rules()));
+        match backend.check(engine.get_rules()) {
+            Ok(_) => eprintln!("nft -c: ruleset is valid"),
+            Err(e) => eprintln!("nft -c: {}", e),
+        }
+        return Ok(());
+    }
+    
+    // Validate Rocky Linux 9 system compatibility. Replaying a capture file
+    // never touches the live system, so it is allowed anywhere.
+    let compatibility = if cli.read.is_some() {
+        Ok(())
+    } else {
+        Config::validate_rocky_linux_9()
+    };
+    if let Err(error_msg) = compatibility {
         eprintln!("\n❌ System Compatibility Error:");
         eprintln!("{}", error_msg);
         eprintln!("\n📋 System Requirements:");
@@ -78,7 +191,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     
     // Configure advanced features based on CLI arguments
     let advanced_features = AdvancedFeatures::from_cli_args(
-        cli.enable_firewall,
+        cli.enable_firewall || cli.enforce_firewall || cli.import_firewall.is_some(),
         cli.enable_metrics,
         cli.enable_search,
         cli.enable_all_advanced,
@@ -108,23 +221,123 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         }
     }
     
+    // A previous run that died while enforcing left its nftables table behind.
+    // The script of an instance that is still running stays locked.
+    match NftablesBackend::recover(DEFAULT_STATE_FILE) {
+        Ok(true) => println!("Rolled back nftables rules left by an unclean shutdown"),
+        Ok(false) => {},
+        Err(e) => eprintln!("Warning: Could not roll back stale nftables rules: {}", e),
+    }
+    
     // Initialize TUI application with advanced features
     let mut app = App::with_advanced_features(advanced_features);
+    app.set_socket_backend(config.capture.socket_backend);
     
-    // Determine which interface to use: CLI argument takes precedence over config file
-    let interface_to_use = cli.interface.or(config.capture.interface);
+    // A resolver given but unusable is a user error, like a bad capture filter
+    let mut hostname_config = config.hostnames.clone();
+    if cli.ptr_resolver.is_some() {
+        hostname_config.ptr_resolver = cli.ptr_resolver.clone();
+    }
+    if let Err(e) = app.configure_hostnames(&hostname_config) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+    if let Some(ref resolver) = hostname_config.ptr_resolver {
+        println!("PTR lookups via {} (at most {}/s)", resolver, hostname_config.ptr_lookups_per_second.max(1));
+    }
     
-    // Try to initialize packet capture (graceful fallback if it fails)
-    if let Err(e) = app.initialize_capture(interface_to_use) {
-        eprintln!("Warning: Packet capture initialization failed: {}", e);
-        eprintln!("Continuing with connection monitoring only...");
+    if let Some(ref capture_file) = cli.read {
+        // Offline replay: a file that cannot be opened is fatal, there is nothing to fall back to
+        if let Err(e) = app.initialize_replay(capture_file, cli.replay_speed) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        println!("Replaying {} at {} speed", capture_file, cli.replay_speed);
+    } else {
+        // Determine which interface to use: CLI argument takes precedence over config file
+        let interface_to_use = cli.interface.or(config.capture.interface);
+        
+        // Try to initialize packet capture (graceful fallback if it fails)
+        if let Err(e) = app.initialize_capture(interface_to_use) {
+            eprintln!("Warning: Packet capture initialization failed: {}", e);
+            eprintln!("Continuing with connection monitoring only...");
+        }
+    }
+    
+    // Compile the BPF filter into the kernel; a bad expression is a user error, so stop
+    if let Some(ref filter) = cli.filter.or(config.capture.bpf_filter.clone()) {
+        if app.capture_engine.is_some() {
+            if let Err(e) = app.set_capture_filter(filter) {
+                eprintln!("Error: {}", e);
+                process::exit(1);
+            }
+            println!("Capture filter: {}", filter);
+        } else {
+            eprintln!("Warning: Capture filter '{}' ignored, packet capture is not active", filter);
+        }
+    }
+    
+    // Restore the saved ruleset before anything is enforced. A malformed file
+    // is fatal: starting with other rules than the ones on disk, and then
+    // autosaving over them, would lose the user's work
+    if let Some(ref mut engine) = app.firewall_engine {
+        match load_firewall_rules(engine, &config.firewall) {
+            Ok(store) => {
+                println!("Loaded {} firewall rules (ruleset file: {})", engine.get_rules().len(), store.get_path().display());
+                // Imported rules are only for this session; autosaving them
+                // would overwrite the saved ruleset
+                if config.firewall.autosave && cli.import_firewall.is_none() {
+                    engine.set_ruleset_store(store);
+                }
+            }
+            Err(e) => {
+                eprintln!("Error: {}", e);
+                eprintln!("Fix or remove the file, or restore one of its numbered backups (.~N~)");
+                process::exit(1);
+            }
+        }
+    }
+    
+    if let (Some(ref path), Some(ref mut engine)) = (&cli.import_firewall, &mut app.firewall_engine) {
+        if let Err(e) = import_firewall_rules(engine, path) {
+            eprintln!("Error: {}", e);
+            process::exit(1);
+        }
+        println!("Imported rules are not saved; the ruleset file is left unchanged");
+    }
+    
+    // Being killed must not leave enforced rules in the kernel
+    if let Err(e) = shutdown::install_handlers() {
+        eprintln!("Warning: Cannot handle termination signals: {}", e);
+    }
+    
+    // Load the firewall rules into the kernel; asked-for enforcement that
+    // silently degrades to simulation would be worse than not starting
+    if cli.enforce_firewall {
+        if let Some(ref mut engine) = app.firewall_engine {
+            if let Err(e) = engine.enable_enforcement(NftablesBackend::new()) {
+                eprintln!("Error: Firewall enforcement failed: {}", e);
+                process::exit(1);
+            }
+            println!("Enforcing firewall rules via nftables table 'inet {}'", DEFAULT_TABLE);
+        }
+    }
+    
+    // Keep raw evidence on disk while monitoring, if configured
+    if config.capture.record.enabled {
+        if let Err(e) = app.start_recording(&config.capture.record) {
+            eprintln!("Warning: Packet recording unavailable: {}", e);
+        }
     }
     
     // Start the TUI
     println!("Starting Network Monitor TUI...");
     println!("Press 'q' to quit, Tab or 1-3 to switch between views");
     
-    if let Err(e) = app.run() {
+    let result = app.run();
+    // Dropping the app rolls back enforced firewall rules; process::exit would skip that
+    drop(app);
+    if let Err(e) = result {
         eprintln!("Application error: {}", e);
         process::exit(1);
     }
@@ -132,3 +345,51 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     println!("Network Monitor TUI stopped.");
     Ok(())
 }
+
+/// Replace the engine's rules with the saved ruleset, or start from the
+/// defaults (if configured) when nothing has been saved yet
+fn load_firewall_rules(engine: &mut FirewallEngine, config: &FirewallConfig) -> Result<RulesetStore, RulesetError> {
+    let store = RulesetStore::new(&config.ruleset_file, config.max_backups);
+    match store.load()? {
+        Some(rules) => engine.replace_rules(rules),
+        None => {
+            engine.replace_rules(Vec::new());
+            if config.load_default_rules {
+                engine.load_default_rules();
+            }
+        }
+    }
+    Ok(store)
+}
+
+/// Rules to try with --what-if: a saved JSON ruleset, or anything --import-firewall accepts
+fn load_candidate_rules(path: &str) -> Result<Vec<FirewallRule>, Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Cannot read '{}': {}", path, e))?;
+    if content.trim_start().starts_with('[') {
+        return Ok(RulesetStore::new(path, 0).load()?.unwrap_or_default());
+    }
+    
+    let translation = translate::import_rules(&content)?;
+    for issue in &translation.issues {
+        eprintln!("Warning: {}: {}", path, issue);
+    }
+    Ok(translation.output)
+}
+
+/// Replace the engine's rules with an existing iptables or nftables policy,
+/// reporting what could not be imported
+fn import_firewall_rules(engine: &mut FirewallEngine, path: &str) -> Result<(), Box<dyn std::error::Error>> {
+    let content = std::fs::read_to_string(path).map_err(|e| format!("Cannot read '{}': {}", path, e))?;
+    let translation = translate::import_rules(&content)?;
+    for issue in &translation.issues {
+        eprintln!("Warning: {}: {}", path, issue);
+    }
+    println!(
+        "Imported {} firewall rules from {} ({} skipped)",
+        translation.output.len(),
+        path,
+        translation.skipped_count()
+    );
+    engine.replace_rules(translation.output);
+    Ok(())
+}
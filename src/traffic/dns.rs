@@ -0,0 +1,586 @@
+use std::collections::{HashMap, VecDeque};
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+use std::time::{Duration, SystemTime};
+use crate::capture::PacketInfo;
+
+/// A query with no response after this long counts as unanswered
+const QUERY_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_PENDING_QUERIES: usize = 4096;
+/// Distinct names tracked; the least queried one makes room for a new one
+const MAX_NAMES: usize = 5000;
+const MAX_CLIENTS: usize = 1000;
+const RECENT_TRANSACTIONS: usize = 200;
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsQuestion {
+    pub name: String,
+    pub record_type: u16,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsAnswer {
+    pub name: String,
+    pub record_type: u16,
+    pub ttl: u32,
+    /// Record data in presentation form ("93.184.216.34", "10 mx.example.com.", ...)
+    pub data: String,
+    /// Address of A and AAAA records
+    pub address: Option<IpAddr>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct DnsMessage {
+    pub id: u16,
+    pub is_response: bool,
+    pub opcode: u8,
+    pub rcode: u8,
+    pub questions: Vec<DnsQuestion>,
+    pub answers: Vec<DnsAnswer>,
+}
+
+impl DnsMessage {
+    /// Decode a DNS message (without the TCP length prefix). Only the
+    /// question and answer sections are read.
+    pub fn parse(data: &[u8]) -> Option<Self> {
+        let header = data.get(..12)?;
+        let flags = u16::from_be_bytes([header[2], header[3]]);
+        let question_count = u16::from_be_bytes([header[4], header[5]]);
+        let answer_count = u16::from_be_bytes([header[6], header[7]]);
+        let mut message = DnsMessage {
+            id: u16::from_be_bytes([header[0], header[1]]),
+            is_response: flags & 0x8000 != 0,
+            opcode: ((flags >> 11) & 0x0f) as u8,
+            rcode: (flags & 0x000f) as u8,
+            questions: Vec::new(),
+            answers: Vec::new(),
+        };
+        
+        let mut offset = 12;
+        for _ in 0..question_count {
+            let (name, next) = read_name(data, offset)?;
+            let fixed = data.get(next..next + 4)?;
+            message.questions.push(DnsQuestion {
+                name,
+                record_type: u16::from_be_bytes([fixed[0], fixed[1]]),
+            });
+            offset = next + 4;
+        }
+        
+        for _ in 0..answer_count {
+            let (name, next) = read_name(data, offset)?;
+            let fixed = data.get(next..next + 10)?;
+            let record_type = u16::from_be_bytes([fixed[0], fixed[1]]);
+            let ttl = u32::from_be_bytes([fixed[4], fixed[5], fixed[6], fixed[7]]);
+            let length = u16::from_be_bytes([fixed[8], fixed[9]]) as usize;
+            let start = next + 10;
+            let rdata = data.get(start..start + length)?;
+            
+            let address = match (record_type, rdata.len()) {
+                (1, 4) => Some(IpAddr::V4(Ipv4Addr::new(rdata[0], rdata[1], rdata[2], rdata[3]))),
+                (28, 16) => {
+                    let mut octets = [0u8; 16];
+                    octets.copy_from_slice(rdata);
+                    Some(IpAddr::V6(Ipv6Addr::from(octets)))
+                },
+                _ => None,
+            };
+            let data_text = match (address, record_type) {
+                (Some(address), _) => address.to_string(),
+                // CNAME, NS, PTR
+                (None, 5 | 2 | 12) => read_name(data, start).map(|(name, _)| name).unwrap_or_default(),
+                // MX
+                (None, 15) if length > 2 => {
+                    let preference = u16::from_be_bytes([rdata[0], rdata[1]]);
+                    let exchange = read_name(data, start + 2).map(|(name, _)| name).unwrap_or_default();
+                    format!("{} {}", preference, exchange)
+                },
+                // TXT: length-prefixed strings
+                (None, 16) => {
+                    let mut text = String::new();
+                    let mut rest = rdata;
+                    while let Some((&len, tail)) = rest.split_first() {
+                        let chunk = &tail[..(len as usize).min(tail.len())];
+                        text.push_str(&String::from_utf8_lossy(chunk));
+                        rest = &tail[chunk.len()..];
+                    }
+                    text
+                },
+                _ => format!("<{} bytes>", length),
+            };
+            
+            message.answers.push(DnsAnswer { name, record_type, ttl, data: data_text, address });
+            offset = start + length;
+        }
+        Some(message)
+    }
+}
+
+/// Read a possibly compressed name starting at `offset`; returns the name
+/// (dot-terminated, "." for the root) and the offset just past it
+fn read_name(data: &[u8], offset: usize) -> Option<(String, usize)> {
+    let mut labels: Vec<String> = Vec::new();
+    let mut position = offset;
+    let mut end = None;
+    // Each pointer must go backwards, which also rules out loops
+    let mut limit = offset;
+    
+    loop {
+        let len = *data.get(position)? as usize;
+        match len {
+            0 => {
+                end.get_or_insert(position + 1);
+                break;
+            },
+            len if len & 0xc0 == 0xc0 => {
+                let target = ((len & 0x3f) << 8) | *data.get(position + 1)? as usize;
+                if target >= limit {
+                    return None;
+                }
+                end.get_or_insert(position + 2);
+                limit = target;
+                position = target;
+            },
+            len if len <= 63 => {
+                let label = data.get(position + 1..position + 1 + len)?;
+                labels.push(escape_label(label));
+                position += 1 + len;
+            },
+            _ => return None,
+        }
+    }
+    
+    let name = if labels.is_empty() { ".".to_string() } else { format!("{}.", labels.join(".")) };
+    Some((name, end?))
+}
+
+/// Presentation form of a label: bytes other than printable ASCII, and the
+/// dot and backslash, become `\DDD`
+fn escape_label(label: &[u8]) -> String {
+    label
+        .iter()
+        .map(|&byte| match byte {
+            b'.' | b'\\' => format!("\\{:03}", byte),
+            byte if byte.is_ascii_graphic() => (byte as char).to_string(),
+            byte => format!("\\{:03}", byte),
+        })
+        .collect()
+}
+
+pub fn record_type_name(record_type: u16) -> String {
+    let name = match record_type {
+        1 => "A",
+        2 => "NS",
+        5 => "CNAME",
+        6 => "SOA",
+        12 => "PTR",
+        15 => "MX",
+        16 => "TXT",
+        28 => "AAAA",
+        33 => "SRV",
+        64 => "SVCB",
+        65 => "HTTPS",
+        255 => "ANY",
+        other => return format!("TYPE{}", other),
+    };
+    name.to_string()
+}
+
+pub fn rcode_name(rcode: u8) -> String {
+    let name = match rcode {
+        0 => "NOERROR",
+        1 => "FORMERR",
+        2 => "SERVFAIL",
+        3 => "NXDOMAIN",
+        4 => "NOTIMP",
+        5 => "REFUSED",
+        other => return format!("RCODE{}", other),
+    };
+    name.to_string()
+}
+
+/// A query and, once it arrives, its response
+#[derive(Debug, Clone)]
+pub struct DnsTransaction {
+    pub timestamp: SystemTime,
+    pub client: IpAddr,
+    pub resolver: IpAddr,
+    pub name: String,
+    pub record_type: u16,
+    /// `None` if no response came in time
+    pub rcode: Option<u8>,
+    pub answers: Vec<DnsAnswer>,
+    pub latency: Option<Duration>,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct NameStats {
+    pub name: String,
+    pub queries: u64,
+    pub nxdomain: u64,
+}
+
+#[derive(Debug, Clone)]
+pub struct ResolverStats {
+    pub resolver: IpAddr,
+    pub queries: u64,
+    pub responses: u64,
+    pub unanswered: u64,
+    pub failures: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl ResolverStats {
+    pub fn average_latency(&self) -> Option<Duration> {
+        (self.responses > 0).then(|| self.total_latency / self.responses as u32)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ClientStats {
+    pub client: IpAddr,
+    pub queries: u64,
+    pub nxdomain: u64,
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DnsTotals {
+    pub queries: u64,
+    pub responses: u64,
+    pub nxdomain: u64,
+    pub unanswered: u64,
+}
+
+impl DnsTotals {
+    /// Share of responses that were NXDOMAIN, in percent
+    pub fn nxdomain_rate(&self) -> f64 {
+        if self.responses == 0 {
+            0.0
+        } else {
+            self.nxdomain as f64 * 100.0 / self.responses as f64
+        }
+    }
+}
+
+struct PendingQuery {
+    timestamp: SystemTime,
+    name: String,
+    record_type: u16,
+}
+
+/// Decodes DNS traffic on port 53 and keeps per-name, per-resolver and
+/// per-client aggregates. Times come from the packets, so latencies of
+/// replayed captures are the recorded ones.
+#[derive(Default)]
+pub struct DnsTracker {
+    /// Queries waiting for a response, by (client, resolver, id)
+    pending: HashMap<(SocketAddr, SocketAddr, u16), PendingQuery>,
+    names: HashMap<String, NameStats>,
+    resolvers: HashMap<IpAddr, ResolverStats>,
+    clients: HashMap<IpAddr, ClientStats>,
+    recent: VecDeque<DnsTransaction>,
+    totals: DnsTotals,
+}
+
+impl DnsTracker {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Decode a captured packet if it is DNS; returns the message so other
+    /// consumers don't have to parse it again
+    pub fn process_packet(&mut self, packet: &PacketInfo) -> Option<DnsMessage> {
+        if packet.src_port != Some(53) && packet.dst_port != Some(53) {
+            return None;
+        }
+        let (src, dst) = packet.socket_addrs()?;
+        let data = match packet.protocol.as_str() {
+            "UDP" => &packet.payload[..],
+            // DNS over TCP: a 2-byte length before each message
+            "TCP" => packet.payload.get(2..)?,
+            _ => return None,
+        };
+        let message = DnsMessage::parse(data)?;
+        
+        self.expire_pending(packet.timestamp);
+        if message.is_response {
+            self.record_response(dst, src, &message, packet.timestamp);
+        } else {
+            self.record_query(src, dst, &message, packet.timestamp);
+        }
+        Some(message)
+    }
+    
+    fn record_query(&mut self, client: SocketAddr, resolver: SocketAddr, message: &DnsMessage, now: SystemTime) {
+        let Some(question) = message.questions.first() else {
+            return;
+        };
+        self.totals.queries += 1;
+        
+        if !self.names.contains_key(&question.name) && self.names.len() >= MAX_NAMES {
+            if let Some(least) = self.names.values().min_by_key(|stats| stats.queries).map(|stats| stats.name.clone()) {
+                self.names.remove(&least);
+            }
+        }
+        let name = self.names.entry(question.name.clone()).or_insert_with(|| NameStats {
+            name: question.name.clone(),
+            ..Default::default()
+        });
+        name.queries += 1;
+        
+        self.resolver_stats(resolver.ip()).queries += 1;
+        if self.clients.len() < MAX_CLIENTS || self.clients.contains_key(&client.ip()) {
+            self.clients
+                .entry(client.ip())
+                .or_insert(ClientStats { client: client.ip(), queries: 0, nxdomain: 0 })
+                .queries += 1;
+        }
+        
+        if self.pending.len() < MAX_PENDING_QUERIES {
+            self.pending.insert((client, resolver, message.id), PendingQuery {
+                timestamp: now,
+                name: question.name.clone(),
+                record_type: question.record_type,
+            });
+        }
+    }
+    
+    fn record_response(&mut self, client: SocketAddr, resolver: SocketAddr, message: &DnsMessage, now: SystemTime) {
+        self.totals.responses += 1;
+        let nxdomain = message.rcode == 3;
+        if nxdomain {
+            self.totals.nxdomain += 1;
+        }
+        
+        let query = self.pending.remove(&(client, resolver, message.id));
+        let latency = query.as_ref().map(|query| now.duration_since(query.timestamp).unwrap_or_default());
+        let (name, record_type) = match (query, message.questions.first()) {
+            (Some(query), _) => (query.name, query.record_type),
+            (None, Some(question)) => (question.name.clone(), question.record_type),
+            (None, None) => (".".to_string(), 0),
+        };
+        
+        if nxdomain {
+            if let Some(stats) = self.names.get_mut(&name) {
+                stats.nxdomain += 1;
+            }
+            if let Some(stats) = self.clients.get_mut(&client.ip()) {
+                stats.nxdomain += 1;
+            }
+        }
+        
+        let resolver_stats = self.resolver_stats(resolver.ip());
+        if let Some(latency) = latency {
+            resolver_stats.responses += 1;
+            resolver_stats.total_latency += latency;
+            resolver_stats.max_latency = resolver_stats.max_latency.max(latency);
+        }
+        if matches!(message.rcode, 2 | 5) {
+            resolver_stats.failures += 1;
+        }
+        
+        self.push_recent(DnsTransaction {
+            timestamp: now,
+            client: client.ip(),
+            resolver: resolver.ip(),
+            name,
+            record_type,
+            rcode: Some(message.rcode),
+            answers: message.answers.clone(),
+            latency,
+        });
+    }
+    
+    fn resolver_stats(&mut self, resolver: IpAddr) -> &mut ResolverStats {
+        self.resolvers.entry(resolver).or_insert(ResolverStats {
+            resolver,
+            queries: 0,
+            responses: 0,
+            unanswered: 0,
+            failures: 0,
+            total_latency: Duration::ZERO,
+            max_latency: Duration::ZERO,
+        })
+    }
+    
+    /// Give up on queries that have waited too long
+    fn expire_pending(&mut self, now: SystemTime) {
+        let expired: Vec<_> = self.pending
+            .iter()
+            .filter(|(_, query)| now.duration_since(query.timestamp).unwrap_or_default() > QUERY_TIMEOUT)
+            .map(|(key, _)| *key)
+            .collect();
+        
+        for key in expired {
+            if let Some(query) = self.pending.remove(&key) {
+                let (client, resolver, _) = key;
+                self.totals.unanswered += 1;
+                self.resolver_stats(resolver.ip()).unanswered += 1;
+                self.push_recent(DnsTransaction {
+                    timestamp: query.timestamp,
+                    client: client.ip(),
+                    resolver: resolver.ip(),
+                    name: query.name,
+                    record_type: query.record_type,
+                    rcode: None,
+                    answers: Vec::new(),
+                    latency: None,
+                });
+            }
+        }
+    }
+    
+    fn push_recent(&mut self, transaction: DnsTransaction) {
+        self.recent.push_back(transaction);
+        if self.recent.len() > RECENT_TRANSACTIONS {
+            self.recent.pop_front();
+        }
+    }
+    
+    /// Most queried names first
+    pub fn top_names(&self, limit: usize) -> Vec<NameStats> {
+        let mut names: Vec<_> = self.names.values().cloned().collect();
+        names.sort_by(|a, b| b.queries.cmp(&a.queries).then_with(|| a.name.cmp(&b.name)));
+        names.truncate(limit);
+        names
+    }
+    
+    /// Slowest average latency first; resolvers that never answered come first
+    pub fn slow_resolvers(&self, limit: usize) -> Vec<ResolverStats> {
+        let mut resolvers: Vec<_> = self.resolvers.values().cloned().collect();
+        resolvers.sort_by(|a, b| {
+            let latency = |stats: &ResolverStats| stats.average_latency().unwrap_or(Duration::MAX);
+            latency(b).cmp(&latency(a)).then_with(|| b.queries.cmp(&a.queries))
+        });
+        resolvers.truncate(limit);
+        resolvers
+    }
+    
+    /// Busiest clients first
+    pub fn top_clients(&self, limit: usize) -> Vec<ClientStats> {
+        let mut clients: Vec<_> = self.clients.values().cloned().collect();
+        clients.sort_by(|a, b| b.queries.cmp(&a.queries).then_with(|| a.client.cmp(&b.client)));
+        clients.truncate(limit);
+        clients
+    }
+    
+    /// Completed and timed-out lookups, oldest first
+    pub fn recent_transactions(&self) -> &VecDeque<DnsTransaction> {
+        &self.recent
+    }
+    
+    pub fn totals(&self) -> DnsTotals {
+        self.totals
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn query(id: u16, name: &str) -> Vec<u8> {
+        let mut message = id.to_be_bytes().to_vec();
+        message.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+        for label in name.trim_end_matches('.').split('.') {
+            message.push(label.len() as u8);
+            message.extend_from_slice(label.as_bytes());
+        }
+        message.extend_from_slice(&[0, 0, 1, 0, 1]);
+        message
+    }
+    
+    /// Response to `query` with one A record pointing back at the question name
+    fn response(query: &[u8], rcode: u8, address: Option<[u8; 4]>) -> Vec<u8> {
+        let mut message = query.to_vec();
+        message[2] = 0x81;
+        message[3] = 0x80 | rcode;
+        if let Some(address) = address {
+            message[7] = 1;
+            message.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1, 0, 0, 0x01, 0x2c, 0, 4]);
+            message.extend_from_slice(&address);
+        }
+        message
+    }
+    
+    fn packet(src: &str, dst: &str, payload: Vec<u8>, offset_ms: u64) -> PacketInfo {
+        let (src, dst): (SocketAddr, SocketAddr) = (src.parse().unwrap(), dst.parse().unwrap());
+        PacketInfo {
+            timestamp: SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000) + Duration::from_millis(offset_ms),
+            length: 42 + payload.len(),
+            protocol: "UDP".to_string(),
+            src_ip: Some(src.ip().to_string()),
+            dst_ip: Some(dst.ip().to_string()),
+            src_port: Some(src.port()),
+            dst_port: Some(dst.port()),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload,
+        }
+    }
+    
+    #[test]
+    fn test_parse_response() {
+        let query = query(0x1234, "example.com");
+        let message = DnsMessage::parse(&response(&query, 0, Some([93, 184, 216, 34]))).unwrap();
+        assert!(message.is_response);
+        assert_eq!(message.questions[0].name, "example.com.");
+        assert_eq!(record_type_name(message.questions[0].record_type), "A");
+        assert_eq!(message.answers[0].name, "example.com.");
+        assert_eq!(message.answers[0].ttl, 300);
+        assert_eq!(message.answers[0].data, "93.184.216.34");
+        assert_eq!(message.answers[0].address, Some(IpAddr::V4(Ipv4Addr::new(93, 184, 216, 34))));
+        
+        // A pointer to itself must not loop
+        let mut looping = query.clone();
+        looping.truncate(12);
+        looping.extend_from_slice(&[0xc0, 0x0c, 0, 1, 0, 1]);
+        assert!(DnsMessage::parse(&looping).is_none());
+    }
+    
+    #[test]
+    fn test_tracker_aggregates() {
+        let mut tracker = DnsTracker::new();
+        let client = "192.168.1.10:40000";
+        let resolver = "192.168.1.1:53";
+        let slow = "9.9.9.9:53";
+        
+        let ok = query(1, "example.com");
+        tracker.process_packet(&packet(client, resolver, ok.clone(), 0));
+        tracker.process_packet(&packet(resolver, client, response(&ok, 0, Some([93, 184, 216, 34])), 20));
+        
+        let missing = query(2, "nope.example");
+        tracker.process_packet(&packet(client, slow, missing.clone(), 100));
+        tracker.process_packet(&packet(slow, client, response(&missing, 3, None), 400));
+        
+        let again = query(3, "example.com");
+        tracker.process_packet(&packet("192.168.1.11:40001", resolver, again, 500));
+        // Not DNS: wrong port
+        assert!(tracker.process_packet(&packet(client, "192.168.1.1:5353", ok.clone(), 600)).is_none());
+        
+        let totals = tracker.totals();
+        assert_eq!((totals.queries, totals.responses, totals.nxdomain), (3, 2, 1));
+        assert_eq!(totals.nxdomain_rate(), 50.0);
+        
+        let names = tracker.top_names(10);
+        assert_eq!(names[0].name, "example.com.");
+        assert_eq!(names[0].queries, 2);
+        assert_eq!(names[1].nxdomain, 1);
+        
+        let resolvers = tracker.slow_resolvers(10);
+        assert_eq!(resolvers[0].resolver.to_string(), "9.9.9.9");
+        assert_eq!(resolvers[0].average_latency(), Some(Duration::from_millis(300)));
+        assert_eq!(resolvers[1].max_latency, Duration::from_millis(20));
+        
+        let clients = tracker.top_clients(10);
+        assert_eq!(clients[0].client.to_string(), "192.168.1.10");
+        assert_eq!((clients[0].queries, clients[0].nxdomain), (2, 1));
+        
+        // The third query times out once later traffic moves the clock on
+        tracker.process_packet(&packet(client, resolver, query(4, "later.example"), 10_000));
+        assert_eq!(tracker.totals().unanswered, 1);
+        let last = tracker.recent_transactions().back().unwrap();
+        assert_eq!((last.name.as_str(), last.rcode), ("example.com.", None));
+        assert_eq!(tracker.slow_resolvers(10).iter().map(|r| r.unanswered).sum::<u64>(), 1);
+    }
+}
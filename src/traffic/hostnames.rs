@@ -0,0 +1,463 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::io;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, UdpSocket};
+use std::time::{Duration, Instant};
+use crate::traffic::dns::DnsMessage;
+use crate::utils::net::{canonical_addr, canonical_ip};
+
+const TYPE_PTR: u16 = 12;
+/// Names kept; the oldest learned is dropped beyond this
+const MAX_HOSTNAMES: usize = 10_000;
+/// Addresses waiting for a PTR lookup
+const MAX_QUEUED_LOOKUPS: usize = 1024;
+const LOOKUP_TIMEOUT: Duration = Duration::from_secs(3);
+/// An address is looked up at most once in this period, answered or not
+const LOOKUP_RETRY: Duration = Duration::from_secs(600);
+
+/// Where a name came from. Forward answers name the host the way the client
+/// asked for it, so they are preferred over PTR records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HostnameSource {
+    /// A or AAAA answer to a captured query
+    Forward,
+    /// PTR answer, captured or from our own lookups
+    Reverse,
+}
+
+#[derive(Debug, Clone)]
+struct HostnameEntry {
+    name: String,
+    source: HostnameSource,
+    /// Learn order, for eviction
+    sequence: u64,
+}
+
+/// Addresses mapped to hostnames, learned passively from DNS responses seen
+/// on the wire and optionally from PTR lookups
+#[derive(Default)]
+pub struct HostnameCache {
+    names: HashMap<IpAddr, HostnameEntry>,
+    next_sequence: u64,
+    resolver: Option<PtrResolver>,
+}
+
+impl HostnameCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Also ask `server` for the PTR record of addresses nobody looked up,
+    /// sending at most `lookups_per_second` queries
+    pub fn enable_ptr_lookups(&mut self, server: SocketAddr, lookups_per_second: u32) -> io::Result<()> {
+        self.resolver = Some(PtrResolver::new(server, lookups_per_second)?);
+        Ok(())
+    }
+    
+    pub fn ptr_lookups_enabled(&self) -> bool {
+        self.resolver.is_some()
+    }
+    
+    /// Record the names in a successful response: A/AAAA addresses get the
+    /// name from the question (not the end of a CNAME chain), PTR records
+    /// name the address encoded in the reverse name
+    pub fn learn(&mut self, message: &DnsMessage) {
+        if !message.is_response || message.rcode != 0 {
+            return;
+        }
+        let asked = message.questions.first().map(|question| question.name.as_str());
+        for answer in &message.answers {
+            match (answer.address, answer.record_type) {
+                (Some(address), _) => {
+                    let name = asked.unwrap_or(&answer.name).to_string();
+                    self.insert(address, &name, HostnameSource::Forward);
+                },
+                (None, TYPE_PTR) => {
+                    if let Some(address) = address_from_ptr_name(&answer.name) {
+                        self.insert(address, &answer.data, HostnameSource::Reverse);
+                    }
+                },
+                _ => {}
+            }
+        }
+    }
+    
+    fn insert(&mut self, address: IpAddr, name: &str, source: HostnameSource) {
+        let address = canonical_ip(address);
+        let name = name.trim_end_matches('.');
+        if name.is_empty() {
+            return;
+        }
+        if let Some(existing) = self.names.get(&address) {
+            if existing.source == HostnameSource::Forward && source == HostnameSource::Reverse {
+                return;
+            }
+        }
+        
+        if self.names.len() >= MAX_HOSTNAMES && !self.names.contains_key(&address) {
+            let oldest = self.names.iter().min_by_key(|(_, entry)| entry.sequence).map(|(address, _)| *address);
+            if let Some(oldest) = oldest {
+                self.names.remove(&oldest);
+            }
+        }
+        self.next_sequence += 1;
+        self.names.insert(address, HostnameEntry {
+            name: name.to_string(),
+            source,
+            sequence: self.next_sequence,
+        });
+    }
+    
+    /// IPv4-mapped addresses of dual-stack sockets find their IPv4 name
+    pub fn lookup(&self, address: IpAddr) -> Option<&str> {
+        self.names.get(&canonical_ip(address)).map(|entry| entry.name.as_str())
+    }
+    
+    pub fn source(&self, address: IpAddr) -> Option<HostnameSource> {
+        self.names.get(&canonical_ip(address)).map(|entry| entry.source)
+    }
+    
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+    
+    pub fn is_empty(&self) -> bool {
+        self.names.is_empty()
+    }
+    
+    /// Queue a PTR lookup for an address without a name. Does nothing
+    /// unless lookups are enabled.
+    pub fn request(&mut self, address: IpAddr, now: Instant) {
+        let address = canonical_ip(address);
+        if self.names.contains_key(&address) {
+            return;
+        }
+        if let Some(ref mut resolver) = self.resolver {
+            resolver.queue(address, now);
+        }
+    }
+    
+    /// Send queued lookups the rate limit allows and record the answers
+    /// that arrived since the last call
+    pub fn poll(&mut self, now: Instant) {
+        let answers = match self.resolver {
+            Some(ref mut resolver) => resolver.poll(now),
+            None => return,
+        };
+        for (address, name) in answers {
+            self.insert(address, &name, HostnameSource::Reverse);
+        }
+    }
+}
+
+/// Non-blocking PTR client for a single DNS server
+pub struct PtrResolver {
+    socket: UdpSocket,
+    server: SocketAddr,
+    interval: Duration,
+    next_send: Instant,
+    next_id: u16,
+    queue: VecDeque<IpAddr>,
+    queued: HashSet<IpAddr>,
+    in_flight: HashMap<u16, (IpAddr, Instant)>,
+    attempted: HashMap<IpAddr, Instant>,
+}
+
+impl PtrResolver {
+    pub fn new(server: SocketAddr, lookups_per_second: u32) -> io::Result<Self> {
+        let local: SocketAddr = match server {
+            SocketAddr::V4(_) => (Ipv4Addr::UNSPECIFIED, 0).into(),
+            SocketAddr::V6(_) => (Ipv6Addr::UNSPECIFIED, 0).into(),
+        };
+        let socket = UdpSocket::bind(local)?;
+        // Only the server's replies get through
+        socket.connect(server)?;
+        socket.set_nonblocking(true)?;
+        
+        Ok(Self {
+            socket,
+            server,
+            interval: Duration::from_secs(1) / lookups_per_second.max(1),
+            next_send: Instant::now(),
+            next_id: std::process::id() as u16,
+            queue: VecDeque::new(),
+            queued: HashSet::new(),
+            in_flight: HashMap::new(),
+            attempted: HashMap::new(),
+        })
+    }
+    
+    pub fn queue(&mut self, address: IpAddr, now: Instant) {
+        if address.is_unspecified() || address.is_multicast() || self.queued.contains(&address) {
+            return;
+        }
+        let recently_tried = self.attempted
+            .get(&address)
+            .is_some_and(|tried| now.duration_since(*tried) < LOOKUP_RETRY);
+        if recently_tried || self.queue.len() >= MAX_QUEUED_LOOKUPS {
+            return;
+        }
+        self.queued.insert(address);
+        self.queue.push_back(address);
+    }
+    
+    /// Collect answers, then send as many queued queries as the rate allows;
+    /// idle time banks at most one second of lookups
+    pub fn poll(&mut self, now: Instant) -> Vec<(IpAddr, String)> {
+        let answers = self.receive();
+        
+        self.in_flight.retain(|_, (_, sent)| now.duration_since(*sent) < LOOKUP_TIMEOUT);
+        self.attempted.retain(|_, tried| now.duration_since(*tried) < LOOKUP_RETRY);
+        
+        let window_start = now.checked_sub(Duration::from_secs(1)).unwrap_or(now);
+        if self.next_send < window_start {
+            self.next_send = window_start;
+        }
+        while self.next_send <= now {
+            let Some(address) = self.queue.pop_front() else { break };
+            self.queued.remove(&address);
+            self.attempted.insert(address, now);
+            
+            let id = self.next_id;
+            self.next_id = self.next_id.wrapping_add(1);
+            match self.socket.send(&ptr_query(id, address)) {
+                Ok(_) => {
+                    self.in_flight.insert(id, (address, now));
+                },
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => {
+                    self.attempted.remove(&address);
+                    self.queued.insert(address);
+                    self.queue.push_front(address);
+                    break;
+                },
+                Err(e) => log::debug!("PTR query to {} failed: {}", self.server, e),
+            }
+            self.next_send += self.interval;
+        }
+        answers
+    }
+    
+    fn receive(&mut self) -> Vec<(IpAddr, String)> {
+        let mut answers = Vec::new();
+        let mut buffer = [0u8; 1500];
+        loop {
+            let length = match self.socket.recv(&mut buffer) {
+                Ok(length) => length,
+                Err(e) if e.kind() == io::ErrorKind::WouldBlock => break,
+                // ICMP unreachable from an earlier send; keep draining
+                Err(e) if e.kind() == io::ErrorKind::ConnectionRefused => continue,
+                Err(e) => {
+                    log::debug!("PTR response from {} unreadable: {}", self.server, e);
+                    break;
+                },
+            };
+            let Some(message) = DnsMessage::parse(&buffer[..length]) else { continue };
+            let Some((address, _)) = self.in_flight.get(&message.id).copied() else { continue };
+            // The id alone is easy to guess; the question must match too
+            let expected = ptr_name(address);
+            if !message.questions.iter().any(|question| question.name.eq_ignore_ascii_case(&expected)) {
+                continue;
+            }
+            self.in_flight.remove(&message.id);
+            
+            let name = message.answers.iter().find(|answer| answer.record_type == TYPE_PTR);
+            if let (0, Some(name)) = (message.rcode, name) {
+                answers.push((address, name.data.clone()));
+            }
+        }
+        answers
+    }
+}
+
+/// Reverse-lookup name of an address: `4.3.2.1.in-addr.arpa.` for 1.2.3.4,
+/// 32 reversed nibbles under `ip6.arpa.` for IPv6
+pub fn ptr_name(address: IpAddr) -> String {
+    match address {
+        IpAddr::V4(address) => {
+            let [a, b, c, d] = address.octets();
+            format!("{}.{}.{}.{}.in-addr.arpa.", d, c, b, a)
+        },
+        IpAddr::V6(address) => {
+            let mut name = String::with_capacity(73);
+            for byte in address.octets().iter().rev() {
+                name.push_str(&format!("{:x}.{:x}.", byte & 0x0f, byte >> 4));
+            }
+            name.push_str("ip6.arpa.");
+            name
+        },
+    }
+}
+
+/// Inverse of `ptr_name`; `None` for names that aren't a full reverse name
+pub fn address_from_ptr_name(name: &str) -> Option<IpAddr> {
+    let name = name.trim_end_matches('.').to_ascii_lowercase();
+    if let Some(labels) = name.strip_suffix(".in-addr.arpa") {
+        let mut octets: Vec<u8> = labels.split('.').map(|label| label.parse().ok()).collect::<Option<_>>()?;
+        if octets.len() != 4 {
+            return None;
+        }
+        octets.reverse();
+        return Some(IpAddr::V4(Ipv4Addr::new(octets[0], octets[1], octets[2], octets[3])));
+    }
+    
+    let labels = name.strip_suffix(".ip6.arpa")?;
+    let nibbles: Vec<u8> = labels
+        .split('.')
+        .map(|label| match label.len() {
+            1 => u8::from_str_radix(label, 16).ok(),
+            _ => None,
+        })
+        .collect::<Option<_>>()?;
+    if nibbles.len() != 32 {
+        return None;
+    }
+    let mut octets = [0u8; 16];
+    for (i, pair) in nibbles.rchunks(2).enumerate() {
+        octets[i] = (pair[1] << 4) | pair[0];
+    }
+    Some(IpAddr::V6(Ipv6Addr::from(octets)))
+}
+
+fn ptr_query(id: u16, address: IpAddr) -> Vec<u8> {
+    let mut query = id.to_be_bytes().to_vec();
+    // Recursion desired, one question
+    query.extend_from_slice(&[0x01, 0x00, 0, 1, 0, 0, 0, 0, 0, 0]);
+    query.extend_from_slice(&encode_name(&ptr_name(address)));
+    query.extend_from_slice(&[0, TYPE_PTR as u8, 0, 1]);
+    query
+}
+
+/// Uncompressed wire form of a dot-separated name
+fn encode_name(name: &str) -> Vec<u8> {
+    let mut encoded = Vec::new();
+    for label in name.trim_end_matches('.').split('.') {
+        encoded.push(label.len() as u8);
+        encoded.extend_from_slice(label.as_bytes());
+    }
+    encoded.push(0);
+    encoded
+}
+
+/// `address` as shown in tables: its hostname when `names` is given and
+/// has one, the address otherwise (IPv4-mapped ones as plain IPv4)
+pub fn format_host(address: IpAddr, names: Option<&HostnameCache>) -> String {
+    let address = canonical_ip(address);
+    match names.and_then(|names| names.lookup(address)) {
+        Some(name) => name.to_string(),
+        None => address.to_string(),
+    }
+}
+
+/// `host:port` form of `format_host`; unnamed IPv6 addresses keep their brackets
+pub fn format_endpoint(address: SocketAddr, names: Option<&HostnameCache>) -> String {
+    let address = canonical_addr(address);
+    match names.and_then(|names| names.lookup(address.ip())) {
+        Some(name) => format!("{}:{}", name, address.port()),
+        None => address.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    /// Response to a query for `name` with the given answer records, each
+    /// (type, rdata)
+    fn response(id: u16, name: &str, record_type: u16, answers: &[(u16, Vec<u8>)]) -> Vec<u8> {
+        let mut message = id.to_be_bytes().to_vec();
+        message.extend_from_slice(&[0x81, 0x80, 0, 1, 0, answers.len() as u8, 0, 0, 0, 0]);
+        message.extend_from_slice(&encode_name(name));
+        message.extend_from_slice(&record_type.to_be_bytes());
+        message.extend_from_slice(&[0, 1]);
+        for (answer_type, rdata) in answers {
+            message.extend_from_slice(&[0xc0, 0x0c]);
+            message.extend_from_slice(&answer_type.to_be_bytes());
+            message.extend_from_slice(&[0, 1, 0, 0, 0x0e, 0x10]);
+            message.extend_from_slice(&(rdata.len() as u16).to_be_bytes());
+            message.extend_from_slice(rdata);
+        }
+        message
+    }
+    
+    #[test]
+    fn test_learns_from_captured_responses() {
+        let mut cache = HostnameCache::new();
+        let cdn: IpAddr = "93.184.216.34".parse().unwrap();
+        
+        // www.example.com CNAME edge.cdn.net, A 93.184.216.34
+        let forward = response(1, "www.example.com", 1, &[
+            (5, encode_name("edge.cdn.net")),
+            (1, vec![93, 184, 216, 34]),
+        ]);
+        cache.learn(&DnsMessage::parse(&forward).unwrap());
+        assert_eq!(cache.lookup(cdn), Some("www.example.com"));
+        
+        // Dual-stack sockets report the same peer as an IPv4-mapped address
+        let mapped: SocketAddr = "[::ffff:93.184.216.34]:443".parse().unwrap();
+        assert_eq!(format_endpoint(mapped, Some(&cache)), "www.example.com:443");
+        assert_eq!(format_endpoint(mapped, None), "93.184.216.34:443");
+        
+        // A PTR answer doesn't replace the name the client asked for
+        let reverse = response(2, &ptr_name(cdn), TYPE_PTR, &[(TYPE_PTR, encode_name("ec2-93-184-216-34.cdn.net"))]);
+        cache.learn(&DnsMessage::parse(&reverse).unwrap());
+        assert_eq!(cache.lookup(cdn), Some("www.example.com"));
+        
+        let v6: IpAddr = "2001:db8::1".parse().unwrap();
+        let reverse = response(3, &ptr_name(v6), TYPE_PTR, &[(TYPE_PTR, encode_name("host.example.net"))]);
+        cache.learn(&DnsMessage::parse(&reverse).unwrap());
+        assert_eq!(cache.lookup(v6), Some("host.example.net"));
+        assert_eq!(cache.source(v6), Some(HostnameSource::Reverse));
+        
+        assert_eq!(format_endpoint("[2001:db8::1]:443".parse().unwrap(), Some(&cache)), "host.example.net:443");
+        assert_eq!(format_endpoint("[2001:db8::1]:443".parse().unwrap(), None), "[2001:db8::1]:443");
+        assert_eq!(format_host("10.0.0.1".parse().unwrap(), Some(&cache)), "10.0.0.1");
+        
+        assert_eq!(ptr_name("192.0.2.7".parse().unwrap()), "7.2.0.192.in-addr.arpa.");
+        assert_eq!(address_from_ptr_name("7.2.0.192.IN-ADDR.ARPA."), Some("192.0.2.7".parse().unwrap()));
+        assert_eq!(address_from_ptr_name(&ptr_name(v6)), Some(v6));
+        // Delegation names for whole networks don't name an address
+        assert_eq!(address_from_ptr_name("2.0.192.in-addr.arpa."), None);
+    }
+    
+    #[test]
+    fn test_ptr_lookups_against_stub_server() {
+        let stub = UdpSocket::bind("127.0.0.1:0").unwrap();
+        stub.set_read_timeout(Some(Duration::from_millis(200))).unwrap();
+        
+        let mut cache = HostnameCache::new();
+        cache.enable_ptr_lookups(stub.local_addr().unwrap(), 1).unwrap();
+        let wanted: IpAddr = "192.0.2.7".parse().unwrap();
+        let now = Instant::now();
+        // Mapped addresses are looked up in in-addr.arpa, not ip6.arpa
+        cache.request("::ffff:192.0.2.7".parse().unwrap(), now);
+        cache.request("192.0.2.8".parse().unwrap(), now);
+        cache.request("192.0.2.9".parse().unwrap(), now);
+        cache.poll(now);
+        
+        // One lookup per second: only the first address was asked for
+        let mut buffer = [0u8; 512];
+        let (length, client) = stub.recv_from(&mut buffer).unwrap();
+        let query = DnsMessage::parse(&buffer[..length]).unwrap();
+        assert_eq!(query.questions[0].name, "7.2.0.192.in-addr.arpa.");
+        assert_eq!(query.questions[0].record_type, TYPE_PTR);
+        assert!(stub.recv_from(&mut buffer).is_err());
+        
+        // A reply with the wrong id is ignored, the right one is learned
+        let name = encode_name("gateway.example.org");
+        stub.send_to(&response(query.id.wrapping_add(1), &ptr_name(wanted), TYPE_PTR, &[(TYPE_PTR, name.clone())]), client).unwrap();
+        stub.send_to(&response(query.id, &ptr_name(wanted), TYPE_PTR, &[(TYPE_PTR, name)]), client).unwrap();
+        for _ in 0..100 {
+            cache.poll(now);
+            if cache.lookup(wanted).is_some() {
+                break;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(cache.lookup(wanted), Some("gateway.example.org"));
+        assert_eq!(cache.len(), 1);
+        
+        // A second later the next address goes out
+        cache.poll(now + Duration::from_secs(1));
+        let (length, _) = stub.recv_from(&mut buffer).unwrap();
+        assert_eq!(DnsMessage::parse(&buffer[..length]).unwrap().questions[0].name, "8.2.0.192.in-addr.arpa.");
+    }
+}
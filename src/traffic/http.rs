@@ -0,0 +1,628 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::net::SocketAddr;
+use std::time::{Duration, SystemTime};
+
+/// Connections followed at once; all are dropped when this is exceeded
+const MAX_CONNECTIONS: usize = 4096;
+/// A request or status line plus headers longer than this loses sync
+const MAX_HEAD_BYTES: usize = 16 * 1024;
+/// Requests sent ahead of their responses (pipelining)
+const MAX_PENDING_REQUESTS: usize = 64;
+const MAX_ENDPOINTS: usize = 2000;
+
+const METHODS: [&str; 9] = ["GET", "POST", "PUT", "DELETE", "HEAD", "OPTIONS", "PATCH", "CONNECT", "TRACE"];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpRequest {
+    pub method: String,
+    /// Request target as sent: a path, or an absolute URL through a proxy
+    pub path: String,
+    pub version: String,
+    pub host: Option<String>,
+    pub user_agent: Option<String>,
+    pub content_length: Option<u64>,
+    pub chunked: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpResponse {
+    pub version: String,
+    pub status: u16,
+    pub reason: String,
+    pub content_length: Option<u64>,
+    pub chunked: bool,
+}
+
+/// A request and the response to it
+#[derive(Debug, Clone, PartialEq)]
+pub struct HttpTransaction {
+    /// When the request started
+    pub timestamp: SystemTime,
+    pub method: String,
+    pub host: Option<String>,
+    pub path: String,
+    pub user_agent: Option<String>,
+    pub request_content_length: Option<u64>,
+    pub status: u16,
+    pub reason: String,
+    pub response_content_length: Option<u64>,
+    /// From the start of the request to the start of the response
+    pub latency: Duration,
+}
+
+/// Start line and header fields of a message head (everything before the
+/// blank line)
+struct Head<'a> {
+    start_line: Vec<&'a str>,
+    headers: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Head<'a> {
+    fn parse(head: &'a str) -> Option<Self> {
+        let mut lines = head.split("\r\n");
+        let start_line: Vec<&str> = lines.next()?.splitn(3, ' ').collect();
+        let mut headers = Vec::new();
+        for line in lines.filter(|line| !line.is_empty()) {
+            let (name, value) = line.split_once(':')?;
+            headers.push((name.trim(), value.trim()));
+        }
+        Some(Self { start_line, headers })
+    }
+    
+    fn header(&self, name: &str) -> Option<&'a str> {
+        self.headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| *value)
+    }
+    
+    fn content_length(&self) -> Option<u64> {
+        self.header("Content-Length").and_then(|value| value.parse().ok())
+    }
+    
+    fn chunked(&self) -> bool {
+        self.header("Transfer-Encoding").is_some_and(|value| value.to_ascii_lowercase().contains("chunked"))
+    }
+}
+
+impl HttpRequest {
+    /// Parse a request head (without the blank line that ends it)
+    pub fn parse(head: &[u8]) -> Option<Self> {
+        let head = std::str::from_utf8(head).ok()?;
+        let parsed = Head::parse(head)?;
+        let [method, path, version] = parsed.start_line[..] else { return None };
+        if !METHODS.contains(&method) || !version.starts_with("HTTP/1.") {
+            return None;
+        }
+        
+        // Through a proxy the target is a URL and may be the only place the host is named
+        let host = parsed.header("Host").map(str::to_string).or_else(|| {
+            let rest = path.strip_prefix("http://")?;
+            Some(rest.split('/').next()?.to_string())
+        });
+        Some(Self {
+            method: method.to_string(),
+            path: path.to_string(),
+            version: version.to_string(),
+            host,
+            user_agent: parsed.header("User-Agent").map(str::to_string),
+            content_length: parsed.content_length(),
+            chunked: parsed.chunked(),
+        })
+    }
+}
+
+impl HttpResponse {
+    /// Parse a response head (without the blank line that ends it)
+    pub fn parse(head: &[u8]) -> Option<Self> {
+        let head = std::str::from_utf8(head).ok()?;
+        let parsed = Head::parse(head)?;
+        let (version, status, reason) = match parsed.start_line[..] {
+            [version, status, reason] => (version, status, reason),
+            [version, status] => (version, status, ""),
+            _ => return None,
+        };
+        let status: u16 = status.parse().ok()?;
+        if !version.starts_with("HTTP/1.") || !(100..=599).contains(&status) {
+            return None;
+        }
+        Some(Self {
+            version: version.to_string(),
+            status,
+            reason: reason.to_string(),
+            content_length: parsed.content_length(),
+            chunked: parsed.chunked(),
+        })
+    }
+    
+    /// Whether a body follows; responses to HEAD never have one
+    fn has_body(&self, method: &str) -> bool {
+        method != "HEAD" && !matches!(self.status, 100..=199 | 204 | 304)
+    }
+}
+
+/// What the bytes after a message head are
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Framing {
+    /// Next message head
+    Head,
+    /// This many body bytes left
+    Body(u64),
+    /// Waiting for a chunk-size line
+    ChunkSize,
+    /// This many bytes of chunk data and its CRLF left
+    ChunkData(u64),
+    /// Trailer fields after the last chunk
+    Trailers,
+    /// Body runs until the connection closes; nothing more to parse
+    UntilClose,
+}
+
+/// One direction of a connection
+struct HttpStream {
+    is_response: bool,
+    buffer: Vec<u8>,
+    framing: Framing,
+    /// Time of the packet the buffered head started in
+    started: Option<SystemTime>,
+    /// TCP sequence number of the next byte not yet seen
+    next_seq: Option<u32>,
+}
+
+impl HttpStream {
+    fn new(is_response: bool) -> Self {
+        Self { is_response, buffer: Vec::new(), framing: Framing::Head, started: None, next_seq: None }
+    }
+    
+    /// Append a segment starting at sequence number `seq`, if known
+    fn push(&mut self, payload: &[u8], seq: Option<u32>) {
+        let mut payload = payload;
+        if let Some(seq) = seq {
+            let end = seq.wrapping_add(payload.len() as u32);
+            if let Some(next_seq) = self.next_seq {
+                // Signed distance, so sequence numbers can wrap around
+                let seen = next_seq.wrapping_sub(seq) as i32;
+                if seen > 0 {
+                    // Retransmission: keep only the bytes past what was seen
+                    match payload.get(seen as usize..) {
+                        Some(rest) if !rest.is_empty() => payload = rest,
+                        _ => return,
+                    }
+                } else if seen < 0 {
+                    // A segment went missing
+                    self.lose_sync();
+                }
+            }
+            self.next_seq = Some(end);
+        }
+        
+        if self.framing == Framing::UntilClose {
+            return;
+        }
+        // Lost sync: wait for a packet that starts a new message
+        if self.framing == Framing::Head && self.buffer.is_empty() && !starts_message(payload, self.is_response) {
+            return;
+        }
+        self.buffer.extend_from_slice(payload);
+    }
+    
+    /// Skip body bytes, then take the next complete head off the buffer
+    /// along with the time it started
+    fn next_head(&mut self, timestamp: SystemTime) -> Option<(Vec<u8>, SystemTime)> {
+        loop {
+            match self.framing {
+                Framing::Head => {
+                    if self.buffer.is_empty() {
+                        return None;
+                    }
+                    let started = *self.started.get_or_insert(timestamp);
+                    match self.buffer.windows(4).position(|window| window == b"\r\n\r\n") {
+                        Some(end) => {
+                            let head = self.buffer[..end].to_vec();
+                            self.buffer.drain(..end + 4);
+                            self.started = None;
+                            return Some((head, started));
+                        },
+                        None => {
+                            if self.buffer.len() > MAX_HEAD_BYTES {
+                                self.lose_sync();
+                            }
+                            return None;
+                        },
+                    }
+                },
+                Framing::Body(remaining) | Framing::ChunkData(remaining) => {
+                    let skipped = remaining.min(self.buffer.len() as u64);
+                    self.buffer.drain(..skipped as usize);
+                    if skipped < remaining {
+                        self.framing = match self.framing {
+                            Framing::Body(_) => Framing::Body(remaining - skipped),
+                            _ => Framing::ChunkData(remaining - skipped),
+                        };
+                        return None;
+                    }
+                    self.framing = match self.framing {
+                        Framing::Body(_) => Framing::Head,
+                        _ => Framing::ChunkSize,
+                    };
+                },
+                Framing::ChunkSize => {
+                    let end = self.buffer.windows(2).position(|window| window == b"\r\n")?;
+                    let line = String::from_utf8_lossy(&self.buffer[..end]).to_string();
+                    let size = line.split(';').next().and_then(|size| u64::from_str_radix(size.trim(), 16).ok());
+                    self.buffer.drain(..end + 2);
+                    self.framing = match size {
+                        Some(0) => Framing::Trailers,
+                        // Chunk data is followed by a CRLF
+                        Some(size) => Framing::ChunkData(size.saturating_add(2)),
+                        None => {
+                            self.lose_sync();
+                            return None;
+                        },
+                    };
+                },
+                Framing::Trailers => {
+                    if self.buffer.starts_with(b"\r\n") {
+                        self.buffer.drain(..2);
+                    } else {
+                        let end = self.buffer.windows(4).position(|window| window == b"\r\n\r\n")?;
+                        self.buffer.drain(..end + 4);
+                    }
+                    self.framing = Framing::Head;
+                },
+                Framing::UntilClose => {
+                    self.buffer.clear();
+                    return None;
+                },
+            }
+        }
+    }
+    
+    fn expect_body(&mut self, content_length: Option<u64>, chunked: bool, has_body: bool) {
+        self.framing = match (has_body, chunked, content_length) {
+            (false, _, _) => Framing::Head,
+            (true, true, _) => Framing::ChunkSize,
+            (true, false, Some(length)) => Framing::Body(length),
+            // A request without a length has no body; a response runs to the close
+            (true, false, None) if self.is_response => Framing::UntilClose,
+            (true, false, None) => Framing::Head,
+        };
+    }
+    
+    fn lose_sync(&mut self) {
+        self.buffer.clear();
+        self.framing = Framing::Head;
+        self.started = None;
+    }
+}
+
+struct HttpConnection {
+    client: SocketAddr,
+    requests: HttpStream,
+    responses: HttpStream,
+    pending: VecDeque<(HttpRequest, SystemTime)>,
+}
+
+impl HttpConnection {
+    fn new(client: SocketAddr) -> Self {
+        Self {
+            client,
+            requests: HttpStream::new(false),
+            responses: HttpStream::new(true),
+            pending: VecDeque::new(),
+        }
+    }
+    
+    fn feed_requests(&mut self, payload: &[u8], seq: Option<u32>, timestamp: SystemTime) {
+        self.requests.push(payload, seq);
+        while let Some((head, started)) = self.requests.next_head(timestamp) {
+            let Some(request) = HttpRequest::parse(&head) else {
+                self.requests.lose_sync();
+                break;
+            };
+            self.requests.expect_body(request.content_length, request.chunked, true);
+            if self.pending.len() >= MAX_PENDING_REQUESTS {
+                self.pending.pop_front();
+            }
+            self.pending.push_back((request, started));
+        }
+    }
+    
+    fn feed_responses(&mut self, payload: &[u8], seq: Option<u32>, timestamp: SystemTime) -> Vec<HttpTransaction> {
+        let mut transactions = Vec::new();
+        self.responses.push(payload, seq);
+        while let Some((head, started)) = self.responses.next_head(timestamp) {
+            let Some(response) = HttpResponse::parse(&head) else {
+                self.responses.lose_sync();
+                break;
+            };
+            // Interim responses (100 Continue) don't answer the request
+            if (100..=199).contains(&response.status) {
+                self.responses.expect_body(None, false, false);
+                continue;
+            }
+            // A response to a request sent before the capture started
+            let Some((request, requested)) = self.pending.pop_front() else {
+                self.responses.expect_body(response.content_length, response.chunked, response.has_body("GET"));
+                continue;
+            };
+            self.responses.expect_body(response.content_length, response.chunked, response.has_body(&request.method));
+            transactions.push(HttpTransaction {
+                timestamp: requested,
+                method: request.method,
+                host: request.host,
+                path: request.path,
+                user_agent: request.user_agent,
+                request_content_length: request.content_length,
+                status: response.status,
+                reason: response.reason,
+                response_content_length: response.content_length,
+                latency: started.duration_since(requested).unwrap_or_default(),
+            });
+        }
+        transactions
+    }
+}
+
+/// Follows plaintext HTTP/1.x over TCP payloads and pairs each request with
+/// its response. Payloads are taken in arrival order. Given sequence
+/// numbers, bytes that were already seen are dropped, and a missing segment
+/// makes a direction wait for the next packet that starts a message.
+#[derive(Default)]
+pub struct HttpReassembler {
+    connections: HashMap<(SocketAddr, SocketAddr), HttpConnection>,
+}
+
+impl HttpReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Add a TCP payload sent from `src` to `dst` with the sequence number of
+    /// its first byte; returns the transactions whose response started in it
+    pub fn feed(
+        &mut self,
+        src: SocketAddr,
+        dst: SocketAddr,
+        payload: &[u8],
+        seq: Option<u32>,
+        timestamp: SystemTime,
+    ) -> Vec<HttpTransaction> {
+        if payload.is_empty() {
+            return Vec::new();
+        }
+        let key = connection_key(src, dst);
+        if !self.connections.contains_key(&key) {
+            // The first message seen tells which end is the client
+            let client = if starts_message(payload, false) {
+                src
+            } else if starts_message(payload, true) {
+                dst
+            } else {
+                return Vec::new();
+            };
+            if self.connections.len() >= MAX_CONNECTIONS {
+                self.connections.clear();
+            }
+            self.connections.insert(key, HttpConnection::new(client));
+        }
+        
+        let connection = self.connections.get_mut(&key).unwrap();
+        if src == connection.client {
+            connection.feed_requests(payload, seq, timestamp);
+            Vec::new()
+        } else {
+            connection.feed_responses(payload, seq, timestamp)
+        }
+    }
+    
+    /// Forget a connection once it is closed
+    pub fn close(&mut self, src: SocketAddr, dst: SocketAddr) {
+        self.connections.remove(&connection_key(src, dst));
+    }
+    
+    pub fn is_tracking(&self, src: SocketAddr, dst: SocketAddr) -> bool {
+        self.connections.contains_key(&connection_key(src, dst))
+    }
+}
+
+/// Whether a payload begins with a request line (or a status line for responses)
+fn starts_message(payload: &[u8], is_response: bool) -> bool {
+    if is_response {
+        return payload.starts_with(b"HTTP/1.");
+    }
+    METHODS.iter().any(|method| payload.starts_with(method.as_bytes()) && payload.get(method.len()) == Some(&b' '))
+}
+
+fn connection_key(src: SocketAddr, dst: SocketAddr) -> (SocketAddr, SocketAddr) {
+    if src <= dst { (src, dst) } else { (dst, src) }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct EndpointStats {
+    pub host: String,
+    /// Path without the query string
+    pub path: String,
+    pub requests: u64,
+    /// Responses with a 4xx or 5xx status
+    pub errors: u64,
+    pub total_latency: Duration,
+    pub max_latency: Duration,
+}
+
+impl EndpointStats {
+    pub fn average_latency(&self) -> Duration {
+        self.total_latency / self.requests.max(1) as u32
+    }
+}
+
+/// Status codes and per-endpoint latency over all transactions seen
+#[derive(Default)]
+pub struct HttpStats {
+    transactions: u64,
+    status_counts: BTreeMap<u16, u64>,
+    endpoints: HashMap<(String, String), EndpointStats>,
+}
+
+impl HttpStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Count a transaction; endpoints beyond the first `MAX_ENDPOINTS` only
+    /// count towards the status codes
+    pub fn record(&mut self, transaction: &HttpTransaction) {
+        self.transactions += 1;
+        *self.status_counts.entry(transaction.status).or_insert(0) += 1;
+        
+        let host = transaction.host.clone().unwrap_or_else(|| "-".to_string());
+        let path = transaction.path.split('?').next().unwrap_or_default().to_string();
+        let key = (host, path);
+        if !self.endpoints.contains_key(&key) && self.endpoints.len() >= MAX_ENDPOINTS {
+            return;
+        }
+        let endpoint = self.endpoints.entry(key.clone()).or_insert_with(|| EndpointStats {
+            host: key.0,
+            path: key.1,
+            requests: 0,
+            errors: 0,
+            total_latency: Duration::ZERO,
+            max_latency: Duration::ZERO,
+        });
+        endpoint.requests += 1;
+        if transaction.status >= 400 {
+            endpoint.errors += 1;
+        }
+        endpoint.total_latency += transaction.latency;
+        endpoint.max_latency = endpoint.max_latency.max(transaction.latency);
+    }
+    
+    pub fn total_transactions(&self) -> u64 {
+        self.transactions
+    }
+    
+    /// Count per status code, lowest code first
+    pub fn status_counts(&self) -> Vec<(u16, u64)> {
+        self.status_counts.iter().map(|(status, count)| (*status, *count)).collect()
+    }
+    
+    /// Endpoints by average latency, slowest first
+    pub fn slowest_endpoints(&self, limit: usize) -> Vec<EndpointStats> {
+        let mut endpoints: Vec<EndpointStats> = self.endpoints.values().cloned().collect();
+        endpoints.sort_by(|a, b| b.average_latency().cmp(&a.average_latency()).then_with(|| a.path.cmp(&b.path)));
+        endpoints.truncate(limit);
+        endpoints
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn at(ms: u64) -> SystemTime {
+        SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000) + Duration::from_millis(ms)
+    }
+    
+    #[test]
+    fn test_parse_heads() {
+        let request = HttpRequest::parse(
+            b"POST /api/items?page=2 HTTP/1.1\r\nHost: shop.example.com\r\nuser-agent: curl/8.5.0\r\nContent-Length: 12"
+        ).unwrap();
+        assert_eq!(request.method, "POST");
+        assert_eq!(request.path, "/api/items?page=2");
+        assert_eq!(request.host.as_deref(), Some("shop.example.com"));
+        assert_eq!(request.user_agent.as_deref(), Some("curl/8.5.0"));
+        assert_eq!(request.content_length, Some(12));
+        
+        let proxied = HttpRequest::parse(b"GET http://example.org/index.html HTTP/1.0").unwrap();
+        assert_eq!(proxied.host.as_deref(), Some("example.org"));
+        
+        let response = HttpResponse::parse(b"HTTP/1.1 404 Not Found\r\nTransfer-Encoding: chunked").unwrap();
+        assert_eq!((response.status, response.reason.as_str(), response.chunked), (404, "Not Found", true));
+        
+        assert!(HttpRequest::parse(b"FETCH / HTTP/1.1").is_none());
+        assert!(HttpResponse::parse(b"HTTP/1.1 abc OK").is_none());
+    }
+    
+    #[test]
+    fn test_pairs_requests_and_responses_across_segments() {
+        let client: SocketAddr = "192.168.1.10:50000".parse().unwrap();
+        let server: SocketAddr = "93.184.216.34:80".parse().unwrap();
+        let mut http = HttpReassembler::new();
+        
+        // Two pipelined requests, the first split across segments and carrying a body
+        assert!(http.feed(client, server, b"POST /login HTTP/1.1\r\nHost: example.com\r\nCont", None, at(0)).is_empty());
+        assert!(http.feed(client, server, b"ent-Length: 5\r\n\r\nhello", None, at(1)).is_empty());
+        assert!(http.feed(client, server, b"HEAD /status HTTP/1.1\r\nHost: example.com\r\n\r\n", None, at(2)).is_empty());
+        
+        // Chunked body, then the HEAD response whose Content-Length has no body behind it
+        let first = http.feed(server, client, b"HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n5\r\nhel", None, at(40));
+        assert_eq!(first.len(), 1);
+        assert_eq!((first[0].method.as_str(), first[0].path.as_str(), first[0].status), ("POST", "/login", 200));
+        assert_eq!(first[0].request_content_length, Some(5));
+        assert_eq!(first[0].latency, Duration::from_millis(40));
+        
+        let second = http.feed(server, client, b"lo\r\n0\r\n\r\nHTTP/1.1 503 Busy\r\nContent-Length: 1000\r\n\r\n", None, at(90));
+        assert_eq!(second.len(), 1);
+        assert_eq!((second[0].method.as_str(), second[0].status, second[0].response_content_length), ("HEAD", 503, Some(1000)));
+        assert_eq!(second[0].latency, Duration::from_millis(88));
+        
+        // The next response follows right away since HEAD had no body
+        http.feed(client, server, b"GET /a?x=1 HTTP/1.1\r\nHost: example.com\r\n\r\n", None, at(100));
+        let third = http.feed(server, client, b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n", None, at(110));
+        assert_eq!(third.len(), 1);
+        
+        let mut stats = HttpStats::new();
+        for transaction in first.iter().chain(&second).chain(&third) {
+            stats.record(transaction);
+        }
+        assert_eq!(stats.total_transactions(), 3);
+        assert_eq!(stats.status_counts(), vec![(200, 2), (503, 1)]);
+        let slowest = stats.slowest_endpoints(2);
+        assert_eq!((slowest[0].path.as_str(), slowest[0].errors), ("/status", 1));
+        assert_eq!(slowest[1].path, "/login");
+        assert_eq!(stats.slowest_endpoints(5)[2].path, "/a");
+        
+        // Encrypted or unrelated traffic never starts a connection
+        let other: SocketAddr = "192.168.1.10:50001".parse().unwrap();
+        assert!(http.feed(other, server, &[0x16, 0x03, 0x01, 0x00, 0x05], None, at(120)).is_empty());
+        assert!(!http.is_tracking(other, server));
+        http.close(server, client);
+        assert!(!http.is_tracking(client, server));
+    }
+    
+    #[test]
+    fn test_retransmitted_segments() {
+        let client: SocketAddr = "192.168.1.10:50000".parse().unwrap();
+        let server: SocketAddr = "93.184.216.34:80".parse().unwrap();
+        let mut http = HttpReassembler::new();
+        let first = b"GET /a HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let second = b"GET /b HTTP/1.1\r\nHost: example.com\r\n\r\n";
+        let second_seq = 1000 + first.len() as u32;
+        
+        // A request sent twice, then one whose retransmission carries more
+        // than the original segment did
+        http.feed(client, server, first, Some(1000), at(0));
+        http.feed(client, server, first, Some(1000), at(10));
+        http.feed(client, server, &second[..10], Some(second_seq), at(20));
+        http.feed(client, server, second, Some(second_seq), at(30));
+        
+        let response = b"HTTP/1.1 204 No Content\r\n\r\n";
+        let response_seq = u32::MAX - 4;
+        let answered = http.feed(server, client, response, Some(response_seq), at(40));
+        assert_eq!(answered.len(), 1);
+        assert_eq!(answered[0].path, "/a");
+        assert!(http.feed(server, client, response, Some(response_seq), at(50)).is_empty());
+        
+        // Sequence numbers wrap around
+        let next_seq = response_seq.wrapping_add(response.len() as u32);
+        let answered = http.feed(server, client, response, Some(next_seq), at(60));
+        assert_eq!(answered.len(), 1);
+        assert_eq!(answered[0].path, "/b");
+        
+        // After a lost segment the next message is picked up again
+        http.feed(client, server, b"GET /c HTTP/1.1\r\nHost: exa", Some(5000), at(70));
+        http.feed(client, server, b"GET /d HTTP/1.1\r\nHost: example.com\r\n\r\n", Some(6000), at(80));
+        let next_seq = next_seq.wrapping_add(response.len() as u32);
+        let answered = http.feed(server, client, response, Some(next_seq), at(90));
+        assert_eq!(answered.len(), 1);
+        assert_eq!(answered[0].path, "/d");
+    }
+}
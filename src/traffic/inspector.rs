@@ -3,6 +3,12 @@ use std::net::{IpAddr, SocketAddr};
 use std::time::{SystemTime, Duration};
 use crate::capture::PacketInfo;
 use crate::analysis::protocols::ProtocolType;
+use crate::traffic::tls::{HandshakeReassembler, TlsInfo};
+use crate::traffic::http::{HttpReassembler, HttpStats, HttpTransaction};
+use pnet::packet::tcp::TcpFlags;
+
+/// HTTP transactions kept on each flow, newest last
+const MAX_FLOW_TRANSACTIONS: usize = 50;
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
 pub enum FlowDirection {
@@ -18,14 +24,22 @@ pub struct TrafficFlow {
     pub src_addr: SocketAddr,
     pub dst_addr: SocketAddr,
     pub protocol: ProtocolType,
+    /// Transport protocol as reported by the capture ("TCP", "UDP", ...)
+    pub transport: String,
     pub direction: FlowDirection,
     pub start_time: SystemTime,
     pub last_seen: SystemTime,
     pub packet_count: u64,
     pub byte_count: u64,
+    pub src_bytes: u64, // bytes sent by src_addr
+    pub dst_bytes: u64, // bytes sent by dst_addr
     pub packets_per_second: f64,
     pub bytes_per_second: f64,
     pub is_active: bool,
+    /// Handshake details, once a TLS hello has been seen on the flow
+    pub tls: Option<TlsInfo>,
+    /// Plaintext HTTP/1.x requests answered on the flow
+    pub http: Vec<HttpTransaction>,
 }
 
 #[derive(Debug, Clone)]
@@ -64,6 +78,9 @@ pub struct TrafficInspector {
     max_flows: usize,
     max_events: usize,
     local_networks: Vec<ipnetwork::IpNetwork>,
+    tls_handshakes: HandshakeReassembler,
+    http: HttpReassembler,
+    http_stats: HttpStats,
 }
 
 impl TrafficInspector {
@@ -78,6 +95,9 @@ impl TrafficInspector {
             max_flows: 10000,
             max_events: 1000,
             local_networks: Vec::new(),
+            tls_handshakes: HandshakeReassembler::new(),
+            http: HttpReassembler::new(),
+            http_stats: HttpStats::new(),
         };
         
         // Initialize common local networks
@@ -85,6 +105,9 @@ impl TrafficInspector {
         inspector.add_local_network("10.0.0.0/8").ok(); // Private Class A
         inspector.add_local_network("172.16.0.0/12").ok(); // Private Class B
         inspector.add_local_network("192.168.0.0/16").ok(); // Private Class C
+        inspector.add_local_network("::1/128").ok(); // IPv6 loopback
+        inspector.add_local_network("fc00::/7").ok(); // IPv6 unique local
+        inspector.add_local_network("fe80::/10").ok(); // IPv6 link-local
         
         inspector
     }
@@ -110,83 +133,106 @@ impl TrafficInspector {
     }
     
     pub fn inspect_packet(&mut self, packet: &PacketInfo, protocol: ProtocolType) {
-        if let (Some(src_ip), Some(dst_ip), Some(src_port), Some(dst_port)) = 
-            (&packet.src_ip, &packet.dst_ip, packet.src_port, packet.dst_port) {
+        if let Some((src_addr, dst_addr)) = packet.socket_addrs() {
+            let flow_id = self.generate_flow_id(&src_addr, &dst_addr);
+            let direction = self.determine_flow_direction(&src_addr, &dst_addr);
+            // Use the capture timestamp so replayed traffic keeps its original timing
+            let now = packet.timestamp;
             
-            if let (Ok(src_addr), Ok(dst_addr)) = (
-                format!("{}:{}", src_ip, src_port).parse::<SocketAddr>(),
-                format!("{}:{}", dst_ip, dst_port).parse::<SocketAddr>()
-            ) {
-                let flow_id = self.generate_flow_id(&src_addr, &dst_addr);
-                let direction = self.determine_flow_direction(&src_addr, &dst_addr);
-                let now = SystemTime::now();
-                
-                // Update or create flow
-                // Check if flow exists or create new one
-                let flow_exists = self.active_flows.contains_key(&flow_id);
-                if !flow_exists {
-                    let new_flow = TrafficFlow {
-                        flow_id: flow_id.clone(),
-                        src_addr,
-                        dst_addr,
-                        protocol: protocol.clone(),
-                        direction,
-                        start_time: now,
-                        last_seen: now,
-                        packet_count: 0,
-                        byte_count: 0,
-                        packets_per_second: 0.0,
-                        bytes_per_second: 0.0,
-                        is_active: true,
-                    };
-                    
-                    self.active_flows.insert(flow_id.clone(), new_flow);
-                    
-                    // Generate flow started event
-                    self.add_event(TrafficEvent {
-                        timestamp: now,
-                        event_type: TrafficEventType::FlowStarted,
-                        flow_id: flow_id.clone(),
-                        description: format!("New {} flow: {} -> {}", protocol, src_addr, dst_addr),
-                        severity: EventSeverity::Info,
-                    });
-                }
-                
-                let flow = self.active_flows.get_mut(&flow_id).unwrap();
+            // Update or create flow
+            // Check if flow exists or create new one
+            let flow_exists = self.active_flows.contains_key(&flow_id);
+            if !flow_exists {
+                let new_flow = TrafficFlow {
+                    flow_id: flow_id.clone(),
+                    src_addr,
+                    dst_addr,
+                    protocol: protocol.clone(),
+                    transport: packet.protocol.clone(),
+                    direction,
+                    start_time: now,
+                    last_seen: now,
+                    packet_count: 0,
+                    byte_count: 0,
+                    src_bytes: 0,
+                    dst_bytes: 0,
+                    packets_per_second: 0.0,
+                    bytes_per_second: 0.0,
+                    is_active: true,
+                    tls: None,
+                    http: Vec::new(),
+                };
                 
-                // Update flow statistics
-                flow.packet_count += 1;
-                flow.byte_count += packet.length as u64;
-                flow.last_seen = now;
-                flow.protocol = protocol;
+                self.active_flows.insert(flow_id.clone(), new_flow);
                 
-                // Calculate rates (simplified - using last update time)
-                if let Ok(duration) = now.duration_since(flow.start_time) {
-                    let seconds = duration.as_secs_f64();
-                    if seconds > 0.0 {
-                        flow.packets_per_second = flow.packet_count as f64 / seconds;
-                        flow.bytes_per_second = flow.byte_count as f64 / seconds;
-                        
-                        // Check for high bandwidth events (moved outside to avoid borrow issues)
-                        let should_alert = flow.bytes_per_second > self.bandwidth_threshold as f64;
-                        if should_alert {
-                            let bandwidth_mb = flow.bytes_per_second / 1_000_000.0;
-                            let _ = flow; // Release borrow before calling add_event
-                            self.add_event(TrafficEvent {
-                                timestamp: now,
-                                event_type: TrafficEventType::HighBandwidth,
-                                flow_id: flow_id.clone(),
-                                description: format!("High bandwidth detected: {:.2} MB/s", bandwidth_mb),
-                                severity: EventSeverity::Warning,
-                            });
-                        }
+                // Generate flow started event
+                self.add_event(TrafficEvent {
+                    timestamp: now,
+                    event_type: TrafficEventType::FlowStarted,
+                    flow_id: flow_id.clone(),
+                    description: format!("New {} flow: {} -> {}", protocol, src_addr, dst_addr),
+                    severity: EventSeverity::Info,
+                });
+            }
+            
+            let flow = self.active_flows.get_mut(&flow_id).unwrap();
+            
+            // Update flow statistics
+            flow.packet_count += 1;
+            flow.byte_count += packet.length as u64;
+            if src_addr == flow.src_addr {
+                flow.src_bytes += packet.length as u64;
+            } else {
+                flow.dst_bytes += packet.length as u64;
+            }
+            flow.last_seen = now;
+            flow.protocol = protocol;
+            
+            if packet.protocol == "TCP" && !flow.tls.as_ref().is_some_and(TlsInfo::is_complete) {
+                if let Some(handshake) = self.tls_handshakes.feed(src_addr, dst_addr, &packet.payload) {
+                    flow.tls.get_or_insert_with(TlsInfo::default).apply(handshake);
+                }
+            }
+            
+            if packet.protocol == "TCP" && (flow.protocol == ProtocolType::Http || self.http.is_tracking(src_addr, dst_addr)) {
+                for transaction in self.http.feed(src_addr, dst_addr, &packet.payload, packet.tcp_seq, now) {
+                    self.http_stats.record(&transaction);
+                    if flow.http.len() >= MAX_FLOW_TRANSACTIONS {
+                        flow.http.remove(0);
+                    }
+                    flow.http.push(transaction);
+                }
+                if packet.tcp_flags.is_some_and(|flags| flags & (TcpFlags::FIN | TcpFlags::RST) != 0) {
+                    self.http.close(src_addr, dst_addr);
+                }
+            }
+            
+            // Calculate rates (simplified - using last update time)
+            if let Ok(duration) = now.duration_since(flow.start_time) {
+                let seconds = duration.as_secs_f64();
+                if seconds > 0.0 {
+                    flow.packets_per_second = flow.packet_count as f64 / seconds;
+                    flow.bytes_per_second = flow.byte_count as f64 / seconds;
+                    
+                    // Check for high bandwidth events (moved outside to avoid borrow issues)
+                    let should_alert = flow.bytes_per_second > self.bandwidth_threshold as f64;
+                    if should_alert {
+                        let bandwidth_mb = flow.bytes_per_second / 1_000_000.0;
+                        let _ = flow; // Release borrow before calling add_event
+                        self.add_event(TrafficEvent {
+                            timestamp: now,
+                            event_type: TrafficEventType::HighBandwidth,
+                            flow_id: flow_id.clone(),
+                            description: format!("High bandwidth detected: {:.2} MB/s", bandwidth_mb),
+                            severity: EventSeverity::Warning,
+                        });
                     }
                 }
             }
         }
         
         // Cleanup old flows
-        self.cleanup_expired_flows();
+        self.cleanup_expired_flows(packet.timestamp);
     }
     
     fn generate_flow_id(&self, src: &SocketAddr, dst: &SocketAddr) -> String {
@@ -214,8 +260,7 @@ impl TrafficInspector {
         self.local_networks.iter().any(|network| network.contains(*addr))
     }
     
-    fn cleanup_expired_flows(&mut self) {
-        let now = SystemTime::now();
+    fn cleanup_expired_flows(&mut self, now: SystemTime) {
         let timeout = self.flow_timeout;
         
         // Move expired flows to history
@@ -262,6 +307,16 @@ impl TrafficInspector {
         &self.active_flows
     }
     
+    /// Status codes and endpoint latency of every HTTP transaction seen
+    pub fn get_http_stats(&self) -> &HttpStats {
+        &self.http_stats
+    }
+    
+    /// Flows that timed out, oldest first
+    pub fn get_flow_history(&self) -> &VecDeque<TrafficFlow> {
+        &self.flow_history
+    }
+    
     pub fn get_flows_by_direction(&self, direction: FlowDirection) -> Vec<&TrafficFlow> {
         self.active_flows
             .values()
@@ -354,7 +409,7 @@ impl std::fmt::Display for EventSeverity {
 #[cfg(test)]
 mod tests {
     use super::*;
-
+    
     #[test]
     fn test_traffic_inspector_creation() {
         let inspector = TrafficInspector::new();
@@ -378,4 +433,30 @@ mod tests {
             FlowDirection::Inbound
         );
     }
+    
+    #[test]
+    fn test_ipv6_flow_tracking() {
+        let mut inspector = TrafficInspector::new();
+        
+        let packet = PacketInfo {
+            timestamp: SystemTime::now(),
+            length: 1280,
+            protocol: "TCP".to_string(),
+            src_ip: Some("fd00::10".to_string()),
+            dst_ip: Some("2606:4700::1111".to_string()),
+            src_port: Some(51000),
+            dst_port: Some(443),
+            tcp_flags: None,
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
+        };
+        
+        inspector.inspect_packet(&packet, ProtocolType::Https);
+        
+        let flow = inspector.get_active_flows().values().next().unwrap();
+        assert_eq!(flow.src_addr.to_string(), "[fd00::10]:51000");
+        assert_eq!(flow.direction, FlowDirection::Outbound);
+        assert_eq!(flow.byte_count, 1280);
+    }
 }
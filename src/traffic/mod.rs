@@ -1,5 +1,13 @@
 pub mod inspector;
 pub mod analyzer;
+pub mod tls;
+pub mod dns;
+pub mod hostnames;
+pub mod http;
 
 pub use inspector::{TrafficInspector, TrafficFlow, FlowDirection, TrafficEvent};
+pub use tls::{TlsInfo, TlsHandshake, HandshakeReassembler};
+pub use dns::{DnsTracker, DnsMessage, DnsTransaction};
+pub use hostnames::HostnameCache;
+pub use http::{HttpTransaction, HttpStats};
 pub use analyzer::{TrafficAnalyzer, TrafficPattern, BandwidthAnalysis, ProtocolBreakdown};
@@ -0,0 +1,539 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use md5::{Digest, Md5};
+use sha2::Sha256;
+
+/// Handshake messages larger than this (after reassembly) are given up on
+const MAX_HANDSHAKE_BYTES: usize = 16 * 1024;
+/// Half-finished handshakes kept at once before the buffer is reset
+const MAX_PENDING_HANDSHAKES: usize = 1024;
+
+const EXTENSION_SNI: u16 = 0x0000;
+const EXTENSION_SUPPORTED_GROUPS: u16 = 0x000a;
+const EXTENSION_EC_POINT_FORMATS: u16 = 0x000b;
+const EXTENSION_SIGNATURE_ALGORITHMS: u16 = 0x000d;
+const EXTENSION_ALPN: u16 = 0x0010;
+const EXTENSION_SUPPORTED_VERSIONS: u16 = 0x002b;
+
+/// What the handshake of a TLS flow revealed. Everything after the hellos is
+/// encrypted, so this is all there is to know without the keys.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TlsInfo {
+    /// Server name the client asked for
+    pub sni: Option<String>,
+    /// Application protocols the client offered, in its order of preference
+    pub alpn_offered: Vec<String>,
+    /// Application protocol the server picked
+    pub alpn: Option<String>,
+    /// Negotiated version (0x0303 for TLS 1.2, 0x0304 for TLS 1.3)
+    pub version: Option<u16>,
+    /// Negotiated cipher suite
+    pub cipher: Option<u16>,
+    /// JA3 fingerprint of the ClientHello (MD5, hex)
+    pub ja3: Option<String>,
+    /// JA4 fingerprint of the ClientHello
+    pub ja4: Option<String>,
+}
+
+impl TlsInfo {
+    pub fn apply(&mut self, handshake: TlsHandshake) {
+        match handshake {
+            TlsHandshake::ClientHello(hello) => {
+                self.ja3 = Some(hello.ja3());
+                self.ja4 = Some(hello.ja4());
+                self.sni = hello.sni;
+                self.alpn_offered = hello.alpn;
+            },
+            TlsHandshake::ServerHello(hello) => {
+                self.version = Some(hello.version);
+                self.cipher = Some(hello.cipher_suite);
+                self.alpn = hello.alpn;
+            },
+        }
+    }
+    
+    /// Both hellos have been seen
+    pub fn is_complete(&self) -> bool {
+        self.ja3.is_some() && self.cipher.is_some()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum TlsHandshake {
+    ClientHello(ClientHello),
+    ServerHello(ServerHello),
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClientHello {
+    pub legacy_version: u16,
+    pub cipher_suites: Vec<u16>,
+    /// Extension types in the order they were sent
+    pub extensions: Vec<u16>,
+    pub sni: Option<String>,
+    pub alpn: Vec<String>,
+    pub supported_groups: Vec<u16>,
+    pub ec_point_formats: Vec<u8>,
+    pub signature_algorithms: Vec<u16>,
+    pub supported_versions: Vec<u16>,
+}
+
+impl ClientHello {
+    /// Parse the body of a ClientHello handshake message (after the 4-byte
+    /// handshake header)
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(body);
+        let mut hello = ClientHello {
+            legacy_version: reader.u16()?,
+            ..Default::default()
+        };
+        reader.take(32)?; // random
+        let session_id = reader.u8()? as usize;
+        reader.take(session_id)?;
+        let cipher_suites = reader.u16()? as usize;
+        hello.cipher_suites = u16_list(reader.take(cipher_suites)?);
+        let compression_methods = reader.u8()? as usize;
+        reader.take(compression_methods)?;
+        
+        // Extensions are optional before TLS 1.3
+        if reader.is_empty() {
+            return Some(hello);
+        }
+        let extensions_len = reader.u16()? as usize;
+        let mut extensions = Reader::new(reader.take(extensions_len)?);
+        while !extensions.is_empty() {
+            let extension = extensions.u16()?;
+            let len = extensions.u16()? as usize;
+            let mut data = Reader::new(extensions.take(len)?);
+            hello.extensions.push(extension);
+            
+            match extension {
+                EXTENSION_SNI => {
+                    // Server name list; only host names (type 0) exist
+                    data.u16()?;
+                    if data.u8()? == 0 {
+                        let name_len = data.u16()? as usize;
+                        hello.sni = Some(String::from_utf8_lossy(data.take(name_len)?).into_owned());
+                    }
+                },
+                EXTENSION_ALPN => hello.alpn = alpn_list(&mut data)?,
+                EXTENSION_SUPPORTED_GROUPS => {
+                    let len = data.u16()? as usize;
+                    hello.supported_groups = u16_list(data.take(len)?);
+                },
+                EXTENSION_EC_POINT_FORMATS => {
+                    let len = data.u8()? as usize;
+                    hello.ec_point_formats = data.take(len)?.to_vec();
+                },
+                EXTENSION_SIGNATURE_ALGORITHMS => {
+                    let len = data.u16()? as usize;
+                    hello.signature_algorithms = u16_list(data.take(len)?);
+                },
+                EXTENSION_SUPPORTED_VERSIONS => {
+                    let len = data.u8()? as usize;
+                    hello.supported_versions = u16_list(data.take(len)?);
+                },
+                _ => {},
+            }
+        }
+        Some(hello)
+    }
+    
+    /// `version,ciphers,extensions,groups,point formats`, decimal values
+    /// joined with `-` and GREASE values left out
+    pub fn ja3_string(&self) -> String {
+        let join = |values: Vec<String>| values.join("-");
+        let decimal = |values: &[u16]| values.iter().filter(|value| !is_grease(**value)).map(u16::to_string).collect();
+        format!(
+            "{},{},{},{},{}",
+            self.legacy_version,
+            join(decimal(&self.cipher_suites)),
+            join(decimal(&self.extensions)),
+            join(decimal(&self.supported_groups)),
+            join(self.ec_point_formats.iter().map(u8::to_string).collect()),
+        )
+    }
+    
+    pub fn ja3(&self) -> String {
+        format!("{:x}", Md5::digest(self.ja3_string().as_bytes()))
+    }
+    
+    /// JA4 (TCP): `t<version><d|i><ciphers><extensions><alpn>_<cipher hash>_<extension hash>`
+    pub fn ja4(&self) -> String {
+        let ciphers: Vec<u16> = self.cipher_suites.iter().copied().filter(|value| !is_grease(*value)).collect();
+        let extensions: Vec<u16> = self.extensions.iter().copied().filter(|value| !is_grease(*value)).collect();
+        
+        let version = self.supported_versions
+            .iter()
+            .copied()
+            .filter(|value| !is_grease(*value))
+            .max()
+            .unwrap_or(self.legacy_version);
+        let version = match version {
+            0x0304 => "13",
+            0x0303 => "12",
+            0x0302 => "11",
+            0x0301 => "10",
+            0x0300 => "s3",
+            0x0002 => "s2",
+            _ => "00",
+        };
+        let destination = if self.sni.is_some() { 'd' } else { 'i' };
+        let alpn = match self.alpn.first().map(String::as_bytes) {
+            Some([first, .., last]) if first.is_ascii_alphanumeric() && last.is_ascii_alphanumeric() => {
+                format!("{}{}", *first as char, *last as char)
+            },
+            Some([only]) if only.is_ascii_alphanumeric() => format!("{0}{0}", *only as char),
+            Some(bytes) if !bytes.is_empty() => {
+                let hex: String = bytes.iter().map(|byte| format!("{:02x}", byte)).collect();
+                format!("{}{}", &hex[..1], &hex[hex.len() - 1..])
+            },
+            _ => "00".to_string(),
+        };
+        let prefix = format!(
+            "t{}{}{:02}{:02}{}",
+            version,
+            destination,
+            ciphers.len().min(99),
+            extensions.len().min(99),
+            alpn,
+        );
+        
+        let mut sorted_ciphers = ciphers;
+        sorted_ciphers.sort_unstable();
+        let mut sorted_extensions: Vec<u16> = extensions
+            .into_iter()
+            .filter(|extension| *extension != EXTENSION_SNI && *extension != EXTENSION_ALPN)
+            .collect();
+        sorted_extensions.sort_unstable();
+        
+        let mut extension_text = hex_list(&sorted_extensions);
+        if !self.signature_algorithms.is_empty() {
+            extension_text = format!("{}_{}", extension_text, hex_list(&self.signature_algorithms));
+        }
+        format!(
+            "{}_{}_{}",
+            prefix,
+            truncated_hash(&sorted_ciphers, &hex_list(&sorted_ciphers)),
+            truncated_hash(&sorted_extensions, &extension_text),
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ServerHello {
+    /// Negotiated version, from supported_versions when present
+    pub version: u16,
+    pub cipher_suite: u16,
+    pub alpn: Option<String>,
+}
+
+impl ServerHello {
+    pub fn parse(body: &[u8]) -> Option<Self> {
+        let mut reader = Reader::new(body);
+        let mut version = reader.u16()?;
+        reader.take(32)?;
+        let session_id = reader.u8()? as usize;
+        reader.take(session_id)?;
+        let cipher_suite = reader.u16()?;
+        reader.u8()?; // compression method
+        
+        let mut alpn = None;
+        if !reader.is_empty() {
+            let extensions_len = reader.u16()? as usize;
+            let mut extensions = Reader::new(reader.take(extensions_len)?);
+            while !extensions.is_empty() {
+                let extension = extensions.u16()?;
+                let len = extensions.u16()? as usize;
+                let mut data = Reader::new(extensions.take(len)?);
+                match extension {
+                    EXTENSION_SUPPORTED_VERSIONS => version = data.u16()?,
+                    EXTENSION_ALPN => alpn = alpn_list(&mut data)?.into_iter().next(),
+                    _ => {},
+                }
+            }
+        }
+        Some(ServerHello { version, cipher_suite, alpn })
+    }
+}
+
+/// Collects the first handshake message of each direction of a TCP stream,
+/// which may be split across TLS records and TCP segments. Segments are
+/// taken in arrival order.
+#[derive(Default)]
+pub struct HandshakeReassembler {
+    pending: HashMap<(SocketAddr, SocketAddr), Vec<u8>>,
+}
+
+enum Reassembly {
+    Complete(Option<TlsHandshake>),
+    NeedMore,
+    Invalid,
+}
+
+impl HandshakeReassembler {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    
+    /// Add a TCP payload sent from `src` to `dst`; returns the hello once
+    /// the whole message has arrived
+    pub fn feed(&mut self, src: SocketAddr, dst: SocketAddr, payload: &[u8]) -> Option<TlsHandshake> {
+        let key = (src, dst);
+        let buffer = match self.pending.get_mut(&key) {
+            Some(buffer) => {
+                buffer.extend_from_slice(payload);
+                buffer
+            },
+            None => {
+                // Only a handshake record can start a hello
+                if payload.len() < 3 || payload[0] != 0x16 || payload[1] != 3 {
+                    return None;
+                }
+                if self.pending.len() >= MAX_PENDING_HANDSHAKES {
+                    self.pending.clear();
+                }
+                self.pending.entry(key).or_insert_with(|| payload.to_vec())
+            },
+        };
+        
+        match reassemble(buffer) {
+            Reassembly::NeedMore if buffer.len() <= MAX_HANDSHAKE_BYTES => None,
+            Reassembly::Complete(handshake) => {
+                self.pending.remove(&key);
+                handshake
+            },
+            _ => {
+                self.pending.remove(&key);
+                None
+            },
+        }
+    }
+}
+
+/// Concatenate handshake records until the first message is complete
+fn reassemble(data: &[u8]) -> Reassembly {
+    let mut message = Vec::new();
+    let mut offset = 0;
+    
+    loop {
+        if let Some(complete) = complete_message(&message) {
+            return complete;
+        }
+        
+        let Some(header) = data.get(offset..offset + 5) else {
+            return Reassembly::NeedMore;
+        };
+        if header[0] != 0x16 || header[1] != 3 {
+            return Reassembly::Invalid;
+        }
+        let record_len = u16::from_be_bytes([header[3], header[4]]) as usize;
+        let start = offset + 5;
+        let end = (start + record_len).min(data.len());
+        message.extend_from_slice(&data[start..end]);
+        if end < start + record_len {
+            // The rest of the record is still in flight
+            return complete_message(&message).unwrap_or(Reassembly::NeedMore);
+        }
+        offset = end;
+    }
+}
+
+fn complete_message(message: &[u8]) -> Option<Reassembly> {
+    let header = message.get(..4)?;
+    let len = u32::from_be_bytes([0, header[1], header[2], header[3]]) as usize;
+    let body = message.get(4..4 + len)?;
+    Some(Reassembly::Complete(match header[0] {
+        1 => ClientHello::parse(body).map(TlsHandshake::ClientHello),
+        2 => ServerHello::parse(body).map(TlsHandshake::ServerHello),
+        _ => None,
+    }))
+}
+
+pub fn version_name(version: u16) -> String {
+    match version {
+        0x0304 => "TLS 1.3".to_string(),
+        0x0303 => "TLS 1.2".to_string(),
+        0x0302 => "TLS 1.1".to_string(),
+        0x0301 => "TLS 1.0".to_string(),
+        0x0300 => "SSL 3.0".to_string(),
+        other => format!("0x{:04x}", other),
+    }
+}
+
+/// IANA name of the common cipher suites, hex for the rest
+pub fn cipher_name(cipher: u16) -> String {
+    let name = match cipher {
+        0x1301 => "TLS_AES_128_GCM_SHA256",
+        0x1302 => "TLS_AES_256_GCM_SHA384",
+        0x1303 => "TLS_CHACHA20_POLY1305_SHA256",
+        0xc02b => "TLS_ECDHE_ECDSA_WITH_AES_128_GCM_SHA256",
+        0xc02c => "TLS_ECDHE_ECDSA_WITH_AES_256_GCM_SHA384",
+        0xc02f => "TLS_ECDHE_RSA_WITH_AES_128_GCM_SHA256",
+        0xc030 => "TLS_ECDHE_RSA_WITH_AES_256_GCM_SHA384",
+        0xcca8 => "TLS_ECDHE_RSA_WITH_CHACHA20_POLY1305_SHA256",
+        0xcca9 => "TLS_ECDHE_ECDSA_WITH_CHACHA20_POLY1305_SHA256",
+        0xc013 => "TLS_ECDHE_RSA_WITH_AES_128_CBC_SHA",
+        0xc014 => "TLS_ECDHE_RSA_WITH_AES_256_CBC_SHA",
+        0x009c => "TLS_RSA_WITH_AES_128_GCM_SHA256",
+        0x009d => "TLS_RSA_WITH_AES_256_GCM_SHA384",
+        0x002f => "TLS_RSA_WITH_AES_128_CBC_SHA",
+        0x0035 => "TLS_RSA_WITH_AES_256_CBC_SHA",
+        other => return format!("0x{:04x}", other),
+    };
+    name.to_string()
+}
+
+/// GREASE values (RFC 8701) are random placeholders like 0x0a0a and 0x1a1a
+fn is_grease(value: u16) -> bool {
+    value & 0x0f0f == 0x0a0a && value >> 8 == value & 0xff
+}
+
+fn u16_list(bytes: &[u8]) -> Vec<u16> {
+    bytes.chunks_exact(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]])).collect()
+}
+
+fn alpn_list(data: &mut Reader) -> Option<Vec<String>> {
+    let len = data.u16()? as usize;
+    let mut list = Reader::new(data.take(len)?);
+    let mut protocols = Vec::new();
+    while !list.is_empty() {
+        let len = list.u8()? as usize;
+        protocols.push(String::from_utf8_lossy(list.take(len)?).into_owned());
+    }
+    Some(protocols)
+}
+
+fn hex_list(values: &[u16]) -> String {
+    values.iter().map(|value| format!("{:04x}", value)).collect::<Vec<_>>().join(",")
+}
+
+/// First 12 hex digits of the SHA-256 of `text`, or zeros for an empty list
+fn truncated_hash(values: &[u16], text: &str) -> String {
+    if values.is_empty() {
+        return "000000000000".to_string();
+    }
+    format!("{:x}", Sha256::digest(text.as_bytes()))[..12].to_string()
+}
+
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data }
+    }
+    
+    fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+    
+    fn take(&mut self, len: usize) -> Option<&'a [u8]> {
+        if len > self.data.len() {
+            return None;
+        }
+        let (head, rest) = self.data.split_at(len);
+        self.data = rest;
+        Some(head)
+    }
+    
+    fn u8(&mut self) -> Option<u8> {
+        self.take(1).map(|bytes| bytes[0])
+    }
+    
+    fn u16(&mut self) -> Option<u16> {
+        self.take(2).map(|bytes| u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    fn extension(kind: u16, data: &[u8]) -> Vec<u8> {
+        let mut bytes = kind.to_be_bytes().to_vec();
+        bytes.extend_from_slice(&(data.len() as u16).to_be_bytes());
+        bytes.extend_from_slice(data);
+        bytes
+    }
+    
+    fn handshake_record(kind: u8, body: &[u8]) -> Vec<u8> {
+        let mut message = vec![kind, 0];
+        message.extend_from_slice(&(body.len() as u16).to_be_bytes());
+        message.extend_from_slice(body);
+        let mut record = vec![0x16, 0x03, 0x01];
+        record.extend_from_slice(&(message.len() as u16).to_be_bytes());
+        record.extend_from_slice(&message);
+        record
+    }
+    
+    /// Chrome-like ClientHello with GREASE values sprinkled in
+    fn client_hello() -> Vec<u8> {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0x11; 32]);
+        body.push(0);
+        body.extend_from_slice(&[0x00, 0x08, 0x0a, 0x0a, 0x13, 0x01, 0x13, 0x02, 0xc0, 0x2b]);
+        body.extend_from_slice(&[0x01, 0x00]);
+        
+        let mut extensions = extension(0x0a0a, &[]);
+        extensions.extend(extension(0x0000, b"\x00\x0e\x00\x00\x0bexample.com"));
+        extensions.extend(extension(0x0010, b"\x00\x0c\x02h2\x08http/1.1"));
+        extensions.extend(extension(0x000a, &[0x00, 0x06, 0x0a, 0x0a, 0x00, 0x1d, 0x00, 0x17]));
+        extensions.extend(extension(0x000b, &[0x01, 0x00]));
+        extensions.extend(extension(0x000d, &[0x00, 0x04, 0x04, 0x03, 0x08, 0x04]));
+        extensions.extend(extension(0x002b, &[0x06, 0x0a, 0x0a, 0x03, 0x04, 0x03, 0x03]));
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend(extensions);
+        handshake_record(1, &body)
+    }
+    
+    #[test]
+    fn test_client_hello_fingerprints() {
+        let mut reassembler = HandshakeReassembler::new();
+        let client: SocketAddr = "192.168.1.10:50000".parse().unwrap();
+        let server: SocketAddr = "93.184.216.34:443".parse().unwrap();
+        
+        // Split mid-record across two segments
+        let record = client_hello();
+        assert!(reassembler.feed(client, server, &record[..40]).is_none());
+        let Some(TlsHandshake::ClientHello(hello)) = reassembler.feed(client, server, &record[40..]) else {
+            panic!("expected a ClientHello");
+        };
+        
+        assert_eq!(hello.sni.as_deref(), Some("example.com"));
+        assert_eq!(hello.alpn, vec!["h2", "http/1.1"]);
+        assert_eq!(hello.ja3_string(), "771,4865-4866-49195,0-16-10-11-13-43,29-23,0");
+        assert_eq!(hello.ja3(), "3736761f91e3f9597a641ce4c92f256c");
+        assert_eq!(hello.ja4(), "t13d0306h2_5559582ccdc4_fb71836bce29");
+        
+        // Not TLS, nothing kept
+        assert!(reassembler.feed(client, server, b"GET / HTTP/1.1\r\n").is_none());
+        assert!(reassembler.pending.is_empty());
+    }
+    
+    #[test]
+    fn test_server_hello() {
+        let mut body = vec![0x03, 0x03];
+        body.extend_from_slice(&[0x22; 32]);
+        body.push(0);
+        body.extend_from_slice(&[0x13, 0x01, 0x00]);
+        let mut extensions = extension(0x002b, &[0x03, 0x04]);
+        extensions.extend(extension(0x0010, b"\x00\x03\x02h2"));
+        body.extend_from_slice(&(extensions.len() as u16).to_be_bytes());
+        body.extend(extensions);
+        
+        let mut reassembler = HandshakeReassembler::new();
+        let server: SocketAddr = "93.184.216.34:443".parse().unwrap();
+        let client: SocketAddr = "192.168.1.10:50000".parse().unwrap();
+        let handshake = reassembler.feed(server, client, &handshake_record(2, &body)).unwrap();
+        
+        let mut info = TlsInfo::default();
+        info.apply(handshake);
+        assert_eq!(info.version, Some(0x0304));
+        assert_eq!(info.alpn.as_deref(), Some("h2"));
+        assert_eq!(version_name(info.version.unwrap()), "TLS 1.3");
+        assert_eq!(cipher_name(info.cipher.unwrap()), "TLS_AES_128_GCM_SHA256");
+        assert!(!info.is_complete());
+    }
+}
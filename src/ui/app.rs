@@ -12,14 +12,22 @@ use crossterm::{
     ExecutableCommand,
 };
 use std::io;
+use std::net::{IpAddr, SocketAddr};
 use std::time::{Duration, Instant};
 use std::collections::HashMap;
-use crate::capture::{PcapEngine, PacketInfo, ProcNetParser, TcpConnection, InterfaceStats};
-use crate::analysis::{ConnectionTracker, StatisticsCollector, NetworkStatistics};
+use crate::capture::{PcapEngine, PacketInfo, ProcNetParser, SocketTable, TcpConnection, TcpState, InterfaceStats, ReplaySpeed};
+use crate::analysis::{ConnectionTracker, StatisticsCollector, NetworkStatistics, ProcessBandwidthTracker, TcpHealth, HealthStatus};
 use crate::ui::protocol_view::ProtocolView;
-use crate::traffic::{TrafficInspector, TrafficAnalyzer};
-use crate::config::AdvancedFeatures;
-use crate::firewall::{FirewallEngine, FirewallView};
+use crate::ui::process_view::ProcessView;
+use crate::ui::dns_view::DnsView;
+use crate::traffic::{TrafficInspector, TrafficAnalyzer, DnsTracker, HostnameCache};
+use crate::traffic::hostnames::format_endpoint;
+use crate::config::{AdvancedFeatures, HostnameConfig, RecordConfig, SocketBackend};
+use crate::firewall::{BlockDialog, BlockDialogAction, BlockTarget, FirewallEngine, FirewallView};
+use crate::utils::shutdown;
+
+/// Rows shown in the Connections tab
+const CONNECTION_ROWS: usize = 20;
 
 pub struct App {
     pub should_quit: bool,
@@ -32,6 +40,7 @@ pub struct App {
     pub last_update: Instant,
     pub capture_engine: Option<PcapEngine>,
     pub interface: String,
+    pub socket_table: SocketTable,
     // Phase 2 enhancements
     pub connection_tracker: ConnectionTracker,
     pub statistics_collector: StatisticsCollector,
@@ -42,11 +51,24 @@ pub struct App {
     pub traffic_inspector: TrafficInspector,
     pub traffic_analyzer: TrafficAnalyzer,
     pub protocol_view: ProtocolView,
+    pub process_tracker: ProcessBandwidthTracker,
+    pub process_view: ProcessView,
+    pub dns_tracker: DnsTracker,
+    pub dns_view: DnsView,
+    pub hostnames: HostnameCache,
+    // Names or raw addresses in the Connections, Protocols and Firewall tabs
+    pub show_hostnames: bool,
     
     // Advanced features (opt-in)
     pub advanced_features: AdvancedFeatures,
     pub firewall_engine: Option<FirewallEngine>,
     pub firewall_view: Option<FirewallView>,
+    // Row picked in the Connections tab
+    pub selected_connection: usize,
+    // Confirmation for blocking the selected connection; takes all keys while open
+    pub block_dialog: Option<BlockDialog>,
+    // Shown in the footer until the next key press
+    pub status_message: Option<String>,
 }
 
 impl App {
@@ -66,6 +88,7 @@ impl App {
             last_update: Instant::now(),
             capture_engine: None,
             interface: "any".to_string(),
+            socket_table: SocketTable::default(),
             // Phase 2 enhancements
             connection_tracker: ConnectionTracker::new(),
             statistics_collector: StatisticsCollector::new(),
@@ -73,6 +96,12 @@ impl App {
             interface_metrics: HashMap::new(),
             // Phase 3 enhancements
             protocol_view: ProtocolView::new(),
+            process_tracker: ProcessBandwidthTracker::new(),
+            process_view: ProcessView::new(),
+            dns_tracker: DnsTracker::new(),
+            dns_view: DnsView::new(),
+            hostnames: HostnameCache::new(),
+            show_hostnames: true,
             traffic_inspector: TrafficInspector::new(),
             traffic_analyzer: TrafficAnalyzer::new(),
             advanced_features: advanced_features.clone(),
@@ -88,6 +117,9 @@ impl App {
             } else {
                 None
             },
+            selected_connection: 0,
+            block_dialog: None,
+            status_message: None,
         }
     }
     
@@ -109,6 +141,63 @@ impl App {
         }
     }
     
+    /// Replay a saved pcap/pcapng file instead of capturing live traffic
+    pub fn initialize_replay(&mut self, path: &str, speed: ReplaySpeed) -> Result<(), Box<dyn std::error::Error>> {
+        let mut engine = PcapEngine::from_file(path, speed)?;
+        engine.start_capture()?;
+        self.interface = path.to_string();
+        self.capture_engine = Some(engine);
+        Ok(())
+    }
+    
+    /// Apply a BPF capture filter to the active capture
+    pub fn set_capture_filter(&mut self, expression: &str) -> Result<(), Box<dyn std::error::Error>> {
+        match self.capture_engine {
+            Some(ref mut engine) => Ok(engine.set_filter(expression)?),
+            None => Err("packet capture is not active".into()),
+        }
+    }
+    
+    /// Start writing captured packets to rotating pcapng files
+    pub fn start_recording(&mut self, config: &RecordConfig) -> Result<(), Box<dyn std::error::Error>> {
+        match self.capture_engine {
+            Some(ref mut engine) => Ok(engine.start_recording(config)?),
+            None => Err("packet capture is not active".into()),
+        }
+    }
+    
+    /// Pick names or addresses to start with, and ask the configured resolver
+    /// (if any) for names the capture doesn't provide
+    pub fn configure_hostnames(&mut self, config: &HostnameConfig) -> Result<(), Box<dyn std::error::Error>> {
+        self.show_hostnames = config.show_hostnames;
+        if let Some(ref resolver) = config.ptr_resolver {
+            let server = match resolver.parse::<SocketAddr>() {
+                Ok(server) => server,
+                Err(_) => resolver
+                    .parse::<IpAddr>()
+                    .map(|address| SocketAddr::new(address, 53))
+                    .map_err(|_| format!("Invalid PTR resolver '{}', expected an address or address:port", resolver))?,
+            };
+            self.hostnames.enable_ptr_lookups(server, config.ptr_lookups_per_second)?;
+        }
+        Ok(())
+    }
+    
+    /// The hostname cache when names are shown, for the views to render from
+    fn shown_hostnames(&self) -> Option<&HostnameCache> {
+        self.show_hostnames.then_some(&self.hostnames)
+    }
+    
+    /// Choose how connections are mapped to the processes owning them
+    pub fn set_socket_backend(&mut self, backend: SocketBackend) {
+        self.socket_table = SocketTable::new(backend);
+    }
+    
+    /// Whether the packet source is a capture file rather than a live interface
+    pub fn is_replaying(&self) -> bool {
+        self.capture_engine.as_ref().map(|engine| engine.is_replay()).unwrap_or(false)
+    }
+    
     pub fn run(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         // CRITICAL: Proper terminal setup for Rocky Linux
         enable_raw_mode()?;
@@ -129,42 +218,87 @@ impl App {
             
             terminal.draw(|f| self.draw(f))?;
             
-            if self.should_quit {
+            // SIGTERM and friends quit like 'q', so rollbacks on drop still run
+            if self.should_quit || shutdown::requested() {
                 break;
             }
             
             // Handle events
             if event::poll(Duration::from_millis(100))? {
                 if let Event::Key(key) = event::read()? {
+                    self.status_message = None;
+                    if self.block_dialog.is_some() {
+                        self.handle_block_dialog_key(key.code);
+                        continue;
+                    }
+                    
+                    // The rule editor needs every key for text entry, except F5
+                    // which runs the what-if on its draft
+                    if self.selected_tab == 6 && self.firewall_view.as_ref().is_some_and(|view| view.is_editing()) {
+                        if let (Some(ref mut view), Some(ref mut engine)) = (&mut self.firewall_view, &mut self.firewall_engine) {
+                            if key.code == KeyCode::F(5) {
+                                let flows = self.traffic_inspector.get_active_flows().values()
+                                    .chain(self.traffic_inspector.get_flow_history());
+                                view.simulate_draft(engine, flows);
+                            } else {
+                                view.handle_key(key.code, engine);
+                            }
+                        }
+                        continue;
+                    }
+                    
                     match key.code {
                         KeyCode::Char('q') => self.should_quit = true,
                         KeyCode::Tab => {
-                            let max_tabs = if self.advanced_features.firewall_enabled { 5 } else { 4 };
+                            let max_tabs = if self.advanced_features.firewall_enabled { 7 } else { 6 };
                             self.selected_tab = (self.selected_tab + 1) % max_tabs;
                         },
                         KeyCode::Char('1') => self.selected_tab = 0,
                         KeyCode::Char('2') => self.selected_tab = 1,
                         KeyCode::Char('3') => self.selected_tab = 2,
                         KeyCode::Char('4') => self.selected_tab = 3,
-                        KeyCode::Char('5') => {
+                        KeyCode::Char('5') => self.selected_tab = 4,
+                        KeyCode::Char('6') => self.selected_tab = 5,
+                        KeyCode::Char('7') => {
                             if self.advanced_features.firewall_enabled {
-                                self.selected_tab = 4;
+                                self.selected_tab = 6;
                             }
                         },
-                        // Handle arrow keys for Protocol View and Firewall navigation
+                        // Sorting and drill-down in the Processes tab
+                        KeyCode::Char('s') if self.selected_tab == 4 => self.process_view.cycle_sort(),
+                        KeyCode::Enter if self.selected_tab == 4 => self.process_view.toggle_details(),
+                        // HTTP drill-down in the Protocols tab
+                        KeyCode::Enter if self.selected_tab == 3 => self.protocol_view.toggle_http(),
+                        KeyCode::Char('h') => {
+                            self.show_hostnames = !self.show_hostnames;
+                            let shown = if self.show_hostnames { "hostnames" } else { "addresses" };
+                            self.status_message = Some(format!("Showing {} ({} names learned)", shown, self.hostnames.len()));
+                        },
+                        // Block the selected connection or flow
+                        KeyCode::Char('b') if self.selected_tab == 1 || self.selected_tab == 3 => self.open_block_dialog(),
+                        // Handle arrow keys for Protocol View, Processes and Firewall navigation
                         KeyCode::Up => {
-                            if self.selected_tab == 3 {
+                            if self.selected_tab == 1 {
+                                self.selected_connection = self.selected_connection.saturating_sub(1);
+                            } else if self.selected_tab == 3 {
                                 self.protocol_view.previous_protocol();
-                            } else if self.selected_tab == 4 && self.firewall_view.is_some() && self.firewall_engine.is_some() {
+                            } else if self.selected_tab == 4 {
+                                self.process_view.previous_process();
+                            } else if self.selected_tab == 6 && self.firewall_view.is_some() && self.firewall_engine.is_some() {
                                 if let (Some(ref mut view), Some(ref mut engine)) = (&mut self.firewall_view, &mut self.firewall_engine) {
                                     view.handle_key(KeyCode::Up, engine);
                                 }
                             }
                         },
                         KeyCode::Down => {
-                            if self.selected_tab == 3 {
+                            if self.selected_tab == 1 {
+                                let last = self.current_connections.len().min(CONNECTION_ROWS).saturating_sub(1);
+                                self.selected_connection = (self.selected_connection + 1).min(last);
+                            } else if self.selected_tab == 3 {
                                 self.protocol_view.next_protocol();
-                            } else if self.selected_tab == 4 && self.firewall_view.is_some() && self.firewall_engine.is_some() {
+                            } else if self.selected_tab == 4 {
+                                self.process_view.next_process();
+                            } else if self.selected_tab == 6 && self.firewall_view.is_some() && self.firewall_engine.is_some() {
                                 if let (Some(ref mut view), Some(ref mut engine)) = (&mut self.firewall_view, &mut self.firewall_engine) {
                                     view.handle_key(KeyCode::Down, engine);
                                 }
@@ -173,7 +307,7 @@ impl App {
                         KeyCode::Left => {
                             if self.selected_tab == 3 {
                                 self.protocol_view.previous_connection();
-                            } else if self.selected_tab == 4 && self.firewall_view.is_some() && self.firewall_engine.is_some() {
+                            } else if self.selected_tab == 6 && self.firewall_view.is_some() && self.firewall_engine.is_some() {
                                 if let (Some(ref mut view), Some(ref mut engine)) = (&mut self.firewall_view, &mut self.firewall_engine) {
                                     view.handle_key(KeyCode::Left, engine);
                                 }
@@ -182,14 +316,22 @@ impl App {
                         KeyCode::Right => {
                             if self.selected_tab == 3 {
                                 self.protocol_view.next_connection();
-                            } else if self.selected_tab == 4 && self.firewall_view.is_some() && self.firewall_engine.is_some() {
+                            } else if self.selected_tab == 6 && self.firewall_view.is_some() && self.firewall_engine.is_some() {
                                 if let (Some(ref mut view), Some(ref mut engine)) = (&mut self.firewall_view, &mut self.firewall_engine) {
                                     view.handle_key(KeyCode::Right, engine);
                                 }
                             }
                         },
+                        // What-if for the selected firewall rule, against the flows seen so far
+                        KeyCode::Char('w') if self.selected_tab == 6 => {
+                            if let (Some(ref mut view), Some(ref engine)) = (&mut self.firewall_view, &self.firewall_engine) {
+                                let flows = self.traffic_inspector.get_active_flows().values()
+                                    .chain(self.traffic_inspector.get_flow_history());
+                                view.simulate_selected_rule(engine, flows);
+                            }
+                        },
                         // Handle other firewall keys
-                        key if self.selected_tab == 4 && self.firewall_view.is_some() && self.firewall_engine.is_some() => {
+                        key if self.selected_tab == 6 && self.firewall_view.is_some() && self.firewall_engine.is_some() => {
                             if let (Some(ref mut view), Some(ref mut engine)) = (&mut self.firewall_view, &mut self.firewall_engine) {
                                 view.handle_key(key, engine);
                             }
@@ -201,48 +343,134 @@ impl App {
         }
         
         // Cleanup
+        if let Some(ref mut engine) = self.capture_engine {
+            if let Err(e) = engine.stop_recording() {
+                eprintln!("Warning: {}", e);
+            }
+        }
+        if let Some(ref mut engine) = self.firewall_engine {
+            // Edits from the last second haven't been saved yet
+            engine.autosave();
+            if let Some(e) = engine.get_save_error() {
+                eprintln!("Warning: {}", e);
+            }
+            if let Err(e) = engine.disable_enforcement() {
+                eprintln!("Warning: Failed to roll back firewall rules: {}", e);
+            }
+        }
         disable_raw_mode()?;
         io::stdout().execute(LeaveAlternateScreen)?;
         io::stdout().execute(DisableMouseCapture)?;
         Ok(())
     }
     
+    /// Only with the firewall enabled; the target is the row selected in the
+    /// Connections tab or the flow selected in the Protocols tab
+    fn open_block_dialog(&mut self) {
+        if !self.advanced_features.firewall_enabled {
+            return;
+        }
+        let target = match self.selected_tab {
+            1 => self.current_connections.get(self.selected_connection).map(|connection| {
+                // Connections to a port this host listens on were opened from outside
+                let inbound = self.current_connections.iter().any(|other| {
+                    matches!(other.state, TcpState::Listen) && other.local_addr.port() == connection.local_addr.port()
+                });
+                BlockTarget::from_tcp_connection(connection, inbound)
+            }),
+            3 => self.protocol_view
+                .selected_flow_id()
+                .and_then(|flow_id| self.traffic_inspector.get_active_flows().get(flow_id))
+                .map(BlockTarget::from_flow),
+            _ => None,
+        };
+        match target {
+            Some(target) => self.block_dialog = Some(BlockDialog::new(target)),
+            None => self.status_message = Some("No connection selected".to_string()),
+        }
+    }
+    
+    fn handle_block_dialog_key(&mut self, key: KeyCode) {
+        let action = match self.block_dialog {
+            Some(ref mut dialog) => dialog.handle_key(key),
+            None => return,
+        };
+        match action {
+            BlockDialogAction::Continue => {}
+            BlockDialogAction::Cancel => self.block_dialog = None,
+            BlockDialogAction::Confirm(rules) => {
+                self.block_dialog = None;
+                if let Some(ref mut engine) = self.firewall_engine {
+                    let names: Vec<String> = rules.iter().map(|rule| format!("'{}'", rule.name)).collect();
+                    for rule in rules {
+                        engine.add_rule(rule);
+                    }
+                    self.status_message = Some(format!("Added firewall rule {}", names.join(", ")));
+                }
+            }
+        }
+    }
+    
+    fn process_captured_packet(&mut self, packet: PacketInfo) {
+        self.packets_captured += 1;
+        self.bytes_captured += packet.length as u64;
+        
+        // Phase 2: Track packet with connection tracker
+        self.connection_tracker.track_packet(&packet);
+        
+        // Phase 3: Inspect packet with traffic inspector
+        let protocol = self.connection_tracker.get_protocol_analyzer().identify_protocol(&packet);
+        self.traffic_inspector.inspect_packet(&packet, protocol);
+        if let Some(message) = self.dns_tracker.process_packet(&packet) {
+            self.hostnames.learn(&message);
+        }
+        
+        // Real packets carry the TCP flags and lengths that connection
+        // tracking and rate limits need
+        if let Some(ref mut firewall_engine) = self.firewall_engine {
+            firewall_engine.process_packet(&packet);
+        }
+        
+        // Keep only recent packets (last 100)
+        self.recent_packets.push(packet);
+        if self.recent_packets.len() > 100 {
+            self.recent_packets.remove(0);
+        }
+    }
+    
     fn update_data(&mut self) {
+        let replaying = self.is_replaying();
+        
         // Update packet capture if available
-        if let Some(ref mut engine) = self.capture_engine {
+        if self.capture_engine.is_some() {
+            // Limit live capture to 10 packets per update to avoid blocking; replay
+            // reads from disk and is paced by the engine, so it gets a larger budget
+            let packet_budget = if replaying { 10_000 } else { 10 };
+            
             // Try to get new packets
-            for _ in 0..10 { // Limit to 10 packets per update to avoid blocking
-                match engine.get_next_packet() {
-                    Ok(Some(packet)) => {
-                        self.packets_captured += 1;
-                        self.bytes_captured += packet.length as u64;
-                        
-                        // Phase 2: Track packet with connection tracker
-                        self.connection_tracker.track_packet(&packet);
-                        
-                        // Phase 3: Inspect packet with traffic inspector
-                        let protocol = self.connection_tracker.get_protocol_analyzer().identify_protocol(&packet);
-                        self.traffic_inspector.inspect_packet(&packet, protocol);
-                        
-                        // Keep only recent packets (last 100)
-                        self.recent_packets.push(packet);
-                        if self.recent_packets.len() > 100 {
-                            self.recent_packets.remove(0);
-                        }
-                    },
-                    Ok(None) => break, // No more packets available
-                    Err(_) => break,   // Error occurred
+            for _ in 0..packet_budget {
+                match self.capture_engine.as_mut().map(|engine| engine.get_next_packet()) {
+                    Some(Ok(Some(packet))) => self.process_captured_packet(packet),
+                    _ => break, // No more packets available, or an error occurred
                 }
             }
             
             // Update statistics from engine
-            let stats = engine.get_statistics();
-            self.packets_captured = stats.packets_captured;
-            self.bytes_captured = stats.bytes_captured;
+            if let Some(ref engine) = self.capture_engine {
+                let stats = engine.get_statistics();
+                self.packets_captured = stats.packets_captured;
+                self.bytes_captured = stats.bytes_captured;
+            }
         }
         
-        // Update connection information using /proc fallback
-        if let Ok(connections) = ProcNetParser::get_tcp_connections() {
+        // Update connection information using /proc fallback. Skipped while
+        // replaying since the local socket table is unrelated to the file.
+        let proc_connections = if replaying {
+            None
+        } else {
+            self.socket_table.get_tcp_connections().ok()
+        };
+        if let Some(connections) = proc_connections {
             self.current_connections = connections.clone();
             // Phase 2: Update connection tracker with /proc data
             self.connection_tracker.update_from_proc(&connections);
@@ -258,23 +486,42 @@ impl App {
                     dst_ip: Some(conn.remote_addr.ip().to_string()),
                     src_port: Some(conn.local_addr.port()),
                     dst_port: Some(conn.remote_addr.port()),
+                    tcp_flags: None,
+                    tcp_seq: None,
+                    icmp_type: None,
+                    payload: Vec::new(),
                 };
                 
                 let protocol = self.connection_tracker.get_protocol_analyzer().identify_protocol(&packet);
                 self.traffic_inspector.inspect_packet(&packet, protocol);
                 
-                // Process packet through firewall if enabled
-                if let Some(ref mut firewall_engine) = self.firewall_engine {
-                    firewall_engine.process_packet(&packet);
+                // Without a capture the firewall only gets to see the socket table.
+                // These packets have no flags or length, so they're kept away from
+                // the engine whenever real packets are available.
+                if self.capture_engine.is_none() {
+                    if let Some(ref mut firewall_engine) = self.firewall_engine {
+                        firewall_engine.process_packet(&packet);
+                    }
                 }
             }
         }
         
+        // Retire expired rules, then push rule edits to the kernel when
+        // enforcement is on, and to disk
+        if let Some(ref mut firewall_engine) = self.firewall_engine {
+            firewall_engine.update_timed_rules(std::time::SystemTime::now());
+            firewall_engine.sync_enforcement();
+            firewall_engine.autosave();
+        }
+        
         // Phase 2: Update interface statistics and metrics
+        // (a capture file has no live interface counters)
         let mut interface_stats_map = HashMap::new();
-        if let Ok(stats) = ProcNetParser::get_interface_stats(&self.interface) {
-            self.interface_stats = Some(stats.clone());
-            interface_stats_map.insert(self.interface.clone(), stats);
+        if !replaying {
+            if let Ok(stats) = ProcNetParser::get_interface_stats(&self.interface) {
+                self.interface_stats = Some(stats.clone());
+                interface_stats_map.insert(self.interface.clone(), stats);
+            }
         }
         
         // Update interface metrics with rate calculations
@@ -299,6 +546,26 @@ impl App {
         
         // Always update protocol view with latest data
         self.protocol_view.update_data(active_flows);
+        self.protocol_view.update_http_stats(self.traffic_inspector.get_http_stats());
+        
+        // Per-process bandwidth from flows matched to resolved sockets
+        self.process_tracker.update(&self.connection_tracker, active_flows);
+        self.process_view.update_data(&self.process_tracker);
+        self.dns_view.update_data(&self.dns_tracker);
+        
+        // Ask for the names of the remote ends on screen that no captured
+        // response has given (a no-op unless PTR lookups are configured)
+        if self.show_hostnames && self.hostnames.ptr_lookups_enabled() {
+            let now = Instant::now();
+            for conn in &self.current_connections {
+                self.hostnames.request(conn.remote_addr.ip(), now);
+            }
+            for flow in self.traffic_inspector.get_active_flows().values() {
+                self.hostnames.request(flow.src_addr.ip(), now);
+                self.hostnames.request(flow.dst_addr.ip(), now);
+            }
+            self.hostnames.poll(now);
+        }
         
         // Force cleanup of expired flows to ensure fresh data
         // This is handled internally by the traffic inspector
@@ -323,10 +590,12 @@ impl App {
             0 => self.draw_dashboard(f, chunks[1]),
             1 => self.draw_connections(f, chunks[1]),
             2 => self.draw_packets(f, chunks[1]),
-            3 => self.protocol_view.render(chunks[1], f),
-            4 if self.advanced_features.firewall_enabled => {
+            3 => self.protocol_view.render(chunks[1], f, self.show_hostnames.then_some(&self.hostnames)),
+            4 => self.process_view.render(chunks[1], f),
+            5 => self.dns_view.render(chunks[1], f),
+            6 if self.advanced_features.firewall_enabled => {
                 if let (Some(ref mut view), Some(ref engine)) = (&mut self.firewall_view, &self.firewall_engine) {
-                    view.render(f, chunks[1], engine);
+                    view.render(f, chunks[1], engine, self.show_hostnames.then_some(&self.hostnames));
                 } else {
                     self.draw_dashboard(f, chunks[1]);
                 }
@@ -334,12 +603,16 @@ impl App {
             _ => self.draw_dashboard(f, chunks[1]),
         }
         
+        if let Some(ref dialog) = self.block_dialog {
+            dialog.render(f, chunks[1]);
+        }
+        
         // Draw footer
         self.draw_footer(f, chunks[2]);
     }
     
     fn draw_header(&self, f: &mut Frame, area: ratatui::layout::Rect) {
-        let mut tabs = vec!["Dashboard", "Connections", "Packets", "Protocols"];
+        let mut tabs = vec!["Dashboard", "Connections", "Packets", "Protocols", "Processes", "DNS"];
         if self.advanced_features.firewall_enabled {
             tabs.push("Firewall");
         }
@@ -353,8 +626,13 @@ impl App {
             Line::from(Span::styled(format!(" {} ", tab), style))
         }).collect();
         
+        let title = match self.capture_engine.as_ref().and_then(|engine| engine.get_filter()) {
+            Some(filter) => format!("Network Monitor - Enhanced Protocol View | BPF: {}", filter),
+            None => "Network Monitor - Enhanced Protocol View".to_string(),
+        };
+        
         let header = Paragraph::new(tab_titles)
-            .block(Block::default().borders(Borders::ALL).title("Network Monitor - Enhanced Protocol View"))
+            .block(Block::default().borders(Borders::ALL).title(title))
             .alignment(Alignment::Center);
         
         f.render_widget(header, area);
@@ -422,25 +700,80 @@ impl App {
     }
     
     fn draw_connections(&self, f: &mut Frame, area: ratatui::layout::Rect) {
-        let rows: Vec<Row> = self.current_connections.iter().take(20).map(|conn| {
+        let resolver = self.connection_tracker.get_process_resolver();
+        let names = self.shown_hostnames();
+        let mut unhealthy = 0;
+        let rows: Vec<Row> = self.current_connections.iter().take(CONNECTION_ROWS).enumerate().map(|(i, conn)| {
+            // Same "name/pid" form as netstat -p; "-" when the owner is not visible
+            let process = resolver.lookup(conn.inode)
+                .map(|process| format!("{}/{}", process.name, process.pid))
+                .unwrap_or_else(|| "-".to_string());
+            
+            // Listening sockets' queues are the accept backlog, not traffic
+            let health = match conn.state {
+                TcpState::Listen => None,
+                _ => Some(TcpHealth::from_connection(conn)),
+            };
+            let (rtt, retrans, queues, cwnd, style) = match health {
+                Some(ref health) => {
+                    let style = match health.status() {
+                        HealthStatus::Healthy => Style::default(),
+                        HealthStatus::Degraded => Style::default().fg(Color::Yellow),
+                        HealthStatus::Unhealthy => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD),
+                    };
+                    if health.status() != HealthStatus::Healthy {
+                        unhealthy += 1;
+                    }
+                    let rtt = health.srtt_us
+                        .map(|srtt| format!("{:.1}ms", srtt as f64 / 1000.0))
+                        .unwrap_or_else(|| "-".to_string());
+                    let retrans = match health.total_retrans {
+                        Some(total) => format!("{}/{}", health.retransmits, total),
+                        None => health.retransmits.to_string(),
+                    };
+                    let cwnd = health.cwnd.map(|cwnd| cwnd.to_string()).unwrap_or_else(|| "-".to_string());
+                    (rtt, retrans, format!("{}/{}", health.send_queue, health.recv_queue), cwnd, style)
+                },
+                None => ("-".to_string(), "-".to_string(), "-".to_string(), "-".to_string(), Style::default()),
+            };
+            let style = if i == self.selected_connection { style.bg(Color::DarkGray) } else { style };
+            
             Row::new(vec![
-                Cell::from(conn.local_addr.to_string()),
-                Cell::from(conn.remote_addr.to_string()),
+                Cell::from(format_endpoint(conn.local_addr, names)),
+                Cell::from(format_endpoint(conn.remote_addr, names)),
                 Cell::from(conn.state.to_string()),
                 Cell::from(conn.uid.to_string()),
-            ])
+                Cell::from(process),
+                Cell::from(rtt),
+                Cell::from(retrans),
+                Cell::from(queues),
+                Cell::from(cwnd),
+            ]).style(style)
         }).collect();
         
+        let mut title = format!("Active Connections (via {})", self.socket_table.active_backend());
+        if self.advanced_features.firewall_enabled {
+            title.push_str(" - ↑↓ select, 'b' block");
+        }
+        if unhealthy > 0 {
+            title.push_str(&format!(" - {} unhealthy", unhealthy));
+        }
+        
         let table = Table::new(rows)
         .widths(&[
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
-            Constraint::Percentage(25),
+            Constraint::Percentage(19),
+            Constraint::Percentage(19),
+            Constraint::Percentage(10),
+            Constraint::Percentage(5),
+            Constraint::Percentage(14),
+            Constraint::Percentage(9),
+            Constraint::Percentage(7),
+            Constraint::Percentage(11),
+            Constraint::Percentage(6),
         ])
-        .header(Row::new(vec!["Local Address", "Remote Address", "State", "UID"])
+        .header(Row::new(vec!["Local Address", "Remote Address", "State", "UID", "Process", "RTT", "Retrans", "Send/Recv-Q", "Cwnd"])
             .style(Style::default().fg(Color::Yellow)))
-        .block(Block::default().borders(Borders::ALL).title("Active Connections"));
+        .block(Block::default().borders(Borders::ALL).title(title));
         
         f.render_widget(table, area);
     }
@@ -480,8 +813,22 @@ impl App {
     }
     
     fn draw_footer(&self, f: &mut Frame, area: ratatui::layout::Rect) {
-        let footer_text = "Press 'q' to quit | Tab/1-4 to switch tabs | Monitoring interface: ";
-        let footer = Paragraph::new(format!("{}{}", footer_text, self.interface))
+        let footer_text = "Press 'q' to quit | Tab/1-7 to switch tabs | 'h' names/addresses | ";
+        let source = match self.capture_engine {
+            Some(ref engine) if engine.is_replay() => {
+                let status = if engine.is_replay_finished() { "finished" } else { "playing" };
+                let speed = engine.get_replay_speed().map(|s| s.to_string()).unwrap_or_default();
+                format!("Replaying file: {} ({}, {})", self.interface, speed, status)
+            },
+            _ => format!("Monitoring interface: {}", self.interface),
+        };
+        let recording = self.capture_engine.as_ref()
+            .and_then(|engine| engine.get_recorder())
+            .and_then(|recorder| recorder.get_current_file())
+            .map(|path| format!(" | REC {}", path.display()))
+            .unwrap_or_default();
+        let status = self.status_message.as_ref().map(|message| format!(" | {}", message)).unwrap_or_default();
+        let footer = Paragraph::new(format!("{}{}{}{}", footer_text, source, recording, status))
             .block(Block::default().borders(Borders::ALL))
             .alignment(Alignment::Center);
         
@@ -505,3 +852,80 @@ impl App {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use pnet::packet::tcp::TcpFlags;
+    use crate::firewall::{ConnState, FirewallRule, RateLimit, RateScope, RuleAction, RuleDirection, RuleProtocol};
+    use crate::firewall::rules::RuleTemplates;
+    
+    fn tcp(src: &str, src_port: u16, dst: &str, dst_port: u16, flags: u8) -> PacketInfo {
+        PacketInfo {
+            timestamp: std::time::SystemTime::now(),
+            length: 60,
+            protocol: "TCP".to_string(),
+            src_ip: Some(src.to_string()),
+            dst_ip: Some(dst.to_string()),
+            src_port: Some(src_port),
+            dst_port: Some(dst_port),
+            tcp_flags: Some(flags),
+            tcp_seq: None,
+            icmp_type: None,
+            payload: Vec::new(),
+        }
+    }
+    
+    #[test]
+    fn test_captured_packets_reach_firewall() {
+        let mut app = App::with_advanced_features(AdvancedFeatures::from_cli_args(true, false, false, false));
+        app.firewall_engine.as_mut().unwrap().replace_rules(vec![
+            RuleTemplates::block_all_incoming(),
+            RuleTemplates::allow_established(),
+        ]);
+        
+        // Unsolicited connection attempt
+        app.process_captured_packet(tcp("203.0.113.5", 40000, "192.168.1.10", 22, TcpFlags::SYN));
+        // Outbound connection whose SYN-ACK has to be let back in
+        app.process_captured_packet(tcp("192.168.1.10", 51000, "93.184.216.34", 443, TcpFlags::SYN));
+        app.process_captured_packet(tcp("93.184.216.34", 443, "192.168.1.10", 51000, TcpFlags::SYN | TcpFlags::ACK));
+        
+        let engine = app.firewall_engine.as_ref().unwrap();
+        let states: Vec<ConnState> = engine.get_recent_events().iter().map(|event| event.state).collect();
+        assert_eq!(states, [ConnState::New, ConnState::Established]);
+        assert_eq!(engine.get_stats().packets_blocked, 1);
+        assert_eq!(engine.get_conntrack().established_count(), 1);
+        assert_eq!(app.recent_packets.len(), 3);
+    }
+    
+    #[test]
+    fn test_captured_packets_consume_rate_limits() {
+        let mut app = App::with_advanced_features(AdvancedFeatures::from_cli_args(true, false, false, false));
+        let download_limit = FirewallRule::new(
+            10,
+            "Limit Downloads".to_string(),
+            RuleAction::RateLimit(RateLimit::bytes_per_second(100, 50, RateScope::PerRule)),
+            RuleDirection::Inbound,
+            RuleProtocol::TCP,
+        )
+        .with_source_port(443);
+        app.firewall_engine.as_mut().unwrap().replace_rules(vec![RuleTemplates::rate_limit_ssh(), download_limit]);
+        
+        // 1/s with a burst of 5 lets six connection attempts through
+        for port in 40000..40008 {
+            app.process_captured_packet(tcp("203.0.113.5", port, "192.168.1.10", 22, TcpFlags::SYN));
+        }
+        // 150 bytes of budget cover two 60 byte segments
+        app.process_captured_packet(tcp("192.168.1.10", 51000, "93.184.216.34", 443, TcpFlags::SYN));
+        for _ in 0..3 {
+            app.process_captured_packet(tcp("93.184.216.34", 443, "192.168.1.10", 51000, TcpFlags::ACK));
+        }
+        
+        let engine = app.firewall_engine.as_ref().unwrap();
+        let rule_id = |name: &str| engine.get_rules().iter().find(|rule| rule.name == name).unwrap().id;
+        let stats = engine.get_stats();
+        assert_eq!(stats.packets_rate_limited, 3);
+        assert_eq!(stats.rate_limited_by_rule.get(&rule_id("Rate Limit SSH")).copied(), Some(2));
+        assert_eq!(stats.rate_limited_by_rule.get(&rule_id("Limit Downloads")).copied(), Some(1));
+    }
+}
@@ -0,0 +1,185 @@
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, List, ListItem, Table, Row, Cell, Paragraph},
+    style::{Color, Style, Modifier},
+};
+use std::time::Duration;
+use crate::traffic::dns::{DnsTracker, DnsTotals, DnsTransaction, NameStats, ResolverStats, ClientStats, record_type_name, rcode_name};
+use crate::utils::formatting::{format_latency, truncate_string};
+
+/// Rows kept for each table; the terminal shows as many as fit
+const TABLE_ROWS: usize = 50;
+/// Average latency above which a resolver is shown in red
+const SLOW_RESOLVER: Duration = Duration::from_millis(200);
+
+pub struct DnsView {
+    totals: DnsTotals,
+    names: Vec<NameStats>,
+    resolvers: Vec<ResolverStats>,
+    clients: Vec<ClientStats>,
+    /// Newest first
+    recent: Vec<DnsTransaction>,
+}
+
+impl DnsView {
+    pub fn new() -> Self {
+        Self {
+            totals: DnsTotals::default(),
+            names: Vec::new(),
+            resolvers: Vec::new(),
+            clients: Vec::new(),
+            recent: Vec::new(),
+        }
+    }
+    
+    pub fn update_data(&mut self, tracker: &DnsTracker) {
+        self.totals = tracker.totals();
+        self.names = tracker.top_names(TABLE_ROWS);
+        self.resolvers = tracker.slow_resolvers(TABLE_ROWS);
+        self.clients = tracker.top_clients(TABLE_ROWS);
+        self.recent = tracker.recent_transactions().iter().rev().take(TABLE_ROWS).cloned().collect();
+    }
+    
+    pub fn render(&self, area: Rect, frame: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Length(3), Constraint::Percentage(50), Constraint::Min(0)])
+            .split(area);
+        let top = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+            .split(chunks[1]);
+        let bottom = Layout::default()
+            .direction(Direction::Horizontal)
+            .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
+            .split(chunks[2]);
+        
+        self.render_summary(chunks[0], frame);
+        self.render_names(top[0], frame);
+        self.render_resolvers(top[1], frame);
+        self.render_clients(bottom[0], frame);
+        self.render_recent(bottom[1], frame);
+    }
+    
+    fn render_summary(&self, area: Rect, frame: &mut Frame) {
+        let nxdomain_color = if self.totals.nxdomain_rate() > 10.0 { Color::Red } else { Color::Green };
+        let line = Line::from(vec![
+            Span::raw(format!("Queries: {} | Responses: {} | NXDOMAIN: ", self.totals.queries, self.totals.responses)),
+            Span::styled(
+                format!("{} ({:.1}%)", self.totals.nxdomain, self.totals.nxdomain_rate()),
+                Style::default().fg(nxdomain_color),
+            ),
+            Span::raw(format!(" | Unanswered: {}", self.totals.unanswered)),
+        ]);
+        let paragraph = Paragraph::new(line)
+            .block(Block::default().title("DNS Activity (port 53)").borders(Borders::ALL));
+        frame.render_widget(paragraph, area);
+    }
+    
+    fn header(cells: &[&'static str]) -> Row<'static> {
+        Row::new(cells.iter().map(|h| {
+            Cell::from(*h).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+        }))
+    }
+    
+    fn render_names(&self, area: Rect, frame: &mut Frame) {
+        let rows = self.names.iter().map(|name| {
+            let rate = name.nxdomain as f64 * 100.0 / name.queries.max(1) as f64;
+            let style = if name.nxdomain > 0 { Style::default().fg(Color::Red) } else { Style::default() };
+            Row::new(vec![
+                Cell::from(truncate_string(&name.name, 40)),
+                Cell::from(name.queries.to_string()),
+                Cell::from(format!("{} ({:.0}%)", name.nxdomain, rate)).style(style),
+            ])
+        });
+        let widths = [Constraint::Min(20), Constraint::Length(8), Constraint::Length(12)];
+        let table = Table::new(rows)
+            .widths(&widths)
+            .header(Self::header(&["Name", "Queries", "NXDOMAIN"]))
+            .block(Block::default().title("Top Queried Names").borders(Borders::ALL));
+        frame.render_widget(table, area);
+    }
+    
+    fn render_resolvers(&self, area: Rect, frame: &mut Frame) {
+        let rows = self.resolvers.iter().map(|resolver| {
+            let average = resolver.average_latency();
+            let style = match average {
+                Some(latency) if latency < SLOW_RESOLVER => Style::default(),
+                _ => Style::default().fg(Color::Red),
+            };
+            Row::new(vec![
+                Cell::from(resolver.resolver.to_string()),
+                Cell::from(resolver.queries.to_string()),
+                Cell::from(average.map(format_latency).unwrap_or_else(|| "-".to_string())).style(style),
+                Cell::from(format_latency(resolver.max_latency)),
+                Cell::from(format!("{}/{}", resolver.unanswered, resolver.failures)),
+            ])
+        });
+        let widths = [
+            Constraint::Min(15),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(8),
+            Constraint::Length(10),
+        ];
+        let table = Table::new(rows)
+            .widths(&widths)
+            .header(Self::header(&["Resolver", "Queries", "Avg", "Max", "Lost/Fail"]))
+            .block(Block::default().title("Resolvers (slowest first)").borders(Borders::ALL));
+        frame.render_widget(table, area);
+    }
+    
+    fn render_clients(&self, area: Rect, frame: &mut Frame) {
+        let rows = self.clients.iter().map(|client| {
+            Row::new(vec![
+                Cell::from(client.client.to_string()),
+                Cell::from(client.queries.to_string()),
+                Cell::from(client.nxdomain.to_string()),
+            ])
+        });
+        let widths = [Constraint::Min(15), Constraint::Length(8), Constraint::Length(8)];
+        let table = Table::new(rows)
+            .widths(&widths)
+            .header(Self::header(&["Client", "Queries", "NXDOMAIN"]))
+            .block(Block::default().title("Queries per Client").borders(Borders::ALL));
+        frame.render_widget(table, area);
+    }
+    
+    fn render_recent(&self, area: Rect, frame: &mut Frame) {
+        let items: Vec<ListItem> = self.recent.iter().map(|transaction| {
+            let (rcode, color) = match transaction.rcode {
+                Some(0) => ("NOERROR".to_string(), Color::Green),
+                Some(rcode) => (rcode_name(rcode), Color::Red),
+                None => ("TIMEOUT".to_string(), Color::Red),
+            };
+            let answers = match transaction.answers.first() {
+                Some(first) => {
+                    let ttl = transaction.answers.iter().map(|answer| answer.ttl).min().unwrap_or(0);
+                    let more = match transaction.answers.len() {
+                        1 => String::new(),
+                        n => format!(" +{}", n - 1),
+                    };
+                    format!("{}{} ttl {}s", first.data, more, ttl)
+                },
+                None => String::new(),
+            };
+            let latency = transaction.latency.map(format_latency).unwrap_or_else(|| "-".to_string());
+            ListItem::new(Line::from(vec![
+                Span::raw(format!("{:<6} {:<5} ", latency, record_type_name(transaction.record_type))),
+                Span::raw(format!("{} ", truncate_string(&transaction.name, 40))),
+                Span::styled(rcode, Style::default().fg(color)),
+                Span::raw(format!(" {} via {}", answers, transaction.resolver)),
+            ]))
+        }).collect();
+        
+        let list = List::new(items)
+            .block(Block::default().title("Recent Lookups (latency, type, name, result)").borders(Borders::ALL));
+        frame.render_widget(list, area);
+    }
+}
+
+impl Default for DnsView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
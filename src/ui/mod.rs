@@ -1,5 +1,9 @@
 pub mod app;
 pub mod protocol_view;
+pub mod process_view;
+pub mod dns_view;
 
 pub use app::App;
 pub use protocol_view::ProtocolView;
+pub use process_view::ProcessView;
+pub use dns_view::DnsView;
@@ -0,0 +1,208 @@
+use ratatui::{
+    prelude::*,
+    widgets::{Block, Borders, Table, Row, Cell, Paragraph},
+    style::{Color, Style, Modifier},
+};
+use crate::analysis::{ProcessBandwidthTracker, ProcessUsage, ProcessSortKey};
+use crate::utils::formatting::{format_bytes, truncate_string};
+
+pub struct ProcessView {
+    processes: Vec<ProcessUsage>,
+    selected_process: usize,
+    sort_key: ProcessSortKey,
+    show_details: bool,
+}
+
+impl ProcessView {
+    pub fn new() -> Self {
+        Self {
+            processes: Vec::new(),
+            selected_process: 0,
+            sort_key: ProcessSortKey::Rate,
+            show_details: false,
+        }
+    }
+    
+    pub fn update_data(&mut self, tracker: &ProcessBandwidthTracker) {
+        // Keep the cursor on the same process when the order changes
+        let selected_pid = self.processes.get(self.selected_process).map(|process| process.pid);
+        self.processes = tracker.get_processes(self.sort_key);
+        self.selected_process = selected_pid
+            .and_then(|pid| self.processes.iter().position(|process| process.pid == pid))
+            .unwrap_or(0);
+    }
+    
+    pub fn next_process(&mut self) {
+        if !self.processes.is_empty() {
+            self.selected_process = (self.selected_process + 1) % self.processes.len();
+        }
+    }
+    
+    pub fn previous_process(&mut self) {
+        if !self.processes.is_empty() {
+            self.selected_process = if self.selected_process == 0 {
+                self.processes.len() - 1
+            } else {
+                self.selected_process - 1
+            };
+        }
+    }
+    
+    pub fn cycle_sort(&mut self) {
+        self.sort_key = self.sort_key.next();
+        let selected_pid = self.processes.get(self.selected_process).map(|process| process.pid);
+        self.sort_key.sort(&mut self.processes);
+        self.selected_process = selected_pid
+            .and_then(|pid| self.processes.iter().position(|process| process.pid == pid))
+            .unwrap_or(0);
+    }
+    
+    pub fn toggle_details(&mut self) {
+        self.show_details = !self.show_details;
+    }
+    
+    pub fn get_sort_key(&self) -> ProcessSortKey {
+        self.sort_key
+    }
+    
+    pub fn render(&mut self, area: Rect, frame: &mut Frame) {
+        if self.show_details {
+            let chunks = Layout::default()
+                .direction(Direction::Vertical)
+                .constraints([Constraint::Percentage(55), Constraint::Percentage(45)])
+                .split(area);
+            
+            self.render_process_table(chunks[0], frame);
+            self.render_process_details(chunks[1], frame);
+        } else {
+            self.render_process_table(area, frame);
+        }
+    }
+    
+    fn render_process_table(&self, area: Rect, frame: &mut Frame) {
+        let title = format!("Processes (sorted by {} | s: sort, Enter: details, ↑↓ to navigate)", self.sort_key);
+        
+        if self.processes.is_empty() {
+            let paragraph = Paragraph::new("No traffic attributed to processes yet (needs live capture and access to /proc/<pid>/fd)")
+                .block(Block::default().title(title).borders(Borders::ALL))
+                .style(Style::default().fg(Color::Gray));
+            frame.render_widget(paragraph, area);
+            return;
+        }
+        
+        let header_cells = ["PID", "Process", "Sent/s", "Recv/s", "Sent", "Received", "Conns"]
+            .iter()
+            .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
+        
+        let header = Row::new(header_cells).height(1).bottom_margin(1);
+        
+        // Scroll so the selected row stays visible
+        let visible = area.height.saturating_sub(4).max(1) as usize;
+        let offset = self.selected_process.saturating_sub(visible - 1);
+        
+        let rows = self.processes
+            .iter()
+            .enumerate()
+            .skip(offset)
+            .take(visible)
+            .map(|(i, process)| {
+                let style = if i == self.selected_process {
+                    Style::default().bg(Color::DarkGray).fg(Color::White)
+                } else {
+                    Style::default().fg(Color::White)
+                };
+                
+                Row::new(vec![
+                    Cell::from(process.pid.to_string()),
+                    Cell::from(truncate_string(&process.name, 20)),
+                    Cell::from(format!("{}/s", format_bytes(process.tx_rate as u64))),
+                    Cell::from(format!("{}/s", format_bytes(process.rx_rate as u64))),
+                    Cell::from(format_bytes(process.tx_bytes)),
+                    Cell::from(format_bytes(process.rx_bytes)),
+                    Cell::from(process.connection_count.to_string()),
+                ]).style(style)
+            });
+        
+        let widths = [
+            Constraint::Length(8),  // PID
+            Constraint::Length(20), // Process
+            Constraint::Length(12), // Sent/s
+            Constraint::Length(12), // Recv/s
+            Constraint::Length(12), // Sent
+            Constraint::Length(12), // Received
+            Constraint::Length(6),  // Conns
+        ];
+        
+        let table = Table::new(rows)
+            .widths(&widths)
+            .header(header)
+            .block(Block::default().borders(Borders::ALL).title(title));
+        
+        frame.render_widget(table, area);
+    }
+    
+    fn render_process_details(&self, area: Rect, frame: &mut Frame) {
+        let process = match self.processes.get(self.selected_process) {
+            Some(process) => process,
+            None => {
+                let paragraph = Paragraph::new("No process selected")
+                    .block(Block::default().title("Process Details").borders(Borders::ALL));
+                frame.render_widget(paragraph, area);
+                return;
+            }
+        };
+        
+        let mut lines = vec![
+            Line::from(format!("{} (pid {})", process.name, process.pid)),
+            Line::from(format!("Command: {}", if process.cmdline.is_empty() { "-" } else { &process.cmdline })),
+            Line::from(format!(
+                "Sent: {} ({}/s) | Received: {} ({}/s) | Connections: {}",
+                format_bytes(process.tx_bytes),
+                format_bytes(process.tx_rate as u64),
+                format_bytes(process.rx_bytes),
+                format_bytes(process.rx_rate as u64),
+                process.connection_count
+            )),
+            Line::from(""),
+            Line::from(Span::styled("Top remote peers:", Style::default().fg(Color::Yellow))),
+        ];
+        
+        if process.top_peers.is_empty() {
+            lines.push(Line::from("  none"));
+        }
+        for peer in &process.top_peers {
+            lines.push(Line::from(format!(
+                "  {:<40} sent {:>12}  received {:>12}",
+                peer.addr,
+                format_bytes(peer.tx_bytes),
+                format_bytes(peer.rx_bytes)
+            )));
+        }
+        
+        let paragraph = Paragraph::new(lines)
+            .block(Block::default().title("Process Details (Enter to close)").borders(Borders::ALL))
+            .style(Style::default().fg(Color::White))
+            .wrap(ratatui::widgets::Wrap { trim: false });
+        
+        frame.render_widget(paragraph, area);
+    }
+}
+
+impl Default for ProcessView {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_process_view_creation() {
+        let view = ProcessView::new();
+        assert!(view.processes.is_empty());
+        assert_eq!(view.get_sort_key(), ProcessSortKey::Rate);
+        assert!(!view.show_details);
+    }
+}
@@ -4,9 +4,16 @@ use ratatui::{
     style::{Color, Style, Modifier},
 };
 use std::collections::HashMap;
+use std::net::SocketAddr;
 use crate::analysis::protocols::ProtocolType;
-use crate::traffic::{TrafficFlow, FlowDirection};
-use crate::utils::formatting::{format_bytes, format_duration};
+use crate::traffic::{TrafficFlow, FlowDirection, TlsInfo, HostnameCache, HttpStats, HttpTransaction};
+use crate::traffic::hostnames::format_endpoint;
+use crate::traffic::http::EndpointStats;
+use crate::traffic::tls::{cipher_name, version_name};
+use crate::utils::formatting::{format_bytes, format_duration, format_latency, truncate_string};
+
+/// Rows kept for the slowest-endpoints table
+const ENDPOINT_ROWS: usize = 50;
 
 #[derive(Clone)]
 pub struct ProtocolOverview {
@@ -22,14 +29,17 @@ pub struct ProtocolOverview {
 
 #[derive(Clone)]
 pub struct ConnectionSummary {
-    pub client: String,
-    pub server: String,
+    pub flow_id: String,
+    pub client: SocketAddr,
+    pub server: SocketAddr,
     pub protocol: ProtocolType,
     pub direction: FlowDirection,
     pub bandwidth: f64,
     pub packets: u64,
     pub duration: std::time::Duration,
     pub status: String,
+    pub tls: Option<TlsInfo>,
+    pub http: Vec<HttpTransaction>,
 }
 
 pub struct ProtocolView {
@@ -38,6 +48,11 @@ pub struct ProtocolView {
     selected_protocol: usize,
     selected_connection: usize,
     total_bandwidth: f64,
+    // HTTP drill-down in place of the protocol overview and details
+    show_http: bool,
+    http_transactions: u64,
+    http_status_counts: Vec<(u16, u64)>,
+    slowest_endpoints: Vec<EndpointStats>,
 }
 
 impl ProtocolView {
@@ -48,6 +63,10 @@ impl ProtocolView {
             selected_protocol: 0,
             selected_connection: 0,
             total_bandwidth: 0.0,
+            show_http: false,
+            http_transactions: 0,
+            http_status_counts: Vec::new(),
+            slowest_endpoints: Vec::new(),
         }
     }
     
@@ -56,6 +75,12 @@ impl ProtocolView {
         self.update_active_connections(flows);
     }
     
+    pub fn update_http_stats(&mut self, stats: &HttpStats) {
+        self.http_transactions = stats.total_transactions();
+        self.http_status_counts = stats.status_counts();
+        self.slowest_endpoints = stats.slowest_endpoints(ENDPOINT_ROWS);
+    }
+    
     fn update_protocol_overviews(&mut self, flows: &HashMap<String, TrafficFlow>) {
         let mut protocol_stats: HashMap<ProtocolType, ProtocolStats> = HashMap::new();
         self.total_bandwidth = 0.0;
@@ -143,16 +168,16 @@ impl ProtocolView {
             .filter(|flow| flow.is_active)
             .map(|flow| {
                 let (client, server) = match flow.direction {
-                    FlowDirection::Outbound => (flow.src_addr.to_string(), flow.dst_addr.to_string()),
-                    FlowDirection::Inbound => (flow.dst_addr.to_string(), flow.src_addr.to_string()),
+                    FlowDirection::Outbound => (flow.src_addr, flow.dst_addr),
+                    FlowDirection::Inbound => (flow.dst_addr, flow.src_addr),
                     FlowDirection::Internal => {
                         if flow.src_addr.port() < flow.dst_addr.port() {
-                            (flow.dst_addr.to_string(), flow.src_addr.to_string())
+                            (flow.dst_addr, flow.src_addr)
                         } else {
-                            (flow.src_addr.to_string(), flow.dst_addr.to_string())
+                            (flow.src_addr, flow.dst_addr)
                         }
                     }
-                    FlowDirection::Unknown => (flow.src_addr.to_string(), flow.dst_addr.to_string()),
+                    FlowDirection::Unknown => (flow.src_addr, flow.dst_addr),
                 };
                 
                 let status = if flow.bytes_per_second > 1_000_000.0 {
@@ -164,6 +189,7 @@ impl ProtocolView {
                 };
                 
                 ConnectionSummary {
+                    flow_id: flow.flow_id.clone(),
                     client,
                     server,
                     protocol: flow.protocol.clone(),
@@ -174,6 +200,8 @@ impl ProtocolView {
                         .duration_since(flow.start_time)
                         .unwrap_or_default(),
                     status,
+                    tls: flow.tls.clone(),
+                    http: flow.http.clone(),
                 }
             })
             .collect();
@@ -204,30 +232,63 @@ impl ProtocolView {
     }
     
     pub fn next_connection(&mut self) {
-        if !self.active_connections.is_empty() {
-            self.selected_connection = (self.selected_connection + 1) % self.active_connections.len();
-        }
+        self.step_connection(true);
     }
     
     pub fn previous_connection(&mut self) {
-        if !self.active_connections.is_empty() {
-            self.selected_connection = if self.selected_connection == 0 {
-                self.active_connections.len() - 1
+        self.step_connection(false);
+    }
+    
+    /// Move the selection one row; the HTTP drill-down skips flows without
+    /// HTTP transactions
+    fn step_connection(&mut self, forward: bool) {
+        let count = self.active_connections.len();
+        for step in 1..=count {
+            let index = if forward {
+                (self.selected_connection + step) % count
             } else {
-                self.selected_connection - 1
+                (self.selected_connection + count - step) % count
             };
+            if !self.show_http || !self.active_connections[index].http.is_empty() {
+                self.selected_connection = index;
+                return;
+            }
         }
     }
     
-    pub fn render(&mut self, area: Rect, frame: &mut Frame) {
+    /// Switch between the protocol overview and the HTTP drill-down
+    pub fn toggle_http(&mut self) {
+        self.show_http = !self.show_http;
+        let has_http = |index: usize| self.active_connections.get(index).is_some_and(|conn| !conn.http.is_empty());
+        if self.show_http && !has_http(self.selected_connection) {
+            if let Some(index) = (0..self.active_connections.len()).find(|index| has_http(*index)) {
+                self.selected_connection = index;
+            }
+        }
+    }
+    
+    /// Flow of the connection selected in the table
+    pub fn selected_flow_id(&self) -> Option<&str> {
+        self.active_connections
+            .get(self.selected_connection)
+            .map(|connection| connection.flow_id.as_str())
+    }
+    
+    /// Client and server are shown by name where `names` has one
+    pub fn render(&mut self, area: Rect, frame: &mut Frame, names: Option<&HostnameCache>) {
         // Create layout: Protocol overview (left) | Active connections (right)
         let chunks = Layout::default()
             .direction(Direction::Horizontal)
             .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
             .split(area);
         
-        self.render_protocol_overview(chunks[0], frame);
-        self.render_active_connections(chunks[1], frame);
+        if self.show_http {
+            self.render_http_summary(chunks[0], frame);
+            self.render_http_connections(chunks[1], frame, names);
+        } else {
+            self.render_protocol_overview(chunks[0], frame);
+            self.render_active_connections(chunks[1], frame, names);
+        }
     }
     
     fn render_protocol_overview(&self, area: Rect, frame: &mut Frame) {
@@ -248,11 +309,12 @@ impl ProtocolView {
                 
                 let color = match overview.protocol {
                     ProtocolType::Http => Color::Green,
-                    ProtocolType::Https => Color::Blue,
+                    ProtocolType::Https | ProtocolType::Tls | ProtocolType::Quic => Color::Blue,
                     ProtocolType::Dns => Color::Yellow,
                     ProtocolType::Ssh => Color::Magenta,
                     ProtocolType::Ftp => Color::Cyan,
                     ProtocolType::Smtp => Color::Red,
+                    ProtocolType::Postgres | ProtocolType::MySql | ProtocolType::Mqtt => Color::LightCyan,
                     _ => Color::White,
                 };
                 
@@ -288,18 +350,18 @@ impl ProtocolView {
         frame.render_widget(list, area);
     }
     
-    fn render_active_connections(&self, area: Rect, frame: &mut Frame) {
+    fn render_active_connections(&self, area: Rect, frame: &mut Frame, names: Option<&HostnameCache>) {
         // Split into connections table and details
         let chunks = Layout::default()
             .direction(Direction::Vertical)
             .constraints([Constraint::Percentage(70), Constraint::Percentage(30)])
             .split(area);
         
-        self.render_connections_table(chunks[0], frame);
-        self.render_connection_details(chunks[1], frame);
+        self.render_connections_table(chunks[0], frame, names);
+        self.render_connection_details(chunks[1], frame, names);
     }
     
-    fn render_connections_table(&self, area: Rect, frame: &mut Frame) {
+    fn render_connections_table(&self, area: Rect, frame: &mut Frame, names: Option<&HostnameCache>) {
         if self.active_connections.is_empty() {
             let block = Block::default()
                 .title("Active Connections")
@@ -336,8 +398,8 @@ impl ProtocolView {
                 };
                 
                 Row::new(vec![
-                    Cell::from(conn.client.clone()),
-                    Cell::from(conn.server.clone()),
+                    Cell::from(format_endpoint(conn.client, names)),
+                    Cell::from(format_endpoint(conn.server, names)),
                     Cell::from(protocol_str),
                     Cell::from(conn.status.clone()).style(Style::default().fg(status_color)),
                     Cell::from(bandwidth_str),
@@ -366,24 +428,37 @@ impl ProtocolView {
         frame.render_widget(table, area);
     }
     
-    fn render_connection_details(&self, area: Rect, frame: &mut Frame) {
+    fn render_connection_details(&self, area: Rect, frame: &mut Frame, names: Option<&HostnameCache>) {
         let content = if let Some(conn) = self.active_connections.get(self.selected_connection) {
             let duration_str = format_duration(conn.duration.as_secs());
-            format!(
+            let mut details = format!(
                 "Selected Connection Details:\n\
                  Client: {} → Server: {}\n\
                  Protocol: {:?} | Direction: {}\n\
                  Bandwidth: {}/s | Packets: {}\n\
                  Duration: {} | Status: {}",
-                conn.client,
-                conn.server,
+                format_endpoint(conn.client, names),
+                format_endpoint(conn.server, names),
                 conn.protocol,
                 conn.direction,
                 format_bytes(conn.bandwidth as u64),
                 conn.packets,
                 duration_str,
                 conn.status
-            )
+            );
+            if let Some(ref tls) = conn.tls {
+                details.push_str(&Self::format_tls_details(tls));
+            }
+            if let Some(last) = conn.http.last() {
+                details.push_str(&format!(
+                    "\nHTTP: {} transactions, last {} {} → {} (Enter for HTTP view)",
+                    conn.http.len(),
+                    last.method,
+                    truncate_string(&last.path, 40),
+                    last.status
+                ));
+            }
+            details
         } else {
             "No connection selected".to_string()
         };
@@ -399,6 +474,143 @@ impl ProtocolView {
         
         frame.render_widget(paragraph, area);
     }
+    
+    fn format_tls_details(tls: &TlsInfo) -> String {
+        let unknown = || "?".to_string();
+        let alpn = match (&tls.alpn, tls.alpn_offered.is_empty()) {
+            (Some(alpn), _) => alpn.clone(),
+            (None, false) => format!("offered {}", tls.alpn_offered.join(",")),
+            (None, true) => "-".to_string(),
+        };
+        format!(
+            "\nTLS: {} | Cipher: {} | SNI: {} | ALPN: {}\n\
+             JA3: {} | JA4: {}",
+            tls.version.map(version_name).unwrap_or_else(unknown),
+            tls.cipher.map(cipher_name).unwrap_or_else(unknown),
+            tls.sni.as_deref().unwrap_or("-"),
+            alpn,
+            tls.ja3.as_deref().unwrap_or("?"),
+            tls.ja4.as_deref().unwrap_or("?"),
+        )
+    }
+    
+    fn render_http_summary(&self, area: Rect, frame: &mut Frame) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(35), Constraint::Percentage(65)])
+            .split(area);
+        
+        let total = self.http_transactions.max(1) as f64;
+        let rows = self.http_status_counts.iter().map(|(status, count)| {
+            Row::new(vec![
+                Cell::from(status.to_string()).style(Style::default().fg(status_color(*status))),
+                Cell::from(count.to_string()),
+                Cell::from(format!("{:.1}%", *count as f64 * 100.0 / total)),
+            ])
+        });
+        let widths = [Constraint::Length(6), Constraint::Length(10), Constraint::Length(8)];
+        let table = Table::new(rows)
+            .widths(&widths)
+            .header(header_row(&["Status", "Count", "Share"]))
+            .block(
+                Block::default()
+                    .title(format!("HTTP Status Codes ({} transactions, Enter to go back)", self.http_transactions))
+                    .borders(Borders::ALL)
+            );
+        frame.render_widget(table, chunks[0]);
+        
+        let rows = self.slowest_endpoints.iter().map(|endpoint| {
+            let errors_style = if endpoint.errors > 0 { Style::default().fg(Color::Red) } else { Style::default() };
+            Row::new(vec![
+                Cell::from(format!("{}{}", endpoint.host, endpoint.path)),
+                Cell::from(endpoint.requests.to_string()),
+                Cell::from(format_latency(endpoint.average_latency())),
+                Cell::from(format_latency(endpoint.max_latency)),
+                Cell::from(endpoint.errors.to_string()).style(errors_style),
+            ])
+        });
+        let widths = [
+            Constraint::Min(20),
+            Constraint::Length(6),
+            Constraint::Length(7),
+            Constraint::Length(7),
+            Constraint::Length(6),
+        ];
+        let table = Table::new(rows)
+            .widths(&widths)
+            .header(header_row(&["Endpoint", "Reqs", "Avg", "Max", "Errors"]))
+            .block(Block::default().title("Slowest Endpoints").borders(Borders::ALL));
+        frame.render_widget(table, chunks[1]);
+    }
+    
+    fn render_http_connections(&self, area: Rect, frame: &mut Frame, names: Option<&HostnameCache>) {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Percentage(40), Constraint::Percentage(60)])
+            .split(area);
+        self.render_connections_table(chunks[0], frame, names);
+        
+        let selected = self.active_connections.get(self.selected_connection);
+        let title = match selected {
+            Some(conn) if !conn.http.is_empty() => format!(
+                "HTTP Transactions: {} → {} (←→ to change flow)",
+                format_endpoint(conn.client, names),
+                format_endpoint(conn.server, names)
+            ),
+            _ => "HTTP Transactions (no HTTP on the selected flow)".to_string(),
+        };
+        
+        // Newest first
+        let transactions = selected.map(|conn| conn.http.as_slice()).unwrap_or_default();
+        let rows = transactions.iter().rev().map(|transaction| {
+            let length = transaction.response_content_length
+                .map(format_bytes)
+                .unwrap_or_else(|| "-".to_string());
+            Row::new(vec![
+                Cell::from(format_latency(transaction.latency)),
+                Cell::from(transaction.method.clone()),
+                Cell::from(format!(
+                    "{}{}",
+                    transaction.host.as_deref().unwrap_or(""),
+                    transaction.path
+                )),
+                Cell::from(format!("{} {}", transaction.status, transaction.reason))
+                    .style(Style::default().fg(status_color(transaction.status))),
+                Cell::from(length),
+                Cell::from(transaction.user_agent.clone().unwrap_or_else(|| "-".to_string())),
+            ])
+        });
+        let widths = [
+            Constraint::Length(7),
+            Constraint::Length(7),
+            Constraint::Min(20),
+            Constraint::Length(16),
+            Constraint::Length(10),
+            Constraint::Percentage(25),
+        ];
+        let table = Table::new(rows)
+            .widths(&widths)
+            .header(header_row(&["Latency", "Method", "URL", "Status", "Length", "User-Agent"]))
+            .block(Block::default().title(title).borders(Borders::ALL));
+        frame.render_widget(table, chunks[1]);
+    }
+}
+
+fn header_row(cells: &[&'static str]) -> Row<'static> {
+    Row::new(cells.iter().map(|h| {
+        Cell::from(*h).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD))
+    }))
+}
+
+/// Green for success, cyan for redirects, yellow for client and red for server errors
+fn status_color(status: u16) -> Color {
+    match status {
+        200..=299 => Color::Green,
+        300..=399 => Color::Cyan,
+        400..=499 => Color::Yellow,
+        500..=599 => Color::Red,
+        _ => Color::White,
+    }
 }
 
 struct ProtocolStats {
@@ -411,11 +623,60 @@ struct ProtocolStats {
 #[cfg(test)]
 mod tests {
     use super::*;
-
+    
     #[test]
     fn test_protocol_view_creation() {
         let view = ProtocolView::new();
         assert_eq!(view.protocol_overviews.len(), 0);
         assert_eq!(view.active_connections.len(), 0);
     }
+    
+    #[test]
+    fn test_http_drill_down_selects_http_flows() {
+        let summary = |flow_id: &str, http: Vec<HttpTransaction>| ConnectionSummary {
+            flow_id: flow_id.to_string(),
+            client: "192.168.1.10:50000".parse().unwrap(),
+            server: "93.184.216.34:80".parse().unwrap(),
+            protocol: ProtocolType::Http,
+            direction: FlowDirection::Outbound,
+            bandwidth: 0.0,
+            packets: 1,
+            duration: std::time::Duration::ZERO,
+            status: "IDLE".to_string(),
+            tls: None,
+            http,
+        };
+        let transaction = HttpTransaction {
+            timestamp: std::time::SystemTime::UNIX_EPOCH,
+            method: "GET".to_string(),
+            host: Some("example.com".to_string()),
+            path: "/".to_string(),
+            user_agent: None,
+            request_content_length: None,
+            status: 200,
+            reason: "OK".to_string(),
+            response_content_length: Some(1256),
+            latency: std::time::Duration::from_millis(30),
+        };
+        
+        let mut view = ProtocolView::new();
+        view.active_connections = vec![
+            summary("a", Vec::new()),
+            summary("b", vec![transaction.clone()]),
+            summary("c", Vec::new()),
+            summary("d", vec![transaction]),
+        ];
+        view.toggle_http();
+        assert_eq!(view.selected_flow_id(), Some("b"));
+        view.next_connection();
+        assert_eq!(view.selected_flow_id(), Some("d"));
+        view.next_connection();
+        assert_eq!(view.selected_flow_id(), Some("b"));
+        view.previous_connection();
+        assert_eq!(view.selected_flow_id(), Some("d"));
+        
+        view.toggle_http();
+        view.next_connection();
+        assert_eq!(view.selected_flow_id(), Some("a"));
+    }
 }
@@ -54,6 +54,15 @@ pub fn format_duration(seconds: u64) -> String {
     }
 }
 
+/// Format a response time: milliseconds below one second
+pub fn format_latency(latency: std::time::Duration) -> String {
+    if latency.as_secs() == 0 {
+        format!("{}ms", latency.as_millis())
+    } else {
+        format!("{:.1}s", latency.as_secs_f64())
+    }
+}
+
 /// Truncate string to specified length with ellipsis
 pub fn truncate_string(s: &str, max_len: usize) -> String {
     if s.len() <= max_len {
@@ -104,6 +113,12 @@ mod tests {
         assert_eq!(format_duration(90061), "1d 1h");
     }
 
+    #[test]
+    fn test_format_latency() {
+        assert_eq!(format_latency(std::time::Duration::from_micros(2500)), "2ms");
+        assert_eq!(format_latency(std::time::Duration::from_millis(1500)), "1.5s");
+    }
+
     #[test]
     fn test_truncate_string() {
         assert_eq!(truncate_string("hello", 10), "hello");
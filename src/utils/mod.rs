@@ -1,3 +1,5 @@
 pub mod formatting;
+pub mod net;
+pub mod shutdown;
 
 pub use formatting::*;
@@ -0,0 +1,31 @@
+use std::net::{IpAddr, SocketAddr};
+
+/// Dual-stack sockets show up in /proc/net/tcp6 as ::ffff:a.b.c.d while the
+/// packets on the wire are plain IPv4
+pub fn canonical_ip(ip: IpAddr) -> IpAddr {
+    match ip {
+        IpAddr::V6(v6) => v6.to_ipv4_mapped().map_or(ip, IpAddr::V4),
+        IpAddr::V4(_) => ip,
+    }
+}
+
+pub fn canonical_addr(addr: SocketAddr) -> SocketAddr {
+    SocketAddr::new(canonical_ip(addr.ip()), addr.port())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_canonical_addr() {
+        let mapped: SocketAddr = "[::ffff:93.184.216.34]:443".parse().unwrap();
+        assert_eq!(canonical_addr(mapped), "93.184.216.34:443".parse().unwrap());
+        
+        // Real IPv6, including the IPv4-compatible form, is left alone
+        for addr in ["[2001:db8::1]:443", "[::1]:22", "[::93.184.216.34]:80", "10.0.0.1:53"] {
+            let addr: SocketAddr = addr.parse().unwrap();
+            assert_eq!(canonical_addr(addr), addr);
+        }
+    }
+}
@@ -0,0 +1,39 @@
+use std::io;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static REQUESTED: AtomicBool = AtomicBool::new(false);
+
+extern "C" fn request(_signal: libc::c_int) {
+    REQUESTED.store(true, Ordering::SeqCst);
+}
+
+/// Turn SIGINT, SIGTERM and SIGHUP into a request to quit, so the TUI exits
+/// through its normal path and rolls back enforced firewall rules
+pub fn install_handlers() -> io::Result<()> {
+    let handler: extern "C" fn(libc::c_int) = request;
+    for signal in [libc::SIGINT, libc::SIGTERM, libc::SIGHUP] {
+        // SAFETY: the handler only stores to an atomic, which is async-signal-safe
+        if unsafe { libc::signal(signal, handler as libc::sighandler_t) } == libc::SIG_ERR {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+/// Whether one of the signals from `install_handlers` has arrived
+pub fn requested() -> bool {
+    REQUESTED.load(Ordering::SeqCst)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_signal_requests_shutdown() {
+        install_handlers().unwrap();
+        // SAFETY: raising a signal that now has a handler installed
+        unsafe { libc::raise(libc::SIGHUP) };
+        assert!(requested());
+    }
+}